@@ -0,0 +1,212 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use rand::RngCore;
+use crate::data::{Item, LookUpTable};
+use crate::errors::NoValuesError;
+
+/// The probability distribution of the outputs of a template, and how often each look-up table
+/// item is drawn (see [Interpreter::analyze(...)](crate::Interpreter::analyze)), such as to check
+/// that a rare item is really as rare as intended
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distribution {
+	/// True if the probabilities were calculated exactly (by following every combination of
+	/// draws), false if they were estimated from random samples
+	pub exact: bool,
+	/// Number of evaluations that were used (the number of combinations of draws if exact)
+	pub samples: usize,
+	/// Probability of each generated text
+	pub outputs: BTreeMap<String, f64>,
+	/// Expected number of draws of each item per evaluation, by look-up table ID and then by
+	/// item ID (see [Item::get_id()](crate::Item::get_id)). Items from nested substitutions are
+	/// included.
+	pub expected_draws: BTreeMap<String, BTreeMap<String, f64>>
+}
+
+impl Distribution {
+	/// Gets the probability of a generated text
+	/// # Arguments
+	/// * `text`: the generated text
+	/// # Returns
+	/// The probability (0 if the text was never generated)
+	pub fn probability(&self, text: &str) -> f64 {
+		self.outputs.get(text).copied().unwrap_or(0.)
+	}
+
+	/// Gets the generated texts, from most likely to least likely
+	/// # Returns
+	/// The texts and their probabilities, sorted by probability and then by text
+	pub fn most_likely(&self) -> Vec<(&String, f64)> {
+		let mut outputs: Vec<(&String, f64)> = self.outputs.iter().map(|(text, p)| (text, *p)).collect();
+		outputs.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+		outputs
+	}
+
+	/// Gets the expected number of draws of an item per evaluation
+	/// # Arguments
+	/// * `table_id`: ID of the look-up table
+	/// * `item_id`: ID of the item (its key, or else its text)
+	/// # Returns
+	/// The expected number of draws (0 if the item was never drawn)
+	pub fn expected_count(&self, table_id: &str, item_id: &str) -> f64 {
+		self.expected_draws.get(table_id).and_then(|items| items.get(item_id)).copied().unwrap_or(0.)
+	}
+
+	/// Gets the share of the draws from a look-up table that drew each item, which is the same
+	/// as the item's weight divided by the total weight unless the draws depend on references,
+	/// caps, or depletion
+	/// # Arguments
+	/// * `table_id`: ID of the look-up table
+	/// # Returns
+	/// The share of each item by item ID, adding up to 1 (empty if the table was never drawn
+	/// from)
+	pub fn item_frequencies(&self, table_id: &str) -> BTreeMap<&String, f64> {
+		let items = match self.expected_draws.get(table_id) {
+			None => return BTreeMap::new(),
+			Some(items) => items
+		};
+		let total: f64 = items.values().sum();
+		items.iter().map(|(item_id, count)| (item_id, count / total)).collect()
+	}
+
+	/// Adds the result of one evaluation with the given probability (or weight)
+	pub(crate) fn add(&mut self, text: String, draws: &[(String, Item)], prob: f64) {
+		*self.outputs.entry(text).or_default() += prob;
+		for (table_id, item) in draws {
+			*self.expected_draws.entry(table_id.clone()).or_default()
+				.entry(item.get_id().clone()).or_default() += prob;
+		}
+		self.samples += 1;
+	}
+
+	/// Divides all probabilities and expected counts by the given number of samples
+	pub(crate) fn normalize(&mut self, samples: usize) {
+		let n = samples.max(1) as f64;
+		self.outputs.values_mut().for_each(|p| *p /= n);
+		self.expected_draws.values_mut().flat_map(|items| items.values_mut()).for_each(|c| *c /= n);
+	}
+}
+
+/// Makes the draws of an evaluation follow a script of item choices instead of drawing at random,
+/// such that every combination of draws can be visited one after another
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChoiceScript {
+	/// Index (among the items that can be drawn) of the item to choose at each draw
+	script: Vec<usize>,
+	/// Number of items that could be chosen at each draw of the current evaluation
+	widths: Vec<usize>,
+	/// Probability of the draws of the current evaluation
+	prob: f64
+}
+
+impl ChoiceScript {
+	/// Prepares for the next evaluation
+	pub fn start(&mut self) {
+		self.widths.clear();
+		self.prob = 1.;
+	}
+
+	/// Gets the probability of the draws of the current evaluation
+	pub fn probability(&self) -> f64 {
+		self.prob
+	}
+
+	/// Chooses an item of a look-up table according to the script (the first item that can be
+	/// drawn if the script does not say), skipping items of weight zero like a random draw
+	pub fn choose(&mut self, lut: &LookUpTable) -> Result<Item, NoValuesError> {
+		let candidates: Vec<&Item> = lut.items().iter().filter(|item| item.get_weight() > 0.).collect();
+		let total: f64 = candidates.iter().map(|item| item.get_weight()).sum();
+		if candidates.is_empty() || total <= 0. {
+			return Err(NoValuesError{});
+		}
+		let step = self.widths.len();
+		let index = self.script.get(step).copied().unwrap_or(0).min(candidates.len() - 1);
+		if step >= self.script.len() {
+			self.script.push(index);
+		}
+		self.widths.push(candidates.len());
+		self.prob *= candidates[index].get_weight() / total;
+		Ok(candidates[index].clone())
+	}
+
+	/// Moves on to the next combination of draws after an evaluation
+	/// # Returns
+	/// False if every combination has been visited
+	pub fn advance(&mut self) -> bool {
+		self.script.truncate(self.widths.len());
+		while let Some(index) = self.script.pop() {
+			let width = self.widths[self.script.len()];
+			if index + 1 < width {
+				self.script.push(index + 1);
+				return true;
+			}
+		}
+		false
+	}
+}
+
+/// A random number generator that always returns zero and remembers that it was used, to find out
+/// whether an evaluation depended on anything other than its scripted draws (eg dice)
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Tripwire {
+	/// Whether any clone of this generator was used
+	used: Arc<AtomicBool>
+}
+
+impl Tripwire {
+	/// Whether any clone of this generator was used
+	pub fn was_used(&self) -> bool {
+		self.used.load(Ordering::Relaxed)
+	}
+}
+
+impl RngCore for Tripwire {
+	fn next_u32(&mut self) -> u32 {
+		self.used.store(true, Ordering::Relaxed);
+		0
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.used.store(true, Ordering::Relaxed);
+		0
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		self.used.store(true, Ordering::Relaxed);
+		dest.fill(0);
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+		self.fill_bytes(dest);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::analysis::ChoiceScript;
+	use crate::data::LookUpTable;
+
+	#[test]
+	fn test_choice_script() {
+		let mut lut = LookUpTable::new();
+		lut.add_item("a", 3.);
+		lut.add_item("never", 0.);
+		lut.add_item("b", 1.);
+		let mut script = ChoiceScript::default();
+		let mut seen: Vec<(String, f64)> = Vec::new();
+		loop {
+			script.start();
+			let first = script.choose(&lut).unwrap();
+			let second = script.choose(&lut).unwrap();
+			seen.push((format!("{}{}", first.get_text(), second.get_text()), script.probability()));
+			if !script.advance() {
+				break;
+			}
+		}
+		assert_eq!(seen, vec![(String::from("aa"), 0.5625), (String::from("ab"), 0.1875),
+			(String::from("ba"), 0.1875), (String::from("bb"), 0.0625)]);
+	}
+}
@@ -1,36 +1,190 @@
 #![deny(unused_must_use)]
 #![deny(missing_docs)]
 #![doc = include_str!("../README.md")]
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::error::Error;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::{ErrorKind, IsTerminal};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use clap::{arg, Parser};
+use clap::{Parser, Subcommand, ValueEnum};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 /// Struct to hold command-line arguments
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = include_str!("long-about.txt"))]
 pub struct TwasArgs {
-	/// Random look-up table files to include. Supported formats: .txt, .csv. .json. yaml, and .yml
-	/// (or any of these with .gz or .zip compression)
-	#[arg[short='i', long="include"]]
+	/// Random look-up table files to include. Supported formats: .txt, .csv, .tsv, .json, .json5, .jsonc, .yaml, .yml, .xml, .md, .twee, .tw, and .xlsx
+	/// (or any of these with .gz or .zip compression), directories, and glob patterns (eg
+	/// "tables/**/*.yaml")
+	#[arg(short='i', long="include", global = true)]
 	includes: Vec<PathBuf>,
-	/// Optional seed for making the random number generator deterministic
+	/// Optional seed for making the random number generator deterministic. Without a seed, a
+	/// random seed is generated and printed to stderr, so that the output can be reproduced later
 	#[arg(short='s', long="seed")]
 	seed: Option<u64>,
+	/// Write the seed of each output as a "# seed: ..." header line before it, instead of printing
+	/// a generated seed to stderr
+	#[arg(long="emit-seed")]
+	emit_seed: bool,
+	/// Compatibility level for the text substitution behavior: "v1" (original behavior, the
+	/// default) or "v2" (improved defaults, eg multiple items are separated by ", ")
+	#[arg(long="compat")]
+	compat: Option<twas::CompatLevel>,
+	/// Replace missing look-up tables and references with a placeholder (eg "⟦missing: animal⟧")
+	/// and print a warning, instead of stopping with an error
+	#[arg(long="lenient")]
+	lenient: bool,
+	/// Match look-up table IDs regardless of letter case and "-" vs "_" separators (eg
+	/// "${Monster_Names}" finds the "monster-names" look-up table)
+	#[arg(long="loose-ids", global = true)]
+	loose_ids: bool,
+	/// Read the front matter (a YAML block between two "---" lines) at the start of each target
+	/// text, which sets default references and the recommended seed (always on with --compat v2)
+	#[arg(long="front-matter")]
+	front_matter: bool,
+	/// Keep the whole rows of .csv and .tsv files too, such that the "row" option can draw all
+	/// columns of a row together (eg "${{row: npc-stats}}")
+	#[arg(long="csv-rows")]
+	csv_rows: bool,
+	/// Text encoding of the included look-up table files (eg "windows-1252" or "utf-16le"). By
+	/// default, the encoding is detected from the byte-order mark, and files that are not valid
+	/// UTF-8 cannot be loaded
+	#[arg(long="encoding", global = true)]
+	encoding: Option<String>,
 	/// Option to specify that output is written to the given filepath instead of being printed to
 	/// the terminal
-	#[arg[short='o', long="output"]]
+	#[arg(short='o', long="output", conflicts_with = "output_dir")]
 	output: Option<PathBuf>,
-	/// Option to read target text for substitution from one or more files
+	/// Append to the file given with -o/--output instead of overwriting it
+	#[arg(long="append", requires = "output")]
+	append: bool,
+	/// Option to write each output to its own numbered file in the given directory (eg
+	/// "0001.txt", "0002.txt", ...) instead of printing it to the terminal. The directory is
+	/// created if it does not exist, and existing files with the same names are overwritten
+	#[arg(long="output-dir")]
+	output_dir: Option<PathBuf>,
+	/// Number of times to evaluate each target text (the look-up tables are only loaded once)
+	#[arg(short='n', long="count", default_value_t = 1)]
+	count: usize,
+	/// Line to print between the outputs (eg "---"), instead of an empty line after each output
+	#[arg(long="separator", allow_hyphen_values = true)]
+	separator: Option<String>,
+	/// Output format: "text" (the default) or "json" (one JSON object per line with the text, the
+	/// seed, the references, and the drawn items of each output)
+	#[arg(long="format", value_enum, default_value_t = OutputFormat::Text)]
+	format: OutputFormat,
+	/// Reference to set before the evaluation, as ID=TEXT (eg "--ref species=elf" for
+	/// "${@species}" and "${names/$species}"). Can be given more than once
+	#[arg(long="ref", value_name = "ID=TEXT", value_parser = parse_ref)]
+	refs: Vec<(String, String)>,
+	/// Make environment variables available to the target text as host variables (eg
+	/// "${var:HOME}"), either all of them (--env) or only the listed ones (eg --env=HOME,USER)
+	#[arg(long="env", value_name = "NAMES", num_args = 0..=1, require_equals = true, value_delimiter = ',')]
+	env: Option<Vec<String>>,
+	/// Option to read target text for substitution from one or more files. Files ending in .yaml
+	/// or .yml are read as generator files, which bundle the target text together with its
+	/// metadata, parameters, and included look-up tables
 	#[arg[short='f', long="file"]]
 	input: Vec<PathBuf>,
+	/// Instead of performing substitutions, list every look-up table ID and reference ID that the
+	/// target text requires but which is not provided by the included look-up tables
+	#[arg(long="list-missing")]
+	list_missing: bool,
+	/// Instead of performing substitutions, check the weights of the included look-up tables for
+	/// likely mistakes (eg weights of 0, or one item holding almost all the probability)
+	#[arg(long="audit-weights")]
+	audit_weights: bool,
 	/// Text to perform substitution on, eg "Meet my pet ${animal}". At least one text string must
 	/// be provided unless you are using -f/--file or providing the target text via pipe
 	/// (eg `$ cat my-story.txt | twas -i my-lookups.zip`)
-	pub target_text: Vec<String>
+	pub target_text: Vec<String>,
+	/// Optional command to run instead of performing substitutions
+	#[command(subcommand)]
+	pub command: Option<TwasCommand>
+}
+
+/// Commands of the twas CLI app that do something other than performing substitutions
+#[derive(Subcommand, Debug, Clone)]
+pub enum TwasCommand {
+	/// List the IDs of all look-up tables in the included files (eg `twas list -i pack.zip`)
+	List {
+		/// Show the IDs as a tree of namespaces (eg "animal/mammal" is listed as "mammal" under
+		/// "animal")
+		#[arg(long="tree")]
+		tree: bool,
+		/// Show the number of items of each look-up table
+		#[arg(long="counts")]
+		counts: bool
+	},
+	/// Check the included look-up tables and the given template files without performing any
+	/// substitutions, and fail if anything is missing or cannot be parsed (eg
+	/// `twas check -i pack/ story.txt`)
+	Check {
+		/// Template files to check (files ending in .yaml or .yml are read as generator files)
+		files: Vec<PathBuf>
+	}
+}
+
+/// Output formats of the twas CLI app
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+	/// The generated text
+	Text,
+	/// One JSON object per line (see [JsonOutput])
+	Json
+}
+
+/// The result of one evaluation, as printed with `--format json`
+#[derive(Serialize, Debug, Clone)]
+pub struct JsonOutput {
+	/// The generated text
+	pub text: String,
+	/// The random number seed of this output, which reproduces it when it is passed to `--seed`
+	/// (see [run(...)](run))
+	pub seed: u64,
+	/// The text of each reference at the end of the evaluation, by reference ID
+	pub refs: BTreeMap<String, String>,
+	/// Every item that was drawn, in the order that they were drawn
+	pub draws: Vec<JsonDraw>
+}
+
+/// An item that was drawn, as printed with `--format json`
+#[derive(Serialize, Debug, Clone)]
+pub struct JsonDraw {
+	/// ID of the look-up table that the item was drawn from
+	pub table: String,
+	/// Text of the item
+	pub text: String,
+	/// Key of the item, if any
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub key: Option<String>
+}
+
+/// A self-describing generator file, which bundles a template with everything needed to run it
+/// (eg `twas -f dungeon.yaml`)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorFile {
+	/// Name of the generator
+	pub name: Option<String>,
+	/// Description of what the generator generates
+	pub description: Option<String>,
+	/// Author of the generator
+	pub author: Option<String>,
+	/// Version of the generator
+	pub version: Option<String>,
+	/// Look-up table files, directories, or .zip archives to include, relative to the location of
+	/// the generator file
+	#[serde(default)]
+	pub includes: Vec<PathBuf>,
+	/// Parameters for the template, which are loaded as look-up tables (a single value, a list of
+	/// values to choose from, or a map of weighted values)
+	pub parameters: Option<serde_yaml::Mapping>,
+	/// The target text for substitution
+	pub template: String
 }
 
 /// Main entry point for the twas CLI app
@@ -45,49 +199,294 @@ pub fn main() -> ExitCode {
 	}
 }
 
-/// Run the twas CLI app with the provided arguments
+/// Run the twas CLI app with the provided arguments. The random number generator is re-seeded
+/// before every output: the n-th output (counting from 0) uses the given or generated seed plus n,
+/// or the seed from the template's front matter plus the repeat number, so that each reported seed
+/// reproduces its output on its own.
 /// # Parameters
 /// * **args: TwasArgs** - A `TwasArgs` struct holding all arguments for the `twas` app (typically
 /// parsed from the CLI args)
 /// # Returns
 /// Returns `Ok(())` result on success, and `Box<dyn Error>` if an error occurs
 pub fn run(args: TwasArgs) -> Result<(), Box<dyn Error>>{
-	let mut gen = match args.seed{
-		None => twas::Interpreter::new(),
-		Some(seed) => twas::Interpreter::from_seed(seed)
-	};
+	let refs: HashMap<String, String> = args.refs.into_iter().collect();
+	let initial_seed: u64 = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+	let mut gen = twas::Interpreter::from_seed(initial_seed);
+	if let Some(compat) = args.compat {
+		gen.set_compat(compat);
+	}
+	gen.set_lenient_missing(args.lenient);
+	gen.set_loose_ids(args.loose_ids);
+	if args.front_matter {
+		gen.set_front_matter(true);
+	}
+	gen.set_encoding(args.encoding.as_deref())?;
+	if args.csv_rows {
+		gen.set_csv_options(twas::CsvOptions::default().with_rows(true));
+	}
 	for inc in args.includes {
 		gen.load_file(inc)?
 	}
+	if let Some(names) = &args.env {
+		for name in names {
+			match std::env::var(name) {
+				Ok(value) => gen.set_var(name, value.as_str())?,
+				Err(e) => return Err(format!("environment variable '{}': {}", name, e).into())
+			}
+		}
+		if names.is_empty() {
+			// variables whose names are not valid host variable names cannot be used anyway
+			for (name, value) in std::env::vars_os() {
+				if let (Some(name), Some(value)) = (name.to_str(), value.to_str()) {
+					gen.set_var(name, value).ok();
+				}
+			}
+		}
+	}
+	match args.command {
+		Some(TwasCommand::List{tree, counts}) => {
+			list_ids(&mut gen, tree, counts);
+			return Ok(());
+		},
+		Some(TwasCommand::Check{files}) => return check(&mut gen, &files),
+		None => {}
+	}
+	if args.audit_weights {
+		for (id, issue) in gen.audit_weights(1000.) {
+			println!("{}: {}", id, issue);
+		}
+		return Ok(());
+	}
 	// sanity checks
 	let stdin = std::io::stdin();
 	// read targets
 	let mut targets = args.target_text;
 	for filepath in args.input {
-		targets.push(std::fs::read_to_string(filepath)?);
+		let is_generator = filepath.extension()
+			.map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+			.unwrap_or(false);
+		if is_generator {
+			targets.push(load_generator(&mut gen, &filepath)?);
+		} else {
+			targets.push(std::fs::read_to_string(filepath)?);
+		}
 	}
 	if ! stdin.is_terminal() {
 		targets.push(read_stdin(&stdin)?)
 	}
+	for (id, base) in gen.unresolved_extensions() {
+		eprintln!("Warning: look-up table '{}' extends '{}', which was not loaded", id, base);
+	}
+	if args.list_missing {
+		let mut missing: BTreeSet<String> = BTreeSet::new();
+		for target in targets {
+			missing.extend(gen.list_missing(target.as_str())?.into_iter()
+				.filter(|id| !id.strip_prefix('@').is_some_and(|ref_id| refs.contains_key(ref_id))));
+		}
+		for id in missing {
+			println!("{}", id);
+		}
+		return Ok(());
+	}
 	let mut fout: Option<File> =
 		match args.output {
 			None => None,
 			Some(outfile) => {
-				Some(File::create(outfile)?)
+				Some(OpenOptions::new().create(true).write(true).append(args.append).truncate(!args.append).open(outfile)?)
 			}
 		};
+	if let Some(dir) = &args.output_dir {
+		std::fs::create_dir_all(dir)?;
+	}
+	let extension = match args.format {
+		OutputFormat::Text => "txt",
+		OutputFormat::Json => "json"
+	};
+	let mut number: usize = 0;
+	let mut first = true;
+	if args.seed.is_none() && !args.emit_seed {
+		eprintln!("Seed: {}", initial_seed);
+	}
 	for target in targets {
-		let result = gen.eval(target.as_str())?;
-		println!("{}", result);
-		println!();
-		match &mut fout {
-			Some(f) => {write!(f, "{}\n\n", result)?;},
-			None => {}
+		// use the recommended seed from the template's front matter, unless a seed was given
+		let front_seed = match args.seed.is_none() && gen.get_options().front_matter {
+			false => None,
+			true => match twas::FrontMatter::split(target.as_str()) {
+				Ok((Some(twas::FrontMatter{seed: Some(front_seed), ..}), _)) => Some(front_seed),
+				_ => None
+			}
+		};
+		for repeat in 0..args.count {
+			// re-seed before every output, so that the reported seed reproduces it
+			let seed = match front_seed {
+				Some(front_seed) => front_seed.wrapping_add(repeat as u64),
+				None => initial_seed.wrapping_add(number as u64)
+			};
+			gen.reseed(seed);
+			let header = match (args.emit_seed, args.format) {
+				(true, OutputFormat::Text) => format!("# seed: {}\n", seed),
+				_ => String::new()
+			};
+			let result = gen.eval_detailed_with_refs(target.as_str(), refs.clone())?;
+			for warning in &result.warnings {
+				eprintln!("Warning: {}", warning);
+			}
+			let body = match args.format {
+				OutputFormat::Json => {
+					let draws = result.draws.into_iter().map(|(table, item)| JsonDraw{table,
+						text: item.get_text().clone(), key: item.get_key().cloned()}).collect();
+					serde_json::to_string(&JsonOutput{text: result.text, seed, refs: result.refs, draws})?
+				},
+				OutputFormat::Text => result.text
+			};
+			let output = match (args.format, &args.separator, first) {
+				(OutputFormat::Json, _, _) => format!("{}\n", body),
+				(OutputFormat::Text, None, _) => format!("{}\n\n", body),
+				(OutputFormat::Text, Some(_), true) => format!("{}\n", body),
+				(OutputFormat::Text, Some(sep), false) => format!("{}\n{}\n", sep, body)
+			};
+			let output = format!("{}{}", header, output);
+			first = false;
+			number += 1;
+			match (&mut fout, &args.output_dir) {
+				(Some(f), _) => write!(f, "{}", output)?,
+				(None, Some(dir)) => std::fs::write(dir.join(format!("{:04}.{}", number, extension)), format!("{}{}\n", header, body))?,
+				(None, None) => print!("{}", output)
+			}
 		}
 	}
 	Ok(())
 }
 
+/// Reads a generator file, loads its included look-up tables and parameters into the provided
+/// interpreter, and returns its template
+fn load_generator<R: Rng>(gen: &mut twas::Interpreter<R>, filepath: &Path) -> Result<String, Box<dyn Error>> {
+	let generator: GeneratorFile = serde_yaml::from_reader(File::open(filepath)?)?;
+	let base_dir = filepath.parent().unwrap_or(Path::new("."));
+	for inc in &generator.includes {
+		match inc.to_str().is_some_and(|p| p.starts_with("http://") || p.starts_with("https://")) {
+			true => gen.load_file(inc)?,
+			false => gen.load_file(base_dir.join(inc))?
+		}
+	}
+	for (key, value) in generator.parameters.unwrap_or_default() {
+		let id = parameter_text(&key).ok_or_else(|| format!("parameter name {:?} must be text", key))?;
+		let invalid = || format!("parameter '{}' must be a single value, a list of values, or a map of weighted values", id);
+		match value {
+			serde_yaml::Value::Sequence(list) => {
+				let items: Option<Vec<String>> = list.iter().map(parameter_text).collect();
+				gen.load_lines(id.as_str(), items.ok_or_else(invalid)?)?;
+			},
+			serde_yaml::Value::Mapping(map) => {
+				let items: Option<Vec<(String, f64)>> = map.iter()
+					.map(|(text, weight)| Some((parameter_text(text)?, weight.as_f64()?))).collect();
+				gen.load_items(id.as_str(), items.ok_or_else(invalid)?)?;
+			},
+			single => gen.load_lines(id.as_str(), [parameter_text(&single).ok_or_else(invalid)?])?
+		}
+	}
+	Ok(generator.template)
+}
+
+/// Gets the text of a YAML string, number, or boolean parameter value, or `None` for any other
+/// kind of value
+fn parameter_text(value: &serde_yaml::Value) -> Option<String> {
+	match value {
+		serde_yaml::Value::String(text) => Some(text.clone()),
+		serde_yaml::Value::Number(n) => Some(n.to_string()),
+		serde_yaml::Value::Bool(b) => Some(b.to_string()),
+		_ => None
+	}
+}
+
+/// Prints the IDs of all look-up tables of the provided interpreter in sorted order, either one
+/// full ID per line or as an indented tree of namespaces, optionally with the number of items of
+/// each look-up table
+fn list_ids<R: Rng>(gen: &mut twas::Interpreter<R>, tree: bool, counts: bool) {
+	let mut ids: Vec<String> = gen.list_ids().into_iter().cloned().collect();
+	ids.sort();
+	let mut prev: Vec<&str> = Vec::new();
+	for id in &ids {
+		let count = match counts {
+			true => gen.get_lut(id).map(|lut| format!(" ({})", lut.stats().count)).unwrap_or_default(),
+			false => String::new()
+		};
+		if !tree {
+			println!("{}{}", id, count);
+			continue;
+		}
+		let parts: Vec<&str> = id.split('/').collect();
+		let shared = parts.iter().zip(prev.iter()).take_while(|(a, b)| a == b).count().min(parts.len() - 1);
+		for (depth, part) in parts.iter().enumerate().skip(shared) {
+			match depth == parts.len() - 1 {
+				true => println!("{}{}{}", "  ".repeat(depth), part, count),
+				false => println!("{}{}/", "  ".repeat(depth), part)
+			}
+		}
+		prev = parts;
+	}
+}
+
+/// Checks the look-up tables of the provided interpreter and the given template files for
+/// missing look-up tables, references, and template functions, look-up tables whose `$extends`
+/// base was not loaded, substitution tokens that cannot be parsed, and unknown substitution
+/// options, and prints each problem
+/// # Returns
+/// Returns `Ok(())` if there were no problems, and an error with the number of problems otherwise
+fn check<R: Rng>(gen: &mut twas::Interpreter<R>, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+	let mut problems: Vec<String> = Vec::new();
+	let mut templates: Vec<(String, String)> = Vec::new();
+	for filepath in files {
+		let is_generator = filepath.extension()
+			.map(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+			.unwrap_or(false);
+		let template = match is_generator {
+			true => load_generator(gen, filepath),
+			false => std::fs::read_to_string(filepath).map_err(|e| e.into())
+		};
+		match template {
+			Ok(template) => templates.push((filepath.display().to_string(), template)),
+			Err(e) => problems.push(format!("{}: cannot be read: {}", filepath.display(), e))
+		}
+	}
+	for (id, base) in gen.unresolved_extensions() {
+		problems.push(format!("{}: extends '{}', which was not loaded", id, base));
+	}
+	for (id, lint) in gen.lint_tables() {
+		if matches!(lint.kind, twas::LintKind::InvalidToken | twas::LintKind::UnknownOption) {
+			problems.push(format!("{}: {}", id, lint));
+		}
+	}
+	for (name, template) in templates {
+		match gen.validate(template.as_str()) {
+			Ok(validation) => {
+				for (token, option) in validation.unknown_options {
+					problems.push(format!("{}: {}: option '{}' is not known and is ignored", name, token, option));
+				}
+				for missing in validation.missing {
+					problems.push(format!("{}: '{}' is missing", name, missing));
+				}
+			},
+			Err(e) => problems.push(format!("{}: {}", name, e))
+		}
+	}
+	for problem in &problems {
+		println!("{}", problem);
+	}
+	match problems.len() {
+		0 => Ok(()),
+		n => Err(format!("found {} problem(s)", n).into())
+	}
+}
+
+/// Parses a reference given as ID=TEXT on the command line
+fn parse_ref(s: &str) -> Result<(String, String), String> {
+	match s.split_once('=') {
+		Some((ref_id, text)) => Ok((String::from(ref_id.trim().trim_start_matches('@')), String::from(text))),
+		None => Err(format!("'{}' is not of the form ID=TEXT", s))
+	}
+}
+
 /// Util function to read stdin to a String
 fn read_stdin(stdin: &std::io::Stdin) -> Result<String, std::io::Error> {
 	let mut input =  Vec::new();
@@ -96,3 +495,36 @@ fn read_stdin(stdin: &std::io::Stdin) -> Result<String, std::io::Error> {
 	String::from_utf8(input)
 		.map_err(|utf_err| std::io::Error::new(ErrorKind::InvalidData, utf_err))
 }
+
+#[cfg(test)]
+mod unit_tests {
+	use std::path::Path;
+	use crate::{load_generator, GeneratorFile};
+
+	#[test]
+	fn test_load_generator() {
+		let mut gen = twas::Interpreter::from_seed(0);
+		let template = load_generator(&mut gen, Path::new("tests/test-data/generator/pet-story.yaml")).unwrap();
+		assert_eq!(template, "${owner} has a ${mood} pet ${animal}.\n");
+		assert_eq!(gen.eval("${owner}").unwrap(), "Alice");
+		assert_eq!(gen.get_lut("mood").unwrap().len(), 2);
+		assert_eq!(gen.get_lut("weather").unwrap().iter().next().unwrap().get_weight(), 3.);
+		assert_eq!(gen.eval("${age} ${trained}").unwrap(), "3 true");
+		assert!(gen.get_lut("").is_none());
+		assert!(gen.get_lut("animal").is_some());
+		let story = gen.eval(template.as_str()).unwrap();
+		assert!(story.starts_with("Alice has a "), "{}", story);
+	}
+
+	#[test]
+	fn test_generator_file_fields() {
+		let generator: GeneratorFile = serde_yaml::from_str("name: Minimal\ntemplate: ${animal}").unwrap();
+		assert_eq!(generator.name.as_deref(), Some("Minimal"));
+		assert!(generator.includes.is_empty());
+		assert!(generator.parameters.is_none());
+		assert!(serde_yaml::from_str::<GeneratorFile>("template: ${animal}\ntitle: Typo").is_err());
+		assert!(load_generator(&mut twas::Interpreter::from_seed(0), Path::new("tests/test-data/generator/missing.yaml")).is_err());
+		assert_eq!(super::parameter_text(&serde_yaml::Value::from(2.5)).as_deref(), Some("2.5"));
+		assert!(super::parameter_text(&serde_yaml::Value::Sequence(Vec::new())).is_none());
+	}
+}
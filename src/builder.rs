@@ -0,0 +1,428 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::HashMap;
+use dicexp::{new_simple_rng, simple_rng};
+use rand::Rng;
+use rand::rngs::StdRng;
+use crate::data::LookUpTable;
+use crate::errors::{ParseError, ParsingError};
+use crate::options::{CompatLevel, CsvOptions, Dedup, EvalOptions, Strictness};
+use crate::Interpreter;
+
+/// Builds a random look-up table step by step. Unlike
+/// [LookUpTable::add_item(...)](crate::data::LookUpTable::add_item), which panics on an invalid
+/// weight, the builder returns an error for weights that are negative, NaN, or infinite.
+/// Duplicate items can be combined and the weights can be normalized when the table is built.
+///
+/// # Example
+/// ```rust
+/// use twas;
+/// let lut = twas::LookUpTableBuilder::new()
+///     .with_item("cat", 3.).unwrap()
+///     .with_items([("dog", 2.), ("cat", 1.)]).unwrap()
+///     .with_dedup(true)
+///     .build().unwrap();
+/// assert_eq!(lut.len(), 2);
+/// let mut interpreter = twas::Interpreter::new();
+/// interpreter.merge_lut("animal", &lut, false).unwrap();
+/// println!("{}", interpreter.eval("I have a ${animal}.").unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LookUpTableBuilder {
+	/// Text, weight, and stable key of each item, in the order they were added
+	items: Vec<(String, f64, Option<String>)>,
+	/// If true, items with the same text (or stable key) are combined into one item
+	dedup: bool,
+	/// If true, the weights are scaled such that they add up to 1
+	normalize: bool
+}
+
+impl LookUpTableBuilder {
+	/// Creates a new builder without any items
+	pub fn new() -> Self {
+		LookUpTableBuilder::default()
+	}
+
+	/// Adds an item
+	/// # Arguments
+	/// * `text`: text of the item
+	/// * `weight`: probability weight of the item (zero or a positive number)
+	/// # Returns
+	/// The builder, or an error if the weight is negative, NaN, or infinite
+	pub fn with_item<T>(mut self, text: T, weight: f64) -> Result<Self, ParsingError> where T: Into<String> {
+		let text = text.into();
+		check_weight(text.as_str(), weight)?;
+		self.items.push((text, weight, None));
+		Ok(self)
+	}
+
+	/// Adds an item with a stable key (see
+	/// [LookUpTable::add_keyed_item(...)](crate::data::LookUpTable::add_keyed_item))
+	/// # Arguments
+	/// * `text`: text of the item
+	/// * `weight`: probability weight of the item (zero or a positive number)
+	/// * `key`: stable key of the item
+	/// # Returns
+	/// The builder, or an error if the weight is negative, NaN, or infinite
+	pub fn with_keyed_item<T, K>(mut self, text: T, weight: f64, key: K) -> Result<Self, ParsingError>
+			where T: Into<String>, K: Into<String> {
+		let text = text.into();
+		check_weight(text.as_str(), weight)?;
+		self.items.push((text, weight, Some(key.into())));
+		Ok(self)
+	}
+
+	/// Adds several items at once
+	/// # Arguments
+	/// * `items`: (text, weight) pairs
+	/// # Returns
+	/// The builder, or an error if any weight is negative, NaN, or infinite (in which case none of
+	/// the items are added)
+	pub fn with_items<T>(mut self, items: impl IntoIterator<Item=(T, f64)>) -> Result<Self, ParsingError> where T: Into<String> {
+		let items: Vec<(String, f64)> = items.into_iter().map(|(text, weight)| (text.into(), weight)).collect();
+		for (text, weight) in &items {
+			check_weight(text.as_str(), *weight)?;
+		}
+		self.items.extend(items.into_iter().map(|(text, weight)| (text, weight, None)));
+		Ok(self)
+	}
+
+	/// Adds several items at once, each with a weight of 1 (eg the lines of a text file)
+	/// # Arguments
+	/// * `lines`: texts of the items
+	pub fn with_lines<T>(mut self, lines: impl IntoIterator<Item=T>) -> Self where T: Into<String> {
+		self.items.extend(lines.into_iter().map(|text| (text.into(), 1., None)));
+		self
+	}
+
+	/// Sets whether items with the same text (or stable key) are combined into one item, whose
+	/// weight is the sum of their weights, such that the probabilities do not change (default is
+	/// false)
+	/// # Arguments
+	/// * `dedup`: true to combine duplicate items
+	pub fn with_dedup(mut self, dedup: bool) -> Self {
+		self.dedup = dedup;
+		self
+	}
+
+	/// Sets whether the weights are scaled such that they add up to 1 (default is false). Note
+	/// that normalized weights are often not simple decimals (eg 1/3), in which case draws use
+	/// floating-point arithmetic (see [LookUpTable::draw_random(...)](crate::data::LookUpTable::draw_random)).
+	/// # Arguments
+	/// * `normalize`: true to normalize the weights
+	pub fn with_normalization(mut self, normalize: bool) -> Self {
+		self.normalize = normalize;
+		self
+	}
+
+	/// Builds the look-up table, which is ready for drawing
+	/// # Returns
+	/// The new look-up table, or an error if the weights are normalized but they add up to zero
+	pub fn build(self) -> Result<LookUpTable, ParsingError> {
+		let mut items = self.items;
+		if self.dedup {
+			let mut index: HashMap<String, usize> = HashMap::new();
+			let mut unique: Vec<(String, f64, Option<String>)> = Vec::with_capacity(items.len());
+			for (text, weight, key) in items {
+				let id = key.clone().unwrap_or_else(|| text.clone());
+				match index.get(&id) {
+					Some(&i) => unique[i].1 += weight,
+					None => {
+						index.insert(id, unique.len());
+						unique.push((text, weight, key));
+					}
+				}
+			}
+			items = unique;
+		}
+		if self.normalize {
+			let total: f64 = items.iter().map(|(_, weight, _)| weight).sum();
+			if total <= 0. || !total.is_finite() {
+				return Err(ParseError{msg: Some(String::from("Cannot normalize look-up table weights that add up to 0")),
+					line: None, col: None}.into());
+			}
+			items.iter_mut().for_each(|(_, weight, _)| *weight /= total);
+		}
+		let mut lut = LookUpTable::new();
+		for (text, weight, key) in items {
+			match key {
+				Some(key) => lut.add_keyed_item(text, weight, key),
+				None => lut.add_item(text, weight)
+			}
+		}
+		Ok(lut)
+	}
+}
+
+/// Builds an [Interpreter] with its configuration collected in one place (see
+/// [Interpreter::builder()](crate::Interpreter::builder)). Every option has the same default as
+/// a new interpreter, and the options can be given in any order: the individual evaluation
+/// options (eg [with_lenient_missing(...)](InterpreterBuilder::with_lenient_missing)) are
+/// applied on top of the compatibility level or [EvalOptions] that were given.
+///
+/// # Example
+/// ```rust
+/// use twas;
+/// let mut interpreter = twas::Interpreter::builder()
+///     .with_compat(twas::CompatLevel::V2)
+///     .with_csv_options(twas::CsvOptions::with_delimiter(';'))
+///     .with_encoding("windows-1252")
+///     .build().unwrap();
+/// interpreter.load_str("pet", "animal;name\ncat;Tom", "csv").unwrap();
+/// assert_eq!(interpreter.eval("${{id: pet/name, count: 2}}").unwrap(), "Tom, Tom");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct InterpreterBuilder {
+	/// Seed of the random number generator, or `None` for a randomly seeded generator
+	seed: Option<u64>,
+	/// Recursion limit, or `None` for the default
+	recursion_limit: Option<usize>,
+	/// Evaluation options that the individual options are applied to
+	options: EvalOptions,
+	/// Lenient mode, or `None` to keep that of the evaluation options
+	lenient_missing: Option<bool>,
+	/// Loose look-up table IDs, or `None` to keep that of the evaluation options
+	loose_ids: Option<bool>,
+	/// Front matter in evaluated texts, or `None` to keep that of the evaluation options
+	front_matter: Option<bool>,
+	/// Strictness, or `None` to keep that of the evaluation options
+	strictness: Option<Strictness>,
+	/// Maximum length of the generated text, or `None` to keep that of the evaluation options
+	output_limit: Option<usize>,
+	/// Maximum number of substitutions, or `None` to keep that of the evaluation options
+	substitution_limit: Option<usize>,
+	/// Name of the text encoding of look-up table files, or `None` to detect the encoding
+	encoding: Option<String>,
+	/// Delimiter and quote character of .csv look-up table files
+	csv_options: CsvOptions,
+	/// Whether directories are loaded lazily
+	lazy_loading: bool,
+	/// How duplicate items are merged when look-up tables are loaded
+	dedup: Dedup
+}
+
+impl InterpreterBuilder {
+	/// Creates a new builder with the default configuration
+	pub fn new() -> Self {
+		InterpreterBuilder::default()
+	}
+
+	/// Seeds the random number generator, such that identical usage with the same seed will
+	/// result in identical results (see [Interpreter::from_seed(...)](crate::Interpreter::from_seed))
+	/// # Arguments
+	/// * `seed`: the seed
+	pub fn with_seed(mut self, seed: u64) -> Self {
+		self.seed = Some(seed);
+		self
+	}
+
+	/// Sets the recursion limit (see
+	/// [Interpreter::set_recursion_limit(...)](crate::Interpreter::set_recursion_limit))
+	/// # Arguments
+	/// * `limit`: the recursion limit
+	pub fn with_recursion_limit(mut self, limit: usize) -> Self {
+		self.recursion_limit = Some(limit);
+		self
+	}
+
+	/// Uses the default evaluation options of the given compatibility level (see
+	/// [Interpreter::set_compat(...)](crate::Interpreter::set_compat))
+	/// # Arguments
+	/// * `compat`: the compatibility level
+	pub fn with_compat(mut self, compat: CompatLevel) -> Self {
+		self.options = EvalOptions::for_compat(compat);
+		self
+	}
+
+	/// Uses the given evaluation options (see
+	/// [Interpreter::set_options(...)](crate::Interpreter::set_options))
+	/// # Arguments
+	/// * `options`: the evaluation options
+	pub fn with_options(mut self, options: EvalOptions) -> Self {
+		self.options = options;
+		self
+	}
+
+	/// Enables or disables lenient mode, in which missing look-up tables and references are
+	/// replaced by a placeholder instead of aborting the evaluation (see
+	/// [Interpreter::set_lenient_missing(...)](crate::Interpreter::set_lenient_missing))
+	/// # Arguments
+	/// * `lenient`: true to enable lenient mode
+	pub fn with_lenient_missing(mut self, lenient: bool) -> Self {
+		self.lenient_missing = Some(lenient);
+		self
+	}
+
+	/// Enables or disables loose look-up table IDs, which ignore letter case, Unicode
+	/// normalization, and `-` vs `_` separators (see
+	/// [Interpreter::set_loose_ids(...)](crate::Interpreter::set_loose_ids))
+	/// # Arguments
+	/// * `loose`: true to enable loose look-up table IDs
+	pub fn with_loose_ids(mut self, loose: bool) -> Self {
+		self.loose_ids = Some(loose);
+		self
+	}
+
+	/// Enables or disables front matter in evaluated texts (see
+	/// [Interpreter::set_front_matter(...)](crate::Interpreter::set_front_matter))
+	/// # Arguments
+	/// * `enabled`: true to enable front matter
+	pub fn with_front_matter(mut self, enabled: bool) -> Self {
+		self.front_matter = Some(enabled);
+		self
+	}
+
+	/// Sets how strictly each kind of problem in a substitution token is treated (see
+	/// [Interpreter::set_strictness(...)](crate::Interpreter::set_strictness))
+	/// # Arguments
+	/// * `strictness`: the policy for each kind of problem
+	pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+		self.strictness = Some(strictness);
+		self
+	}
+
+	/// Limits the length of the generated text (see
+	/// [Interpreter::set_output_limit(...)](crate::Interpreter::set_output_limit))
+	/// # Arguments
+	/// * `limit`: maximum length of the text in bytes
+	pub fn with_output_limit(mut self, limit: usize) -> Self {
+		self.output_limit = Some(limit);
+		self
+	}
+
+	/// Limits the number of substitutions per evaluation (see
+	/// [Interpreter::set_substitution_limit(...)](crate::Interpreter::set_substitution_limit))
+	/// # Arguments
+	/// * `limit`: maximum number of substitutions
+	pub fn with_substitution_limit(mut self, limit: usize) -> Self {
+		self.substitution_limit = Some(limit);
+		self
+	}
+
+	/// Sets the text encoding of look-up table files (see
+	/// [Interpreter::set_encoding(...)](crate::Interpreter::set_encoding)). An unknown encoding
+	/// is reported by [build()](InterpreterBuilder::build).
+	/// # Arguments
+	/// * `label`: name of the encoding (eg `"windows-1252"`)
+	pub fn with_encoding<T>(mut self, label: T) -> Self where T: Into<String> {
+		self.encoding = Some(label.into());
+		self
+	}
+
+	/// Sets the delimiter and quote character of .csv look-up table files (see
+	/// [Interpreter::set_csv_options(...)](crate::Interpreter::set_csv_options))
+	/// # Arguments
+	/// * `options`: delimiter and quote character to use
+	pub fn with_csv_options(mut self, options: CsvOptions) -> Self {
+		self.csv_options = options;
+		self
+	}
+
+	/// Turns lazy loading of directories on or off (see
+	/// [Interpreter::set_lazy_loading(...)](crate::Interpreter::set_lazy_loading))
+	/// # Arguments
+	/// * `lazy`: true to load directories lazily
+	pub fn with_lazy_loading(mut self, lazy: bool) -> Self {
+		self.lazy_loading = lazy;
+		self
+	}
+
+	/// Sets how duplicate items are merged when look-up tables are loaded (see
+	/// [Interpreter::set_dedup(...)](crate::Interpreter::set_dedup))
+	/// # Arguments
+	/// * `mode`: how duplicate items are merged
+	pub fn with_dedup(mut self, mode: Dedup) -> Self {
+		self.dedup = mode;
+		self
+	}
+
+	/// Builds the interpreter, with a random number generator that is seeded with the seed of
+	/// [with_seed(...)](InterpreterBuilder::with_seed) (or randomly if there is none)
+	/// # Returns
+	/// The new interpreter, or an error if the configuration is not valid (eg an unknown text
+	/// encoding)
+	pub fn build(self) -> Result<Interpreter<StdRng>, ParsingError> {
+		let rng = match self.seed {
+			Some(seed) => simple_rng(seed),
+			None => new_simple_rng()
+		};
+		self.build_with_rng(rng)
+	}
+
+	/// Builds the interpreter with the provided random number generator (ignoring the seed of
+	/// [with_seed(...)](InterpreterBuilder::with_seed))
+	/// # Arguments
+	/// * `rng`: the random number generator to use
+	/// # Returns
+	/// The new interpreter, or an error if the configuration is not valid (eg an unknown text
+	/// encoding)
+	pub fn build_with_rng<R: Rng>(self, rng: R) -> Result<Interpreter<R>, ParsingError> {
+		let mut interpreter = Interpreter::from_rng(rng);
+		interpreter.set_encoding(self.encoding.as_deref())?;
+		let mut options = self.options;
+		if let Some(lenient) = self.lenient_missing {
+			options.lenient_missing = lenient;
+		}
+		if let Some(loose) = self.loose_ids {
+			options.loose_ids = loose;
+		}
+		if let Some(front_matter) = self.front_matter {
+			options.front_matter = front_matter;
+		}
+		if let Some(strictness) = self.strictness {
+			options.strictness = strictness;
+		}
+		if self.output_limit.is_some() {
+			options.max_output_len = self.output_limit;
+		}
+		if self.substitution_limit.is_some() {
+			options.max_substitutions = self.substitution_limit;
+		}
+		interpreter.set_options(options);
+		if let Some(limit) = self.recursion_limit {
+			interpreter.set_recursion_limit(limit);
+		}
+		interpreter.set_csv_options(self.csv_options);
+		interpreter.set_lazy_loading(self.lazy_loading);
+		interpreter.set_dedup(self.dedup);
+		Ok(interpreter)
+	}
+}
+
+/// Checks that an item weight is zero or a positive number
+fn check_weight(text: &str, weight: f64) -> Result<(), ParsingError> {
+	if weight >= 0. && weight.is_finite() {
+		Ok(())
+	} else {
+		Err(ParseError{msg: Some(format!("Item '{}' has invalid weight {} (weights must be zero or a positive number)", text, weight)),
+			line: None, col: None}.into())
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::builder::LookUpTableBuilder;
+
+	#[test]
+	fn test_builder() {
+		assert!(LookUpTableBuilder::new().with_item("cat", -1.).is_err());
+		assert!(LookUpTableBuilder::new().with_item("cat", f64::NAN).is_err());
+		assert!(LookUpTableBuilder::new().with_items([("cat", 1.), ("dog", f64::INFINITY)]).is_err());
+		let lut = LookUpTableBuilder::new()
+			.with_lines(["cat", "dog"])
+			.with_keyed_item("Cat", 2., "cat").unwrap()
+			.with_item("dog", 1.).unwrap()
+			.with_dedup(true)
+			.build().unwrap();
+		assert_eq!(lut.len(), 2);
+		assert_eq!(lut.get(0).unwrap().get_weight(), 3.);
+		assert_eq!(lut.get(1).unwrap().get_weight(), 2.);
+		let lut = LookUpTableBuilder::new()
+			.with_items([("cat", 3.), ("dog", 1.)]).unwrap()
+			.with_normalization(true)
+			.build().unwrap();
+		assert_eq!(lut.get(0).unwrap().get_weight(), 0.75);
+		assert_eq!(lut.get(1).unwrap().get_weight(), 0.25);
+		assert!(LookUpTableBuilder::new().with_item("cat", 0.).unwrap().with_normalization(true).build().is_err());
+	}
+}
@@ -0,0 +1,214 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use dicexp::new_simple_rng;
+use rand::Rng;
+use rand::rngs::StdRng;
+use crate::data::LookUpTable;
+use crate::errors::ParsingError;
+use crate::evaluation::EvalStats;
+use crate::function::FunctionRegistry;
+use crate::lazy::LazyRegistry;
+use crate::{apply_front_matter_refs, do_eval_refs, is_rows_table, validate_ref, DrawState, EvalContext, EvalOptions, Interpreter, RefValue};
+
+/// A frozen copy of an interpreter's look-up tables, options, and template functions (see
+/// [Interpreter::freeze()](crate::Interpreter::freeze)) that can be shared between threads (eg
+/// in an `Arc`) without a mutex, since evaluating a text with it only needs `&self` and a
+/// random number generator. Use an [Evaluator] per thread to keep references and depletion
+/// state between evaluations.
+///
+/// # Example
+/// ```rust
+/// use std::sync::Arc;
+/// use twas;
+/// let mut interpreter = twas::Interpreter::new();
+/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).unwrap();
+/// let tables = Arc::new(interpreter.freeze());
+/// let workers: Vec<_> = (0..4).map(|_| {
+///     let tables = Arc::clone(&tables);
+///     std::thread::spawn(move || tables.eval("I have a pet ${animal}.", &mut rand::thread_rng()).unwrap())
+/// }).collect();
+/// for worker in workers {
+///     println!("{}", worker.join().unwrap());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CompiledRegistry {
+	/// Registry of look-up tables
+	registry: HashMap<String, Arc<LookUpTable>>,
+	/// Lazily loaded files and table sources, which are loaded on first use
+	lazy: LazyRegistry,
+	/// Recursion limit
+	recursion_limit: usize,
+	/// Evaluation options
+	options: EvalOptions,
+	/// Template functions
+	functions: FunctionRegistry,
+	/// Depletion modes of look-up tables and the items that were used up when frozen
+	draws: DrawState,
+	/// Persistent references when frozen
+	refs: BTreeMap<String, RefValue>,
+	/// Host variables when frozen
+	vars: BTreeMap<String, String>
+}
+
+impl CompiledRegistry {
+	/// Creates a frozen copy of the given interpreter (see
+	/// [Interpreter::freeze()](crate::Interpreter::freeze))
+	pub(crate) fn from_interpreter<R: Rng>(interpreter: &Interpreter<R>) -> Self {
+		CompiledRegistry{registry: interpreter.registry.clone(), lazy: interpreter.lazy.copy_pending(),
+			recursion_limit: interpreter.recursion_limit, options: interpreter.options.clone(),
+			functions: interpreter.functions.clone(), draws: interpreter.draws.clone(),
+			refs: interpreter.persistent_refs.clone(), vars: interpreter.vars.clone()}
+	}
+
+	/// Evaluates the given text (see [Interpreter::eval(...)](crate::Interpreter::eval)) with the
+	/// given random number generator. Every evaluation starts from the references and depletion
+	/// state that the interpreter had when it was frozen, and references that are saved with the
+	/// `persist` option are forgotten afterwards (use an [Evaluator] to keep them).
+	/// # Arguments
+	/// * `text`: The target text to evaluate.
+	/// * `rng`: The random number generator for the draws and dice rolls.
+	/// # Returns
+	/// The result of the evaluation, or an error if the evaluation fails.
+	pub fn eval<T, G>(&self, text: T, rng: &mut G) -> Result<String, ParsingError> where T: Into<String>, G: Rng {
+		let mut state = self.new_state();
+		self.eval_with(text.into(), HashMap::new(), rng, &mut state)
+	}
+
+	/// Gets the IDs of all look-up tables (not including lazily loaded tables that were not used
+	/// yet)
+	pub fn get_ids(&self) -> impl Iterator<Item=&String> {
+		self.registry.keys().filter(|id| !is_rows_table(id))
+	}
+
+	/// Creates the state of an evaluator, as it was when the interpreter was frozen
+	fn new_state(&self) -> EvalState {
+		EvalState{draws: self.draws.clone(),
+			persistent_refs: self.refs.clone(), warnings: Vec::new()}
+	}
+
+	/// Evaluates the given text
+	/// # Arguments
+	/// * `text`: The target text to evaluate.
+	/// * `preset_refs`: References provided by the host application
+	/// * `rng`: Random number generator for the draws
+	/// * `state`: The state of the evaluator, which is updated
+	fn eval_with<G: Rng>(&self, text: String, preset_refs: HashMap<String, String>, rng: &mut G, state: &mut EvalState) -> Result<String, ParsingError> {
+		for ref_id in preset_refs.keys() {
+			validate_ref(ref_id)?;
+		}
+		state.warnings.clear();
+		let mut refs: HashMap<String, RefValue> = state.persistent_refs.iter()
+			.map(|(ref_id, value)| (ref_id.clone(), value.clone())).collect();
+		refs.extend(preset_refs.into_iter().map(|(ref_id, text)| (ref_id, RefValue{text, key: None, fields: None})));
+		let mut ctx = EvalContext{reg: &self.registry, rng, recursion_limit: self.recursion_limit,
+			options: &self.options, warnings: &mut state.warnings, lazy: &self.lazy, draws: &mut state.draws,
+			persistent_refs: &mut state.persistent_refs, vars: &self.vars, appearances: HashMap::new(), recording: None,
+			functions: &self.functions, hook: None, choices: None, depth: None,
+			stats: EvalStats::default(), tape: None, deadline: None, resolved: HashMap::new()};
+		let body = apply_front_matter_refs(text.as_str(), &mut refs, &self.options);
+		do_eval_refs(String::from(body), 0, &mut ctx, &mut refs, 0)
+	}
+}
+
+/// A lightweight, single-threaded evaluator of the look-up tables of a shared
+/// [CompiledRegistry], with its own random number generator, persistent references, and
+/// depletion state (eg one evaluator per worker thread of a web server)
+///
+/// # Example
+/// ```rust
+/// use std::sync::Arc;
+/// use twas;
+/// let mut interpreter = twas::Interpreter::new();
+/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).unwrap();
+/// let tables = Arc::new(interpreter.freeze());
+/// let mut evaluator = twas::Evaluator::new(Arc::clone(&tables));
+/// println!("{}", evaluator.eval("I have a pet ${{id: animal, ref: pet, persist: true}}.").unwrap());
+/// println!("{}", evaluator.eval("My ${@pet} is hungry.").unwrap());
+/// ```
+#[derive(Debug)]
+pub struct Evaluator<R> where R: Rng {
+	/// The shared look-up tables
+	compiled: Arc<CompiledRegistry>,
+	/// Random number generator for the draws
+	rng: R,
+	/// Dice, references, and depletion state
+	state: EvalState
+}
+
+/// The state that an [Evaluator] keeps between evaluations
+#[derive(Debug)]
+struct EvalState {
+	/// Depletion state
+	draws: DrawState,
+	/// Persistent references
+	persistent_refs: BTreeMap<String, RefValue>,
+	/// Warnings from the most recent evaluation
+	warnings: Vec<String>
+}
+
+impl Evaluator<StdRng> {
+	/// Creates an evaluator with a randomly seeded random number generator
+	/// # Arguments
+	/// * `compiled`: The shared look-up tables
+	pub fn new(compiled: Arc<CompiledRegistry>) -> Self {
+		Evaluator::from_rng(compiled, new_simple_rng())
+	}
+}
+
+impl<R> Evaluator<R> where R: Rng {
+	/// Creates an evaluator using the provided random number generator
+	/// # Arguments
+	/// * `compiled`: The shared look-up tables
+	/// * `rng`: The random number generator to use
+	pub fn from_rng(compiled: Arc<CompiledRegistry>, rng: R) -> Self {
+		let state = compiled.new_state();
+		Evaluator{compiled, rng, state}
+	}
+
+	/// Evaluates the given text (see [Interpreter::eval(...)](crate::Interpreter::eval))
+	/// # Arguments
+	/// * `text`: The target text to evaluate.
+	/// # Returns
+	/// The result of the evaluation, or an error if the evaluation fails.
+	pub fn eval<T>(&mut self, text: T) -> Result<String, ParsingError> where T: Into<String> {
+		self.eval_with_refs(text, HashMap::new())
+	}
+
+	/// Evaluates the given text with references provided by the host application (see
+	/// [Interpreter::eval_with_refs(...)](crate::Interpreter::eval_with_refs))
+	/// # Arguments
+	/// * `text`: The target text to evaluate.
+	/// * `refs`: The text of each known reference, by reference ID (without the `@`)
+	/// # Returns
+	/// The result of the evaluation, or an error if the evaluation fails.
+	pub fn eval_with_refs<T>(&mut self, text: T, refs: HashMap<String, String>) -> Result<String, ParsingError> where T: Into<String> {
+		self.compiled.eval_with(text.into(), refs, &mut self.rng, &mut self.state)
+	}
+
+	/// Gets the warnings from the most recent evaluation (see
+	/// [Interpreter::get_warnings()](crate::Interpreter::get_warnings))
+	pub fn get_warnings(&self) -> &[String] {
+		&self.state.warnings
+	}
+
+	/// Gets the shared look-up tables
+	pub fn get_compiled(&self) -> &Arc<CompiledRegistry> {
+		&self.compiled
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::compiled::{CompiledRegistry, Evaluator};
+
+	fn assert_send_sync<T: Send + Sync>() {}
+
+	#[test]
+	fn test_send_sync() {
+		assert_send_sync::<CompiledRegistry>();
+		assert_send_sync::<Evaluator<rand::rngs::StdRng>>();
+	}
+}
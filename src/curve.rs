@@ -0,0 +1,65 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+
+/// Falloff curve for assigning weights to a ranked list of items, from the most common item to
+/// the rarest one (see [LookUpTable::from_ranked(...)](crate::LookUpTable::from_ranked))
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+	/// Every item has a weight of 1
+	Flat,
+	/// The weights fall off in equal steps, from the number of items for the first item down to 1
+	/// for the last item (eg 3, 2, 1)
+	Linear,
+	/// Each item is the given factor times as likely as the item before it, where the first item
+	/// has a weight of 1 (eg 1, 0.5, 0.25 for a factor of 0.5)
+	Exponential(f64),
+	/// Zipf's law with the given exponent: the weight of the item of rank *n* (starting at 1) is
+	/// 1 / *n*^exponent (eg 1, 1/2, 1/3 for an exponent of 1), which is how often words are used
+	/// in natural languages
+	Zipf(f64)
+}
+
+impl Curve {
+	/// Calculates the weight of an item of the ranked list
+	/// # Arguments
+	/// * `rank`: position of the item in the list, starting at 0 for the most common item
+	/// * `count`: number of items in the list
+	/// # Returns
+	/// The weight of the item
+	/// # Panics
+	/// Panics if the factor of an exponential curve is not a positive number, or the exponent of
+	/// a Zipf curve is negative or not finite
+	pub fn weight(&self, rank: usize, count: usize) -> f64 {
+		match self {
+			Curve::Flat => 1.,
+			Curve::Linear => count.saturating_sub(rank) as f64,
+			Curve::Exponential(factor) => {
+				if !(factor.is_finite() && *factor > 0.) {
+					panic!("Invalid state: exponential curve factor must be a positive number, but was {}", factor);
+				}
+				factor.powi(rank as i32)
+			},
+			Curve::Zipf(exponent) => {
+				if !(exponent.is_finite() && *exponent >= 0.) {
+					panic!("Invalid state: Zipf curve exponent must be zero or a positive number, but was {}", exponent);
+				}
+				1. / ((rank + 1) as f64).powf(*exponent)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::curve::Curve;
+
+	#[test]
+	fn test_curve_weights() {
+		let weights = |curve: Curve| (0..4).map(|rank| curve.weight(rank, 4)).collect::<Vec<f64>>();
+		assert_eq!(weights(Curve::Flat), vec![1., 1., 1., 1.]);
+		assert_eq!(weights(Curve::Linear), vec![4., 3., 2., 1.]);
+		assert_eq!(weights(Curve::Exponential(0.5)), vec![1., 0.5, 0.25, 0.125]);
+		assert_eq!(weights(Curve::Zipf(1.)), vec![1., 0.5, 1. / 3., 0.25]);
+		assert!(std::panic::catch_unwind(|| Curve::Exponential(-1.).weight(1, 2)).is_err());
+	}
+}
@@ -1,18 +1,53 @@
 #![deny(unused_must_use)]
 #![deny(missing_docs)]
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
 use rand::Rng;
 use serde::{Serialize, Deserialize};
-use crate::errors::NoValuesError;
+use regex::Regex;
+use crate::errors::{NoValuesError, ParseError, ParsingError};
+use crate::subspec::SubstitutionOptions;
+use crate::curve::Curve;
+use crate::stats::TableStats;
+use crate::options::Dedup;
 
 /// An item represents an entry in a random look-up table. It has a probability weight and a text
 /// value
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct Item {
 	/// The look-up value (text)
 	text: String,
 	/// The probability weight for drawing this item from the look-up table
-	weight: f64
+	weight: f64,
+	/// Optional inclusive numeric range (min, max) for selecting this item by value instead of at
+	/// random
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	range: Option<(f64, f64)>,
+	/// Optional stable key that identifies this item independently of its (editable) text
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	key: Option<String>,
+	/// Optional weight expression (eg `$region == coast ? 5 : 1`) that replaces the weight at
+	/// draw time
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	weight_expr: Option<String>,
+	/// Optional named fields of a record item (eg `damage: 1d8` and `cost: 15` for a weapon),
+	/// which can be accessed through a reference to the drawn item (eg `${@w.damage}`)
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	fields: Option<BTreeMap<String, String>>,
+	/// Optional requirement expression (eg `$terrain == desert`), such that the item can only be
+	/// drawn when the requirement is met
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	requires: Option<String>,
+	/// Optional maximum number of times that this item can be drawn in a single evaluation (eg 1
+	/// for a legendary item), which takes precedence over the `max-per-eval` option of the draw
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	max_per_eval: Option<usize>,
+	/// Optional inclusive numeric range (min, max) of an item that produces a uniformly random
+	/// number within the range when it is drawn, instead of its text (eg 3 to 18)
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	number_range: Option<(f64, f64)>
 }
 
 impl Item {
@@ -25,6 +60,160 @@ impl Item {
 	/// # Returns
 	/// The probability weight associated with this `Item`.
 	pub fn get_weight(&self) -> f64 {self.weight}
+
+	/// Get the numeric range covered by this item, if any.
+	/// # Returns
+	/// The inclusive (min, max) range associated with this `Item`, or `None` if this item is not
+	/// selected by value.
+	pub fn get_range(&self) -> Option<(f64, f64)> {self.range}
+
+	/// Get the stable key of the item, if any.
+	/// # Returns
+	/// The key associated with this `Item`, or `None` if the item was not given a key.
+	pub fn get_key(&self) -> Option<&String> {self.key.as_ref()}
+
+	/// Get the identifier for this item, which is its stable key if it has one, otherwise its text.
+	/// # Returns
+	/// A reference to the key (or text) of this `Item`.
+	pub fn get_id(&self) -> &String {self.key.as_ref().unwrap_or(&self.text)}
+
+	/// Get the weight expression of the item, if any.
+	/// # Returns
+	/// The conditional weight expression of this `Item`, or `None` if the item has a fixed weight.
+	pub fn get_weight_expr(&self) -> Option<&String> {self.weight_expr.as_ref()}
+
+	/// Get the ID of the look-up table that this item links to, if any. An item whose text is
+	/// `@table:` followed by a look-up table ID (eg `@table:monsters/undead`) is replaced by an
+	/// item drawn from that look-up table whenever it is drawn.
+	/// # Returns
+	/// The ID of the linked look-up table, or `None` if this `Item` is not a link.
+	pub fn get_table_link(&self) -> Option<&str> {
+		self.text.strip_prefix(TABLE_LINK).map(|id| id.trim())
+	}
+
+	/// Get the requirement expression of the item, if any.
+	/// # Returns
+	/// The expression that must be true for this `Item` to be drawn (eg `$terrain == desert`), or
+	/// `None` if the item can always be drawn.
+	pub fn get_requires(&self) -> Option<&String> {self.requires.as_ref()}
+
+	/// Gets the maximum number of times that this item can be drawn in a single evaluation, if
+	/// the item has its own limit
+	pub fn get_max_per_eval(&self) -> Option<usize> {self.max_per_eval}
+
+	/// Gets the inclusive numeric range (min, max) of an item that produces a random number when
+	/// it is drawn (see [LookUpTable::add_number_item(...)](LookUpTable::add_number_item)), if it
+	/// is such an item
+	pub fn get_number_range(&self) -> Option<(f64, f64)> {self.number_range}
+
+	/// Rolls the random number of an item with a numeric range (see
+	/// [get_number_range()](Item::get_number_range)). The number has as many decimal places as
+	/// the bounds of the range (eg a whole number for 3 to 18, or one decimal for 0.5 to 2.5).
+	/// # Arguments
+	/// * `rng` - A random number generator implementing the `Rng` trait.
+	/// # Returns
+	/// An item whose text is the random number, or `None` if this item has no numeric range.
+	pub fn roll_number(&self, rng: &mut impl Rng) -> Option<Item> {
+		let (min, max) = self.number_range?;
+		let places = decimal_places(min).unwrap_or(MAX_INT_PLACES).max(decimal_places(max).unwrap_or(MAX_INT_PLACES));
+		let scale = 10f64.powi(places as i32);
+		let number = rng.gen_range((min * scale).round() as i64..=(max * scale).round() as i64) as f64 / scale;
+		Some(self.to_number_item(format!("{:.*}", places as usize, number)))
+	}
+
+	/// Makes a copy of this number range item (see [get_number_range()](Item::get_number_range))
+	/// whose text is the given number, as if the number was rolled
+	pub(crate) fn to_number_item(&self, number: String) -> Item {
+		Item{text: number, number_range: None, ..self.clone()}
+	}
+
+	/// Get the named fields of the item, if it is a record.
+	/// # Returns
+	/// The fields of this `Item` by name, or `None` if the item is not a record.
+	pub fn get_fields(&self) -> Option<&BTreeMap<String, String>> {self.fields.as_ref()}
+
+	/// Get the value of a named field of the item. Fields from the `meta` mapping of a YAML or
+	/// JSON item can also be named with a `meta.` prefix (eg "meta.cr").
+	/// # Arguments
+	/// * `name` - The name of the field (eg "damage").
+	/// # Returns
+	/// The value of the field, or `None` if the item does not have a field with that name.
+	pub fn get_field(&self, name: &str) -> Option<&String> {
+		self.fields.as_ref().and_then(|fields| field_value(fields, name))
+	}
+
+	/// Makes a copy of this item whose text is the value of the given field (eg to output the
+	/// challenge rating of a drawn monster instead of its name)
+	/// # Returns
+	/// The copy, or `None` if the item does not have a field with that name
+	pub(crate) fn to_field_item(&self, name: &str) -> Option<Item> {
+		self.get_field(name).map(|value| Item{text: value.clone(), ..self.clone()})
+	}
+}
+
+/// Gets the value of a named field of a record, where fields from the `meta` mapping of a YAML or
+/// JSON item can also be named with a `meta.` prefix (eg "meta.cr" for field "cr")
+pub(crate) fn field_value<'a>(fields: &'a BTreeMap<String, String>, name: &str) -> Option<&'a String> {
+	fields.get(name).or_else(|| name.strip_prefix("meta.").and_then(|name| fields.get(name)))
+}
+
+/// Duplicate items of a look-up table that were merged into one item, as reported by
+/// [dedup(...)](LookUpTable::dedup)
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergedDuplicate {
+	/// Text of the merged item
+	pub text: String,
+	/// Number of duplicate items that were merged (at least 2)
+	pub count: usize,
+	/// Weight of the merged item
+	pub weight: f64
+}
+
+/// Prefix of the text of an item that links to another look-up table (see
+/// [Item::get_table_link()](Item::get_table_link))
+pub(crate) const TABLE_LINK: &str = "@table:";
+
+/// A likely authoring mistake in the weights of a random look-up table, as found by
+/// [audit_weights(...)](LookUpTable::audit_weights)
+#[derive(Clone, Debug, PartialEq)]
+pub enum WeightIssue {
+	/// The table mixes very large and very small weights (eg 1000 and 0.1)
+	MixedScale {
+		/// Smallest non-zero weight in the table
+		smallest: f64,
+		/// Largest weight in the table
+		largest: f64
+	},
+	/// The item has a weight of zero, so it can never be drawn
+	ZeroWeight {
+		/// Text of the item
+		text: String
+	},
+	/// The item has an infinite weight
+	InfiniteWeight {
+		/// Text of the item
+		text: String
+	},
+	/// More than 99% of the probability is concentrated in one item
+	Dominant {
+		/// Text of the item
+		text: String,
+		/// Probability of drawing the item (0 to 1)
+		share: f64
+	}
+}
+
+impl Display for WeightIssue {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			WeightIssue::MixedScale{smallest, largest} => write!(f,
+				"weights range from {} to {} (ratio of {})", smallest, largest, largest / smallest),
+			WeightIssue::ZeroWeight{text} => write!(f, "item '{}' has a weight of 0 and will never be drawn", text),
+			WeightIssue::InfiniteWeight{text} => write!(f, "item '{}' has an infinite weight", text),
+			WeightIssue::Dominant{text, share} => write!(f,
+				"item '{}' is drawn {:.2}% of the time", text, share * 100.)
+		}
+	}
 }
 
 /// A random lookup table that holds items with associated weights for random selection.
@@ -33,17 +222,91 @@ impl Item {
 pub struct LookUpTable {
 	items: Vec<Item>,
 	total: f64,
-	equal_weights: bool
+	equal_weights: bool,
+	/// Number of decimal places needed to express every weight as an integer, or `None` if the
+	/// weights are not all simple decimals (in which case draws use floating-point arithmetic)
+	#[serde(default)]
+	int_places: Option<u32>,
+	/// Running totals of the weights (the sum of the weight of each item and the weights of all
+	/// items before it), for drawing with a binary search instead of scanning all items
+	#[serde(skip)]
+	cumulative: Vec<f64>,
+	/// Running totals of the weights scaled to integers (see `int_places`), for exact draws
+	#[serde(skip)]
+	int_cumulative: Vec<u64>,
+	/// Default substitution options (eg `case: title`) that apply whenever this table is drawn
+	/// from, unless the substitution token sets them
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	defaults: Option<SubstitutionOptions>
+}
+
+/// Largest number of decimal places for which weights are drawn with exact integer arithmetic
+const MAX_INT_PLACES: u32 = 6;
+
+/// Finds the number of decimal places of a weight (eg 2 for 0.25), or `None` if the weight is not
+/// a simple decimal number
+fn decimal_places(weight: f64) -> Option<u32> {
+	(0..=MAX_INT_PLACES).find(|places| {
+		let scale = 10f64.powi(*places as i32);
+		let scaled = (weight * scale).round();
+		scaled < 2f64.powi(53) && scaled / scale == weight
+	})
+}
+
+/// Calculates the running totals of the weights of the given items scaled to integers with the
+/// given number of decimal places (see [decimal_places(...)](decimal_places)), starting from the
+/// given sum, or returns `None` if the totals are too large for a `u64`
+fn int_running_totals(items: &[Item], places: u32, start: u64) -> Option<Vec<u64>> {
+	let scale = 10f64.powi(places as i32);
+	let mut sum = start;
+	items.iter().map(|item| {
+		sum = sum.checked_add((item.weight * scale).round() as u64)?;
+		Some(sum)
+	}).collect()
 }
 
 impl LookUpTable {
 	/// Creates a new, empty `LookUpTable` with default settings.
 	pub fn new() -> Self {
-		LookUpTable {items: Vec::new(), total: 0., equal_weights: true}
+		LookUpTable {items: Vec::new(), total: 0., equal_weights: true, int_places: Some(0),
+			cumulative: Vec::new(), int_cumulative: Vec::new(), defaults: None}
+	}
+
+	/// Creates a new lookup table from a ranked list of items, from the most common item to the
+	/// rarest one, where the weights are assigned by a falloff curve instead of by hand.
+	/// # Arguments
+	/// * `items` - The text values of the items, in order of rank (accepts both &str and String).
+	/// * `curve` - The falloff curve that assigns the weights (eg `Curve::Exponential(0.5)`).
+	/// # Returns
+	/// A new `LookUpTable` with one item per text value.
+	/// # Panics
+	/// Panics if the parameter of the curve is not valid (see [Curve::weight(...)](Curve::weight)).
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// use twas::{Curve, LookUpTable};
+	/// let gems = LookUpTable::from_ranked(["quartz", "garnet", "ruby", "diamond"], Curve::Exponential(0.5));
+	/// assert_eq!(gems.get(3).unwrap().get_weight(), 0.125);
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.merge_lut("gem", &gems, false).unwrap();
+	/// println!("{}", interpreter.eval("You find a ${gem}.").unwrap());
+	/// ```
+	pub fn from_ranked<T>(items: impl IntoIterator<Item=T>, curve: Curve) -> Self where T: Into<String> {
+		let items: Vec<String> = items.into_iter().map(|text| text.into()).collect();
+		let count = items.len();
+		let mut lut = LookUpTable::new();
+		for (rank, text) in items.into_iter().enumerate() {
+			lut.add_item(text, curve.weight(rank, count));
+		}
+		lut
 	}
 
 	/// Draws one item at random from the lookup table or returns a `NoValuesError` if there are
-	/// no items to draw from.
+	/// no items to draw from. If all weights are integers or simple decimals (up to 6 decimal
+	/// places, eg 0.25), then the draw uses exact integer arithmetic, such that the same seed
+	/// draws the same item on every platform. Items are found with a binary search over the
+	/// running totals of the weights, so drawing from a large table is fast.
 	/// # Arguments
 	/// * `rng` - A random number generator implementing the `Rng` trait.
 	/// # Returns
@@ -51,19 +314,38 @@ impl LookUpTable {
 	pub fn draw_random(&self, rng: &mut impl Rng) -> Result<Item, NoValuesError> {
 		if self.items.len() == 0 {return Err(NoValuesError{});}
 		if self.equal_weights {
+			// items of weight zero are never drawn, even if all weights are zero
+			if self.total <= 0. {return Err(NoValuesError{});}
 			// simple integer draw
-			let i = rng.gen_range(0..self.items.len());
+			let i = rng.gen_range(0..self.items.len() as u64) as usize;
+			Ok(self.items[i].clone())
+		} else if let Some(int_cumulative) = self.int_places.and_then(|places|
+				match self.int_cumulative.len() == self.items.len() {
+					true => Some(Cow::Borrowed(&self.int_cumulative)),
+					// running totals are not kept after deserialization
+					false => int_running_totals(&self.items, places, 0).map(Cow::Owned)
+				}) {
+			// exact integer draw
+			let int_total: u64 = *int_cumulative.last().unwrap();
+			if int_total == 0 {return Err(NoValuesError{});}
+			let draw = rng.gen_range(0..int_total);
+			let i = int_cumulative.partition_point(|sum| *sum <= draw);
 			Ok(self.items[i].clone())
 		} else {
-			let mut draw = self.total * rng.gen_range(0f64..1f64);
-			for item in &self.items {
-				if draw <= item.weight {
-					return Ok(item.clone());
+			let computed: Vec<f64>;
+			let cumulative = match self.cumulative.len() == self.items.len() {
+				true => &self.cumulative,
+				false => {
+					computed = self.items.iter().scan(0f64, |sum, item| {
+						*sum += item.weight;
+						Some(*sum)
+					}).collect();
+					&computed
 				}
-				draw -= item.weight;
-			}
-			assert!(false, "Logic violation. Output of random number generator exceeded range of 0-1");
-			return Ok(self.items.last().unwrap().clone())
+			};
+			let draw = self.total * rng.gen_range(0f64..1f64);
+			let i = cumulative.partition_point(|sum| *sum < draw);
+			Ok(self.items[i.min(self.items.len() - 1)].clone())
 		}
 	}
 
@@ -82,16 +364,16 @@ impl LookUpTable {
 		Ok(result)
 	}
 
-	/// Shuffles all the items and returns the shuffled list or returns a NoValuesError is there are
-	/// no items to draw from
+	/// Shuffles all the items (except for items of weight zero, which are never drawn) and returns
+	/// the shuffled list or returns a NoValuesError is there are no items to draw from
 	/// # Arguments
 	/// * `rng` - A random number generator implementing the `Rng` trait.
 	/// * `count` - The number of items to draw.
 	/// # Returns
 	/// Returns a vector of randomly selected `Item`s or a `NoValuesError` if the table is empty.
 	pub fn shuffle(&self, rng: &mut impl Rng) -> Result<Vec<Item>, NoValuesError> {
-		if self.items.len() == 0 {return Err(NoValuesError{});}
-		let mut copy = self.items.clone();
+		let mut copy: Vec<Item> = self.items.iter().filter(|item| item.weight > 0.).cloned().collect();
+		if copy.is_empty() {return Err(NoValuesError{});}
 		for i in copy.len()-1 .. 1 {
 			let j = rng.gen_range(0..=i);
 			copy.swap(j, i);
@@ -107,8 +389,8 @@ impl LookUpTable {
 	/// # Returns
 	/// Returns a vector of randomly selected `Item`s or a `NoValuesError` if the table is empty.
 	pub fn shuffle_draw(&self, rng: &mut impl Rng, count: usize) ->  Result<Vec<Item>, NoValuesError> {
-		if self.items.len() == 0 {return Err(NoValuesError{});}
-		let s = self.items.len();
+		let s = self.items.iter().filter(|item| item.weight > 0.).count();
+		if s == 0 {return Err(NoValuesError{});}
 		let mut buffer: Vec<Item> = Vec::with_capacity(s * (1 + (count % s)));
 		while buffer.len() < count {
 			buffer.extend(self.shuffle(rng)?);
@@ -117,6 +399,45 @@ impl LookUpTable {
 		Ok(buffer)
 	}
 
+	/// Checks the weights of this table for common authoring mistakes: a mix of very large and
+	/// very small weights, items with a weight of zero or infinity, and tables where more than 99%
+	/// of the probability is concentrated in one item. Items with conditional weights are ignored,
+	/// as their weights are only known at draw time.
+	/// # Arguments
+	/// * `max_ratio` - Largest acceptable ratio between the largest and smallest non-zero weight.
+	/// # Returns
+	/// Returns a list of the issues that were found (empty if the weights look fine).
+	pub fn audit_weights(&self, max_ratio: f64) -> Vec<WeightIssue> {
+		let mut issues: Vec<WeightIssue> = Vec::new();
+		let fixed: Vec<&Item> = self.items.iter().filter(|item| item.weight_expr.is_none()).collect();
+		let mut smallest = f64::INFINITY;
+		let mut largest = 0f64;
+		let mut total = 0f64;
+		for item in &fixed {
+			if item.weight == 0. {
+				issues.push(WeightIssue::ZeroWeight{text: item.text.clone()});
+			} else if item.weight.is_infinite() {
+				issues.push(WeightIssue::InfiniteWeight{text: item.text.clone()});
+			} else {
+				smallest = smallest.min(item.weight);
+				largest = largest.max(item.weight);
+				total += item.weight;
+			}
+		}
+		if largest > 0. && largest / smallest > max_ratio {
+			issues.push(WeightIssue::MixedScale{smallest, largest});
+		}
+		if fixed.len() > 1 && fixed.len() == self.items.len() {
+			for item in &fixed {
+				let share = item.weight / total;
+				if item.weight.is_finite() && share > 0.99 {
+					issues.push(WeightIssue::Dominant{text: item.text.clone(), share});
+				}
+			}
+		}
+		issues
+	}
+
 	/// Adds an item to the lookup table.
 	/// # Arguments
 	/// * `item` - The `Item` to add to the table.
@@ -124,11 +445,38 @@ impl LookUpTable {
 	/// Panics if the item's weight is negative or NaN.
 	pub fn add(&mut self, item: Item) {
 		if item.weight >= 0. {
+			if self.cumulative.len() != self.items.len() {
+				// running totals are not kept after deserialization
+				self.update_cumulative(0);
+			}
 			let w = item.weight;
 			if self.items.len() > 0 {
 				self.equal_weights = self.equal_weights && self.items.last().unwrap().weight == w;
 			}
 			self.total += w;
+			self.cumulative.push(self.total);
+			let mut places = self.int_places.and_then(|p| decimal_places(w).map(|d| p.max(d)));
+			if let (Some(old), Some(new)) = (self.int_places, places) {
+				// more decimal places may be needed, so scale up the running totals (the last
+				// running total is the largest, so the others fit if it does)
+				let factor = 10u64.pow(new - old);
+				let sum = self.int_cumulative.last().copied().unwrap_or(0).checked_mul(factor)
+					.and_then(|sum| sum.checked_add((w * 10f64.powi(new as i32)).round() as u64));
+				match sum {
+					Some(sum) => {
+						if factor > 1 {
+							self.int_cumulative.iter_mut().for_each(|sum| *sum *= factor);
+						}
+						self.int_cumulative.push(sum);
+					},
+					// too large for exact integer arithmetic
+					None => places = None
+				}
+			}
+			if places.is_none() {
+				self.int_cumulative.clear();
+			}
+			self.int_places = places;
 			self.items.push(item);
 		} else {
 			// do not add negative or NaN weighted items
@@ -136,6 +484,55 @@ impl LookUpTable {
 		}
 	}
 
+	/// Adds all items of another lookup table to the end of this lookup table.
+	/// # Arguments
+	/// * `other` - The lookup table whose items are added.
+	pub(crate) fn append(&mut self, other: LookUpTable) {
+		for item in other.items {
+			self.add(item);
+		}
+	}
+
+	/// Removes one equal item for each item of another lookup table (eg the items that a file
+	/// added, when the file is re-loaded)
+	pub(crate) fn subtract(&mut self, other: &LookUpTable) {
+		let mut remove: Vec<bool> = vec![false; self.items.len()];
+		for item in &other.items {
+			if let Some(i) = (0..self.items.len()).find(|&i| !remove[i] && self.items[i] == *item) {
+				remove[i] = true;
+			}
+		}
+		let mut i = 0;
+		self.remove_where(|_| {
+			i += 1;
+			remove[i - 1]
+		});
+	}
+
+	/// Adds all items of another lookup table to this lookup table (eg to combine a base table
+	/// pack with an expansion pack).
+	/// # Arguments
+	/// * `other` - The lookup table whose items are added.
+	/// * `sum_duplicates` - If `true`, then an item of `other` with the same text (or stable key)
+	///   as an item of this table is not added as a separate item, but instead its weight is added
+	///   to the weight of the existing item. Items with a weight expression, a requirement, a
+	///   numeric range, or a `max-per-eval` limit are always added separately.
+	pub fn merge(&mut self, other: &LookUpTable, sum_duplicates: bool) {
+		for item in &other.items {
+			let summable = |item: &Item| item.weight_expr.is_none() && item.requires.is_none() && item.range.is_none()
+				&& item.max_per_eval.is_none() && item.number_range.is_none();
+			let existing = match sum_duplicates && summable(item) {
+				true => self.items.iter_mut().find(|mine| mine.get_id() == item.get_id() && summable(mine)),
+				false => None
+			};
+			match existing {
+				Some(mine) => mine.weight += item.weight,
+				None => self.items.push(item.clone())
+			}
+		}
+		self.recount();
+	}
+
 	/// Adds an item to the lookup table by specifying its text and weight.
 	/// # Arguments
 	/// * `text` - The text value for the new item (accepts both &str and String).
@@ -143,48 +540,471 @@ impl LookUpTable {
 	/// # Panics
 	/// Panics if the item's weight is negative or NaN.
 	pub fn add_item<T>(&mut self, text: T, weight: f64) where T: Into<String> {
-		self.add(Item{text: text.into(), weight})
+		self.add(Item{text: text.into(), weight, range: None, key: None, weight_expr: None, fields: None, requires: None, max_per_eval: None, number_range: None})
+	}
+
+	/// Adds an item with a stable key to the lookup table. References to this item use the key
+	/// instead of the text, such that the text can be edited without breaking anything that
+	/// refers to the item.
+	/// # Arguments
+	/// * `text` - The text value for the new item (accepts both &str and String).
+	/// * `weight` - The weight for the new item.
+	/// * `key` - The stable key for the new item (accepts both &str and String).
+	/// # Panics
+	/// Panics if the item's weight is negative or NaN.
+	pub fn add_keyed_item<T, K>(&mut self, text: T, weight: f64, key: K) where T: Into<String>, K: Into<String> {
+		self.add(Item{text: text.into(), weight, range: None, key: Some(key.into()), weight_expr: None, fields: None, requires: None, max_per_eval: None, number_range: None})
+	}
+
+	/// Adds an item whose weight is calculated at draw time from an expression that depends on
+	/// references (eg `$region == coast ? 5 : 1`). The item has a weight of 1 until the
+	/// expression is evaluated.
+	/// # Arguments
+	/// * `text` - The text value for the new item (accepts both &str and String).
+	/// * `weight_expr` - The weight expression for the new item (accepts both &str and String).
+	/// * `key` - Optional stable key for the new item.
+	pub fn add_conditional_item<T, E>(&mut self, text: T, weight_expr: E, key: Option<String>) where T: Into<String>, E: Into<String> {
+		self.add(Item{text: text.into(), weight: 1., range: None, key, weight_expr: Some(weight_expr.into()), fields: None, requires: None, max_per_eval: None, number_range: None})
+	}
+
+	/// Adds a record item, which has named fields (eg `damage` and `cost`) in addition to its
+	/// text. The fields of a drawn record can be accessed through a reference to it (eg
+	/// `${weapons@w}` and then `${@w.damage}`).
+	/// # Arguments
+	/// * `text` - The text value for the new item (accepts both &str and String).
+	/// * `weight` - The weight for the new item.
+	/// * `key` - Optional stable key for the new item.
+	/// * `fields` - The named fields of the new item.
+	/// # Panics
+	/// Panics if the item's weight is negative or NaN.
+	pub fn add_record<T>(&mut self, text: T, weight: f64, key: Option<String>, fields: BTreeMap<String, String>) where T: Into<String> {
+		self.add(Item{text: text.into(), weight, range: None, key, weight_expr: None, fields: Some(fields), requires: None, max_per_eval: None, number_range: None})
+	}
+
+	/// Sets the named fields of the most recently added item (eg a record with a conditional
+	/// weight)
+	pub(crate) fn set_last_item_fields(&mut self, fields: BTreeMap<String, String>) {
+		if let Some(item) = self.items.last_mut() {
+			item.fields = Some(fields);
+		}
+	}
+
+	/// Sets the requirement expression of the most recently added item (eg `$terrain == desert`),
+	/// such that the item is left out by [resolve_weights(...)](LookUpTable::resolve_weights)
+	/// when the requirement is not met
+	pub(crate) fn set_last_item_requires(&mut self, requires: String) {
+		if let Some(item) = self.items.last_mut() {
+			item.requires = Some(requires);
+		}
+	}
+
+	/// Sets the maximum number of times that the most recently added item can be drawn in a single
+	/// evaluation (eg 1 for a legendary item)
+	pub(crate) fn set_last_item_max_per_eval(&mut self, max: usize) {
+		if let Some(item) = self.items.last_mut() {
+			item.max_per_eval = Some(max);
+		}
+	}
+
+	/// Turns the most recently added item into an item that produces a random number within the
+	/// given inclusive range when it is drawn (see [add_number_item(...)](LookUpTable::add_number_item))
+	pub(crate) fn set_last_item_number_range(&mut self, min: f64, max: f64) {
+		if let Some(item) = self.items.last_mut() {
+			item.number_range = Some((min, max));
+		}
+	}
+
+	/// Checks whether any item of this table limits how many times it can be drawn in a single
+	/// evaluation (see [Item::get_max_per_eval()])
+	pub fn has_item_caps(&self) -> bool {
+		self.items.iter().any(|item| item.max_per_eval.is_some())
+	}
+
+	/// Calculates summary statistics of this table, such as the number of items and the Shannon
+	/// entropy of a draw (see [TableStats]). Items with a weight expression count with their
+	/// placeholder weight of 1.
+	/// # Returns
+	/// The statistics of this table
+	pub fn stats(&self) -> TableStats {
+		TableStats::from_weights(self.items.iter().map(|item| item.weight))
+	}
+
+	/// Lists the IDs of the look-up tables that the items of this table link to (see
+	/// [Item::get_table_link()](Item::get_table_link)), without duplicates
+	/// # Returns
+	/// The linked look-up table IDs, in the order of the items
+	pub fn linked_ids(&self) -> Vec<&str> {
+		let mut ids: Vec<&str> = Vec::new();
+		for id in self.items.iter().filter_map(|item| item.get_table_link()) {
+			if !ids.contains(&id) {
+				ids.push(id);
+			}
+		}
+		ids
+	}
+
+	/// Checks whether any item in this table has a conditional weight expression or a
+	/// requirement.
+	/// # Returns
+	/// Returns `true` if the weights must be resolved with
+	/// [resolve_weights(...)](LookUpTable::resolve_weights) before drawing.
+	pub fn has_weight_exprs(&self) -> bool {
+		self.items.iter().any(|item| item.weight_expr.is_some() || item.requires.is_some())
+	}
+
+	/// Creates a copy of this lookup table where every conditional weight expression has been
+	/// replaced by its evaluated weight. Items with a weight of zero are left out, as they can
+	/// never be drawn, and so are items whose requirement is not met.
+	/// # Arguments
+	/// * `eval` - Function that evaluates a weight expression to a non-negative weight.
+	/// * `check` - Function that evaluates a requirement expression to `true` (met) or `false`.
+	/// # Returns
+	/// Returns the resolved `LookUpTable`, or the first error returned by `eval` or `check`.
+	pub fn resolve_weights<E>(&self, eval: impl FnMut(&str) -> Result<f64, E>,
+			check: impl FnMut(&str) -> Result<bool, E>) -> Result<LookUpTable, E> {
+		Ok(self.with_resolved_weights(&self.resolved_weights(eval, check)?))
+	}
+
+	/// Evaluates the conditional weight expression and requirement of every item, without
+	/// copying the look-up table (see [resolve_weights(...)](LookUpTable::resolve_weights)).
+	/// # Arguments
+	/// * `eval` - Function that evaluates a weight expression to a non-negative weight.
+	/// * `check` - Function that evaluates a requirement expression to `true` (met) or `false`.
+	/// # Returns
+	/// Returns the weight of each item in order (zero for items whose requirement is not met), or
+	/// the first error returned by `eval` or `check`.
+	pub fn resolved_weights<E>(&self, mut eval: impl FnMut(&str) -> Result<f64, E>,
+			mut check: impl FnMut(&str) -> Result<bool, E>) -> Result<Vec<f64>, E> {
+		let mut weights = Vec::with_capacity(self.items.len());
+		for item in &self.items {
+			if let Some(requires) = &item.requires {
+				if !check(requires.as_str())? {
+					weights.push(0.);
+					continue;
+				}
+			}
+			weights.push(match &item.weight_expr {
+				None => item.weight,
+				Some(expr) => eval(expr.as_str())?
+			});
+		}
+		Ok(weights)
+	}
+
+	/// Creates a copy of this lookup table with the given weights (eg from
+	/// [resolved_weights(...)](LookUpTable::resolved_weights)) instead of the weight
+	/// expressions and requirements of the items. Items with a weight of zero are left out.
+	/// # Arguments
+	/// * `weights` - The weight of each item, in order.
+	/// # Returns
+	/// Returns the new `LookUpTable`.
+	pub fn with_resolved_weights(&self, weights: &[f64]) -> LookUpTable {
+		let mut resolved = LookUpTable::new();
+		resolved.defaults = self.defaults.clone();
+		for (item, weight) in self.items.iter().zip(weights.iter().copied()) {
+			if weight > 0. {
+				resolved.add(Item{weight, weight_expr: None, requires: None, ..item.clone()});
+			}
+		}
+		resolved
+	}
+
+	/// Adds an item that produces a uniformly random number within the given inclusive range each
+	/// time it is drawn (eg 3 to 18 for a character's age, or 50 to 200 for the value of a
+	/// treasure), instead of enumerating every number as a separate item. The text of the item is
+	/// the range (eg "3-18").
+	/// # Arguments
+	/// * `min` - The smallest number.
+	/// * `max` - The largest number.
+	/// * `weight` - The probability weight of the item.
+	/// # Panics
+	/// Panics if the bounds are not finite numbers or `min` is greater than `max`.
+	pub fn add_number_item(&mut self, min: f64, max: f64, weight: f64) {
+		assert!(min.is_finite() && max.is_finite() && min <= max, "Invalid number range {} to {}", min, max);
+		self.add(Item{text: format!("{}-{}", min, max), weight, number_range: Some((min, max)), ..Default::default()});
+	}
+
+	/// Adds an item that covers an inclusive numeric range, such that it can be selected by value
+	/// with [lookup_value(...)](LookUpTable::lookup_value) (eg "mighty" for strength 10 to 12).
+	/// When drawn at random, the weight of the item is the number of whole values in the range
+	/// (eg 5 for the d100 range 1 to 5), or 1 if the range is open-ended or not whole numbers.
+	/// # Arguments
+	/// * `text` - The text value for the new item (accepts both &str and String).
+	/// * `min` - The lowest value (inclusive) that selects this item.
+	/// * `max` - The highest value (inclusive) that selects this item.
+	pub fn add_range_item<T>(&mut self, text: T, min: f64, max: f64) where T: Into<String> {
+		let weight = if max.is_finite() && min.fract() == 0. && max.fract() == 0. && max >= min {
+			max - min + 1.
+		} else {
+			1.
+		};
+		self.add(Item{text: text.into(), weight, range: Some((min, max)), key: None, weight_expr: None, fields: None, requires: None, max_per_eval: None, number_range: None})
 	}
 
-	/// Removes an item from the lookup table based on its text value.
+	/// Finds the first item whose numeric range contains the given value.
 	/// # Arguments
-	/// * `text` - The text value to search for and remove (accepts both &str and String).
+	/// * `value` - The value to look up.
 	/// # Returns
-	/// Returns `true` if an item matching the given text was found and removed, otherwise `false`.
+	/// Returns the matching `Item`, or `None` if no item covers the given value.
+	pub fn lookup_value(&self, value: f64) -> Option<&Item> {
+		self.items.iter().find(|item| match item.range {
+			None => false,
+			Some((min, max)) => value >= min && value <= max
+		})
+	}
+
+	/// Finds an item by its stable key or, if no item has that key, by its text, regardless of its
+	/// weight. This way items of weight zero, which are never drawn at random, can still be
+	/// selected explicitly.
+	/// # Arguments
+	/// * `text` - The stable key or text of the item.
+	/// # Returns
+	/// Returns the matching `Item`, or `None` if there is no such item.
+	pub fn find_item(&self, text: &str) -> Option<&Item> {
+		self.items.iter().find(|item| item.key.as_deref() == Some(text))
+			.or_else(|| self.items.iter().find(|item| item.text == text))
+	}
+
+	/// Removes an item from the lookup table based on its text value or its stable key.
+	/// # Arguments
+	/// * `text` - The text value or key to search for and remove (accepts both &str and String).
+	/// # Returns
+	/// Returns `true` if an item matching the given text or key was found and removed, otherwise
+	/// `false`.
 	pub fn remove_item<T>(&mut self, text: T) -> bool where T: Into<String> {
 		let text = text.into();
-		let mut removed = false;
-		let mut i = self.items.len();
-		while i > 0 {
-			i -= 1;
-			if &self.items[i].text == &text {
-				removed = true;
-				self.items.remove(i);
+		self.remove_where(|item| item.text == text || item.key.as_ref() == Some(&text)) > 0
+	}
+
+	/// Merges duplicate items (items with the same text or stable key) into one item, which keeps
+	/// the place of the first duplicate. Items with a weight expression, a requirement, a numeric
+	/// range, record fields, or a `max-per-eval` limit are never merged.
+	/// # Arguments
+	/// * `mode` - How to combine the weights of the duplicates (see [Dedup]).
+	/// # Returns
+	/// Returns the merged items (empty if there were no duplicates or the mode is `Dedup::Keep`).
+	pub fn dedup(&mut self, mode: Dedup) -> Vec<MergedDuplicate> {
+		if mode == Dedup::Keep {
+			return Vec::new();
+		}
+		let mergeable = |item: &Item| item.weight_expr.is_none() && item.requires.is_none()
+			&& item.range.is_none() && item.fields.is_none() && item.max_per_eval.is_none()
+			&& item.number_range.is_none();
+		let mut index: HashMap<String, usize> = HashMap::new();
+		let mut counts: Vec<usize> = Vec::with_capacity(self.items.len());
+		let mut unique: Vec<Item> = Vec::with_capacity(self.items.len());
+		for item in std::mem::take(&mut self.items) {
+			if mergeable(&item) {
+				if let Some(&i) = index.get(item.get_id()) {
+					unique[i].weight = match mode {
+						Dedup::Max => unique[i].weight.max(item.weight),
+						_ => unique[i].weight + item.weight
+					};
+					counts[i] += 1;
+					continue;
+				}
+				index.insert(item.get_id().clone(), unique.len());
+			}
+			counts.push(1);
+			unique.push(item);
+		}
+		self.items = unique;
+		if counts.len() == self.items.len() && counts.iter().all(|count| *count == 1) {
+			return Vec::new();
+		}
+		self.recount();
+		self.items.iter().zip(counts).filter(|(_, count)| *count > 1)
+			.map(|(item, count)| MergedDuplicate{text: item.text.clone(), count, weight: item.weight})
+			.collect()
+	}
+
+	/// Keeps only the items for which the given predicate returns `true` (eg to prune a loaded
+	/// third-party table without rebuilding it).
+	/// # Arguments
+	/// * `keep` - Function that decides whether to keep an item.
+	pub fn retain(&mut self, mut keep: impl FnMut(&Item) -> bool) {
+		self.remove_where(|item| !keep(item));
+	}
+
+	/// Removes every item whose text matches the given regular expression (eg `(?i)\bspider`
+	/// to remove all items that mention spiders).
+	/// # Arguments
+	/// * `pattern` - The regular expression (see the `regex` crate for the syntax).
+	/// # Returns
+	/// Returns the number of items that were removed, or a `ParsingError` if the regular
+	/// expression is not valid.
+	pub fn remove_matching(&mut self, pattern: &str) -> Result<usize, ParsingError> {
+		let regex = Regex::new(pattern).map_err(|e| ParseError{
+			msg: Some(format!("'{}' is not a valid regular expression: {}", pattern, e)), line: None, col: None
+		})?;
+		Ok(self.remove_where(|item| regex.is_match(item.text.as_str())))
+	}
+
+	/// Removes every item for which the given predicate returns `true`, and returns the number of
+	/// removed items
+	fn remove_where(&mut self, mut remove: impl FnMut(&Item) -> bool) -> usize {
+		let count = self.items.len();
+		let mut first: Option<usize> = None;
+		let mut i = 0;
+		self.items.retain(|item| {
+			let removed = remove(item);
+			if removed && first.is_none() {
+				first = Some(i);
+			}
+			i += 1;
+			!removed
+		});
+		if let Some(first) = first {
+			let places = self.items.iter().try_fold(0, |p, item| decimal_places(item.weight).map(|d| p.max(d)));
+			if places != self.int_places {
+				// the integer running totals must all be re-scaled
+				self.int_places = places;
+				self.int_cumulative.clear();
+			}
+			// the running totals before the first removed item are unchanged
+			self.update_cumulative(first);
+		}
+		count - self.items.len()
+	}
+
+	/// Changes the weight of the item(s) with the given text value or stable key. Any weight
+	/// expression of the item(s) is replaced by the new weight.
+	/// # Arguments
+	/// * `text` - The text value or key to search for (accepts both &str and String).
+	/// * `weight` - The new weight.
+	/// # Returns
+	/// Returns `true` if an item matching the given text or key was found, otherwise `false`.
+	/// # Panics
+	/// Panics if the weight is negative or NaN.
+	pub fn set_weight<T>(&mut self, text: T, weight: f64) -> bool where T: Into<String> {
+		if weight.is_nan() || weight < 0. {
+			panic!("Invalid state: item weight must be a positive real number");
+		}
+		let text = text.into();
+		let mut found = false;
+		for item in self.items.iter_mut() {
+			if item.text == text || item.key.as_ref() == Some(&text) {
+				found = true;
+				item.weight = weight;
+				item.weight_expr = None;
 			}
 		}
 		self.recount();
-		removed
+		found
+	}
+
+	/// Multiplies the weights of all items by the given factor (eg 2 to make every item twice
+	/// as likely compared to the items of another table, or 0 to disable the whole table). Weight
+	/// expressions are not affected, since they replace the weight at draw time.
+	/// # Arguments
+	/// * `factor` - The number to multiply each weight by.
+	/// # Panics
+	/// Panics if the factor is negative, infinite, or NaN.
+	pub fn scale_weights(&mut self, factor: f64) {
+		if !factor.is_finite() || factor < 0. {
+			panic!("Invalid state: weight scale factor must be a finite positive real number");
+		}
+		for item in self.items.iter_mut() {
+			item.weight *= factor;
+		}
+		self.recount();
+	}
+
+	/// Gets the default substitution options of this lookup table, if any
+	pub(crate) fn get_defaults(&self) -> Option<&SubstitutionOptions> {
+		self.defaults.as_ref()
 	}
 
-	/// Re-evaluates the sum of all weights
+	/// Sets the default substitution options of this lookup table (eg `case: title` for a table
+	/// of names), which apply whenever the table is drawn from, unless the substitution token sets
+	/// them
+	pub(crate) fn set_defaults(&mut self, defaults: Option<SubstitutionOptions>) {
+		self.defaults = defaults;
+	}
+
+	/// Gets all items in this lookup table
+	pub(crate) fn items(&self) -> &[Item] {
+		&self.items
+	}
+
+	/// Iterates over the items of the lookup table, in the order in which they were added (eg to
+	/// display or audit a loaded table).
+	/// # Returns
+	/// An iterator over the `Item`s of this table, which hold the text and weight of each item.
+	pub fn iter(&self) -> std::slice::Iter<'_, Item> {
+		self.items.iter()
+	}
+
+	/// Gets the number of items in the lookup table.
+	/// # Returns
+	/// The number of items, including items with a weight of zero.
+	pub fn len(&self) -> usize {
+		self.items.len()
+	}
+
+	/// Checks whether the lookup table has no items.
+	/// # Returns
+	/// Returns `true` if the table has no items, otherwise `false`.
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+
+	/// Gets the item at the given position in the lookup table.
+	/// # Arguments
+	/// * `index` - The position of the item, in the order in which the items were added.
+	/// # Returns
+	/// The `Item` at that position, or `None` if the index is out of bounds.
+	pub fn get(&self, index: usize) -> Option<&Item> {
+		self.items.get(index)
+	}
+
+	/// Re-evaluates the sum of all weights (and whether they are all equal)
 	fn recount(&mut self) {
-		let mut sum = 0f64;
-		for item in &self.items {
+		self.equal_weights = self.items.windows(2).all(|pair| pair[0].weight == pair[1].weight);
+		self.int_places = self.items.iter().try_fold(0, |p, item| decimal_places(item.weight).map(|d| p.max(d)));
+		self.update_cumulative(0);
+	}
+
+	/// Re-calculates the running totals of the weights (and the sum of all weights), starting at
+	/// the item with the given index. The running totals before that item are kept.
+	fn update_cumulative(&mut self, from: usize) {
+		let from = from.min(self.cumulative.len());
+		self.cumulative.truncate(from);
+		let mut sum = self.cumulative.last().copied().unwrap_or(0.);
+		for item in &self.items[from..] {
 			sum += item.weight;
+			self.cumulative.push(sum);
 		}
 		self.total = sum;
+		match self.int_places {
+			None => self.int_cumulative.clear(),
+			Some(places) => {
+				let from = from.min(self.int_cumulative.len());
+				self.int_cumulative.truncate(from);
+				let sum = self.int_cumulative.last().copied().unwrap_or(0);
+				match int_running_totals(&self.items[from..], places, sum) {
+					Some(sums) => self.int_cumulative.extend(sums),
+					None => {
+						// too large for exact integer arithmetic
+						self.int_places = None;
+						self.int_cumulative.clear();
+					}
+				}
+			}
+		}
 	}
 }
 
 #[cfg(test)]
 mod unit_tests {
-	use crate::data::{Item, LookUpTable};
+	use crate::data::{Item, LookUpTable, MergedDuplicate, WeightIssue};
+	use crate::options::Dedup;
 
 	#[test]
 	fn weight_check(){
 		let w = 0.5f64;
 		let text = "test";
-		let i = Item{text: String::from(text), weight: w};
+		let i = Item{text: String::from(text), weight: w, range: None, key: None, weight_expr: None, fields: None, requires: None, max_per_eval: None, number_range: None};
 		assert_eq!(i.get_weight(), w);
 		let mut lut = LookUpTable::new();
 		assert_eq!(lut.total, 0f64);
@@ -196,4 +1016,247 @@ mod unit_tests {
 		assert!(! lut.remove_item(text));
 		assert_eq!(lut.total, w);
 	}
+
+	#[test]
+	fn merge_check(){
+		let mut base = LookUpTable::new();
+		base.add_item("goblin", 2.);
+		base.add_item("rat", 1.);
+		let mut expansion = LookUpTable::new();
+		expansion.add_item("goblin", 1.);
+		expansion.add_item("dragon", 1.);
+		let mut merged = base.clone();
+		merged.merge(&expansion, false);
+		assert_eq!(merged.len(), 4);
+		assert_eq!(merged.total, 5.);
+		base.merge(&expansion, true);
+		assert_eq!(base.len(), 3);
+		assert_eq!(base.get(0).unwrap().get_weight(), 3.);
+		assert_eq!(base.total, 5.);
+		assert!(!base.equal_weights);
+	}
+
+	#[test]
+	fn item_access_check(){
+		let mut lut = LookUpTable::new();
+		assert!(lut.is_empty());
+		lut.add_item("common", 6.);
+		lut.add_item("rare", 1.);
+		assert_eq!(lut.len(), 2);
+		assert_eq!(lut.get(1).unwrap().get_text(), "rare");
+		assert!(lut.get(2).is_none());
+		let items: Vec<(&str, f64)> = lut.iter().map(|item| (item.get_text().as_str(), item.get_weight())).collect();
+		assert_eq!(items, vec![("common", 6.), ("rare", 1.)]);
+	}
+
+	#[test]
+	fn lookup_value_check(){
+		let mut lut = LookUpTable::new();
+		lut.add_range_item("feeble", 3., 5.);
+		lut.add_range_item("average", 6., 9.);
+		lut.add_range_item("mighty", 10., f64::INFINITY);
+		assert_eq!(lut.lookup_value(3.).unwrap().get_text(), "feeble");
+		assert_eq!(lut.lookup_value(9.).unwrap().get_text(), "average");
+		assert_eq!(lut.lookup_value(42.).unwrap().get_text(), "mighty");
+		assert!(lut.lookup_value(1.).is_none());
+		assert_eq!(lut.lookup_value(3.).unwrap().get_weight(), 3.);
+		assert_eq!(lut.lookup_value(42.).unwrap().get_weight(), 1.);
+		assert!(lut.set_weight("feeble", 0.5));
+		assert!(!lut.set_weight("legendary", 2.));
+		assert_eq!(lut.lookup_value(3.).unwrap().get_weight(), 0.5);
+		assert_eq!(lut.total, 5.5);
+		lut.scale_weights(2.);
+		assert_eq!(lut.lookup_value(3.).unwrap().get_weight(), 1.);
+		assert_eq!(lut.total, 11.);
+		assert!(!lut.equal_weights);
+		assert!(lut.set_weight("average", 1.));
+		assert!(lut.set_weight("mighty", 1.));
+		assert!(lut.equal_weights);
+	}
+	#[test]
+	fn keyed_item_check(){
+		let mut lut = LookUpTable::new();
+		lut.add_keyed_item("a goblin ambush", 1., "goblin-ambush-01");
+		lut.add_item("a quiet night", 1.);
+		assert_eq!(lut.items()[0].get_id(), "goblin-ambush-01");
+		assert_eq!(lut.items()[1].get_id(), "a quiet night");
+		assert!(lut.remove_item("goblin-ambush-01"));
+		assert_eq!(lut.items().len(), 1);
+		assert_eq!(lut.total, 1.);
+	}
+	#[test]
+	fn resolve_weights_check(){
+		let mut lut = LookUpTable::new();
+		lut.add_item("crab", 2.);
+		lut.add_conditional_item("shark", "$region == coast ? 5 : 0", None);
+		assert!(lut.has_weight_exprs());
+		let resolved = lut.resolve_weights(|_| Ok::<f64, ()>(5.), |_| Ok(true)).unwrap();
+		assert!(!resolved.has_weight_exprs());
+		assert_eq!(resolved.total, 7.);
+		let resolved = lut.resolve_weights(|_| Ok::<f64, ()>(0.), |_| Ok(true)).unwrap();
+		assert_eq!(resolved.items().len(), 1);
+		assert_eq!(resolved.items()[0].get_text(), "crab");
+		lut.add_item("camel", 1.);
+		lut.set_last_item_requires(String::from("$terrain == desert"));
+		assert_eq!(lut.resolve_weights(|_| Ok::<f64, ()>(5.), |_| Ok(true)).unwrap().len(), 3);
+		let resolved = lut.resolve_weights(|_| Ok::<f64, ()>(5.), |_| Ok(false)).unwrap();
+		assert_eq!(resolved.len(), 2);
+		assert!(resolved.items().iter().all(|item| item.get_requires().is_none()));
+		let weights = lut.resolved_weights(|_| Ok::<f64, ()>(5.), |_| Ok(false)).unwrap();
+		assert_eq!(weights, vec![2., 5., 0.]);
+		assert_eq!(lut.with_resolved_weights(&weights).total, 7.);
+	}
+	#[test]
+	fn audit_weights_check(){
+		let mut lut = LookUpTable::new();
+		lut.add_item("common", 5000.);
+		lut.add_item("rare", 1.);
+		lut.add_item("never", 0.);
+		let issues = lut.audit_weights(1000.);
+		assert_eq!(issues.len(), 3);
+		assert_eq!(issues[0], WeightIssue::ZeroWeight{text: String::from("never")});
+		assert_eq!(issues[1], WeightIssue::MixedScale{smallest: 1., largest: 5000.});
+		assert!(matches!(&issues[2], WeightIssue::Dominant{text, ..} if text == "common"));
+		let mut lut = LookUpTable::new();
+		lut.add_item("a", 3.);
+		lut.add_item("b", 1.);
+		assert!(lut.audit_weights(1000.).is_empty());
+	}
+	#[test]
+	fn int_weights_check(){
+		use crate::data::decimal_places;
+		assert_eq!(decimal_places(3.), Some(0));
+		assert_eq!(decimal_places(0.25), Some(2));
+		assert_eq!(decimal_places(0.1), Some(1));
+		assert_eq!(decimal_places(1. / 3.), None);
+		let mut lut = LookUpTable::new();
+		lut.add_item("a", 0.5);
+		lut.add_item("b", 2.);
+		assert_eq!(lut.int_places, Some(1));
+		lut.add_item("c", 1. / 3.);
+		assert_eq!(lut.int_places, None);
+		assert!(lut.remove_item("c"));
+		assert_eq!(lut.int_places, Some(1));
+		// weights that are too large for exact integer arithmetic
+		let mut lut = LookUpTable::new();
+		for i in 0..300 {
+			lut.add_item(format!("{}", i), 9e15);
+		}
+		lut.add_item("big", 1e15);
+		assert_eq!(lut.int_places, Some(0));
+		lut.add_item("half", 0.5);
+		assert_eq!(lut.int_places, None);
+		assert!(lut.draw_random(&mut rand::thread_rng()).is_ok());
+		for i in 0..3000 {
+			lut.add_item(format!("more {}", i), 9e15);
+		}
+		assert!(lut.remove_item("half"));
+		assert_eq!(lut.int_places, None);
+		assert!(lut.draw_random(&mut rand::thread_rng()).is_ok());
+	}
+	#[test]
+	fn retain_check(){
+		let mut lut = LookUpTable::new();
+		lut.add_item("giant spider", 1.);
+		lut.add_item("wolf", 2.);
+		lut.add_item("Spider swarm", 0.5);
+		lut.add_item("bear", 1.);
+		assert_eq!(lut.remove_matching("(?i)spider").unwrap(), 2);
+		assert_eq!(lut.len(), 2);
+		assert_eq!(lut.total, 3.);
+		assert_eq!(lut.int_cumulative, vec![2, 3]);
+		assert_eq!(lut.remove_matching("dragon").unwrap(), 0);
+		assert!(lut.remove_matching("(").is_err());
+		lut.retain(|item| item.get_weight() < 2.);
+		assert_eq!(lut.len(), 1);
+		assert_eq!(lut.get(0).unwrap().get_text(), "bear");
+		assert_eq!(lut.cumulative, vec![1.]);
+	}
+	#[test]
+	fn dedup_check(){
+		let mut lut = LookUpTable::new();
+		lut.add_item("wolf", 1.);
+		lut.add_item("bear", 2.);
+		lut.add_item("wolf", 3.);
+		lut.add_range_item("gold", 1., 6.);
+		lut.add_range_item("gold", 1., 6.);
+		lut.add_item("wolf", 0.5);
+		let mut max_lut = lut.clone();
+		assert!(lut.clone().dedup(Dedup::Keep).is_empty());
+		let merged = lut.dedup(Dedup::Sum);
+		assert_eq!(merged, vec![MergedDuplicate{text: "wolf".into(), count: 3, weight: 4.5}]);
+		assert_eq!(lut.len(), 4);
+		assert_eq!(lut.get(0).unwrap().get_text(), "wolf");
+		assert_eq!(lut.total, 18.5);
+		assert_eq!(lut.int_cumulative, vec![45, 65, 125, 185]);
+		max_lut.dedup(Dedup::Max);
+		assert_eq!(max_lut.get(0).unwrap().get_weight(), 3.);
+		assert_eq!(max_lut.total, 17.);
+		assert!(max_lut.dedup(Dedup::Max).is_empty());
+	}
+	#[test]
+	fn zero_weight_check(){
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(1);
+		let mut lut = LookUpTable::new();
+		lut.add_item("canon", 0.);
+		lut.add_item("also canon", 0.);
+		assert!(lut.draw_random(&mut rng).is_err());
+		assert!(lut.shuffle_draw(&mut rng, 1).is_err());
+		lut.add_keyed_item("Excalibur", 0., "sword");
+		lut.add_item("stick", 1.);
+		for _ in 0..20 {
+			assert_eq!(lut.draw_random(&mut rng).unwrap().get_text(), "stick");
+		}
+		assert_eq!(lut.shuffle(&mut rng).unwrap().len(), 1);
+		assert_eq!(lut.find_item("sword").unwrap().get_text(), "Excalibur");
+		assert_eq!(lut.find_item("canon").unwrap().get_weight(), 0.);
+		assert!(lut.find_item("spoon").is_none());
+	}
+	#[test]
+	fn number_item_check(){
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(1);
+		let mut lut = LookUpTable::new();
+		lut.add_number_item(3., 18., 1.);
+		lut.add_number_item(0.5, 2.5, 1.);
+		assert_eq!(lut.get(0).unwrap().get_text(), "3-18");
+		assert_eq!(lut.get(1).unwrap().get_number_range(), Some((0.5, 2.5)));
+		for _ in 0..20 {
+			let number = lut.get(0).unwrap().roll_number(&mut rng).unwrap();
+			assert!(number.get_number_range().is_none());
+			let value: i64 = number.get_text().parse().unwrap();
+			assert!((3..=18).contains(&value));
+			let number = lut.get(1).unwrap().roll_number(&mut rng).unwrap();
+			assert_eq!(number.get_text().split('.').nth(1).unwrap().len(), 1);
+			let value: f64 = number.get_text().parse().unwrap();
+			assert!((0.5..=2.5).contains(&value));
+		}
+		lut.add_item("cat", 1.);
+		assert!(lut.get(2).unwrap().roll_number(&mut rng).is_none());
+	}
+	#[test]
+	fn cumulative_check(){
+		let mut lut = LookUpTable::new();
+		lut.add_item("a", 1.);
+		lut.add_item("b", 2.);
+		lut.add_item("c", 0.5);
+		assert_eq!(lut.cumulative, vec![1., 3., 3.5]);
+		assert_eq!(lut.int_cumulative, vec![10, 30, 35]);
+		lut.add_item("d", 1.);
+		lut.add_item("b", 0.25);
+		assert!(lut.remove_item("b"));
+		assert_eq!(lut.cumulative, vec![1., 1.5, 2.5]);
+		assert_eq!(lut.int_cumulative, vec![10, 15, 25]);
+		assert_eq!(lut.total, 2.5);
+		lut.add_item("e", 1. / 3.);
+		assert!(lut.int_cumulative.is_empty());
+		assert!(lut.remove_item("e"));
+		assert_eq!(lut.int_cumulative, vec![10, 15, 25]);
+		// draws always land on an item with weight
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(7);
+		lut.add_item("never", 0.);
+		lut.add_item("f", 2.);
+		for _ in 0..100 {
+			assert_ne!(lut.draw_random(&mut rng).unwrap().get_text(), "never");
+		}
+	}
 }
@@ -0,0 +1,143 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::HashMap;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use crate::data::{Item, LookUpTable};
+use crate::errors::{ParseError, ParsingError};
+use crate::options::Depletion;
+
+/// Keeps track of the depletion mode of each look-up table and the items that were drawn from
+/// depleting look-up tables (see [Depletion]), which stay used up across evaluations until they
+/// are reset. The draw state of an [Interpreter](crate::Interpreter) can be saved (eg as JSON with
+/// serde) with [get_draw_state()](crate::Interpreter::get_draw_state) and restored with
+/// [set_draw_state(...)](crate::Interpreter::set_draw_state), such that items that were used up
+/// in one session (eg the plot hooks already used in a campaign) stay used up in the next one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DrawState {
+	/// Depletion mode of each look-up table that does not put its drawn items back
+	#[serde(default)]
+	modes: HashMap<String, Depletion>,
+	/// Number of times that each item (by text or stable key) was drawn, by look-up table ID
+	#[serde(default)]
+	consumed: HashMap<String, HashMap<String, usize>>
+}
+
+impl DrawState {
+	/// Gets the depletion mode of the given look-up table
+	pub(crate) fn get_mode(&self, id: &str) -> Depletion {
+		self.modes.get(id).copied().unwrap_or_default()
+	}
+
+	/// Sets the depletion mode of the given look-up table
+	pub(crate) fn set_mode(&mut self, id: &str, mode: Depletion) {
+		match mode {
+			Depletion::Replace => {self.modes.remove(id);},
+			_ => {self.modes.insert(String::from(id), mode);}
+		}
+	}
+
+	/// Draws one item from the given look-up table, leaving out the items that were already used
+	/// up, and then uses up the drawn item
+	/// # Arguments
+	/// * `id`: ID of the look-up table
+	/// * `lut`: the look-up table
+	/// * `rng`: random number generator
+	/// * `mode`: depletion mode, which decides what happens if the table is used up
+	/// # Returns
+	/// The drawn item, or an error if all items are used up (and the mode does not reset the
+	/// table)
+	pub(crate) fn draw(&mut self, id: &str, lut: &LookUpTable, rng: &mut impl Rng, mode: Depletion) -> Result<Item, ParsingError> {
+		let mut remaining = self.remaining(id, lut);
+		if remaining.is_empty() && mode == Depletion::DepleteAndReset {
+			self.consumed.remove(id);
+			remaining = self.remaining(id, lut);
+		}
+		if remaining.is_empty() {
+			return Err(ParseError{
+				msg: Some(format!("All items of look-up table '{}' are used up", id)), line: None, col: None
+			}.into());
+		}
+		let item = remaining.draw_random(rng)?;
+		*self.consumed.entry(String::from(id)).or_default().entry(item.get_id().clone()).or_default() += 1;
+		Ok(item)
+	}
+
+	/// Puts all used up items of the given look-up table back
+	/// # Returns
+	/// `true` if any items of the table were used up
+	pub(crate) fn reset(&mut self, id: &str) -> bool {
+		self.consumed.remove(id).is_some()
+	}
+
+	/// Moves the depletion mode and used up items of a look-up table to a new look-up table ID
+	/// (eg because the look-up table was renamed)
+	pub(crate) fn rename(&mut self, old_id: &str, new_id: &str) {
+		if let Some(mode) = self.modes.remove(old_id) {
+			self.modes.insert(String::from(new_id), mode);
+		}
+		if let Some(consumed) = self.consumed.remove(old_id) {
+			self.consumed.insert(String::from(new_id), consumed);
+		}
+	}
+
+	/// Forgets the depletion mode and used up items of a look-up table (eg because the look-up
+	/// table was removed, so that it starts afresh if it is loaded again)
+	pub(crate) fn forget(&mut self, id: &str) {
+		self.modes.remove(id);
+		self.consumed.remove(id);
+	}
+
+	/// Puts all used up items of all look-up tables back
+	pub(crate) fn reset_all(&mut self) {
+		self.consumed.clear();
+	}
+
+	/// Gets the items of the look-up table that are not used up yet (items that cannot be drawn,
+	/// because their weight is zero, are left out too)
+	fn remaining(&self, id: &str, lut: &LookUpTable) -> LookUpTable {
+		let mut skip: HashMap<&String, usize> = match self.consumed.get(id) {
+			None => HashMap::new(),
+			Some(consumed) => consumed.iter().map(|(item_id, count)| (item_id, *count)).collect()
+		};
+		let mut remaining = LookUpTable::new();
+		for item in lut.iter() {
+			match skip.get_mut(item.get_id()) {
+				Some(count) if *count > 0 => *count -= 1,
+				_ => if item.get_weight() > 0. { remaining.add(item.clone()) }
+			}
+		}
+		remaining
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use dicexp::simple_rng;
+	use crate::data::LookUpTable;
+	use crate::deplete::DrawState;
+	use crate::options::Depletion;
+
+	#[test]
+	fn test_deplete() {
+		let mut deck = LookUpTable::new();
+		deck.add_item("ace", 1.);
+		deck.add_item("ace", 1.);
+		deck.add_item("king", 1.);
+		let mut state = DrawState::default();
+		let mut rng = simple_rng(42);
+		let mut drawn: Vec<String> = (0..3).map(|_| state.draw("deck", &deck, &mut rng, Depletion::Deplete)
+			.unwrap().get_text().clone()).collect();
+		drawn.sort();
+		assert_eq!(drawn, vec!["ace", "ace", "king"]);
+		assert!(state.draw("deck", &deck, &mut rng, Depletion::Deplete).is_err());
+		assert!(state.draw("deck", &deck, &mut rng, Depletion::DepleteAndReset).is_ok());
+		assert!(state.reset("deck"));
+		assert!(!state.reset("deck"));
+		state.set_mode("deck", Depletion::Deplete);
+		state.draw("deck", &deck, &mut rng, Depletion::Deplete).unwrap();
+		state.forget("deck");
+		assert_eq!(state.get_mode("deck"), Depletion::Replace);
+		assert!(!state.reset("deck"));
+	}
+}
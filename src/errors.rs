@@ -3,6 +3,7 @@
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::num::ParseFloatError;
+use std::time::Duration;
 use zip;
 
 /// Represents an error that occurs during parsing of look-up tables or text substitution
@@ -15,9 +16,15 @@ pub enum ParsingError {
 	KeyNotFoundError(KeyNotFoundError),
 	NoValuesError(NoValuesError),
 	RecursionLimitReached(RecursionLimitReached),
+	LimitExceeded(LimitExceeded),
+	Timeout(Timeout),
 	InvalidCombinationError(InvalidCombinationError),
+	InvalidOptionError(InvalidOptionError),
 	SerdeYAMLParserError(serde_yaml::Error),
-	SerdeJSONParserError(serde_json::Error)
+	SerdeJSONParserError(serde_json::Error),
+	JSON5ParserError(json5::Error),
+	XMLParserError(roxmltree::Error),
+	SpreadsheetError(calamine::XlsxError)
 }
 
 impl Display for ParsingError {
@@ -30,9 +37,15 @@ impl Display for ParsingError {
 			ParsingError::KeyNotFoundError(e) => Display::fmt(&e, f),
 			ParsingError::NoValuesError(e) => Display::fmt(&e, f),
 			ParsingError::RecursionLimitReached(e) => Display::fmt(&e, f),
+			ParsingError::LimitExceeded(e) => Display::fmt(&e, f),
+			ParsingError::Timeout(e) => Display::fmt(&e, f),
 			ParsingError::InvalidCombinationError(e) => Display::fmt(&e, f),
+			ParsingError::InvalidOptionError(e) => Display::fmt(&e, f),
 			ParsingError::SerdeYAMLParserError(e) => Display::fmt(&e, f),
-			ParsingError::SerdeJSONParserError(e) => Display::fmt(&e, f)
+			ParsingError::SerdeJSONParserError(e) => Display::fmt(&e, f),
+			ParsingError::JSON5ParserError(e) => Display::fmt(&e, f),
+			ParsingError::XMLParserError(e) => Display::fmt(&e, f),
+			ParsingError::SpreadsheetError(e) => Display::fmt(&e, f)
 		}
 	}
 }
@@ -75,10 +88,22 @@ impl From<RecursionLimitReached> for ParsingError {
 	fn from(value: RecursionLimitReached) -> Self { ParsingError::RecursionLimitReached(value) }
 }
 
+impl From<LimitExceeded> for ParsingError {
+	fn from(value: LimitExceeded) -> Self { ParsingError::LimitExceeded(value) }
+}
+
+impl From<Timeout> for ParsingError {
+	fn from(value: Timeout) -> Self { ParsingError::Timeout(value) }
+}
+
 impl From<InvalidCombinationError> for ParsingError {
 	fn from(value: InvalidCombinationError) -> Self { ParsingError::InvalidCombinationError(value) }
 }
 
+impl From<InvalidOptionError> for ParsingError {
+	fn from(value: InvalidOptionError) -> Self { ParsingError::InvalidOptionError(value) }
+}
+
 impl From<serde_yaml::Error> for ParsingError {
 	fn from(value: serde_yaml::Error) -> Self { ParsingError::SerdeYAMLParserError(value) }
 }
@@ -87,6 +112,18 @@ impl From<serde_json::Error> for ParsingError {
 	fn from(value: serde_json::Error) -> Self { ParsingError::SerdeJSONParserError(value) }
 }
 
+impl From<json5::Error> for ParsingError {
+	fn from(value: json5::Error) -> Self { ParsingError::JSON5ParserError(value) }
+}
+
+impl From<roxmltree::Error> for ParsingError {
+	fn from(value: roxmltree::Error) -> Self { ParsingError::XMLParserError(value) }
+}
+
+impl From<calamine::XlsxError> for ParsingError {
+	fn from(value: calamine::XlsxError) -> Self { ParsingError::SpreadsheetError(value) }
+}
+
 /// Represents an error that occurs during parsing with additional information.
 #[derive(Clone)]
 pub struct ParseError {
@@ -189,13 +226,19 @@ impl Error for NoValuesError {}
 
 #[derive(Clone)]
 pub struct RecursionLimitReached {
-	pub limit: usize
+	pub limit: usize,
+	/// The substitution token (or linked look-up table) that went past the limit, if known
+	pub token: Option<String>
 }
 
 impl RecursionLimitReached{
 	/// Formats and prints the error message
 	fn print(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-		write!(f, "Recursion limit {} exceeded. Substitution text contains circular reference or is too complex to operate upon.", self.limit)
+		write!(f, "Recursion limit {} exceeded", self.limit)?;
+		if let Some(token) = &self.token {
+			write!(f, " at '{}'", token)?;
+		}
+		write!(f, ". Substitution text contains circular reference or is too complex to operate upon.")
 	}
 }
 
@@ -214,6 +257,71 @@ impl core::fmt::Display for RecursionLimitReached {
 impl Error for RecursionLimitReached {}
 
 
+/// An evaluation went past one of its size limits (see
+/// [EvalOptions::max_output_len](crate::EvalOptions::max_output_len) and
+/// [EvalOptions::max_substitutions](crate::EvalOptions::max_substitutions))
+#[derive(Clone)]
+pub struct LimitExceeded {
+	/// What was limited (eg `substitutions`)
+	pub what: &'static str,
+	/// The limit
+	pub limit: usize
+}
+
+impl LimitExceeded{
+	/// Formats and prints the error message
+	fn print(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Limit of {} {} exceeded. Substitution text might expand without end or is too large to operate upon.",
+			self.limit, self.what)
+	}
+}
+
+impl Debug for LimitExceeded {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		self.print(f)
+	}
+}
+
+impl core::fmt::Display for LimitExceeded {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		self.print(f)
+	}
+}
+
+impl Error for LimitExceeded {}
+
+
+/// An evaluation took longer than its time limit and was stopped (see
+/// [Interpreter::eval_with_deadline(...)](crate::Interpreter::eval_with_deadline))
+#[derive(Clone)]
+pub struct Timeout {
+	/// The time limit
+	pub limit: Duration
+}
+
+impl Timeout{
+	/// Formats and prints the error message
+	fn print(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Time limit of {:?} exceeded. Substitution text might expand without end or is too complex to operate upon.",
+			self.limit)
+	}
+}
+
+impl Debug for Timeout {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		self.print(f)
+	}
+}
+
+impl core::fmt::Display for Timeout {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		self.print(f)
+	}
+}
+
+impl Error for Timeout {}
+
+
 #[derive(Clone)]
 pub struct InvalidIDError {
 	msg: String
@@ -274,3 +382,37 @@ impl core::fmt::Display for InvalidCombinationError {
 }
 
 impl Error for InvalidCombinationError {}
+
+
+/// A substitution option has a value that is not allowed (eg `case: shouting`)
+#[derive(Clone)]
+pub struct InvalidOptionError {
+	/// Name of the option (eg `case`)
+	pub option: String,
+	/// The value that is not allowed
+	pub value: String,
+	/// The allowed values
+	pub allowed: &'static [&'static str]
+}
+
+impl InvalidOptionError{
+	/// Formats and prints the error message
+	fn print(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "'{}' is not a valid value of option '{}' (must be one of: {})", self.value, self.option,
+			self.allowed.join(", "))
+	}
+}
+
+impl Debug for InvalidOptionError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		self.print(f)
+	}
+}
+
+impl core::fmt::Display for InvalidOptionError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		self.print(f)
+	}
+}
+
+impl Error for InvalidOptionError {}
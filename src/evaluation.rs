@@ -0,0 +1,167 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::Duration;
+use crate::data::Item;
+
+/// The detailed result of evaluating a text (see
+/// [Interpreter::eval_detailed(...)](crate::Interpreter::eval_detailed))
+#[derive(Clone, Debug, Default)]
+pub struct Evaluation {
+	/// The generated text
+	pub text: String,
+	/// The text of each reference at the end of the evaluation, by reference ID (including the
+	/// persistent references and the references from the front matter)
+	pub refs: BTreeMap<String, String>,
+	/// Every item that was drawn, in the order that they were drawn, with the ID of the look-up
+	/// table that it was drawn from. Items from nested substitutions are included, and a number
+	/// range item appears with the number that was rolled.
+	pub draws: Vec<(String, Item)>,
+	/// The parts of the text that were produced by each substitution token (see [Span]), ordered
+	/// by their position in the text (a span comes before the spans nested within it)
+	pub spans: Vec<Span>,
+	/// Warnings from the evaluation (see
+	/// [Interpreter::get_warnings()](crate::Interpreter::get_warnings))
+	pub warnings: Vec<String>,
+	/// How much work the evaluation took (see [EvalStats])
+	pub stats: EvalStats
+}
+
+/// A part of the generated text and the substitution token that produced it, such as to let a
+/// user click on a word to re-roll it. Spans are nested when the drawn text contained tokens of
+/// its own (eg the span of `${monster}` contains the span of `${size}` if the drawn monster was
+/// `${size} goblin`). Tokens that are evaluated in their own reference scope (the `scoped`
+/// option) or as structured output (the `format` option) do not have spans for their nested
+/// tokens.
+#[derive(Clone, Debug)]
+pub struct Span {
+	/// Byte range of the part of the generated text (empty if the token produced no text, eg a
+	/// hidden token)
+	pub range: Range<usize>,
+	/// The token as it was written (eg `${animal@pet}` or `#{1d6}`)
+	pub token: String,
+	/// ID of the look-up table that the items were drawn from, or `None` if the token did not
+	/// draw from a look-up table (eg a reference, a template function, or dice)
+	pub table_id: Option<String>,
+	/// The drawn items, if any
+	pub items: Vec<Item>
+}
+
+impl Evaluation {
+	/// Gets the items that were drawn from the given look-up table
+	/// # Arguments
+	/// * `id`: ID of the look-up table
+	/// # Returns
+	/// An iterator over the drawn items, in the order that they were drawn
+	pub fn draws_from<'a>(&'a self, id: &'a str) -> impl Iterator<Item=&'a Item> + 'a {
+		self.draws.iter().filter(move |(table_id, _)| table_id == id).map(|(_, item)| item)
+	}
+
+	/// Gets the innermost span that covers the given byte position of the text (eg the word
+	/// that a user clicked on)
+	/// # Arguments
+	/// * `pos`: byte position in [Evaluation::text]
+	/// # Returns
+	/// The innermost span that contains the position, or `None` if the position is not part of
+	/// any substitution
+	pub fn span_at(&self, pos: usize) -> Option<&Span> {
+		self.spans.iter().rev().find(|span| span.range.contains(&pos))
+	}
+}
+
+/// How much work an evaluation took, such as to find out why a template is slow (see
+/// [Interpreter::last_eval_stats()](crate::Interpreter::last_eval_stats) and
+/// [Evaluation::stats])
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EvalStats {
+	/// Number of substitution tokens that were substituted, including the tokens in the drawn
+	/// items
+	pub tokens: usize,
+	/// Number of items that were drawn from each look-up table, by look-up table ID
+	pub draws: BTreeMap<String, usize>,
+	/// Deepest recursion depth of a substitution token (see
+	/// [EvalHook::on_token_depth(...)](crate::EvalHook::on_token_depth))
+	pub max_depth: usize,
+	/// Wall-clock time of the evaluation
+	pub duration: Duration
+}
+
+impl EvalStats {
+	/// Gets the number of items that were drawn from all look-up tables together
+	pub fn total_draws(&self) -> usize {
+		self.draws.values().sum()
+	}
+
+	/// Counts items that were drawn from a look-up table
+	pub(crate) fn add_draws(&mut self, table_id: &str, count: usize) {
+		match self.draws.get_mut(table_id) {
+			Some(total) => *total += count,
+			None => {
+				self.draws.insert(String::from(table_id), count);
+			}
+		}
+	}
+}
+
+/// Records the draws and spans of an evaluation (see
+/// [Interpreter::eval_detailed(...)](crate::Interpreter::eval_detailed))
+#[derive(Debug, Default)]
+pub(crate) struct Recording {
+	/// Items that were drawn and the IDs of their look-up tables
+	pub draws: Vec<(String, Item)>,
+	/// Spans of the text, in the order that the tokens were substituted
+	pub spans: Vec<Span>,
+	/// Look-up table ID and drawn items of the token that is being substituted (at the top
+	/// level of the evaluation)
+	pub last_sub: Option<(String, Vec<Item>)>
+}
+
+impl Recording {
+	/// Records that the token at the given byte range of the text was replaced with text of the
+	/// given length, moving the spans after the token and growing the spans around it
+	/// # Arguments
+	/// * `token_range`: byte range of the token in the text before the substitution
+	/// * `len`: length of the substituted text
+	/// * `token`: the token as it was written
+	pub fn add_span(&mut self, token_range: Range<usize>, len: usize, token: &str) {
+		let (start, end) = (token_range.start, token_range.end);
+		for span in self.spans.iter_mut() {
+			if span.range.start >= end {
+				span.range = span.range.start + len - (end - start)..span.range.end + len - (end - start);
+			} else if span.range.start <= start && span.range.end >= end {
+				span.range.end = span.range.end + len - (end - start);
+			}
+		}
+		let (table_id, items) = match self.last_sub.take() {
+			Some((table_id, items)) => (Some(table_id), items),
+			None => (None, Vec::new())
+		};
+		self.spans.push(Span{range: start..start + len, token: String::from(token), table_id, items});
+	}
+
+	/// Takes the spans, ordered by their position in the text
+	pub fn take_spans(&mut self) -> Vec<Span> {
+		let mut spans = std::mem::take(&mut self.spans);
+		spans.sort_by_key(|span| (span.range.start, std::cmp::Reverse(span.range.end)));
+		spans
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::evaluation::Recording;
+
+	#[test]
+	fn test_add_span() {
+		// "${a} ${b}" -> "x${c}y ${b}" -> "xzzy ${b}" -> "xzzy w"
+		let mut recording = Recording::default();
+		recording.add_span(0..4, 6, "${a}");
+		recording.add_span(1..5, 2, "${c}");
+		recording.add_span(5..9, 1, "${b}");
+		let spans = recording.take_spans();
+		let ranges: Vec<_> = spans.iter().map(|span| span.range.clone()).collect();
+		assert_eq!(ranges, vec![0..4, 1..3, 5..6]);
+		assert_eq!(spans[1].token, "${c}");
+	}
+}
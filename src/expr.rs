@@ -0,0 +1,346 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use crate::errors::{ParseError, ParsingError};
+
+/// Value produced by evaluating a weight expression, either a number or a text string
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+	/// Numeric value
+	Num(f64),
+	/// Text value (eg the value of a reference or a bare word like `coast`)
+	Text(String)
+}
+
+impl Value {
+	/// Converts this value to a number, if possible
+	pub(crate) fn as_num(&self) -> Option<f64> {
+		match self {
+			Value::Num(n) => Some(*n),
+			Value::Text(t) => t.trim().parse::<f64>().ok()
+		}
+	}
+
+	/// Numbers are true if they are not zero, text is true if it is not empty
+	pub(crate) fn is_true(&self) -> bool {
+		match self {
+			Value::Num(n) => *n != 0.,
+			Value::Text(t) => !t.is_empty()
+		}
+	}
+
+	/// Converts this value to text (eg for comparing a number with text)
+	fn as_text(&self) -> String {
+		match self {
+			Value::Num(n) => n.to_string(),
+			Value::Text(t) => t.clone()
+		}
+	}
+}
+
+/// Parsed expression tree
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Expr {
+	/// Literal number or text
+	Literal(Value),
+	/// Reference value (eg `$region`)
+	Ref(String),
+	/// Unary operation (`-` or `!`)
+	Unary(char, Box<Expr>),
+	/// Binary operation (eg `==`, `&&`, `+`)
+	Binary(&'static str, Box<Expr>, Box<Expr>),
+	/// Conditional `condition ? then : else`
+	Ternary(Box<Expr>, Box<Expr>, Box<Expr>)
+}
+
+/// Token of an expression
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+	/// Number (eg `2.5`)
+	Num(f64),
+	/// Bare word or quoted text (eg `coast` or `'deep sea'`)
+	Word(String),
+	/// Reference name, without the `$`
+	Ref(String),
+	/// Operator or parenthesis
+	Op(&'static str)
+}
+
+/// Operators and parentheses, with the two-character operators first so that they are matched
+/// before their one-character prefixes
+const OPERATORS: [&str; 17] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/",
+	"!", "?", ":", "(", ")"];
+
+/// Creates the error for a malformed expression
+fn expr_error(expr: &str, msg: &str) -> ParsingError {
+	ParseError{msg: Some(format!("Invalid expression '{}': {}", expr, msg)), line: None, col: None}.into()
+}
+
+/// Checks whether the character at the given index continues a reference name or bare word.
+/// Names may contain `-` (eg `$npc-name`), but only when it is followed by a letter or `_`, so
+/// that `$level-1` subtracts 1 from `$level`.
+fn is_word_char(chars: &[char], i: usize) -> bool {
+	match chars[i] {
+		'-' => chars.get(i + 1).is_some_and(|c| c.is_alphabetic() || *c == '_'),
+		c => c.is_alphanumeric() || c == '_'
+	}
+}
+
+/// Splits an expression into numbers, words, references, and operators
+fn tokenize(expr: &str) -> Result<Vec<Token>, ParsingError> {
+	let chars: Vec<char> = expr.chars().collect();
+	let mut tokens: Vec<Token> = Vec::new();
+	let mut i = 0;
+	'outer: while i < chars.len() {
+		let c = chars[i];
+		if c.is_whitespace() {
+			i += 1;
+		} else if c.is_ascii_digit() || (c == '.' && i + 1 < chars.len() && chars[i+1].is_ascii_digit()) {
+			let start = i;
+			while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') { i += 1; }
+			let num: String = chars[start..i].iter().collect();
+			tokens.push(Token::Num(num.parse::<f64>().map_err(|_| expr_error(expr, "bad number"))?));
+		} else if c == '$' {
+			let start = i + 1;
+			i = start;
+			while i < chars.len() && is_word_char(&chars, i) { i += 1; }
+			if i == start { return Err(expr_error(expr, "missing reference name after '$'")); }
+			tokens.push(Token::Ref(chars[start..i].iter().collect()));
+		} else if c == '"' || c == '\'' {
+			let start = i + 1;
+			i = start;
+			while i < chars.len() && chars[i] != c { i += 1; }
+			if i >= chars.len() { return Err(expr_error(expr, "unterminated quote")); }
+			tokens.push(Token::Word(chars[start..i].iter().collect()));
+			i += 1;
+		} else if c.is_alphabetic() || c == '_' {
+			let start = i;
+			while i < chars.len() && is_word_char(&chars, i) { i += 1; }
+			tokens.push(Token::Word(chars[start..i].iter().collect()));
+		} else {
+			for op in OPERATORS {
+				let len = op.chars().count();
+				if i + len <= chars.len() && chars[i..i+len].iter().copied().eq(op.chars()) {
+					tokens.push(Token::Op(op));
+					i += len;
+					continue 'outer;
+				}
+			}
+			return Err(expr_error(expr, format!("unexpected character '{}'", c).as_str()));
+		}
+	}
+	Ok(tokens)
+}
+
+/// Recursive descent parser for weight expressions
+struct Parser<'a> {
+	/// The expression text, for error messages
+	expr: &'a str,
+	/// Tokens of the expression
+	tokens: Vec<Token>,
+	/// Index of the next token
+	pos: usize
+}
+
+impl<'a> Parser<'a> {
+	/// Gets the operator at the current position, if the current token is an operator
+	fn peek_op(&self) -> Option<&'static str> {
+		match self.tokens.get(self.pos) {
+			Some(Token::Op(op)) => Some(op),
+			_ => None
+		}
+	}
+
+	/// Skips the given operator, or returns an error if it is not at the current position
+	fn expect_op(&mut self, op: &str) -> Result<(), ParsingError> {
+		if self.peek_op() == Some(op) {
+			self.pos += 1;
+			Ok(())
+		} else {
+			Err(expr_error(self.expr, format!("expected '{}'", op).as_str()))
+		}
+	}
+
+	/// Parses a conditional `condition ? then : otherwise` (or any expression without one)
+	fn ternary(&mut self) -> Result<Expr, ParsingError> {
+		let cond = self.binary(0)?;
+		if self.peek_op() == Some("?") {
+			self.pos += 1;
+			let then = self.ternary()?;
+			self.expect_op(":")?;
+			let otherwise = self.ternary()?;
+			return Ok(Expr::Ternary(Box::new(cond), Box::new(then), Box::new(otherwise)));
+		}
+		Ok(cond)
+	}
+
+	/// Parses binary operators, from lowest precedence level to highest
+	fn binary(&mut self, level: usize) -> Result<Expr, ParsingError> {
+		const LEVELS: [&[&str]; 5] = [&["||"], &["&&"], &["==", "!=", "<=", ">=", "<", ">"],
+			&["+", "-"], &["*", "/"]];
+		if level >= LEVELS.len() {
+			return self.unary();
+		}
+		let mut lhs = self.binary(level + 1)?;
+		while let Some(op) = self.peek_op().filter(|op| LEVELS[level].contains(op)) {
+			self.pos += 1;
+			let rhs = self.binary(level + 1)?;
+			lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+		}
+		Ok(lhs)
+	}
+
+	/// Parses a unary `-` or `!`, a parenthesized expression, or a single operand
+	fn unary(&mut self) -> Result<Expr, ParsingError> {
+		match self.peek_op() {
+			Some("-") => { self.pos += 1; Ok(Expr::Unary('-', Box::new(self.unary()?))) },
+			Some("!") => { self.pos += 1; Ok(Expr::Unary('!', Box::new(self.unary()?))) },
+			Some("(") => {
+				self.pos += 1;
+				let inner = self.ternary()?;
+				self.expect_op(")")?;
+				Ok(inner)
+			},
+			_ => {
+				let token = self.tokens.get(self.pos).cloned()
+					.ok_or_else(|| expr_error(self.expr, "unexpected end of expression"))?;
+				self.pos += 1;
+				match token {
+					Token::Num(n) => Ok(Expr::Literal(Value::Num(n))),
+					Token::Word(w) => Ok(Expr::Literal(Value::Text(w))),
+					Token::Ref(r) => Ok(Expr::Ref(r)),
+					Token::Op(op) => Err(expr_error(self.expr, format!("unexpected '{}'", op).as_str()))
+				}
+			}
+		}
+	}
+}
+
+/// Parses an expression, such as `$region == coast ? 5 : 1`. Supported operators (from lowest
+/// to highest precedence) are `? :`, `||`, `&&`, comparisons (`==`, `!=`, `<`, `>`, `<=`, `>=`),
+/// `+ -`, `* /`, and unary `-` and `!`. Operands are numbers, `$ref` reference values, and
+/// text (bare words or quoted strings).
+/// # Arguments
+/// * `expr`: the expression text
+/// # Returns
+/// Returns the parsed expression tree, or a `ParsingError` if the expression is malformed
+pub(crate) fn parse_expr(expr: &str) -> Result<Expr, ParsingError> {
+	let mut parser = Parser{expr, tokens: tokenize(expr)?, pos: 0};
+	let tree = parser.ternary()?;
+	if parser.pos < parser.tokens.len() {
+		return Err(expr_error(expr, "unexpected trailing text"));
+	}
+	Ok(tree)
+}
+
+impl Expr {
+	/// Evaluates this expression
+	/// # Arguments
+	/// * `lookup`: function that returns the value of a reference (or an error if the reference
+	///   does not exist)
+	/// # Returns
+	/// Returns the resulting value, or a `ParsingError` if a reference could not be resolved or a
+	/// number was expected but not found
+	pub(crate) fn eval(&self, lookup: &dyn Fn(&str) -> Result<String, ParsingError>) -> Result<Value, ParsingError> {
+		match self {
+			Expr::Literal(v) => Ok(v.clone()),
+			Expr::Ref(r) => Ok(Value::Text(lookup(r.as_str())?)),
+			Expr::Unary(op, inner) => {
+				let v = inner.eval(lookup)?;
+				match op {
+					'-' => Ok(Value::Num(-num_of(&v)?)),
+					_ => Ok(bool_value(!v.is_true()))
+				}
+			},
+			Expr::Ternary(cond, then, otherwise) => {
+				if cond.eval(lookup)?.is_true() { then.eval(lookup) } else { otherwise.eval(lookup) }
+			},
+			Expr::Binary(op, lhs, rhs) => {
+				let a = lhs.eval(lookup)?;
+				match *op {
+					// short-circuit logic
+					"&&" => return Ok(bool_value(a.is_true() && rhs.eval(lookup)?.is_true())),
+					"||" => return Ok(bool_value(a.is_true() || rhs.eval(lookup)?.is_true())),
+					_ => {}
+				}
+				let b = rhs.eval(lookup)?;
+				match *op {
+					"+" => Ok(Value::Num(num_of(&a)? + num_of(&b)?)),
+					"-" => Ok(Value::Num(num_of(&a)? - num_of(&b)?)),
+					"*" => Ok(Value::Num(num_of(&a)? * num_of(&b)?)),
+					"/" => Ok(Value::Num(num_of(&a)? / num_of(&b)?)),
+					cmp => {
+						// compare as numbers if possible, otherwise as text
+						let ordering = match (a.as_num(), b.as_num()) {
+							(Some(x), Some(y)) => x.partial_cmp(&y),
+							_ => Some(a.as_text().cmp(&b.as_text()))
+						};
+						let result = match ordering {
+							None => cmp == "!=",
+							Some(o) => match cmp {
+								"==" => o.is_eq(),
+								"!=" => o.is_ne(),
+								"<" => o.is_lt(),
+								">" => o.is_gt(),
+								"<=" => o.is_le(),
+								_ => o.is_ge()
+							}
+						};
+						Ok(bool_value(result))
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Converts a boolean to the number 1 (true) or 0 (false)
+fn bool_value(b: bool) -> Value {
+	Value::Num(if b {1.} else {0.})
+}
+
+/// Converts a value to a number, or returns an error if it is not a number
+fn num_of(v: &Value) -> Result<f64, ParsingError> {
+	v.as_num().ok_or_else(|| ParseError{msg: Some(format!("Expected a number but found '{}'", v.as_text())),
+		line: None, col: None}.into())
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::errors::{KeyNotFoundError, ParsingError};
+	use crate::expr::{parse_expr, Value};
+
+	fn lookup(name: &str) -> Result<String, ParsingError> {
+		match name {
+			"region" => Ok(String::from("coast")),
+			"level" => Ok(String::from("3")),
+			"npc-name" => Ok(String::from("Brunhilde")),
+			_ => Err(KeyNotFoundError{key: name.into()}.into())
+		}
+	}
+
+	#[test]
+	fn test_expr_eval() {
+		let eval = |e: &str| parse_expr(e).unwrap().eval(&lookup).unwrap();
+		assert_eq!(eval("$region == coast ? 5 : 1"), Value::Num(5.));
+		assert_eq!(eval("$region == 'deep-sea' ? 5 : 1"), Value::Num(1.));
+		assert_eq!(eval("$level * 2 + 1"), Value::Num(7.));
+		assert_eq!(eval("$level >= 3 && $region != desert"), Value::Num(1.));
+		assert_eq!(eval("-(1 + 2) * 2"), Value::Num(-6.));
+		assert_eq!(eval("$level > 5 ? 10 : $level > 2 ? 3 : 0"), Value::Num(3.));
+		assert_eq!(eval("1 || $missing"), Value::Num(1.));
+		assert_eq!(eval("$level == 1 ? $missing : 2"), Value::Num(2.));
+		assert_eq!(eval("$level-1"), Value::Num(2.));
+		assert_eq!(eval("$level-$level"), Value::Num(0.));
+		assert_eq!(eval("$npc-name == Brunhilde"), Value::Num(1.));
+	}
+
+	#[test]
+	fn test_expr_errors() {
+		assert!(parse_expr("$region ==").is_err());
+		assert!(parse_expr("(1 + 2").is_err());
+		assert!(parse_expr("1 2").is_err());
+		assert!(parse_expr("1 # 2").is_err());
+		assert!(parse_expr("$missing == 1").unwrap().eval(&lookup).is_err());
+		assert!(parse_expr("$region + 1").unwrap().eval(&lookup).is_err());
+	}
+}
@@ -0,0 +1,40 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use crate::data::LookUpTable;
+use crate::errors::ParsingError;
+
+/// A look-up table that is defined as a copy of another (base) look-up table with some items
+/// added, removed, or re-weighted (eg `{$extends: monsters, $add: [dragon], $remove: [rat]}`)
+#[derive(Clone, Debug)]
+pub(crate) struct TableExtension {
+	/// ID of the new look-up table
+	pub id: String,
+	/// ID of the base look-up table
+	pub base: String,
+	/// Items to add to the copy of the base table
+	pub added: LookUpTable,
+	/// Texts (or stable keys) of the items to remove from the copy of the base table
+	pub removed: Vec<String>,
+	/// New weights for items of the copy of the base table, by text (or stable key)
+	pub weights: Vec<(String, f64)>
+}
+
+impl TableExtension {
+	/// Creates the new look-up table from the given base table
+	/// # Arguments
+	/// * `base`: the look-up table that this table extends
+	/// # Returns
+	/// The base table with the items of this extension added, removed, and re-weighted, or an
+	/// error if one of the new weights is negative, NaN, or infinite
+	pub fn apply(&self, base: &LookUpTable) -> Result<LookUpTable, ParsingError> {
+		let mut lut = base.clone();
+		for text in &self.removed {
+			lut.remove_item(text.as_str());
+		}
+		for (text, weight) in &self.weights {
+			lut.set_weight(text.as_str(), crate::check_weight(*weight, text.as_str())?);
+		}
+		lut.append(self.added.clone());
+		Ok(lut)
+	}
+}
@@ -0,0 +1,160 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use regex::Regex;
+use crate::errors::ParseError;
+
+/// Look-up table file format, for loading look-up tables from a stream with
+/// [Interpreter::load_reader(...)](crate::Interpreter::load_reader)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+	/// Plain text, where each line is a look-up table item (.txt)
+	Txt,
+	/// Comma-separated values, where each column is a look-up table (.csv)
+	Csv,
+	/// Tab-separated values, where each column is a look-up table (.tsv)
+	Tsv,
+	/// JSON, including JSON5 comments and trailing commas (.json, .json5, or .jsonc)
+	Json,
+	/// YAML (.yaml or .yml)
+	Yaml,
+	/// XML (.xml)
+	Xml,
+	/// Markdown lists and pipe tables (.md)
+	Md,
+	/// Twee interactive fiction passages (.twee or .tw)
+	Twee,
+	/// Excel spreadsheet (.xlsx)
+	Xlsx,
+	/// Zip archive of look-up table files (.zip)
+	Zip
+}
+
+impl Format {
+	/// Gets the format of the given file extension (without the `.`), ignoring case
+	/// # Arguments
+	/// * `ext`: file extension, eg "yml"
+	/// # Returns
+	/// The matching `Format`, or `None` if the file extension is not a supported format
+	pub fn from_extension(ext: &str) -> Option<Format> {
+		match ext.to_lowercase().as_str() {
+			"txt" => Some(Format::Txt),
+			"csv" => Some(Format::Csv),
+			"tsv" => Some(Format::Tsv),
+			"json" | "json5" | "jsonc" => Some(Format::Json),
+			"yaml" | "yml" => Some(Format::Yaml),
+			"xml" => Some(Format::Xml),
+			"md" => Some(Format::Md),
+			"twee" | "tw" => Some(Format::Twee),
+			"xlsx" => Some(Format::Xlsx),
+			"zip" => Some(Format::Zip),
+			_ => None
+		}
+	}
+
+	/// Guesses the format of look-up table file content, for files that do not have a file
+	/// extension (eg a file named `names`). Content that does not look like any other format is
+	/// plain text.
+	/// # Arguments
+	/// * `content`: the content of the file
+	/// # Returns
+	/// The most likely `Format` of the content
+	pub fn sniff(content: &[u8]) -> Format {
+		if content.starts_with(b"PK\x03\x04") {
+			// an Excel spreadsheet is a zip archive that contains a workbook
+			return match content.windows(11).any(|w| w == b"xl/workbook") {
+				true => Format::Xlsx,
+				false => Format::Zip
+			};
+		}
+		let text = String::from_utf8_lossy(content);
+		let text = text.trim_start_matches('\u{feff}').trim_start();
+		if text.starts_with('{') || text.starts_with('[') || text.starts_with("//") || text.starts_with("/*") {
+			return Format::Json;
+		}
+		if text.starts_with('<') {
+			return Format::Xml;
+		}
+		if text.starts_with("::") {
+			return Format::Twee;
+		}
+		let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).take(20).collect();
+		// YAML: only comments, list items, "key: value" lines, and indented lines
+		let yaml_line: Regex = Regex::new(r#"^(\s*#.*|\s*-(\s.*)?|\s+\S.*|[^\s#][^:]*:(\s.*)?)$"#).unwrap();
+		let yaml_start: Regex = Regex::new(r#"^(---|-(\s.*)?|[^\s#][^:]*:(\s.*)?)$"#).unwrap();
+		let first_item = lines.iter().find(|line| !line.trim_start().starts_with('#'));
+		if first_item.is_some_and(|line| yaml_start.is_match(line)) && lines.iter().all(|line| yaml_line.is_match(line)) {
+			return Format::Yaml;
+		}
+		// CSV or TSV: the same (non-zero) number of delimiters on every line
+		for (delimiter, format) in [('\t', Format::Tsv), (',', Format::Csv)] {
+			let count = |line: &&str| line.matches(delimiter).count();
+			if lines.len() > 1 && count(&lines[0]) > 0 && lines.iter().all(|line| count(line) == count(&lines[0])) {
+				return format;
+			}
+		}
+		Format::Txt
+	}
+
+	/// Gets the usual file extension of this format (without the `.`), eg "yaml"
+	pub fn extension(&self) -> &'static str {
+		match self {
+			Format::Txt => "txt",
+			Format::Csv => "csv",
+			Format::Tsv => "tsv",
+			Format::Json => "json",
+			Format::Yaml => "yaml",
+			Format::Xml => "xml",
+			Format::Md => "md",
+			Format::Twee => "twee",
+			Format::Xlsx => "xlsx",
+			Format::Zip => "zip"
+		}
+	}
+}
+
+impl Display for Format {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.extension())
+	}
+}
+
+impl FromStr for Format {
+	type Err = ParseError;
+
+	/// Parses a format from its file extension, such as "csv" or ".yml"
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let ext = s.trim();
+		Format::from_extension(ext.strip_prefix('.').unwrap_or(ext))
+			.ok_or_else(|| ParseError{msg: Some(format!("'{}' is not a supported look-up table format", s)), line: None, col: None})
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::format::Format;
+
+	#[test]
+	fn test_format_parse() {
+		assert_eq!("yml".parse::<Format>().unwrap(), Format::Yaml);
+		assert_eq!(".JSON5".parse::<Format>().unwrap(), Format::Json);
+		assert_eq!(Format::from_extension("tw"), Some(Format::Twee));
+		assert!("png".parse::<Format>().is_err());
+		assert_eq!(Format::Yaml.to_string(), "yaml");
+	}
+
+	#[test]
+	fn test_format_sniff() {
+		assert_eq!(Format::sniff(b"aardvark\nbird\ncat\n"), Format::Txt);
+		assert_eq!(Format::sniff(b"Dr. Smith: the surgeon\nNurse Jones\n"), Format::Txt);
+		assert_eq!(Format::sniff(b"# pets\n- cat\n- dog\n"), Format::Yaml);
+		assert_eq!(Format::sniff(b"colors:\n  light: [white]\n  dark:\n    - black\n"), Format::Yaml);
+		assert_eq!(Format::sniff(b"  {\"pets\": [\"cat\"]}"), Format::Json);
+		assert_eq!(Format::sniff(b"weight,pet\n3,cat\n1,dog\n"), Format::Csv);
+		assert_eq!(Format::sniff(b"weight\tpet\n3\tcat\n"), Format::Tsv);
+		assert_eq!(Format::sniff(b"<pets><pet>cat</pet></pets>"), Format::Xml);
+		assert_eq!(Format::sniff(b":: Start\nOnce upon a time\n"), Format::Twee);
+		assert_eq!(Format::sniff(b"PK\x03\x04..."), Format::Zip);
+	}
+}
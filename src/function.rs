@@ -0,0 +1,155 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use rand::RngCore;
+use crate::errors::{ParseError, ParsingError};
+
+/// Prefix of the IDs that call a template function (eg `${fn:weather(season=winter)}`)
+pub(crate) const FN_PREFIX: &str = "fn:";
+
+/// A template function (see
+/// [Interpreter::register_function(...)](crate::Interpreter::register_function))
+pub(crate) type TemplateFunction = Arc<dyn Fn(&FunctionArgs, &mut FunctionContext) -> Result<String, String> + Send + Sync>;
+
+/// The template functions of an interpreter, by name
+#[derive(Clone, Default)]
+pub(crate) struct FunctionRegistry {
+	/// Functions by name
+	functions: HashMap<String, TemplateFunction>
+}
+
+impl FunctionRegistry {
+	/// Adds a function, replacing any function with the same name
+	pub fn insert(&mut self, name: String, function: TemplateFunction) {
+		self.functions.insert(name, function);
+	}
+
+	/// Removes a function
+	/// # Returns
+	/// True if there was a function with that name
+	pub fn remove(&mut self, name: &str) -> bool {
+		self.functions.remove(name).is_some()
+	}
+
+	/// Gets a function by name
+	pub fn get(&self, name: &str) -> Option<&TemplateFunction> {
+		self.functions.get(name)
+	}
+}
+
+impl Debug for FunctionRegistry {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.debug_set().entries(self.functions.keys()).finish()
+	}
+}
+
+/// The arguments of a template function call, such as `winter` and `day=3` in
+/// `${fn:weather(winter, day=3)}`. Arguments are separated by commas, and an argument with an `=`
+/// is a named argument. References and dice expressions in the arguments (eg `$season` or
+/// `#{1d6}`) are substituted before the function is called.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionArgs {
+	/// Arguments without a name, in order
+	positional: Vec<String>,
+	/// Named arguments
+	named: BTreeMap<String, String>
+}
+
+impl FunctionArgs {
+	/// Parses a function call such as `weather(season=winter)` (the parentheses are optional if
+	/// there are no arguments)
+	/// # Returns
+	/// The function name and the arguments, or an error if the call is malformed
+	pub(crate) fn parse_call(call: &str) -> Result<(&str, FunctionArgs), ParsingError> {
+		let malformed = || -> ParsingError {ParseError{
+			msg: Some(format!("'{}{}' is not a valid function call (must be eg 'fn:name(arg, key=value)')", FN_PREFIX, call)),
+			line: None, col: None
+		}.into()};
+		let (name, args) = match call.split_once('(') {
+			None => (call.trim(), ""),
+			Some((name, rest)) => (name.trim(), rest.trim_end().strip_suffix(')').ok_or_else(malformed)?)
+		};
+		if name.is_empty() {
+			return Err(malformed());
+		}
+		let mut parsed = FunctionArgs::default();
+		if !args.trim().is_empty() {
+			for arg in args.split(',') {
+				match arg.split_once('=') {
+					None => parsed.positional.push(String::from(arg.trim())),
+					Some((key, value)) => {parsed.named.insert(String::from(key.trim()), String::from(value.trim()));}
+				}
+			}
+		}
+		Ok((name, parsed))
+	}
+
+	/// Gets a named argument
+	/// # Arguments
+	/// * `name`: name of the argument
+	/// # Returns
+	/// The value of the argument, or `None` if the call does not have that argument
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.named.get(name).map(String::as_str)
+	}
+
+	/// Gets the arguments without a name, in order
+	pub fn positional(&self) -> &[String] {
+		&self.positional
+	}
+
+	/// Gets the named arguments
+	pub fn named(&self) -> &BTreeMap<String, String> {
+		&self.named
+	}
+}
+
+/// Gives a template function access to the state of the evaluation that calls it
+pub struct FunctionContext<'a> {
+	/// Text of each reference that is defined at the call, by reference ID
+	refs: HashMap<&'a str, &'a str>,
+	/// Random number generator of the interpreter
+	rng: &'a mut dyn RngCore
+}
+
+impl<'a> FunctionContext<'a> {
+	/// Creates the context of a function call
+	pub(crate) fn new(refs: HashMap<&'a str, &'a str>, rng: &'a mut dyn RngCore) -> Self {
+		FunctionContext{refs, rng}
+	}
+
+	/// Gets the text of a reference
+	/// # Arguments
+	/// * `ref_id`: ID of the reference (without the `@`)
+	/// # Returns
+	/// The text of the reference, or `None` if it is not defined
+	pub fn get_ref(&self, ref_id: &str) -> Option<&str> {
+		self.refs.get(ref_id).copied()
+	}
+
+	/// Gets the random number generator of the interpreter, which the function should use for
+	/// random choices such that seeded interpreters stay reproducible
+	pub fn rng(&mut self) -> &mut dyn RngCore {
+		&mut *self.rng
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::function::FunctionArgs;
+
+	#[test]
+	fn test_parse_call() {
+		let (name, args) = FunctionArgs::parse_call("weather(spring, day = 3)").unwrap();
+		assert_eq!(name, "weather");
+		assert_eq!(args.positional(), ["spring"]);
+		assert_eq!(args.get("day"), Some("3"));
+		let (name, args) = FunctionArgs::parse_call("today").unwrap();
+		assert_eq!(name, "today");
+		assert_eq!(args, FunctionArgs::default());
+		assert!(FunctionArgs::parse_call("weather(spring").is_err());
+		assert!(FunctionArgs::parse_call("(spring)").is_err());
+	}
+}
@@ -0,0 +1,31 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use rand::Rng;
+use crate::errors::ParsingError;
+use crate::Interpreter;
+
+/// An endless iterator that evaluates the same text again and again (see
+/// [Interpreter::iter(...)](crate::Interpreter::iter)). Each call to `next()` returns a new
+/// result, so use adapters such as `take(n)` to stop.
+#[derive(Debug)]
+pub struct EvalIter<'a, R> where R: Rng {
+	/// The interpreter that evaluates the text
+	interpreter: &'a mut Interpreter<R>,
+	/// The text to evaluate
+	template: String
+}
+
+impl<'a, R> EvalIter<'a, R> where R: Rng {
+	/// Creates an iterator that evaluates the given text with the given interpreter
+	pub(crate) fn new(interpreter: &'a mut Interpreter<R>, template: String) -> Self {
+		EvalIter{interpreter, template}
+	}
+}
+
+impl<R> Iterator for EvalIter<'_, R> where R: Rng {
+	type Item = Result<String, ParsingError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		Some(self.interpreter.eval(self.template.as_str()))
+	}
+}
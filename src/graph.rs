@@ -0,0 +1,165 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Which look-up tables and references each loaded look-up table (and template) uses, built
+/// without drawing from any look-up tables (see
+/// [Interpreter::dependency_graph()](crate::Interpreter::dependency_graph)). Look-up table IDs
+/// that depend on references or dice rolls (eg `pet-names/$pet`) cannot be known without
+/// evaluating the text and are not included.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DependencyGraph {
+	/// IDs of the look-up tables used by each node (look-up table or template)
+	tables: BTreeMap<String, BTreeSet<String>>,
+	/// IDs of the references used by each node
+	refs: BTreeMap<String, BTreeSet<String>>
+}
+
+impl DependencyGraph {
+	/// Adds a node (look-up table or template) with the look-up tables and references that it
+	/// uses
+	pub(crate) fn add_node(&mut self, id: String, tables: BTreeSet<String>, refs: BTreeSet<String>) {
+		self.tables.entry(id.clone()).or_default().extend(tables);
+		self.refs.entry(id).or_default().extend(refs);
+	}
+
+	/// Gets the IDs of all nodes (look-up tables and templates), sorted
+	pub fn nodes(&self) -> impl Iterator<Item=&String> {
+		self.tables.keys()
+	}
+
+	/// Gets the look-up tables that a node uses directly
+	/// # Arguments
+	/// * `id`: ID of the look-up table (or name of the template)
+	/// # Returns
+	/// The IDs of the used look-up tables, or `None` if there is no such node
+	pub fn dependencies(&self, id: &str) -> Option<&BTreeSet<String>> {
+		self.tables.get(id)
+	}
+
+	/// Gets the references that a node uses directly (eg `@pet` or `$pet`)
+	/// # Arguments
+	/// * `id`: ID of the look-up table (or name of the template)
+	/// # Returns
+	/// The IDs of the used references, or `None` if there is no such node
+	pub fn refs_used(&self, id: &str) -> Option<&BTreeSet<String>> {
+		self.refs.get(id)
+	}
+
+	/// Gets the nodes that use the given look-up table directly
+	/// # Arguments
+	/// * `id`: ID of the look-up table
+	/// # Returns
+	/// The IDs of the look-up tables (and names of the templates) that use it
+	pub fn dependents(&self, id: &str) -> BTreeSet<&String> {
+		self.tables.iter().filter(|(_, tables)| tables.contains(id)).map(|(node, _)| node).collect()
+	}
+
+	/// Gets the look-up tables that are used but which are not nodes of the graph (eg because
+	/// they are not loaded)
+	pub fn missing(&self) -> BTreeSet<&String> {
+		self.tables.values().flatten().filter(|id| !self.tables.contains_key(*id)).collect()
+	}
+
+	/// Finds the groups of look-up tables that use each other in a cycle (eg `a` uses `b` and `b`
+	/// uses `a`, or `a` uses itself). A cycle is not necessarily an error, as long as each
+	/// look-up table in it has items that end the cycle, but otherwise the evaluation fails when
+	/// it reaches the recursion limit.
+	/// # Returns
+	/// The cycles, each as a sorted list of look-up table IDs, sorted by their first ID
+	pub fn cycles(&self) -> Vec<Vec<String>> {
+		let mut tarjan = Tarjan{graph: self, index: BTreeMap::new(), low: BTreeMap::new(),
+			stack: Vec::new(), on_stack: BTreeSet::new(), cycles: Vec::new()};
+		for node in self.tables.keys() {
+			if !tarjan.index.contains_key(node.as_str()) {
+				tarjan.connect(node.as_str());
+			}
+		}
+		let mut cycles = tarjan.cycles;
+		cycles.iter_mut().for_each(|cycle| cycle.sort());
+		cycles.sort();
+		cycles
+	}
+}
+
+/// State of Tarjan's algorithm for finding the strongly connected components of a graph
+struct Tarjan<'a> {
+	/// The graph
+	graph: &'a DependencyGraph,
+	/// Visiting order of each visited node
+	index: BTreeMap<&'a str, usize>,
+	/// Lowest visiting order reachable from each visited node
+	low: BTreeMap<&'a str, usize>,
+	/// Nodes of the components that are not complete yet
+	stack: Vec<&'a str>,
+	/// Nodes that are on the stack
+	on_stack: BTreeSet<&'a str>,
+	/// Components that are cycles
+	cycles: Vec<Vec<String>>
+}
+
+impl<'a> Tarjan<'a> {
+	/// Visits a node and everything reachable from it
+	fn connect(&mut self, node: &'a str) {
+		let order = self.index.len();
+		self.index.insert(node, order);
+		self.low.insert(node, order);
+		self.stack.push(node);
+		self.on_stack.insert(node);
+		let mut self_loop = false;
+		for next in self.graph.tables.get(node).into_iter().flatten() {
+			let next = next.as_str();
+			if next == node {
+				self_loop = true;
+			}
+			if !self.graph.tables.contains_key(next) {
+				continue;
+			}
+			if !self.index.contains_key(next) {
+				self.connect(next);
+				let low = self.low[node].min(self.low[next]);
+				self.low.insert(node, low);
+			} else if self.on_stack.contains(next) {
+				let low = self.low[node].min(self.index[next]);
+				self.low.insert(node, low);
+			}
+		}
+		if self.low[node] == self.index[node] {
+			let mut component: Vec<String> = Vec::new();
+			while let Some(member) = self.stack.pop() {
+				self.on_stack.remove(member);
+				component.push(String::from(member));
+				if member == node {
+					break;
+				}
+			}
+			if component.len() > 1 || self_loop {
+				self.cycles.push(component);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use std::collections::BTreeSet;
+	use crate::graph::DependencyGraph;
+
+	fn ids(ids: &[&str]) -> BTreeSet<String> {
+		ids.iter().map(|id| String::from(*id)).collect()
+	}
+
+	#[test]
+	fn test_cycles() {
+		let mut graph = DependencyGraph::default();
+		graph.add_node(String::from("a"), ids(&["b"]), ids(&[]));
+		graph.add_node(String::from("b"), ids(&["c", "x"]), ids(&["pet"]));
+		graph.add_node(String::from("c"), ids(&["a"]), ids(&[]));
+		graph.add_node(String::from("d"), ids(&["d", "a"]), ids(&[]));
+		graph.add_node(String::from("e"), ids(&["a"]), ids(&[]));
+		assert_eq!(graph.cycles(), vec![vec!["a", "b", "c"], vec!["d"]]);
+		assert_eq!(graph.dependents("a").into_iter().collect::<Vec<_>>(), vec!["c", "d", "e"]);
+		assert_eq!(graph.missing().into_iter().collect::<Vec<_>>(), vec!["x"]);
+		assert!(graph.refs_used("b").unwrap().contains("pet"));
+	}
+}
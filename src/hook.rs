@@ -0,0 +1,67 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::fmt::{Debug, Formatter};
+use crate::data::Item;
+
+/// Observes the decisions that an interpreter makes while evaluating a text, such as to log or
+/// visualize how a text was generated (see
+/// [Interpreter::set_hook(...)](crate::Interpreter::set_hook)). Every callback does nothing by
+/// default, so implement only the ones you need.
+///
+/// # Example
+/// ```rust
+/// use twas;
+/// struct Logger;
+/// impl twas::EvalHook for Logger {
+///     fn on_draw(&mut self, table_id: &str, item: &twas::Item) {
+///         println!("drew '{}' from {}", item.get_text(), table_id);
+///     }
+/// }
+/// let mut interpreter = twas::Interpreter::new();
+/// interpreter.load_lines("animal", vec!["cat", "dog"]).unwrap();
+/// interpreter.set_hook(Logger);
+/// println!("{}", interpreter.eval("I have a ${animal}.").unwrap());
+/// ```
+pub trait EvalHook: Send {
+	/// Called for each substitution token before it is evaluated
+	/// # Arguments
+	/// * `token`: the token, without the enclosing `${` and `}` (eg `animal@pet`)
+	fn on_token(&mut self, token: &str) {
+		let _ = token;
+	}
+
+	/// Called for each substitution token before it is evaluated (right after
+	/// [on_token(...)](EvalHook::on_token)), with its recursion depth: 0 for the tokens of the
+	/// evaluated text, 1 for the tokens in the text that they produced, and so on. The evaluation
+	/// fails when the depth goes past the recursion limit (see
+	/// [Interpreter::set_recursion_limit(...)](crate::Interpreter::set_recursion_limit)).
+	/// # Arguments
+	/// * `token`: the token, without the enclosing `${` and `}` (eg `animal@pet`)
+	/// * `depth`: the recursion depth of the token
+	fn on_token_depth(&mut self, token: &str, depth: usize) {
+		let _ = (token, depth);
+	}
+
+	/// Called for each item that is drawn from a look-up table (or selected by value), in the
+	/// order that the items are drawn
+	/// # Arguments
+	/// * `table_id`: ID of the look-up table
+	/// * `item`: the drawn item (a number range item appears with the number that was rolled)
+	fn on_draw(&mut self, table_id: &str, item: &Item) {
+		let _ = (table_id, item);
+	}
+
+	/// Called for each reference that is stored
+	/// # Arguments
+	/// * `ref_id`: ID of the reference (without the `@`)
+	/// * `text`: text of the reference
+	fn on_ref(&mut self, ref_id: &str, text: &str) {
+		let _ = (ref_id, text);
+	}
+}
+
+impl Debug for dyn EvalHook {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "EvalHook")
+	}
+}
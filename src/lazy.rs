@@ -0,0 +1,177 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use encoding_rs::Encoding;
+use crate::data::{LookUpTable, MergedDuplicate};
+use crate::extend::TableExtension;
+use crate::options::{CsvOptions, Dedup, EvalOptions};
+use crate::reload::SourceFile;
+use crate::source::TableSource;
+
+/// The settings of an interpreter that affect how look-up table files are parsed, as they were
+/// when a file was indexed, such that the file is parsed the same way as if it had been loaded
+/// right away
+#[derive(Clone, Debug)]
+pub(crate) struct LoadSettings {
+	/// Text encoding of the files (see [Interpreter::set_encoding(...)](crate::Interpreter::set_encoding))
+	pub encoding: Option<&'static Encoding>,
+	/// Options for reading .csv and .tsv files
+	pub csv_options: CsvOptions,
+	/// How duplicate items are merged when the file is loaded
+	pub dedup: Dedup,
+	/// Evaluation options (including the compatibility level)
+	pub options: EvalOptions
+}
+
+/// A look-up table file that has been indexed but not parsed yet
+#[derive(Clone, Debug)]
+pub(crate) struct LazyFile {
+	/// Path of the file
+	pub path: PathBuf,
+	/// ID prefix that the file will be loaded with
+	pub id_prefix: String,
+	/// Base ID of the look-up tables in the file (eg "animal" for "animal.txt")
+	pub base_id: String,
+	/// Settings that the file will be parsed with
+	pub settings: LoadSettings
+}
+
+impl LazyFile {
+	/// Checks whether this file is expected to provide the look-up table with the given ID, which
+	/// is the case if the ID is the file's base ID or a path within it (eg "colors/paint" for
+	/// "colors.yaml")
+	pub fn may_provide(&self, id: &str) -> bool {
+		id == self.base_id || (id.starts_with(self.base_id.as_str())
+			&& id[self.base_id.len()..].starts_with('/'))
+	}
+}
+
+/// Look-up table files that are only parsed the first time that one of their look-up tables is
+/// needed, and the table sources that are asked for look-up tables that are not loaded. The tables
+/// that are parsed (or provided by a table source) during an evaluation are kept here until the
+/// evaluation is done, and then they are moved into the interpreter's registry.
+#[derive(Debug, Default)]
+pub(crate) struct LazyRegistry {
+	/// Indexed files that have not been parsed yet
+	pending: Mutex<Vec<LazyFile>>,
+	/// Look-up tables that were parsed during the current evaluation
+	loaded: Mutex<HashMap<String, Arc<LookUpTable>>>,
+	/// Files that were parsed during the current evaluation
+	sources: Mutex<Vec<SourceFile>>,
+	/// Table extensions of the files that were parsed during the current evaluation, whose base
+	/// look-up table was not in the same file
+	extensions: Mutex<Vec<TableExtension>>,
+	/// Duplicate items that were merged while parsing files during the current evaluation
+	dedup_report: Mutex<Vec<(String, MergedDuplicate)>>,
+	/// Table sources, in the order that they are asked for look-up tables
+	table_sources: Vec<Arc<dyn TableSource>>
+}
+
+impl LazyRegistry {
+	/// Adds a file to the index
+	pub fn add(&mut self, file: LazyFile) {
+		self.pending.get_mut().unwrap().push(file);
+	}
+
+	/// Checks whether any indexed file is expected to provide the given look-up table
+	pub fn may_provide(&self, id: &str) -> bool {
+		self.pending.lock().unwrap().iter().any(|file| file.may_provide(id))
+	}
+
+	/// Removes and returns the indexed files that are expected to provide the given look-up table
+	pub fn take_matching(&self, id: &str) -> Vec<LazyFile> {
+		let mut pending = self.pending.lock().unwrap();
+		let (matching, rest): (Vec<LazyFile>, Vec<LazyFile>) = pending.drain(..)
+			.partition(|file| file.may_provide(id));
+		*pending = rest;
+		matching
+	}
+
+	/// Removes and returns all indexed files
+	pub fn take_all(&mut self) -> Vec<LazyFile> {
+		std::mem::take(self.pending.get_mut().unwrap())
+	}
+
+	/// Creates a new lazy registry with the same indexed files, but without any look-up tables
+	/// that were parsed during the current evaluation
+	pub fn copy_pending(&self) -> LazyRegistry {
+		LazyRegistry{pending: Mutex::new(self.pending.lock().unwrap().clone()),
+			table_sources: self.table_sources.clone(), ..Default::default()}
+	}
+
+	/// Adds a table source, which is asked for look-up tables after the previously added sources
+	pub fn add_table_source(&mut self, source: Arc<dyn TableSource>) {
+		self.table_sources.push(source);
+	}
+
+	/// Removes all table sources
+	pub fn clear_table_sources(&mut self) {
+		self.table_sources.clear();
+	}
+
+	/// Checks whether any table source provides the given look-up table (without keeping it)
+	pub fn can_resolve(&self, id: &str) -> bool {
+		self.table_sources.iter().any(|source| source.resolve(id).is_some())
+	}
+
+	/// Asks the table sources for the given look-up table. The table is kept until the end of the
+	/// current evaluation (and then moved into the registry), unless the source says otherwise.
+	pub fn resolve(&self, id: &str) -> Option<Arc<LookUpTable>> {
+		for source in &self.table_sources {
+			if let Some(lut) = source.resolve(id) {
+				let lut = Arc::new(lut);
+				if source.keep_tables() {
+					self.loaded.lock().unwrap().insert(String::from(id), lut.clone());
+				}
+				return Some(lut);
+			}
+		}
+		None
+	}
+
+	/// Removes the indexed files for which the given predicate returns false
+	pub fn retain(&mut self, keep: impl FnMut(&LazyFile) -> bool) {
+		self.pending.get_mut().unwrap().retain(keep);
+	}
+
+	/// Gets a look-up table that was parsed during the current evaluation
+	pub fn get_loaded(&self, id: &str) -> Option<Arc<LookUpTable>> {
+		self.loaded.lock().unwrap().get(id).cloned()
+	}
+
+	/// Puts a file back into the index (eg because it could not be parsed)
+	pub fn restore(&self, file: LazyFile) {
+		self.pending.lock().unwrap().push(file);
+	}
+
+	/// Stores the look-up tables (and pending table extensions and merged duplicates) that were
+	/// parsed from file(s) during the current evaluation
+	pub fn add_loaded(&self, tables: HashMap<String, Arc<LookUpTable>>, sources: Vec<SourceFile>,
+			extensions: Vec<TableExtension>, dedup_report: Vec<(String, MergedDuplicate)>) {
+		let mut loaded = self.loaded.lock().unwrap();
+		for (id, lut) in tables {
+			match loaded.get_mut(&id) {
+				Some(existing) => Arc::make_mut(existing).append(Arc::unwrap_or_clone(lut)),
+				None => {loaded.insert(id, lut);}
+			}
+		}
+		self.sources.lock().unwrap().extend(sources);
+		self.extensions.lock().unwrap().extend(extensions);
+		self.dedup_report.lock().unwrap().extend(dedup_report);
+	}
+
+	/// Takes the look-up tables, files, and pending table extensions that were parsed during the
+	/// current evaluation
+	pub fn take_loaded(&mut self) -> (HashMap<String, Arc<LookUpTable>>, Vec<SourceFile>, Vec<TableExtension>) {
+		let tables = std::mem::take(self.loaded.get_mut().unwrap());
+		(tables, std::mem::take(self.sources.get_mut().unwrap()), std::mem::take(self.extensions.get_mut().unwrap()))
+	}
+
+	/// Takes the duplicate items that were merged while parsing files during the current
+	/// evaluation
+	pub fn take_dedup_report(&mut self) -> Vec<(String, MergedDuplicate)> {
+		std::mem::take(self.dedup_report.get_mut().unwrap())
+	}
+}
@@ -1,33 +1,131 @@
 #![deny(unused_must_use)]
 #![deny(missing_docs)]
 #![doc = include_str!("../README.md")]
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::Debug;
 use std::fs::File;
+use std::sync::{Arc, OnceLock};
 use std::{fs, io};
 use std::io::prelude::*;
-use std::io::{BufReader, ErrorKind, Read};
+use std::io::{BufReader, Cursor, ErrorKind, Read, Seek};
 use std::path::{Path, PathBuf};
-use rand::Rng;
+use std::time::{Duration, Instant};
+use rand::{Rng, RngCore};
 use dicexp::{DiceBag, simple_rng, new_simple_rng};
 use rand::rngs::StdRng;
 use regex::Regex;
 use zip;
 use serde_yaml;
 use serde_json;
-use zip::result::ZipError;
+use serde::{Serialize, Deserialize};
 use utf8_chars::BufReadCharsExt;
+use calamine::{Reader, Xlsx};
+use flate2::read::GzDecoder;
+use encoding_rs::Encoding;
+use unicode_normalization::UnicodeNormalization;
 mod errors;
 mod data;
 mod subspec;
+mod expr;
+mod options;
+mod template;
+mod reload;
+mod lazy;
+mod extend;
+mod format;
+mod deplete;
+mod state;
+mod builder;
+mod curve;
+mod stats;
+mod evaluation;
+mod generate;
+mod source;
+mod function;
+mod hook;
+mod validate;
+mod graph;
+mod analysis;
+mod compiled;
+mod lint;
+mod replay;
 use crate::errors::*;
-use crate::data::{Item, LookUpTable};
-use crate::subspec::SubstitutionOptions;
+use crate::data::{field_value, TABLE_LINK};
+pub use crate::data::{Item, LookUpTable, MergedDuplicate, WeightIssue};
+use crate::subspec::{PickOptions, SubstitutionOptions};
+use crate::reload::SourceFile;
+use crate::lazy::{LazyFile, LazyRegistry, LoadSettings};
+use crate::extend::TableExtension;
+use crate::state::{SavedState, STATE_VERSION};
+pub use crate::deplete::DrawState;
+pub use crate::builder::{InterpreterBuilder, LookUpTableBuilder};
+pub use crate::curve::Curve;
+pub use crate::stats::{NamespaceStats, TableStats};
+pub use crate::evaluation::{EvalStats, Evaluation, Span};
+use crate::evaluation::Recording;
+pub use crate::generate::EvalIter;
+pub use crate::source::TableSource;
+pub use crate::function::{FunctionArgs, FunctionContext};
+use crate::function::{FunctionRegistry, FN_PREFIX};
+pub use crate::hook::EvalHook;
+pub use crate::validate::Validation;
+pub use crate::graph::DependencyGraph;
+pub use crate::analysis::Distribution;
+use crate::analysis::{ChoiceScript, Tripwire};
+pub use crate::compiled::{CompiledRegistry, Evaluator};
+pub use crate::lint::{Lint, LintKind};
+pub use crate::replay::{Decision, DrawLog, DrawnItem};
+use crate::replay::{mismatch, DrawTape, LOG_VERSION};
+#[cfg(feature = "hot-reload")]
+use crate::reload::FileWatcher;
+pub use crate::options::{CompatLevel, CsvOptions, Dedup, Depletion, DirLoadOptions, ErrorPolicy, EvalOptions, Strictness};
+pub use crate::template::{FrontMatter, Template};
+pub use crate::format::Format;
+#[cfg(feature = "embed")]
+#[doc(hidden)]
+pub use include_dir;
+
+/// Embeds a directory of look-up table files into the compiled binary, for loading with
+/// [Interpreter::load_embedded(...)](Interpreter::load_embedded) (requires the `embed` feature).
+/// The path is relative to the current working directory of the compiler, so it is best to
+/// start it with `$CARGO_MANIFEST_DIR` (eg `twas::embed_dir!("$CARGO_MANIFEST_DIR/tables")`).
+///
+/// # Example
+/// ```rust,ignore
+/// static TABLES: twas::EmbeddedDir = twas::embed_dir!("$CARGO_MANIFEST_DIR/tables");
+/// let mut interpreter = twas::Interpreter::new();
+/// interpreter.load_embedded(&TABLES).expect("Failed to load embedded tables");
+/// ```
+#[cfg(feature = "embed")]
+#[macro_export]
+macro_rules! embed_dir {
+	($path:tt) => {{
+		use $crate::include_dir as include_dir;
+		include_dir::include_dir!($path)
+	}};
+}
+
+/// A directory of look-up table files that is embedded in the binary with [embed_dir!(...)](embed_dir)
+#[cfg(feature = "embed")]
+pub type EmbeddedDir = include_dir::Dir<'static>;
 
 /// Marks the start of a substitution expression
 const SUB_START: &str = "${";
 /// Marks the start of a dice number expression
 const DICE_START: &str = "#{";
+/// Last part of the ID of the hidden look-up table that holds the whole rows of a CSV file (eg
+/// "npc-stats/$rows" for file "npc-stats.csv"), for `row` draws
+const ROWS_TABLE: &str = "$rows";
+/// Prefix of the IDs that substitute a host variable (eg `${var:campaign}`, see
+/// [Interpreter::set_var(...)](Interpreter::set_var))
+const VAR_PREFIX: &str = "var:";
+/// Time limit for downloading a look-up table file from a URL (see
+/// [Interpreter::load_url(...)](Interpreter::load_url)), so that a stalled server cannot block
+/// loading forever
+#[cfg(feature = "url")]
+const URL_TIMEOUT: Duration = Duration::from_secs(30);
+/// File types (suffixes) of look-up table files that are loaded when scanning a directory
+const TABLE_FILE_TYPES: [&str; 13] = ["txt", "csv", "tsv", "yml", "yaml", "json", "json5", "jsonc", "xml", "md", "twee", "tw", "xlsx"];
 
 /** The `Interpreter` struct is the text parsing engine for `twas`. It is initialized with a random
  number generator and then loaded with random lookup tables with the various `load_...()`
@@ -86,10 +184,46 @@ println!("{}", interpreter.eval(story).expect("Failed to eval"));
  */
 #[derive(Debug)]
 pub struct Interpreter<R> where R: Rng {
-	registry: HashMap<String, LookUpTable>,
-	dice: DiceBag<StdRng>,
+	registry: HashMap<String, Arc<LookUpTable>>,
 	rng: R,
-	recursion_limit: usize
+	recursion_limit: usize,
+	options: EvalOptions,
+	warnings: Vec<String>,
+	sources: Vec<SourceFile>,
+	#[cfg(feature = "hot-reload")]
+	watcher: Option<FileWatcher>,
+	lazy_loading: bool,
+	lazy: LazyRegistry,
+	/// Canonical paths of the files that are currently being loaded, which is used to resolve
+	/// `$include` paths and to detect circular includes
+	loading_files: Vec<PathBuf>,
+	/// Canonical paths of the files that were included (with `$include` or `!include`) since the
+	/// last file was loaded, which are remembered as part of that file (see [SourceFile])
+	included_files: BTreeSet<PathBuf>,
+	/// Table extensions (`$extends`) whose base look-up table has not been loaded yet
+	extensions: Vec<TableExtension>,
+	/// Text encoding of look-up table files, or `None` to detect the encoding
+	encoding: Option<&'static Encoding>,
+	/// Delimiter and quote character of .csv look-up table files
+	csv_options: CsvOptions,
+	/// Depletion modes of look-up tables and the items that are used up
+	draws: DrawState,
+	/// Persistent references, which are kept between evaluations (see the `persist` option)
+	persistent_refs: BTreeMap<String, RefValue>,
+	/// Variables set by the host application (eg `${var:campaign}`)
+	vars: BTreeMap<String, String>,
+	/// How duplicate items are merged when look-up tables are loaded
+	dedup: Dedup,
+	/// IDs of the look-up tables that were loaded since duplicates were last merged
+	dedup_pending: BTreeSet<String>,
+	/// Duplicate items that were merged since the report was last taken
+	dedup_report: Vec<(String, MergedDuplicate)>,
+	/// Template functions (eg `${fn:weather(season=winter)}`)
+	functions: FunctionRegistry,
+	/// Observer of the evaluations (if any)
+	hook: Option<Box<dyn EvalHook>>,
+	/// Statistics of the most recent evaluation
+	last_stats: EvalStats
 }
 
 impl<R> Interpreter<R> where R: Rng {
@@ -97,14 +231,77 @@ impl<R> Interpreter<R> where R: Rng {
 	/// Creates a new interpreter using the provided random number generator.
 	/// # Arguments
 	/// * rng: The random number generator to use.
-	pub fn from_rng(mut rng: R) -> Interpreter<R> {
-		let dice_seed: u64 = rng.gen();
-		Interpreter { registry: HashMap::new(), rng, dice: DiceBag::new(simple_rng(dice_seed)),
-			recursion_limit: 1000}
+	pub fn from_rng(rng: R) -> Interpreter<R> {
+		Interpreter { registry: HashMap::new(), rng,
+			recursion_limit: 1000, options: EvalOptions::default(), warnings: Vec::new(), sources: Vec::new(),
+			#[cfg(feature = "hot-reload")]
+			watcher: None, lazy_loading: false, lazy: LazyRegistry::default(), loading_files: Vec::new(),
+			included_files: BTreeSet::new(), extensions: Vec::new(), encoding: None, csv_options: CsvOptions::default(), draws: DrawState::default(), persistent_refs: BTreeMap::new(),
+			vars: BTreeMap::new(), dedup: Dedup::default(), dedup_pending: BTreeSet::new(), dedup_report: Vec::new(),
+			functions: FunctionRegistry::default(), hook: None, last_stats: EvalStats::default()}
+	}
+
+	/// Creates an independent copy of this interpreter that shares the loaded look-up tables with
+	/// this interpreter, instead of copying them. A look-up table is only copied when either
+	/// interpreter changes it (eg by loading more items into it). The fork has its own random
+	/// number generator (seeded from this interpreter's random number generator), and its own
+	/// copies of the evaluation options, persistent references, variables, and draw state, such that the
+	/// interpreters do not affect each other. This makes it cheap to load a large table pack once
+	/// and then answer many generation requests concurrently, with one fork per request or thread.
+	/// Note that the fork does not watch the loaded files for changes (see
+	/// `enable_hot_reload()`).
+	/// # Returns
+	/// The new interpreter
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).unwrap();
+	/// let workers: Vec<_> = (0..4).map(|_| {
+	///     let mut fork = interpreter.fork();
+	///     std::thread::spawn(move || fork.eval("I have a pet ${animal}.").unwrap())
+	/// }).collect();
+	/// for worker in workers {
+	///     println!("{}", worker.join().unwrap());
+	/// }
+	/// ```
+	pub fn fork(&mut self) -> Interpreter<StdRng> {
+		let mut fork = Interpreter::from_rng(simple_rng(self.rng.gen()));
+		fork.registry = self.registry.clone();
+		fork.recursion_limit = self.recursion_limit;
+		fork.options = self.options.clone();
+		fork.sources = self.sources.clone();
+		fork.lazy_loading = self.lazy_loading;
+		fork.lazy = self.lazy.copy_pending();
+		fork.extensions = self.extensions.clone();
+		fork.encoding = self.encoding;
+		fork.csv_options = self.csv_options.clone();
+		fork.draws = self.draws.clone();
+		fork.persistent_refs = self.persistent_refs.clone();
+		fork.vars = self.vars.clone();
+		fork.dedup = self.dedup;
+		fork.functions = self.functions.clone();
+		fork
+	}
+
+	/// Creates a frozen copy of this interpreter's look-up tables, options, template functions,
+	/// persistent references, variables, and depletion state, which can be shared between threads without
+	/// a mutex (see [CompiledRegistry]). Like a [fork()](Interpreter::fork), the frozen copy
+	/// shares the loaded look-up tables with this interpreter instead of copying them. Lazily
+	/// loaded files and table sources are loaded by the frozen copy when they are first used.
+	/// # Returns
+	/// The frozen copy
+	pub fn freeze(&self) -> CompiledRegistry {
+		CompiledRegistry::from_interpreter(self)
 	}
 
 	/// Sets the recursion limit to ensure that an infinite loop does not cause the program to
-	/// run indefinitely (default is 1000)
+	/// run indefinitely (default is 1000). The recursion depth of a substitution token is 0 if
+	/// it is in the evaluated text, 1 if it is in the text that such a token produced, and so on
+	/// (see [EvalHook::on_token_depth(...)](EvalHook::on_token_depth)). Use
+	/// [eval_with_limit(...)](Interpreter::eval_with_limit) to change the limit for one
+	/// evaluation only.
 	/// # Arguments
 	/// * `limit`: The new recursion limit.
 	pub fn set_recursion_limit(&mut self, limit: usize) {
@@ -116,6 +313,251 @@ impl<R> Interpreter<R> where R: Rng {
 		self.recursion_limit
 	}
 
+	/// Sets the compatibility level, which resets all evaluation options to the defaults of that
+	/// level (default is [CompatLevel::V1], the original behavior). Use [CompatLevel::V2] (or
+	/// newer) to opt into improved defaults.
+	/// # Arguments
+	/// * `compat`: The new compatibility level.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.set_compat(twas::CompatLevel::V2);
+	/// interpreter.load_str("animal", "cat", "txt").unwrap();
+	/// assert_eq!(interpreter.eval("${{id: animal, count: 2}}").unwrap(), "cat, cat");
+	/// ```
+	pub fn set_compat(&mut self, compat: CompatLevel) {
+		self.options = EvalOptions::for_compat(compat);
+	}
+
+	/// Gets the compatibility level of the current evaluation options.
+	pub fn get_compat(&self) -> CompatLevel {
+		self.options.compat
+	}
+
+	/// Replaces the evaluation options (see [EvalOptions]).
+	/// # Arguments
+	/// * `options`: The new evaluation options.
+	pub fn set_options(&mut self, options: EvalOptions) {
+		self.options = options;
+	}
+
+	/// Gets the current evaluation options.
+	pub fn get_options(&self) -> &EvalOptions {
+		&self.options
+	}
+
+	/// Enables or disables lenient mode, in which missing look-up tables and references are
+	/// replaced by a placeholder like `⟦missing: animal⟧` instead of aborting the evaluation with
+	/// an error (default is disabled). Use [get_warnings()](Interpreter::get_warnings) to list the
+	/// missing keys after evaluation.
+	/// # Arguments
+	/// * `lenient`: true to enable lenient mode
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.set_lenient_missing(true);
+	/// let output = interpreter.eval("I have a pet ${animal}.").unwrap();
+	/// assert_eq!(output, "I have a pet ⟦missing: animal⟧.");
+	/// assert_eq!(interpreter.get_warnings().len(), 1);
+	/// ```
+	pub fn set_lenient_missing(&mut self, lenient: bool) {
+		self.options.lenient_missing = lenient;
+	}
+
+	/// Sets how strictly each kind of problem in a substitution token is treated (default is
+	/// [Strictness::strict()], where every problem stops the evaluation with an error): missing
+	/// look-up tables and references, look-up tables without items, option values that are not
+	/// allowed (eg `case: shouting`), and tokens that cannot be parsed. Each kind of problem
+	/// either stops the evaluation, or is replaced by a placeholder, or is left in the text as
+	/// it was written, and the problems that do not stop the evaluation are listed by
+	/// [get_warnings()](Interpreter::get_warnings). Note that lenient mode (see
+	/// [set_lenient_missing(...)](Interpreter::set_lenient_missing)) takes precedence for missing
+	/// look-up tables and references.
+	/// # Arguments
+	/// * `strictness`: the policy for each kind of problem
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("animal", vec!["cat"]).unwrap();
+	/// let mut strictness = twas::Strictness::lenient();
+	/// strictness.invalid_tokens = twas::ErrorPolicy::Literal;
+	/// interpreter.set_strictness(strictness);
+	/// let output = interpreter.eval("${{id: animal, case: shouting}} ${dragon} ${{id: animal").unwrap();
+	/// assert_eq!(output, "⟦invalid: {id: animal, case: shouting}⟧ ⟦missing: dragon⟧ ${{id: animal");
+	/// let output = interpreter.eval("${{id: animal, count: [}}!").unwrap();
+	/// assert_eq!(output, "${{id: animal, count: [}}!");
+	/// assert_eq!(interpreter.get_warnings().len(), 1);
+	/// ```
+	pub fn set_strictness(&mut self, strictness: Strictness) {
+		self.options.strictness = strictness;
+	}
+
+	/// Gets how strictly each kind of problem in a substitution token is treated (see
+	/// [set_strictness(...)](Interpreter::set_strictness))
+	pub fn get_strictness(&self) -> Strictness {
+		self.options.strictness
+	}
+
+	/// Limits the length of the generated text (default is no limit), such that a look-up table
+	/// pack whose items expand without end (eg `${more} and ${more}`) cannot use up all memory
+	/// before it reaches the recursion limit. The limit applies to the text while it is
+	/// evaluated (including the substitution tokens that are not substituted yet), and an
+	/// evaluation that goes past it fails with an error.
+	/// # Arguments
+	/// * `limit`: maximum length of the text in bytes, or `None` for no limit
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("more", vec!["${more} and ${more}"]).unwrap();
+	/// interpreter.set_output_limit(Some(10_000));
+	/// assert!(interpreter.eval("${more}").is_err());
+	/// ```
+	pub fn set_output_limit(&mut self, limit: Option<usize>) {
+		self.options.max_output_len = limit;
+	}
+
+	/// Limits the number of substitution tokens that are substituted in one evaluation,
+	/// including the tokens in the drawn items (default is no limit). An evaluation that goes
+	/// past the limit fails with an error.
+	/// # Arguments
+	/// * `limit`: maximum number of substitutions, or `None` for no limit
+	pub fn set_substitution_limit(&mut self, limit: Option<usize>) {
+		self.options.max_substitutions = limit;
+	}
+
+	/// Enables or disables loose look-up table IDs, where an ID that is not registered as written
+	/// also matches a registered ID that differs only in letter case, Unicode normalization, or
+	/// `-` vs `_` separators (default is disabled). This helps with table packs from different
+	/// sources that do not agree on the ID style.
+	/// # Arguments
+	/// * `loose`: true to enable loose look-up table IDs
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("monster-names", vec!["Grog"]).unwrap();
+	/// interpreter.set_loose_ids(true);
+	/// assert_eq!(interpreter.eval("${Monster_Names}").unwrap(), "Grog");
+	/// ```
+	pub fn set_loose_ids(&mut self, loose: bool) {
+		self.options.loose_ids = loose;
+	}
+
+	/// Enables or disables front matter in evaluated texts (default is disabled, except with
+	/// [CompatLevel::V2]). With front matter, a text that starts with a YAML block between two
+	/// `---` lines (see [FrontMatter]) uses the references of the block as default references,
+	/// and the block is removed from the text. Without front matter, such a block is evaluated
+	/// like any other text.
+	/// # Arguments
+	/// * `enabled`: true to enable front matter
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.set_front_matter(true);
+	/// assert_eq!(interpreter.eval("---\nrefs: {hero: Robin}\n---\nHi ${@hero}").unwrap(), "Hi Robin");
+	/// ```
+	pub fn set_front_matter(&mut self, enabled: bool) {
+		self.options.front_matter = enabled;
+	}
+
+	/// Sets the text encoding of the look-up table files that are loaded from now on, such as
+	/// `"windows-1252"` or `"utf-16le"` for table files that were exported from a spreadsheet
+	/// program. By default (`None`), the encoding is detected from the byte-order mark (if any),
+	/// and loading a file that is not valid UTF-8 is an error. A byte-order mark always takes
+	/// precedence over the given encoding.
+	/// # Arguments
+	/// * `label`: Name of the encoding (see the [WHATWG Encoding Standard](https://encoding.spec.whatwg.org/#names-and-labels)),
+	///   or `None` to detect the encoding
+	/// # Returns
+	/// A `Result` indicating success, or failure if the encoding is not known
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.set_encoding(Some("latin1")).unwrap();
+	/// assert_eq!(interpreter.get_encoding(), Some("windows-1252"));
+	/// assert!(interpreter.set_encoding(Some("klingon")).is_err());
+	/// ```
+	pub fn set_encoding(&mut self, label: Option<&str>) -> Result<(), ParsingError> {
+		self.encoding = match label {
+			None => None,
+			Some(label) => Some(Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| ParseError{
+				msg: Some(format!("'{}' is not a known text encoding", label)), line: None, col: None
+			})?)
+		};
+		Ok(())
+	}
+
+	/// Gets the name of the text encoding of look-up table files (see
+	/// [set_encoding(...)](Interpreter::set_encoding)), or `None` if the encoding is detected
+	pub fn get_encoding(&self) -> Option<&'static str> {
+		self.encoding.map(|encoding| encoding.name())
+	}
+
+	/// Sets the delimiter and quote character of the .csv look-up table files (and CSV text)
+	/// that are loaded from now on (default is `CsvOptions::default()`), such as
+	/// `CsvOptions::with_delimiter(';')` for the semicolon-separated files that spreadsheet
+	/// programs export in many European locales. Tab-separated (.tsv) files are not affected.
+	/// # Arguments
+	/// * `options`: delimiter and quote character to use
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.set_csv_options(twas::CsvOptions::with_delimiter(';'));
+	/// interpreter.load_str("pet", "animal;name\ncat;Tom", "csv").unwrap();
+	/// assert_eq!(interpreter.eval("${pet/name}").unwrap(), "Tom");
+	/// ```
+	pub fn set_csv_options(&mut self, options: CsvOptions) {
+		self.csv_options = options;
+	}
+
+	/// Gets the delimiter and quote character of .csv look-up table files (see
+	/// [set_csv_options(...)](Interpreter::set_csv_options))
+	pub fn get_csv_options(&self) -> &CsvOptions {
+		&self.csv_options
+	}
+
+	/// Gets the statistics of the most recent evaluation (even if it failed), such as the number
+	/// of substitution tokens, the number of draws from each look-up table, the deepest recursion
+	/// depth, and the wall-clock time, eg to find out why a template is slow
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("monster", vec!["${size} goblin"]).unwrap();
+	/// interpreter.load_lines("size", vec!["big", "small"]).unwrap();
+	/// interpreter.eval("${monster} and ${{id: size, count: 2}}").unwrap();
+	/// let stats = interpreter.last_eval_stats();
+	/// assert_eq!(stats.tokens, 3);
+	/// assert_eq!(stats.draws["size"], 3);
+	/// assert_eq!(stats.max_depth, 1);
+	/// println!("took {:?}", stats.duration);
+	/// ```
+	pub fn last_eval_stats(&self) -> &EvalStats {
+		&self.last_stats
+	}
+
+	/// Gets the warnings from the most recent evaluation, such as the look-up tables that were
+	/// missing in lenient mode (see [set_lenient_missing(...)](Interpreter::set_lenient_missing))
+	pub fn get_warnings(&self) -> &[String] {
+		&self.warnings
+	}
+
 	/// Evaluates the given text to perform all text substitutions as per the `twas` text
 	/// substitution syntax. See the [twas module](twas) description for more details on text
 	/// substitution syntax.
@@ -134,637 +576,3671 @@ impl<R> Interpreter<R> where R: Rng {
 	/// println!("{}", interpreter.eval(story).expect("Failed to eval"));
 	/// ```
 	pub fn eval<T>(&mut self, text: T) -> Result<String, ParsingError> where T: Into<String> {
-		do_eval(text.into(), 0, &self.registry, &mut self.dice, &mut self.rng, self.recursion_limit, 0)
+		self.eval_with(Source::Text(text.into()), EvalCall::default()).map(|(text, _, _)| text)
 	}
 
-	/// Loads a string containing a random look-up table in plain text (one line per item),
-	/// comma-separated values (CSV), YAML, or JSON format. The parsed random look-up table is
-	/// stored under the given look-up table ID. It is generally better to use the
-	/// [load_file(...)](Interpreter::load_file) method instead of
-	/// [load_str(...)](Interpreter::load_str).
-	///
-	/// See the [twas module](twas) description for more details on random look-up file formats.
+	/// Same as [eval(...)](Interpreter::eval), but starts the evaluation with the given
+	/// references, such that the host application can pass in values that it already knows (eg
+	/// the player's name or the chosen region), which the text can then use like any other
+	/// reference (eg `${@player}` or `${pet-names/$species}`). The given references take
+	/// precedence over persistent references and the default references of the front matter, but
+	/// the text can replace them by saving a reference with the same ID.
 	/// # Arguments
-	/// * `id`: The identifier for the string.
-	/// * `s`: The string to load.
-	/// * `format`: The format of the string.
+	/// * `text`: The target text to evaluate.
+	/// * `refs`: The text of each reference, by reference ID (without the `@`)
 	/// # Returns
-	/// A `Result` indicating success or failure.
+	/// The result of the evaluation, or an error if a reference ID is not valid or the evaluation
+	/// fails.
 	///
 	/// # Example
 	/// ```rust
+	/// use std::collections::HashMap;
 	/// use twas;
 	/// let mut interpreter = twas::Interpreter::new();
-	/// interpreter.load_str("animal", include_str!("../animal.txt"), "txt");
-	/// let story = "I have a pet ${animal}.";
-	/// println!("{}", interpreter.eval(story).expect("Failed to eval"));
+	/// interpreter.load_str("greeting", "{desert: [Sand and sun to you], forest: [Well met]}", "yaml").unwrap();
+	/// let refs = HashMap::from([
+	///     (String::from("player"), String::from("Robin")),
+	///     (String::from("region"), String::from("forest"))
+	/// ]);
+	/// let text = interpreter.eval_with_refs("${greeting/$region}, ${@player}!", refs).unwrap();
+	/// assert_eq!(text, "Well met, Robin!");
 	/// ```
-	pub fn load_str<T>(&mut self, id: &str, s: T, format: &str) -> Result<(), errors::ParsingError> where T: Into<String> {
-		validate_id(id)?;
-		let key = id;
-		match format.to_lowercase().as_str() {
-			"txt"  => self.load_txt_str(key, s)?,
-			"csv"  => self.load_csv_str(key, s)?,
-			"json" => self.load_json_str(key, s)?,
-			"yml"  => self.load_yaml_str(key, s)?,
-			"yaml" => self.load_yaml_str(key, s)?,
-			_ => return Err(ParseError{ msg: Some(format!(", format {} not supported", format)), line: None, col: None }.into())
-		};
-		Ok(())
+	pub fn eval_with_refs<T>(&mut self, text: T, refs: HashMap<String, String>) -> Result<String, ParsingError> where T: Into<String> {
+		self.eval_with(Source::Text(text.into()), EvalCall{refs, ..Default::default()}).map(|(text, _, _)| text)
 	}
 
-	/// Loads one (or more) random look-up table(s) from the given file. The base look-up table ID
-	/// is just the filename without the file type suffix (eg "animal" for file "animal.txt"). In
-	/// most cases, you should use [load_file(...)](Interpreter::load_file) with a directory or
-	/// .zip file instead of this method.
-	///
-	/// Supported file formats:
-	/// * .txt - each line is a look-up table item
-	/// * .csv - each column is a look-up table, with optional `weight` column for specifying probability
-	/// * .yaml|.yml - each list (unbiased table) and each map of string-number pairs (weighted table) is a look-up table
-	/// * .json - each list (unbiased table) and each map of string-number pairs (weighted table) is a look-up table
-	/// * directory - recursively load all supported files in directory
-	/// * .zip - recursively load all supported files in the .zip archive
-	///
-	/// See the [twas module](twas) description for more details on random look-up file formats.
-	///
+	/// Same as [eval(...)](Interpreter::eval), but with a different recursion limit for this
+	/// evaluation only (see [set_recursion_limit(...)](Interpreter::set_recursion_limit)), such
+	/// that templates that are legitimately deep can go deeper while others fail fast
 	/// # Arguments
-	/// * `filepath`: The path to the file to load.
+	/// * `text`: The target text to evaluate.
+	/// * `limit`: The recursion limit for this evaluation.
 	/// # Returns
-	/// A `Result` indicating success or failure.
+	/// The result of the evaluation, or an error if the evaluation fails (eg because it went
+	/// past the recursion limit).
 	///
 	/// # Example
-	/// `animal.txt`
-	/// ```txt
-	/// aardvark
-	/// bird
-	/// cat
-	/// dog
-	/// elephant
-	/// ```
-	/// `pet-names.csv`
-	/// ```txt
-	/// aardvark,bird,cat,dog,elephant
-	/// aaron,pip,paws,spot,nosey
-	/// aarnold,seed,mew,spike,jumbo
-	/// aaragon,lola,claws,rolf,tuba
-	/// ```
-	/// Program code:
 	/// ```rust
 	/// use twas;
 	/// let mut interpreter = twas::Interpreter::new();
-	/// interpreter.load_file("animal.txt").expect("Failed to load file");
-	/// interpreter.load_file("pet-names.csv").expect("Failed to load file");
-	/// println!("Loaded IDs: {:?}", interpreter.list_ids());
-	/// // prints "Loaded IDs: [animal, pet-names/aardvark, pet-names/bird, pet-names/cat,
-	/// //     pet-names/dog, pet-names/elephant]"
-	/// let story = r#"I have a pet ${animal@pet}. His name is ${{id: "pet-names/$pet", case: title}}! \
-	/// ${{id: "@pet", aan: true, case: "first"}} is a girl's best friend."#;
-	/// println!("{}", interpreter.eval(story).expect("Failed to eval"));
-	/// // prints: "I have a pet elephant. His name is Tuba! An elephant is a girl's best friend."
+	/// interpreter.load_lines("phrase", vec!["very ${adjective}"]).unwrap();
+	/// interpreter.load_lines("adjective", vec!["${phrase}"]).unwrap();
+	/// assert!(interpreter.eval_with_limit("${phrase}", 10).is_err());
 	/// ```
-	pub fn load_file<P>(&mut self, filepath: P) -> Result<(), ParsingError> where P: Into<PathBuf> {
-		self.load_file_namespaced(filepath, "")
+	pub fn eval_with_limit<T>(&mut self, text: T, limit: usize) -> Result<String, ParsingError> where T: Into<String> {
+		self.eval_with(Source::Text(text.into()), EvalCall{recursion_limit: Some(limit), ..Default::default()}).map(|(text, _, _)| text)
 	}
-	/// Loads one (or more) random look-up table(s) from the given file (just like
-	/// [load_file(...)](Interpreter::load_file), but with the given "namespace" prefix added to
-	/// the front of the look-up table ID(s). The base look-up table ID will be
-	/// `namespace/filename` where `filename` is the file's base name without the file type
-	/// suffix (eg "animal" for file "animal.txt"). You should prefer to use
-	///
-	/// Supported file formats:
-	/// * .txt - each line is a look-up table item
-	/// * .csv - each column is a look-up table, with optional `weight` column for specifying
-	/// probability
-	/// * .yaml - each list (unbiased table) and each map of string-number pairs (weighted table)
-	/// is a look-up table
-	///
-	/// See the [twas module](twas) description for more details on random look-up file formats.
-	///
+
+	/// Same as [eval(...)](Interpreter::eval), but stops the evaluation with a timeout error if
+	/// it takes longer than the given time, such as to bound the work of untrusted templates in
+	/// a web service more tightly than the recursion limit does. The time is checked before each
+	/// substitution token, so a single token that takes long (eg a draw of a million items) is
+	/// not interrupted.
 	/// # Arguments
-	/// * `filepath`: The path to the file to load.
-	/// * `id_prefix`: ID prefix
+	/// * `text`: The target text to evaluate.
+	/// * `limit`: The time limit for this evaluation.
 	/// # Returns
-	/// A `Result` indicating success or failure.
+	/// The result of the evaluation, or an error if the evaluation fails or takes too long.
 	///
 	/// # Example
-	/// `animal.txt`
-	/// ```txt
-	/// aardvark
-	/// bird
-	/// cat
-	/// dog
-	/// elephant
+	/// ```rust
+	/// use std::time::Duration;
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).unwrap();
+	/// assert!(interpreter.eval_with_deadline("a ${animal}", Duration::from_secs(1)).is_ok());
+	/// let e = interpreter.eval_with_deadline("a ${animal}", Duration::ZERO).unwrap_err();
+	/// assert!(e.to_string().starts_with("Time limit"));
 	/// ```
-	/// `pet-names.csv`
-	/// ```txt
-	/// aardvark,bird,cat,dog,elephant
-	/// aaron,pip,paws,spot,nosey
-	/// aarnold,seed,mew,spike,jumbo
-	/// aaragon,lola,claws,rolf,tuba
+	pub fn eval_with_deadline<T>(&mut self, text: T, limit: Duration) -> Result<String, ParsingError> where T: Into<String> {
+		self.eval_with(Source::Text(text.into()), EvalCall{deadline: Some(limit), ..Default::default()}).map(|(text, _, _)| text)
+	}
+
+	/// Evaluates a compiled template (see [Template]), which is faster than evaluating the same
+	/// text with [eval(...)](Interpreter::eval) again and again, because the substitution tokens
+	/// of the template were already found and parsed when it was compiled.
+	/// # Arguments
+	/// * `template`: The compiled template to evaluate.
+	/// # Returns
+	/// The result of the evaluation, or an error if the evaluation fails.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).unwrap();
+	/// let template = twas::Template::compile("I have ${{id: animal, aan: true}}.").unwrap();
+	/// let stories: Vec<String> = (0..100).map(|_| interpreter.render(&template).unwrap()).collect();
+	/// assert!(stories.iter().all(|story| story.starts_with("I have a")));
 	/// ```
-	/// Program code:
+	pub fn render(&mut self, template: &Template) -> Result<String, ParsingError> {
+		self.eval_with(Source::Compiled(template), EvalCall::default()).map(|(text, _, _)| text)
+	}
+
+	/// Same as [eval(...)](Interpreter::eval), but draws from a temporary random number generator
+	/// (for both look-up tables and dice) that is created from the given seed, such that one
+	/// interpreter can serve reproducible, independent requests that are identified by their
+	/// seeds. The interpreter's own random number generator is not used, so the results of
+	/// other evaluations do not change. Evaluating a text with a seed gives the same result as
+	/// evaluating it with a new interpreter from [from_seed(...)](Interpreter::from_seed) with the
+	/// same seed (and the same look-up tables).
+	/// # Arguments
+	/// * `text`: The target text to evaluate.
+	/// * `seed`: The seed of the temporary random number generator.
+	/// # Returns
+	/// The result of the evaluation, or an error if the evaluation fails.
+	///
+	/// # Example
 	/// ```rust
 	/// use twas;
 	/// let mut interpreter = twas::Interpreter::new();
-	/// interpreter.load_file_namespaced("animal.txt", "my-story").unwrap();
-	/// interpreter.load_file_namespaced("pet-names.csv", "my-story").unwrap();
-	/// println!("Loaded IDs: {:?}", interpreter.list_ids());
-	/// // prints "Loaded IDs: [my-story/animal, my-story/pet-names/aardvark,
-	/// //     my-story/pet-names/bird, my-story/pet-names/cat,
-	/// //     my-story/pet-names/dog, my-story/pet-names/elephant]"
-	/// let story = r#"I have a pet ${my-story/animal@pet}. \
-	/// His name is ${{id: "my-story/pet-names/$pet", case: title}}!"#;
-	/// println!("{}", interpreter.eval(story).expect("Failed to eval"));
-	/// // prints: "I have a pet dog. His name is Spot!"
+	/// interpreter.load_lines("animal", vec!["cat", "dog", "bird", "fish"]).unwrap();
+	/// let first = interpreter.eval_seeded("a ${animal} and #{1d6} mice", 42).unwrap();
+	/// let again = interpreter.eval_seeded("a ${animal} and #{1d6} mice", 42).unwrap();
+	/// assert_eq!(first, again);
 	/// ```
-	pub fn load_file_namespaced<P>(&mut self, filepath: P, id_prefix: &str) -> Result<(), ParsingError> where P: Into<PathBuf> {
-		validate_id(id_prefix)?;
-		let id_prefix = id_prefix.trim();
-		let filepath: PathBuf = filepath.into();
-		if ! filepath.exists(){
-			return Err(io::Error::from(ErrorKind::NotFound).into());
-		}
-		if filepath.is_dir() {
-			return self.load_dir_namespaced(filepath, id_prefix);
-		}
-		let path = filepath.as_path();
-		let file_type = path.extension()
-			.ok_or_else(||ParseError{
-				msg: Some(format!("{:?} has unknown file type, please append .txt or other extension to it", filepath)), line: None, col: None
-			})?.to_str().ok_or_else(||
-			io::Error::new(ErrorKind::Unsupported, "Invalid characters in file extension")
-		)?;
-		let filename = path.file_name().ok_or_else(||ParseError{
-			msg: Some("Cannot get name of file".into()), line: None, col: None
-		})?.to_str().ok_or_else(||
-			io::Error::new(ErrorKind::Unsupported, "Invalid characters in file name")
-		)?;
-		let mut id: String = id_prefix.into();
-		if ! id.is_empty() { id.push_str("/"); }
-		id.push_str(&filename[0..filename.rfind(".").unwrap_or(filename.len())]);
-		match file_type.to_lowercase().as_str() {
-			"txt" => {
-				let input_file = File::open(path)?;
-				let reader = io::BufReader::new(input_file);
-				for line in reader.lines() {
-					let entry = line?;
-					self.get_or_create_lut(&id).add_item(entry, 1f64);
-				}
-			},
-			"csv" => {let input_file = File::open(path)?;
-				let reader = io::BufReader::new(input_file);
-				self.load_csv(id.as_str(), reader)?;
-			},
-			"json" => {
-				let input_file = File::open(path)?;
-				let reader = io::BufReader::new(input_file);
-				self.load_json(id.as_str(), reader)?;
-			},
-			"yml" | "yaml" => {
-				let input_file = File::open(path)?;
-				let reader = io::BufReader::new(input_file);
-				self.load_yaml(id.as_str(), reader)?;
-			},
-			"zip" => {
-				return self.load_zip_namespaced(filepath, id_prefix)
-			},
-			_ => return Err(ParseError{ msg: Some(format!("file type '{}' not supported", file_type)), line: None, col: None }.into())
-		}
-		Ok(())
-	}
-
-	/// Parses a YAML map object (recursive). If the map contains key:value pairs where the value
-	/// is a number, then it is parsed as a weighted look-up table. If the map contains nested
-	/// maps or lists, then it is recursively parsed.
-	fn load_yaml_mapping(&mut self, map: serde_yaml::mapping::Mapping, id_prefix: &str) -> Result<(), ParsingError> {
-		let id = String::from(id_prefix);
-		for (k, v) in map {
-			match k {
-				serde_yaml::Value::String(text) => match v {
-					serde_yaml::Value::Number(weight) => {
-						let weight: f64 = weight.as_f64().ok_or_else(|| ParseError{
-							msg: Some(format!("Could not convert {:?} to float", weight)), line: None, col: None,
-						})?;
-						self.get_or_create_lut(&id).add_item(text, weight);
-					},
-					serde_yaml::Value::Mapping(nested_map) => {
-						// sub-table
-						let mut next_id = id.clone();
-						if !id_prefix.is_empty() { next_id.push_str("/"); }
-						next_id.push_str(text.as_str());
-						self.load_yaml_mapping(nested_map, next_id.as_str())?;
-					},
-					serde_yaml::Value::Sequence(list) => {
-						let mut next_id = id.clone();
-						if !id_prefix.is_empty() { next_id.push_str("/"); }
-						next_id.push_str(text.as_str());
-						self.load_yaml_sequence(list, next_id.as_str())?;
-					},
-					_ => return Err(ParseError{ msg: Some(format!("Weight must be a number, but weight for '{}' was '{:?}' instead", text, v)), line: None, col: None }.into())
-				},
-				_ => return Err(ParseError{ msg: Some(format!("Invalid key format, key must be a string")), line: None, col: None }.into())
-			}
-		}
-		Ok(())
+	pub fn eval_seeded<T>(&mut self, text: T, seed: u64) -> Result<String, ParsingError> where T: Into<String> {
+		self.eval_with(Source::Text(text.into()), EvalCall{seed: Some(seed), ..Default::default()}).map(|(text, _, _)| text)
 	}
 
-	/// Parses a YAML list object as an unbiased look-up table
-	fn load_yaml_sequence(&mut self, list: serde_yaml::Sequence, id_prefix: &str) -> Result<(), ParsingError> {
-		let id = String::from(id_prefix);
-		for entry in list{
-			match entry {
-				// list of strings
-				serde_yaml::Value::String(text) => self.get_or_create_lut(&id).add_item(text, 1f64),
-				_ => return Err(ParseError{ msg: Some(format!("Only lists of strings are supported, found {:?}", entry)), line: None, col: None }.into())
-			}
-		}
-		Ok(())
+	/// Same as [eval(...)](Interpreter::eval), but returns the details of the evaluation along
+	/// with the generated text: the final references, every item that was drawn (and from which
+	/// look-up table), and the warnings. This lets downstream tools work with the structure of
+	/// the result (eg to index which NPC names were generated) instead of parsing the text.
+	/// # Arguments
+	/// * `text`: The target text to evaluate.
+	/// # Returns
+	/// The details of the evaluation, or an error if the evaluation fails.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("name", vec!["Alice", "Bob"]).unwrap();
+	/// let result = interpreter.eval_detailed("${name@hero} meets ${name}.").unwrap();
+	/// println!("{}", result.text);
+	/// assert_eq!(result.draws.len(), 2);
+	/// assert!(result.refs.contains_key("hero"));
+	/// ```
+	pub fn eval_detailed<T>(&mut self, text: T) -> Result<Evaluation, ParsingError> where T: Into<String> {
+		self.eval_detailed_with_refs(text, HashMap::new())
 	}
 
-	/// Recursively scans the provided directory for random look-up table(s) from all supported
-	/// file formats found within the directory. The base look-up table ID for each table is the
-	/// relative filepath of the look-up table files (eg "bar/animal" for file
-	/// "foo/bar/animal.txt"). In most cases, you should use
-	/// [load_file(...)](Interpreter::load_file) instead of this method.
-	///
-	/// See the [twas module](twas) description for more details on supported random look-up
-	/// file formats.
-	///
+	/// Same as [eval_detailed(...)](Interpreter::eval_detailed), but with references provided by
+	/// the host application (see [eval_with_refs(...)](Interpreter::eval_with_refs))
 	/// # Arguments
-	/// * `dirpath`: The path to the directory to load.
+	/// * `text`: The target text to evaluate.
+	/// * `refs`: The text of each reference, by reference ID (without the `@`)
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_dir<P>(&mut self, dirpath: P) -> Result<(), ParsingError> where P: Into<PathBuf> {
-		self.load_dir_namespaced(dirpath, "")
+	/// The details of the evaluation, or an error if a reference ID is not valid or the
+	/// evaluation fails.
+	pub fn eval_detailed_with_refs<T>(&mut self, text: T, refs: HashMap<String, String>) -> Result<Evaluation, ParsingError> where T: Into<String> {
+		let (text, refs, mut recording) = self.eval_with(Source::Text(text.into()), EvalCall{refs, record: true, ..Default::default()})?;
+		Ok(Evaluation{
+			text,
+			refs: refs.into_iter().map(|(ref_id, value)| (ref_id, value.text)).collect(),
+			spans: recording.take_spans(),
+			draws: recording.draws,
+			warnings: self.warnings.clone(),
+			stats: self.last_stats.clone()
+		})
 	}
 
-	/// Recursively scans the provided directory for random look-up table(s) from all supported
-	/// file formats found within the directory. The base look-up table ID for each table is the
-	/// relative filepath of the look-up table files (eg "bar/animal" for file
-	/// "foo/bar/animal.txt"). In most cases, you should use
-	/// [load_file(...)](Interpreter::load_file) instead of this method.
-	///
-	/// See the [twas module](twas) description for more details on supported random look-up
-	/// file formats.
-	///
+	/// Same as [eval(...)](Interpreter::eval), but also returns a compact log of every random
+	/// decision of the evaluation (see [DrawLog]): the items that were drawn, the dice rolls, and
+	/// the results of template functions and host variables. Together with the loaded look-up
+	/// tables, the log reproduces the exact same text with
+	/// [eval_replay(...)](Interpreter::eval_replay), even on another machine or with another
+	/// version of twas, which makes it a good attachment for a bug report about a generated
+	/// document.
 	/// # Arguments
-	/// * `dirpath`: The path to the directory to load.
-	/// * `id_prefix`: ID prefix path, use an empty String ("") if this directory is the root of
-	/// the directory tree
+	/// * `text`: The target text to evaluate.
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_dir_namespaced<P>(&mut self, dirpath: P, id_prefix: &str) -> Result<(), ParsingError> where P: Into<PathBuf> {
-		validate_id(id_prefix)?;
-		for file in fs::read_dir(dirpath.into())? {
-			let file_path = file?.path();
-			match file_path.is_dir() {
-				true => {
-					let dir_name = file_path.file_name().ok_or_else(||ParseError{
-						msg: Some("Cannot get name of directory".into()), line: None, col: None
-					})?.to_str().ok_or_else(||
-						io::Error::new(ErrorKind::Unsupported, "Invalid characters in directory file name")
-					)?;
-					let mut new_id: String = id_prefix.into();
-					new_id.push_str(dir_name);
-					self.load_dir_namespaced(&file_path, new_id.as_str())?;
-				}
-				false => {
-					match file_path.extension() {
-						None => {}, // ignore
-						Some(suffix) => {
-							let suffix = suffix.to_str().ok_or_else(||
-								io::Error::new(ErrorKind::Unsupported, "Invalid characters in file extension")
-							)?;
-							match suffix.to_lowercase().as_str() {
-								"txt" | "csv" | "yml" | "yaml" | "json" => {
-									self.load_file_namespaced(file_path.as_path(), id_prefix)?
-								}
-								_ => {} // ignore
-							}
-						}
-					}
-				}
-			}
-		}
-		Ok(())
+	/// The generated text and the draw log, or an error if the evaluation fails.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("animal", vec!["cat", "dog", "bird", "fish"]).unwrap();
+	/// let (text, log) = interpreter.eval_recorded("a ${animal} and #{1d6} mice").unwrap();
+	/// let saved = log.to_string();
+	/// let mut other = twas::Interpreter::new();
+	/// other.load_lines("animal", vec!["fish", "bird", "dog", "cat"]).unwrap();
+	/// assert_eq!(other.eval_replay(&saved.parse().unwrap()).unwrap(), text);
+	/// ```
+	pub fn eval_recorded<T>(&mut self, text: T) -> Result<(String, DrawLog), ParsingError> where T: Into<String> {
+		let text = text.into();
+		let refs: BTreeMap<String, String> = self.persistent_refs.iter()
+			.map(|(ref_id, value)| (ref_id.clone(), value.text.clone())).collect();
+		let mut tape = DrawTape::Record(Vec::new());
+		let (output, _, _) = self.eval_with(Source::Text(text.clone()), EvalCall{tape: Some(&mut tape), ..Default::default()})?;
+		Ok((output, DrawLog{version: LOG_VERSION, text, refs, decisions: tape.into_decisions()}))
 	}
 
-	/// Reads the provided zip archive for random look-up table(s) from all supported
-	/// file formats found within the archive. The base look-up table ID for each table is the
-	/// relative filepath of the look-up table files  within the zip archive (eg "bar/animal" for
-	/// file "bar/animal.txt" in "foo.zip"). In most cases, you should use
-	/// [load_file(...)](Interpreter::load_file) instead of this method.
-	///
-	/// See the [twas module](twas) description for more details on supported random look-up
-	/// file formats.
-	///
+	/// Reproduces the text of an evaluation from its draw log (see
+	/// [eval_recorded(...)](Interpreter::eval_recorded)). Instead of drawing at random, each draw
+	/// finds the logged items in the loaded look-up tables by their IDs (their keys, or else their
+	/// texts), so the order of the items and the random number generator do not matter. The
+	/// persistent references of the log are used instead of this interpreter's, and the replay
+	/// does not change the persistent references, the depletion state, or the random number
+	/// generator of this interpreter.
 	/// # Arguments
-	/// * `zippath`: The path to the zip file to load.
+	/// * `log`: The draw log of the evaluation to reproduce.
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_zip<P>(&mut self, zippath: P) -> Result<(), ParsingError> where P: Into<PathBuf> {
-		self.load_zip_namespaced(zippath, "")
+	/// The reproduced text, or an error if the log does not match the loaded look-up tables (eg
+	/// a drawn item was removed) or the evaluation fails.
+	pub fn eval_replay(&mut self, log: &DrawLog) -> Result<String, ParsingError> {
+		let refs: BTreeMap<String, RefValue> = log.refs.iter()
+			.map(|(ref_id, text)| (ref_id.clone(), RefValue{text: text.clone(), key: None, fields: None})).collect();
+		let persistent_refs = std::mem::replace(&mut self.persistent_refs, refs);
+		let draws = self.draws.clone();
+		let mut tape = DrawTape::Replay(log.decisions.clone(), 0);
+		let result = self.eval_with(Source::Text(log.text.clone()),
+			EvalCall{seed: Some(0), tape: Some(&mut tape), ..Default::default()});
+		self.persistent_refs = persistent_refs;
+		self.draws = draws;
+		result.map(|(text, _, _)| text)
 	}
 
-	/// Reads the provided zip archive for random look-up table(s) from all supported
-	/// file formats found within the archive. The base look-up table ID for each table is the
-	/// relative filepath of the look-up table files  within the zip archive (eg "bar/animal" for
-	/// file "bar/animal.txt" in "foo.zip"). In most cases, you should use
-	/// [load_file(...)](Interpreter::load_file) instead of this method.
-	///
-	/// See the [twas module](twas) description for more details on supported random look-up
-	/// file formats.
-	///
+	/// Creates an endless iterator that evaluates the given text every time that it is advanced,
+	/// which is convenient for streaming consumers and iterator adapters (eg `take(n)`,
+	/// `filter(...)`, or `find(...)`). Each item is the result of one
+	/// [eval(...)](Interpreter::eval), such that an evaluation error does not end the iteration.
 	/// # Arguments
-	/// * `zippath`: The path to the zip file to load.
-	/// * `id_prefix`: ID prefix path, use an empty String ("") if not adding a prefix
+	/// * `template`: The target text to evaluate.
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_zip_namespaced<P>(&mut self, zippath: P, id_prefix: &str) -> Result<(), ParsingError> where P: Into<PathBuf> {
-		// extract files and then parse the directory
-		let tmp_dir = tempfile::tempdir()?;
-		unzip_file(zippath.into().as_path(), tmp_dir.path())?;
-		self.load_dir_namespaced(tmp_dir.path(), id_prefix)
+	/// An iterator that never ends (see [EvalIter])
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).unwrap();
+	/// let pets: Vec<String> = interpreter.iter("a pet ${animal}").take(5)
+	///     .collect::<Result<Vec<String>, _>>().unwrap();
+	/// assert_eq!(pets.len(), 5);
+	/// ```
+	pub fn iter<T>(&mut self, template: T) -> EvalIter<'_, R> where T: Into<String> {
+		EvalIter::new(self, template.into())
 	}
 
-	/// Parses the provided string as a .txt file. Each line will be parsed as an entry in a
-	/// look-up table, with all possible values having equal weight.
-	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
+	/// Calculates the probability of each text that the given template can generate, and how
+	/// often each look-up table item is drawn, such as to check the rarity of items without
+	/// eyeballing thousands of evaluations. If the template has no more than `samples` possible
+	/// combinations of draws (and uses no dice, random counts, depletion, or other randomness
+	/// besides plain draws), the probabilities are calculated exactly by following every
+	/// combination. Otherwise, they are estimated by evaluating the template `samples` times.
 	///
+	/// The analysis does not change the interpreter's references or depletion state.
 	/// # Arguments
-	/// * `id`: look-up table ID to register this look-up table for text substitution
-	/// * `txt`: the text to parse
+	/// * `template`: The target text to analyze.
+	/// * `samples`: The maximum number of combinations to follow, and the number of evaluations
+	///   to estimate from if there are more.
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_txt_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
-		validate_id(id)?;
-		let id = String::from(id);
-		if ! self.registry.contains_key(&id) {
-			self.registry.insert(id.clone(), LookUpTable::new());
+	/// The probability distribution (see [Distribution]), or an error if an evaluation fails.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_str("loot", "{copper coin: 8, silver coin: 3, gold ring: 1}", "yaml").unwrap();
+	/// let dist = interpreter.analyze("You find a ${loot}.", 10_000).unwrap();
+	/// assert!(dist.exact);
+	/// assert_eq!(dist.probability("You find a gold ring."), 1. / 12.);
+	/// assert_eq!(dist.expected_count("loot", "silver coin"), 0.25);
+	/// ```
+	pub fn analyze<T>(&mut self, template: T, samples: usize) -> Result<Distribution, ParsingError> where T: Into<String> {
+		let template: String = template.into();
+		if let Ok(distribution) = self.enumerate_draws(template.as_str(), samples) {
+			return Ok(distribution);
 		}
-		let lut = self.registry.get_mut(&id).unwrap();
-		let txt: String = txt.into();
-		for line in txt.split("\n") {
-			lut.add_item(line.trim(), 1.);
+		let mut fork = self.fork();
+		let mut distribution = Distribution::default();
+		for _ in 0..samples {
+			let result = fork.eval_detailed(template.as_str())?;
+			distribution.add(result.text, &result.draws, 1.);
 		}
-		Ok(())
+		distribution.normalize(samples);
+		Ok(distribution)
 	}
 
-	/// Parses the provided string as a .csv file. The text will be interpreted as standard
-	/// comma-separate value (CSV) file, where the first row is the header row containing column
-	/// names and all subsequent rows are the possible values for each column. Each column is its
-	/// own random look-up table. All rows have equal probability, unless there is a column
-	/// named `weight`. If a `weight` column is present, then the probability of each row is
-	/// weighted by the decimal value in the corresponding `weight` column.
-	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
-	///
+	/// Generates every text that the given template can generate, by systematically following
+	/// every combination of draws from look-up tables, such as to unit-test a template or to use
+	/// a small grammar as a pool of content. The evaluations do not change the interpreter's
+	/// references or depletion state.
 	/// # Arguments
-	/// * `id`: each column in the CSV text will be registered as a look-up table with ID `id/column-name`
-	/// * `txt`: the text to parse
+	/// * `template`: The target text to evaluate.
+	/// * `limit`: The maximum number of combinations of draws to follow
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_csv_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
-		let txt: String = txt.into();
-		let reader = BufReader::new(txt.as_bytes());
-		self.load_csv(id, reader)
+	/// The distinct generated texts, sorted, or an error if there are more combinations than the
+	/// limit, if the template uses randomness that cannot be enumerated (eg dice, random counts,
+	/// or depletion), or if an evaluation fails
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("color", vec!["red", "blue"]).unwrap();
+	/// interpreter.load_lines("animal", vec!["fox", "owl"]).unwrap();
+	/// let outputs = interpreter.enumerate_outputs("a ${color} ${animal}", 100).unwrap();
+	/// assert_eq!(outputs, vec!["a blue fox", "a blue owl", "a red fox", "a red owl"]);
+	/// assert!(interpreter.enumerate_outputs("a ${color} ${animal}", 3).is_err());
+	/// ```
+	pub fn enumerate_outputs<T>(&mut self, template: T, limit: usize) -> Result<Vec<String>, ParsingError> where T: Into<String> {
+		let template: String = template.into();
+		let distribution = self.enumerate_draws(template.as_str(), limit)?;
+		Ok(distribution.outputs.into_keys().collect())
 	}
 
-	/// Parses the provided string as JSON. A JSON object can contain one or multiple random
-	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the JSON
-	/// will be parsed as look-up tables with equal probability for all items, while
-	/// weighted-probabilities are specified using a string-number mapping
-	/// (eg `rarity: {"common": 6, "uncommon": 3, "rare": 0.9, "very rare": 0.1}`). The tables can be
-	/// organized by nesting map objects, with each nesting adding a level to the look-up table
-	/// ID path.
-	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
-	///
+	/// Evaluates the given text once for every combination of draws from look-up tables, with
+	/// the probability of each combination (see [analyze(...)](Interpreter::analyze))
 	/// # Arguments
-	/// * `id`: this id will be prefixed to the look-up tables nested in the provided JSON string
-	/// * `txt`: the text to parse
+	/// * `text`: The target text to evaluate.
+	/// * `limit`: The maximum number of combinations
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_json_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
-		let txt: String = txt.into();
-		let reader = BufReader::new(txt.as_bytes());
-		self.load_json(id, reader)
+	/// The exact distribution, or an error if there are more combinations than the limit, if the
+	/// evaluation uses other randomness than draws (eg dice), or if an evaluation fails
+	fn enumerate_draws(&mut self, text: &str, limit: usize) -> Result<Distribution, ParsingError> {
+		let tripwire = Tripwire::default();
+		let mut rng = tripwire.clone();
+		let mut script = ChoiceScript::default();
+		let mut distribution = Distribution{exact: true, ..Default::default()};
+		let mut base_refs: HashMap<String, RefValue> = self.persistent_refs.clone().into_iter().collect();
+		let body = apply_front_matter_refs(text, &mut base_refs, &self.options);
+		loop {
+			if distribution.samples >= limit {
+				return Err(ParseError{
+					msg: Some(format!("Template has more than {} combinations of draws", limit)), line: None, col: None
+				}.into());
+			}
+			script.start();
+			let mut warnings: Vec<String> = Vec::new();
+			let mut draws = self.draws.clone();
+			let mut persistent_refs = self.persistent_refs.clone();
+			let mut refs = base_refs.clone();
+			let mut ctx = EvalContext{reg: &self.registry, rng: &mut rng,
+				recursion_limit: self.recursion_limit, options: &self.options, warnings: &mut warnings,
+				lazy: &self.lazy, draws: &mut draws, persistent_refs: &mut persistent_refs,
+				vars: &self.vars, appearances: HashMap::new(), recording: Some(Recording::default()), functions: &self.functions,
+				hook: None, choices: Some(&mut script), depth: None, stats: EvalStats::default(), tape: None,
+				deadline: None, resolved: HashMap::new()};
+			let result = do_eval_refs(String::from(body), 0, &mut ctx, &mut refs, 0);
+			let recording = ctx.recording.take().unwrap_or_default();
+			self.keep_loaded_tables();
+			if tripwire.was_used() {
+				return Err(ParseError{
+					msg: Some(String::from("Template uses randomness other than draws from look-up tables (eg dice)")),
+					line: None, col: None
+				}.into());
+			}
+			distribution.add(result?, &recording.draws, script.probability());
+			if !script.advance() {
+				return Ok(distribution);
+			}
+		}
 	}
 
-	/// Parses the provided string as YAML. A YAML object can contain one or multiple random
-	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the YAML
-	/// content will be parsed as look-up tables with equal probability for all items, while
-	/// weighted-probabilities are specified using a string-number mapping
-	/// (eg `rarity: {common: 6, uncommon: 3, rare: 0.9, "very rare": 0.1}`). The tables can be
-	/// organized by nesting map objects, with each nesting adding a level to the look-up table
-	/// ID path.
-	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
-	///
+	/// Evaluates the given text (see [eval(...)](Interpreter::eval))
 	/// # Arguments
-	/// * `id`: this id will be prefixed to the look-up tables nested in the provided YAML string
-	/// * `txt`: the text to parse
+	/// * `source`: The target text (or compiled template) to evaluate.
+	/// * `call`: The settings of this evaluation (see [EvalCall])
 	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_yaml_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
-		let txt: String = txt.into();
-		let reader = BufReader::new(txt.as_bytes());
-		self.load_yaml(id, reader)
+	/// The generated text, the final references, and the recorded draws and spans (empty if not
+	/// recorded)
+	fn eval_with(&mut self, source: Source, call: EvalCall<'_>) -> Result<(String, HashMap<String, RefValue>, Recording), ParsingError> {
+		let EvalCall{refs: preset_refs, record, seed, recursion_limit, tape, deadline} = call;
+		let start_time = Instant::now();
+		for ref_id in preset_refs.keys() {
+			validate_ref(ref_id)?;
+		}
+		self.warnings.clear();
+		#[cfg(feature = "hot-reload")]
+		if let Err(e) = self.reload_changed() {
+			self.warnings.push(format!("Failed to re-load a changed look-up table file: {}", e));
+		}
+		// draw from a temporary random number generator instead of the interpreter's, if seeded
+		let mut seeded: Option<StdRng> = seed.map(simple_rng);
+		let mut rng: &mut dyn RngCore = match &mut seeded {
+			Some(rng) => rng,
+			None => &mut self.rng
+		};
+		let mut ctx = EvalContext{reg: &self.registry, rng: &mut rng,
+			recursion_limit: recursion_limit.unwrap_or(self.recursion_limit), options: &self.options, warnings: &mut self.warnings,
+			lazy: &self.lazy, draws: &mut self.draws, persistent_refs: &mut self.persistent_refs,
+			vars: &self.vars, appearances: HashMap::new(), recording: record.then(Recording::default), functions: &self.functions,
+			hook: self.hook.as_deref_mut(), choices: None, depth: None, stats: EvalStats::default(), tape,
+			deadline: deadline.map(|limit| (start_time, limit)), resolved: HashMap::new()};
+		let mut refs: HashMap<String, RefValue> = ctx.persistent_refs.iter()
+			.map(|(ref_id, value)| (ref_id.clone(), value.clone())).collect();
+		refs.extend(preset_refs.into_iter().map(|(ref_id, text)| (ref_id, RefValue{text, key: None, fields: None})));
+		let result = match source {
+			Source::Text(text) => {
+				let body = apply_front_matter_refs(text.as_str(), &mut refs, ctx.options);
+				do_eval_refs(String::from(body), 0, &mut ctx, &mut refs, 0)
+			},
+			Source::Compiled(template) => template.render(&mut ctx, &mut refs)
+		};
+		let recording = ctx.recording.take().unwrap_or_default();
+		let mut stats = std::mem::take(&mut ctx.stats);
+		stats.duration = start_time.elapsed();
+		self.last_stats = stats;
+		self.keep_loaded_tables();
+		result.map(|text| (text, refs, recording))
 	}
 
-	/// Parses the provided stream as a .txt file. Each line will be parsed as an entry in a
-	/// look-up table, with all possible values having equal weight.
-	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
-	///
-	/// # Arguments
-	/// * `id`: look-up table ID to register this look-up table for text substitution
-	/// * `reader`: the text stream to parse
-	/// # Returns
-	/// A `Result` indicating success or failure.
-	pub fn load_txt<I: Read>(&mut self, id: &str, mut reader: I) -> Result<(), ParsingError> {
-		let mut content = String::new();
-		reader.read_to_string(&mut content)?;
-		self.load_txt_str(id, content)?;
-		Ok(())
+	/// Keeps the look-up tables that were lazily loaded during an evaluation
+	fn keep_loaded_tables(&mut self) {
+		let (tables, sources, extensions) = self.lazy.take_loaded();
+		let merged = self.lazy.take_dedup_report();
+		self.dedup_report.extend(merged);
+		self.add_tables(tables);
+		for source in sources {
+			if let Err(e) = self.add_source(source) {
+				self.warnings.push(format!("Failed to watch a look-up table file: {}", e));
+			}
+		}
+		self.extensions.extend(extensions);
+		if let Err(e) = self.resolve_extensions() {
+			self.warnings.push(format!("Failed to extend a look-up table: {}", e));
+		}
 	}
 
-	/// Parses the provided stream as a .csv file. The text will be interpreted as standard
-	/// comma-separate value (CSV) file, where the first row is the header row containing column
-	/// names and all subsequent rows are the possible values for each column. Each column is its
-	/// own random look-up table. All rows have equal probability, unless there is a column
-	/// named `weight`. If a `weight` column is present, then the probability of each row is
-	/// weighted by the decimal value in the corresponding `weight` column.
-	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
+	/// Loads a string containing a random look-up table in plain text (one line per item),
+	/// comma-separated values (CSV), YAML, or JSON format. The parsed random look-up table is
+	/// stored under the given look-up table ID. It is generally better to use the
+	/// [load_file(...)](Interpreter::load_file) method instead of
+	/// [load_str(...)](Interpreter::load_str).
 	///
+	/// See the [twas module](twas) description for more details on random look-up file formats.
 	/// # Arguments
-	/// * `id`: each column in the CSV text will be registered as a look-up table with ID `id/column-name`
-	/// * `reader`: the text stream to parse
+	/// * `id`: The identifier for the string.
+	/// * `s`: The string to load.
+	/// * `format`: The format of the string.
 	/// # Returns
 	/// A `Result` indicating success or failure.
-	pub fn load_csv<I: Read>(&mut self, id_prefix: &str, reader: I) -> Result<(), ParsingError> {
-		validate_id(id_prefix)?;
-		let mut buffered_reader = BufReader::new(reader);
-		let mut char_iter = buffered_reader.chars();
-		let cols = match read_csv_row(&mut char_iter) {
-			Some(row) => row,
-			None => return Err(ParsingError::from(NoValuesError {})),
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_str("animal", include_str!("../animal.txt"), "txt");
+	/// let story = "I have a pet ${animal}.";
+	/// println!("{}", interpreter.eval(story).expect("Failed to eval"));
+	/// ```
+	pub fn load_str<T>(&mut self, id: &str, s: T, format: &str) -> Result<(), errors::ParsingError> where T: Into<String> {
+		validate_id(id)?;
+		let key = id;
+		match format.to_lowercase().as_str() {
+			"txt"  => self.load_txt_str(key, s)?,
+			"csv"  => self.load_csv_str(key, s)?,
+			"tsv"  => self.load_csv_str_with(key, s, &CsvOptions{rows: self.csv_options.rows, ..CsvOptions::tsv()})?,
+			"json" | "json5" | "jsonc" => self.load_json_str(key, s)?,
+			"yml"  => self.load_yaml_str(key, s)?,
+			"yaml" => self.load_yaml_str(key, s)?,
+			"xml"  => self.load_xml_str(key, s)?,
+			"md"   => self.load_md_str(key, s)?,
+			"twee" | "tw" => self.load_twee_str(key, s)?,
+			_ => return Err(ParseError{ msg: Some(format!(", format {} not supported", format)), line: None, col: None }.into())
 		};
-		let mut weights_col: Option<usize> = None;
-		for i in 0..cols.len() {
-			let col = &cols[i];
-			if col.as_str() == "weight" {
-				weights_col = Some(i);
-			}
-		}
-		while match read_csv_row(&mut char_iter) {
-			None => false,
-			Some(row) => {
-				let w = match weights_col {
-					None => 1f64,
-					Some(c) => row[c].parse::<f64>()?
-				};
-				for i in 0..row.len() {
-					let col: &String = &cols[i];
-					let cell: &String = &row[i];
-					if cell.is_empty() {
-						// empty cell, assume uneven table and do nothing
-					} else {
-						let mut id: String = id_prefix.into();
-						if !id_prefix.is_empty() { id.push_str("/"); }
-						id.push_str(col.as_str());
-						self.get_or_create_lut(&id).add_item(cell.clone(), w);
-					}
-				}
-				true
-			}
-		}{};
 		Ok(())
 	}
 
-	/// Parses the provided stream as JSON. A JSON object can contain one or multiple random
-	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the JSON
-	/// will be parsed as look-up tables with equal probability for all items, while
-	/// weighted-probabilities are specified using a string-number mapping
-	/// (eg `rarity: {"common": 6, "uncommon": 3, "rare": 0.9, "very rare": 0.1}`). The tables can be
-	/// organized by nesting map objects, with each nesting adding a level to the look-up table
-	/// ID path.
+	/// Loads one (or more) random look-up table(s) from the given file. The base look-up table ID
+	/// is just the filename without the file type suffix (eg "animal" for file "animal.txt"). In
+	/// most cases, you should use [load_file(...)](Interpreter::load_file) with a directory or
+	/// .zip file instead of this method.
 	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
+	/// Supported file formats:
+	/// * .txt - each line is a look-up table item
+	/// * .csv - each column is a look-up table, with optional `weight` column for specifying probability
+	/// * .tsv - same as .csv, but with tab-separated values
+	/// * .yaml|.yml - each list (unbiased table) and each map of string-number pairs (weighted table) is a look-up table
+	/// * .json|.json5|.jsonc - each list (unbiased table) and each map of string-number pairs (weighted table) is a look-up table (comments and trailing commas are allowed)
+	/// * .xml - each element that contains text is an item (with optional `weight` attribute), and each other element is a look-up table or namespace
+	/// * .md - each bullet list is a look-up table and each column of a pipe table is a look-up table (with optional `weight` column), namespaced by the headings
+	/// * .twee|.tw - each Twee passage is a look-up table item in the look-up table named after the passage
+	/// * .xlsx - each sheet is a namespace and each column is a look-up table, with optional `weight` column
+	/// * .gz - gzip-compressed version of any of the above file formats (eg `names.csv.gz`)
+	/// * http:// or https:// URL - download and load any of the above (requires the `url` feature)
+	/// * directory - recursively load all supported files in directory
+	/// * .zip - recursively load all supported files in the .zip archive
+	/// * glob pattern - load all supported files (and directories) that match the pattern (eg
+	///   `tables/**/*.yaml`), namespaced by their path relative to the part of the pattern before
+	///   the first wildcard (eg `monsters/undead` for `tables/monsters/undead.yaml`)
+	///
+	/// See the [twas module](twas) description for more details on random look-up file formats.
 	///
 	/// # Arguments
-	/// * `id`: this id will be prefixed to the look-up tables nested in the provided JSON string
-	/// * `reader`: the text stream to parse
+	/// * `filepath`: The path to the file to load.
 	/// # Returns
 	/// A `Result` indicating success or failure.
-	pub fn load_json<I: Read>(&mut self, id: &str, reader: I) -> Result<(), ParsingError> {
-		self.load_yaml(id, reader)
+	///
+	/// # Example
+	/// `animal.txt`
+	/// ```txt
+	/// aardvark
+	/// bird
+	/// cat
+	/// dog
+	/// elephant
+	/// ```
+	/// `pet-names.csv`
+	/// ```txt
+	/// aardvark,bird,cat,dog,elephant
+	/// aaron,pip,paws,spot,nosey
+	/// aarnold,seed,mew,spike,jumbo
+	/// aaragon,lola,claws,rolf,tuba
+	/// ```
+	/// Program code:
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_file("animal.txt").expect("Failed to load file");
+	/// interpreter.load_file("pet-names.csv").expect("Failed to load file");
+	/// println!("Loaded IDs: {:?}", interpreter.list_ids());
+	/// // prints "Loaded IDs: [animal, pet-names/aardvark, pet-names/bird, pet-names/cat,
+	/// //     pet-names/dog, pet-names/elephant]"
+	/// let story = r#"I have a pet ${animal@pet}. His name is ${{id: "pet-names/$pet", case: title}}! \
+	/// ${{id: "@pet", aan: true, case: "first"}} is a girl's best friend."#;
+	/// println!("{}", interpreter.eval(story).expect("Failed to eval"));
+	/// // prints: "I have a pet elephant. His name is Tuba! An elephant is a girl's best friend."
+	/// ```
+	pub fn load_file<P>(&mut self, filepath: P) -> Result<(), ParsingError> where P: Into<PathBuf> {
+		self.load_file_namespaced(filepath, "")
 	}
-
-	/// Parses the provided stream as YAML. A YAML object can contain one or multiple random
-	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the YAML
-	/// stream will be parsed as look-up tables with equal probability for all items, while
-	/// weighted-probabilities are specified using a string-number mapping
-	/// (eg `rarity: {common: 6, uncommon: 3, rare: 0.9, "very rare": 0.1}`). The tables can be
-	/// organized by nesting map objects, with each nesting adding a level to the look-up table
-	/// ID path.
+	/// Loads one (or more) random look-up table(s) from the given file (just like
+	/// [load_file(...)](Interpreter::load_file), but with the given "namespace" prefix added to
+	/// the front of the look-up table ID(s). The base look-up table ID will be
+	/// `namespace/filename` where `filename` is the file's base name without the file type
+	/// suffix (eg "animal" for file "animal.txt"). You should prefer to use
 	///
-	/// See the [twas module](twas) description for more details on random look-up formats.
+	/// Supported file formats:
+	/// * .txt - each line is a look-up table item
+	/// * .csv - each column is a look-up table, with optional `weight` column for specifying
+	/// probability
+	/// * .yaml - each list (unbiased table) and each map of string-number pairs (weighted table)
+	/// is a look-up table
+	///
+	/// See the [twas module](twas) description for more details on random look-up file formats.
 	///
 	/// # Arguments
-	/// * `id`: this id will be prefixed to the look-up tables nested in the provided YAML string
-	/// * `reader`: the text stream to parse
+	/// * `filepath`: The path to the file to load.
+	/// * `id_prefix`: ID prefix
 	/// # Returns
 	/// A `Result` indicating success or failure.
-	pub fn load_yaml<I: Read>(&mut self, id: &str, reader: I) -> Result<(), ParsingError> {
-		let parsed: serde_yaml::Value = serde_yaml::from_reader(reader)?;
-		match parsed {
-			serde_yaml::Value::Sequence(list) => {
-				self.load_yaml_sequence(list, id)?;
-			},
-			serde_yaml::Value::Mapping(map) => {
-				// map of items and weights or map of maps of items
-				self.load_yaml_mapping(map, id)?
-			},
-			_ => return Err(ParseError{ msg: Some(
-				format!("Failed to parse {}, wrong structure (should be list or mapping object)", id)
-			), line: None, col: None }.into())
+	///
+	/// # Example
+	/// `animal.txt`
+	/// ```txt
+	/// aardvark
+	/// bird
+	/// cat
+	/// dog
+	/// elephant
+	/// ```
+	/// `pet-names.csv`
+	/// ```txt
+	/// aardvark,bird,cat,dog,elephant
+	/// aaron,pip,paws,spot,nosey
+	/// aarnold,seed,mew,spike,jumbo
+	/// aaragon,lola,claws,rolf,tuba
+	/// ```
+	/// Program code:
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_file_namespaced("animal.txt", "my-story").unwrap();
+	/// interpreter.load_file_namespaced("pet-names.csv", "my-story").unwrap();
+	/// println!("Loaded IDs: {:?}", interpreter.list_ids());
+	/// // prints "Loaded IDs: [my-story/animal, my-story/pet-names/aardvark,
+	/// //     my-story/pet-names/bird, my-story/pet-names/cat,
+	/// //     my-story/pet-names/dog, my-story/pet-names/elephant]"
+	/// let story = r#"I have a pet ${my-story/animal@pet}. \
+	/// His name is ${{id: "my-story/pet-names/$pet", case: title}}!"#;
+	/// println!("{}", interpreter.eval(story).expect("Failed to eval"));
+	/// // prints: "I have a pet dog. His name is Spot!"
+	/// ```
+	pub fn load_file_namespaced<P>(&mut self, filepath: P, id_prefix: &str) -> Result<(), ParsingError> where P: Into<PathBuf> {
+		validate_id(id_prefix)?;
+		let id_prefix = id_prefix.trim();
+		let filepath: PathBuf = filepath.into();
+		if let Some(url) = filepath.to_str().filter(|p| is_url(p)) {
+			return self.load_url_namespaced(url, id_prefix);
 		}
-		Ok(())
+		if ! filepath.exists(){
+			if let Some(pattern) = filepath.to_str().filter(|p| is_glob(p)) {
+				return self.load_glob_namespaced(pattern, id_prefix);
+			}
+			return Err(io::Error::from(ErrorKind::NotFound).into());
+		}
+		if filepath.is_dir() {
+			return self.load_dir_namespaced(filepath, id_prefix);
+		}
+		// load the file's tables separately, to remember which tables came from which file
+		let loaded = self.load_file_separately(filepath.as_path(), id_prefix)?;
+		let includes = std::mem::take(&mut self.included_files);
+		let tables: BTreeMap<String, Arc<LookUpTable>> = loaded.iter().map(|(id, lut)| (id.clone(), Arc::clone(lut))).collect();
+		self.add_tables(loaded);
+		self.resolve_extensions()?;
+		let path = fs::canonicalize(filepath)?;
+		self.add_source(SourceFile{path, id_prefix: id_prefix.into(), tables, includes})
 	}
 
-	/// Gets a random look-up table from the registry by it's registered ID
-	/// (eg `animal` for `animal.txt`). If there is no look-up table for that ID, then a new empty
-	/// look-up table will be created
-	/// #Arguments
-	/// * `id`: look-up table ID to fetch
-	/// # Returns
-	/// A [LookUpTable](twas::data::LookUpTable) object representing a random look-up table
-	pub fn get_or_create_lut(&mut self, id: &str) -> &mut LookUpTable {
-		let id = String::from(id);
-		if self.registry.contains_key(&id) {
-			return self.registry.get_mut(&id).unwrap();
-		} else {
-			let lut = LookUpTable::new();
-			self.registry.insert(id.clone(), lut);
-			return self.registry.get_mut(&id).unwrap();
+	/// Loads all supported files and directories that match the given glob pattern (eg
+	/// `tables/**/*.yaml`). The ID prefix of each match is its path relative to the part of the
+	/// pattern before the first wildcard (eg `monsters/undead` for `tables/monsters/undead.yaml`).
+	fn load_glob_namespaced(&mut self, pattern: &str, id_prefix: &str) -> Result<(), ParsingError> {
+		let base: PathBuf = Path::new(pattern).components()
+			.take_while(|part| !is_glob(part.as_os_str().to_str().unwrap_or_default()))
+			.collect();
+		let matches = glob::glob(pattern).map_err(|e| ParseError{
+			msg: Some(format!("Invalid file pattern '{}': {}", pattern, e)), line: None, col: None
+		})?;
+		let mut found = false;
+		let mut loaded_dirs: Vec<PathBuf> = Vec::new();
+		for path in matches {
+			let path = path.map_err(io::Error::from)?;
+			if loaded_dirs.iter().any(|dir| path.starts_with(dir)) {
+				continue; // already loaded as part of a matching directory (eg for "tables/**")
+			}
+			let rel_path = path.strip_prefix(&base).unwrap_or(path.as_path());
+			let mut prefix = String::from(id_prefix);
+			let rel_dir = match path.is_dir() {
+				true => {
+					loaded_dirs.push(path.clone());
+					Some(rel_path)
+				},
+				false if is_table_file(path.file_name().and_then(|n| n.to_str()).unwrap_or_default()) => rel_path.parent(),
+				false => continue // not a look-up table file (eg an image)
+			};
+			for part in rel_dir.iter().flat_map(|dir| dir.components()) {
+				let part = part.as_os_str().to_str().ok_or_else(||
+					io::Error::new(ErrorKind::Unsupported, "Invalid characters in file path")
+				)?;
+				if !prefix.is_empty() { prefix.push('/'); }
+				prefix.push_str(part);
+			}
+			self.load_file_namespaced(path.as_path(), prefix.as_str())?;
+			found = true;
+		}
+		match found {
+			true => Ok(()),
+			false => Err(io::Error::new(ErrorKind::NotFound, format!("No files match '{}'", pattern)).into())
 		}
 	}
 
-	/// Gets a random look-up table from the registry by it's registered ID, if one exists.
-	/// #Arguments
-	/// * `id`: look-up table ID to fetch
-	/// # Returns
-	/// An `Option` containing a [LookUpTable](twas::data::LookUpTable) object representing a
-	/// random look-up table, or None if no look-up table has been registered with the requested ID
-	pub fn get_lut(&mut self, id: &str) -> Option<&mut LookUpTable> {
-		let id = String::from(id);
-		self.registry.get_mut(&id)
+	/// Remembers which look-up tables came from which file (see
+	/// [reload_file(...)](Interpreter::reload_file))
+	fn add_source(&mut self, source: SourceFile) -> Result<(), ParsingError> {
+		#[cfg(feature = "hot-reload")]
+		if let Some(watcher) = self.watcher.as_mut() {
+			for path in std::iter::once(&source.path).chain(source.includes.iter()) {
+				watcher.watch(path.as_path()).map_err(io::Error::other)?;
+			}
+		}
+		match self.sources.iter_mut().find(|src| src.path == source.path && src.id_prefix == source.id_prefix) {
+			Some(existing) => {
+				for (id, lut) in source.tables {
+					match existing.tables.get_mut(&id) {
+						Some(table) => Arc::make_mut(table).append(Arc::unwrap_or_clone(lut)),
+						None => {existing.tables.insert(id, lut);}
+					}
+				}
+				existing.includes.extend(source.includes);
+			},
+			None => self.sources.push(source)
+		}
+		Ok(())
 	}
 
-	/// Gets a list of all currently registered look-up tables
-	pub fn list_ids(&self) -> Vec<&String> {
-		self.registry.keys().collect::<Vec<&String>>()
+	/// Loads the given look-up table file (not a directory) into a new registry, leaving the
+	/// interpreter's registry unchanged
+	fn load_file_separately(&mut self, path: &Path, id_prefix: &str) -> Result<HashMap<String, Arc<LookUpTable>>, ParsingError> {
+		let registry = std::mem::take(&mut self.registry);
+		self.included_files.clear();
+		let result = self.load_local_file(path, id_prefix);
+		let loaded = std::mem::replace(&mut self.registry, registry);
+		result.map(|_| loaded)
 	}
-}
-
-impl Interpreter<rand::rngs::StdRng> {
 
-	/// Creates a new interpreter
-	pub fn new() -> Interpreter<rand::rngs::StdRng> {
-		Interpreter::from_rng(new_simple_rng())
+	/// Creates the look-up tables of the pending table extensions whose base look-up table is
+	/// loaded (replacing any existing table with the same ID). The other table extensions stay
+	/// pending until their base table is loaded (see
+	/// [unresolved_extensions()](Interpreter::unresolved_extensions)).
+	/// # Returns
+	/// An error if an extension cannot be applied to its base table (the other extensions are
+	/// still created)
+	fn resolve_extensions(&mut self) -> Result<(), ParsingError> {
+		let mut failure = None;
+		let mut progress = true;
+		while progress {
+			progress = false;
+			for ext in std::mem::take(&mut self.extensions) {
+				if !self.registry.contains_key(&ext.base) && self.lazy.may_provide(ext.base.as_str()) {
+					// parsing errors are reported when the look-up table is used by eval(...)
+					let _ = self.load_lazy(ext.base.as_str());
+				}
+				match self.registry.get(&ext.base) {
+					Some(base) => {
+						match ext.apply(base) {
+							Ok(lut) => {self.registry.insert(ext.id, Arc::new(lut));},
+							Err(e) => {failure.get_or_insert(e);}
+						}
+						progress = true;
+					},
+					None => self.extensions.push(ext)
+				}
+			}
+		}
+		match failure {
+			Some(e) => Err(e),
+			None => Ok(())
+		}
 	}
 
-	/// Creates a new interpreter with a seeded random number generator, such that identical usage
-	/// with the same seed will result in identical results
+	/// Lists the look-up tables that extend another look-up table (with `$extends`) whose base
+	/// look-up table has not been loaded, and which therefore do not exist yet. Such look-up
+	/// tables are created as soon as their base table is loaded, so this is best checked after
+	/// all look-up tables are loaded (eg to catch a typo in the base ID).
+	/// # Returns
+	/// The (ID, base ID) pairs of the pending look-up tables, sorted by ID
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_str("campaign", "monsters: {$extends: core/monstres, $add: [dragon]}", "yaml").unwrap();
+	/// assert_eq!(interpreter.unresolved_extensions(), vec![("campaign/monsters", "core/monstres")]);
+	/// ```
+	pub fn unresolved_extensions(&self) -> Vec<(&str, &str)> {
+		let mut pending: Vec<(&str, &str)> = self.extensions.iter()
+			.map(|ext| (ext.id.as_str(), ext.base.as_str())).collect();
+		pending.sort();
+		pending
+	}
+
+	/// Parses a YAML map that defines a look-up table as an extension of another look-up table
+	/// (see [load_yaml(...)](Interpreter::load_yaml)). The new look-up table is created as soon
+	/// as the base look-up table is loaded.
+	fn load_table_extension(&mut self, map: serde_yaml::mapping::Mapping, id: &str) -> Result<(), ParsingError> {
+		validate_id(id)?;
+		let mut ext = TableExtension{id: id.into(), base: String::new(), added: LookUpTable::new(),
+			removed: Vec::new(), weights: Vec::new()};
+		for (k, v) in map {
+			let field = k.as_str().unwrap_or_default();
+			match field {
+				"$extends" => ext.base = v.as_str().ok_or_else(|| ParseError{
+					msg: Some(format!("$extends of '{}' must be a look-up table ID, found {:?}", id, v)), line: None, col: None
+				})?.into(),
+				"$add" => {
+					// parse the added items like any other look-up table
+					let registry = std::mem::take(&mut self.registry);
+					let result = match v {
+						serde_yaml::Value::Sequence(list) => self.load_yaml_sequence(list, ""),
+						serde_yaml::Value::Mapping(map) => self.load_yaml_mapping(map, ""),
+						_ => Err(ParseError{
+							msg: Some(format!("$add of '{}' must be a list or a map, found {:?}", id, v)), line: None, col: None
+						}.into())
+					};
+					let mut added = std::mem::replace(&mut self.registry, registry);
+					result?;
+					ext.added = added.remove("").map(Arc::unwrap_or_clone).unwrap_or_else(LookUpTable::new);
+				},
+				"$remove" => ext.removed = match v {
+					serde_yaml::Value::Sequence(list) => list.iter().filter_map(yaml_scalar_text).collect(),
+					_ => yaml_scalar_text(&v).into_iter().collect()
+				},
+				"$weights" => {
+					let map = v.as_mapping().ok_or_else(|| ParseError{
+						msg: Some(format!("$weights of '{}' must be a map of items and weights, found {:?}", id, v)), line: None, col: None
+					})?;
+					for (text, weight) in map {
+						let text = yaml_scalar_text(text).unwrap_or_default();
+						let weight = check_weight(weight.as_f64().ok_or_else(|| ParseError{
+							msg: Some(format!("Weight must be a number, but weight for '{}' was '{:?}' instead", text, weight)), line: None, col: None
+						})?, text.as_str())?;
+						ext.weights.push((text, weight));
+					}
+				},
+				_ => return Err(ParseError{
+					msg: Some(format!("Unknown field {:?} in look-up table '{}', which extends another look-up table", k, id)), line: None, col: None
+				}.into())
+			}
+		}
+		self.extensions.push(ext);
+		Ok(())
+	}
+
+	/// Adds the given look-up tables to the registry, appending the items of any tables that are
+	/// already registered
+	fn add_tables(&mut self, tables: HashMap<String, Arc<LookUpTable>>) {
+		for (id, lut) in tables {
+			match self.registry.get_mut(&id) {
+				Some(existing) => Arc::make_mut(existing).append(Arc::unwrap_or_clone(lut)),
+				None => {self.registry.insert(id.clone(), lut);}
+			}
+			self.mark_loaded(id.as_str());
+		}
+		self.dedup_loaded();
+	}
+
+	/// Parses the given look-up table file (not a directory)
+	fn load_local_file(&mut self, path: &Path, id_prefix: &str) -> Result<(), ParsingError> {
+		let filename = path.file_name().ok_or_else(||ParseError{
+			msg: Some("Cannot get name of file".into()), line: None, col: None
+		})?.to_str().ok_or_else(||
+			io::Error::new(ErrorKind::Unsupported, "Invalid characters in file name")
+		)?;
+		if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) {
+			return self.load_zip_namespaced(path, id_prefix);
+		}
+		let input_file = File::open(path)?;
+		self.loading_files.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+		let result = self.load_named_reader(id_prefix, filename, io::BufReader::new(input_file));
+		self.loading_files.pop();
+		result
+	}
+
+	/// Loads the file(s) of a `$include` key or `!include` tag in a YAML or JSON file into the given
+	/// look-up table ID. Paths are resolved relative to the including file, and must not lead
+	/// outside of its directory, so that a downloaded table pack cannot read other local files.
+	/// Includes are only allowed in files that are loaded from disk (not in text, archives,
+	/// downloads, or embedded files).
+	fn load_include(&mut self, include: &serde_yaml::Value, id: &str) -> Result<(), ParsingError> {
+		let paths: Vec<&str> = match include {
+			serde_yaml::Value::String(path) => vec![path.as_str()],
+			serde_yaml::Value::Sequence(list) => list.iter().map(|path| path.as_str().ok_or_else(|| ParseError{
+				msg: Some(format!("Include path must be a string, found {:?}", path)), line: None, col: None
+			})).collect::<Result<Vec<&str>, ParseError>>()?,
+			_ => return Err(ParseError{
+				msg: Some(format!("Include must be a path or a list of paths, found {:?}", include)), line: None, col: None
+			}.into())
+		};
+		let dir = match self.loading_files.last().and_then(|file| file.parent()) {
+			Some(dir) => dir.to_path_buf(),
+			None => return Err(ParseError{
+				msg: Some(String::from("Includes are only allowed in files that are loaded from disk")), line: None, col: None
+			}.into())
+		};
+		for path in paths {
+			let full_path = dir.join(path).canonicalize()?;
+			if Path::new(path).is_absolute() || !full_path.starts_with(&dir) {
+				return Err(ParseError{
+					msg: Some(format!("Included file '{}' is outside of the directory of the including file", path)), line: None, col: None
+				}.into());
+			}
+			let path = full_path;
+			self.included_files.insert(path.clone());
+			if self.loading_files.contains(&path) {
+				return Err(ParseError{
+					msg: Some(format!("{:?} includes itself", path)), line: None, col: None
+				}.into());
+			}
+			let file_type = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase();
+			let input_file = File::open(&path)?;
+			self.loading_files.push(path);
+			let result = self.load_typed_reader(id, io::BufReader::new(input_file), file_type.as_str());
+			self.loading_files.pop();
+			result?;
+		}
+		Ok(())
+	}
+
+	/// Turns lazy loading on or off (default is off). With lazy loading,
+	/// [load_dir(...)](Interpreter::load_dir) (and [load_file(...)](Interpreter::load_file) with a
+	/// directory) only indexes the look-up table files in the directory, and each file is parsed
+	/// the first time that one of its look-up tables is needed (eg by
+	/// [eval(...)](Interpreter::eval)). This greatly reduces the start-up time for large table
+	/// packs when only a few of the tables are used. Note that the IDs of look-up tables that have
+	/// not been parsed yet are not included in [list_ids()](Interpreter::list_ids).
+	///
+	/// Each file is parsed with the text encoding, CSV options, duplicate merging, and evaluation
+	/// options that the interpreter had when the file was indexed. Files that add to a look-up
+	/// table that is already loaded are parsed right away. A look-up table that is loaded after
+	/// the directory was indexed hides the indexed files that would add to it, so load such tables
+	/// before the directory.
+	/// # Arguments
+	/// * `lazy`: If true, then directories that are loaded from now on are loaded lazily
+	pub fn set_lazy_loading(&mut self, lazy: bool) {
+		self.lazy_loading = lazy;
+	}
+
+	/// Checks whether lazy loading is turned on (see
+	/// [set_lazy_loading(...)](Interpreter::set_lazy_loading))
+	pub fn is_lazy_loading(&self) -> bool {
+		self.lazy_loading
+	}
+
+	/// Adds a table source, which the interpreter asks for look-up tables that are not loaded
+	/// (see [TableSource]). This lets applications provide look-up tables from a database, a
+	/// procedural generator, or a network service. Table sources are asked in the order that they
+	/// were added, and forks of this interpreter share its table sources.
+	/// # Arguments
+	/// * `source`: The table source (eg a closure taking the look-up table ID and returning an
+	///   `Option<LookUpTable>`)
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.add_table_source(|id: &str| match id {
+	///     "weather" => {
+	///         let mut lut = twas::LookUpTable::new();
+	///         lut.add_item("sunny", 3.);
+	///         lut.add_item("rainy", 1.);
+	///         Some(lut)
+	///     },
+	///     _ => None
+	/// });
+	/// println!("{}", interpreter.eval("It is ${weather} today.").unwrap());
+	/// ```
+	pub fn add_table_source<S>(&mut self, source: S) where S: TableSource + 'static {
+		self.lazy.add_table_source(Arc::new(source));
+	}
+
+	/// Registers a template function, which templates can call like `${fn:name(arguments)}`
+	/// (eg `${fn:weather(season=$season)}`). This lets a template use values that need code,
+	/// such as date math or inventory look-ups. The function receives the arguments of the call
+	/// (see [FunctionArgs]) and the evaluation context (see [FunctionContext]), and returns
+	/// either the generated text (which may contain substitution tokens of its own) or an error
+	/// message. Forks of this interpreter share its template functions.
+	/// # Arguments
+	/// * `name`: name of the function, which can only contain letters, numbers, _, -, and/or +
+	/// * `function`: the function, which replaces any function with the same name
+	/// # Returns
+	/// An error if the name is not valid
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.register_function("weather", |args, _ctx| {
+	///     match args.get("season") {
+	///         Some("winter") => Ok(String::from("snowing")),
+	///         Some(_) => Ok(String::from("sunny")),
+	///         None => Err(String::from("missing season"))
+	///     }
+	/// }).unwrap();
+	/// interpreter.set_front_matter(true);
+	/// let text = interpreter.eval("---\nrefs: {season: winter}\n---\nIt is ${fn:weather(season=$season)}.").unwrap();
+	/// assert_eq!(text, "It is snowing.");
+	/// ```
+	pub fn register_function<F>(&mut self, name: &str, function: F) -> Result<(), ParsingError>
+			where F: Fn(&FunctionArgs, &mut FunctionContext) -> Result<String, String> + Send + Sync + 'static {
+		let checker: Regex = Regex::new(r#"^[\d\pL_\-+]+$"#).unwrap();
+		if !checker.is_match(name) {
+			return Err(InvalidIDError::new(format!("'{}' is not a valid function name. Function names can only contain letters, numbers, _, -, and/or +", name)).into());
+		}
+		self.functions.insert(String::from(name), Arc::new(function));
+		Ok(())
+	}
+
+	/// Removes a template function (see [register_function(...)](Interpreter::register_function))
+	/// # Arguments
+	/// * `name`: name of the function
+	/// # Returns
+	/// True if there was a function with that name
+	pub fn unregister_function(&mut self, name: &str) -> bool {
+		self.functions.remove(name)
+	}
+
+	/// Sets an observer that is notified of each substitution token, each draw, and each stored
+	/// reference of the following evaluations (see [EvalHook]), such as to log or visualize how
+	/// a text was generated. This replaces the previous hook (if any). Note that forks of this
+	/// interpreter do not have a hook.
+	/// # Arguments
+	/// * `hook`: the observer
+	pub fn set_hook<H>(&mut self, hook: H) where H: EvalHook + 'static {
+		self.hook = Some(Box::new(hook));
+	}
+
+	/// Removes the hook (see [set_hook(...)](Interpreter::set_hook))
+	/// # Returns
+	/// The removed hook, if any
+	pub fn take_hook(&mut self) -> Option<Box<dyn EvalHook>> {
+		self.hook.take()
+	}
+
+	/// Removes all table sources (see [add_table_source(...)](Interpreter::add_table_source)).
+	/// The look-up tables that were already provided by the table sources are kept.
+	pub fn clear_table_sources(&mut self) {
+		self.lazy.clear_table_sources();
+	}
+
+	/// Parses the lazily indexed file(s) that provide the given look-up table (if any), with the
+	/// settings that the interpreter had when the files were indexed
+	fn load_lazy(&mut self, id: &str) -> Result<(), ParsingError> {
+		let mut files = self.lazy.take_matching(id).into_iter();
+		while let Some(file) = files.next() {
+			let current = self.set_load_settings(file.settings.clone());
+			let result = self.load_file_namespaced(file.path.as_path(), file.id_prefix.as_str());
+			self.set_load_settings(current);
+			if let Err(e) = result {
+				// try again next time
+				self.lazy.restore(file);
+				files.for_each(|file| self.lazy.restore(file));
+				return Err(e);
+			}
+		}
+		Ok(())
+	}
+
+	/// Gets the settings that affect how look-up table files are parsed (see [LoadSettings])
+	fn get_load_settings(&self) -> LoadSettings {
+		LoadSettings{encoding: self.encoding, csv_options: self.csv_options.clone(), dedup: self.dedup,
+			options: self.options.clone()}
+	}
+
+	/// Replaces the settings that affect how look-up table files are parsed (see [LoadSettings])
+	/// # Returns
+	/// The previous settings
+	fn set_load_settings(&mut self, settings: LoadSettings) -> LoadSettings {
+		let previous = self.get_load_settings();
+		self.encoding = settings.encoding;
+		self.csv_options = settings.csv_options;
+		self.dedup = settings.dedup;
+		self.options = settings.options;
+		previous
+	}
+
+	/// Re-loads a look-up table file that was previously loaded with
+	/// [load_file(...)](Interpreter::load_file) (or as part of a directory), replacing the
+	/// items that came from that file while leaving all other items untouched (including items
+	/// that other files added to the same look-up tables).
+	/// If the file has been deleted, then its look-up tables are removed. If the file cannot be
+	/// parsed, then the previous look-up tables are kept and an error is returned.
+	///
+	/// # Arguments
+	/// * `filepath`: The path to the file to re-load.
+	/// # Returns
+	/// `true` if the file was previously loaded (and thus re-loaded), `false` if it was not, or
+	/// an error if the file could not be re-loaded.
+	pub fn reload_file<P>(&mut self, filepath: P) -> Result<bool, ParsingError> where P: Into<PathBuf> {
+		let filepath: PathBuf = filepath.into();
+		let path = fs::canonicalize(&filepath).unwrap_or(filepath);
+		let mut found = false;
+		for i in 0..self.sources.len() {
+			// a changed include re-loads the file that includes it
+			if self.sources[i].path != path && !self.sources[i].includes.contains(&path) { continue; }
+			found = true;
+			let source_path = self.sources[i].path.clone();
+			let loaded = match source_path.is_file() {
+				true => self.load_file_separately(source_path.as_path(), self.sources[i].id_prefix.clone().as_str())?,
+				false => HashMap::new() // deleted
+			};
+			self.sources[i].includes = std::mem::take(&mut self.included_files);
+			// only remove the items that came from this file, since other files may have added
+			// items to the same look-up tables
+			for (id, old) in std::mem::take(&mut self.sources[i].tables) {
+				if let Some(lut) = self.registry.get_mut(&id) {
+					match Arc::ptr_eq(lut, &old) {
+						true => {self.registry.remove(&id);},
+						false => {
+							Arc::make_mut(lut).subtract(&old);
+							if lut.is_empty() { self.registry.remove(&id); }
+						}
+					}
+				}
+			}
+			self.sources[i].tables = loaded.iter().map(|(id, lut)| (id.clone(), Arc::clone(lut))).collect();
+			self.add_tables(loaded);
+			self.resolve_extensions()?;
+		}
+		self.sources.retain(|src| src.path != path || path.is_file());
+		Ok(found)
+	}
+
+	/// Starts watching all loaded look-up table files (including those loaded later) for
+	/// changes (requires the `hot-reload` feature). Changed files are re-loaded automatically at
+	/// the start of each [eval(...)](Interpreter::eval), or when calling
+	/// [reload_changed()](Interpreter::reload_changed), without restarting the program. Only the
+	/// look-up tables from the changed files are replaced, all other look-up tables are left
+	/// untouched (see [reload_file(...)](Interpreter::reload_file)).
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	#[cfg(feature = "hot-reload")]
+	pub fn enable_hot_reload(&mut self) -> Result<(), ParsingError> {
+		if self.watcher.is_some() { return Ok(()); }
+		let mut watcher = FileWatcher::new().map_err(io::Error::other)?;
+		for source in &self.sources {
+			for path in std::iter::once(&source.path).chain(source.includes.iter()) {
+				watcher.watch(path.as_path()).map_err(io::Error::other)?;
+			}
+		}
+		self.watcher = Some(watcher);
+		Ok(())
+	}
+
+	/// Stops watching the loaded look-up table files for changes (see
+	/// [enable_hot_reload()](Interpreter::enable_hot_reload))
+	#[cfg(feature = "hot-reload")]
+	pub fn disable_hot_reload(&mut self) {
+		self.watcher = None;
+	}
+
+	/// Re-loads all of the loaded look-up table files that have changed since hot-reloading was
+	/// enabled (or since the last re-load). This happens automatically at the start of each
+	/// [eval(...)](Interpreter::eval), but can also be called directly (requires the `hot-reload`
+	/// feature).
+	/// # Returns
+	/// The paths of the re-loaded files, or an error if a file could not be re-loaded (in which
+	/// case its previous look-up tables are kept)
+	#[cfg(feature = "hot-reload")]
+	pub fn reload_changed(&mut self) -> Result<Vec<PathBuf>, ParsingError> {
+		let changed = match self.watcher.as_mut() {
+			None => return Ok(Vec::new()),
+			Some(watcher) => watcher.take_changed()
+		};
+		let changed: Vec<PathBuf> = changed.into_iter().collect();
+		let mut reloaded = Vec::new();
+		for (i, path) in changed.iter().enumerate() {
+			match self.reload_file(path.as_path()) {
+				Ok(true) => reloaded.push(path.clone()),
+				Ok(false) => {}, // not a loaded file
+				Err(e) => {
+					// try the remaining files next time
+					if let Some(watcher) = self.watcher.as_mut() { watcher.defer(changed[i + 1..].to_vec()); }
+					return Err(e);
+				}
+			}
+		}
+		Ok(reloaded)
+	}
+
+	/// Parses the provided stream as a look-up table file with the given file name (eg
+	/// "animal.txt" or "names.csv.gz"), which determines the file type and the base look-up table ID
+	fn load_named_reader<I: Read + Seek>(&mut self, id_prefix: &str, filename: &str, mut reader: I) -> Result<(), ParsingError> {
+		// gzip-compressed files are named like "names.csv.gz"
+		let gzipped = filename.to_lowercase().ends_with(".gz");
+		let base_name = match gzipped {
+			true => &filename[0..filename.len() - 3],
+			false => filename
+		};
+		let file_type = base_name.rfind(".").map(|i| base_name[i + 1..].to_lowercase());
+		let mut id: String = id_prefix.into();
+		if ! id.is_empty() { id.push_str("/"); }
+		id.push_str(&base_name[0..base_name.rfind(".").unwrap_or(base_name.len())]);
+		if gzipped || file_type.is_none() {
+			let mut content: Vec<u8> = Vec::new();
+			match gzipped {
+				true => GzDecoder::new(reader).read_to_end(&mut content)?,
+				false => reader.read_to_end(&mut content)?
+			};
+			// files without a file extension are loaded as whichever format they look like
+			return match file_type {
+				Some(file_type) => self.load_typed_reader(id.as_str(), Cursor::new(content), file_type.as_str()),
+				None => {
+					let format = Format::sniff(&content);
+					self.load_formatted_reader(id.as_str(), Cursor::new(content), format)
+				}
+			};
+		}
+		self.load_typed_reader(id.as_str(), reader, file_type.unwrap_or_default().as_str())
+	}
+
+	/// Downloads one (or more) random look-up table(s) from the given HTTP(S) URL, such as a
+	/// community table pack hosted on GitHub. The file type and base look-up table ID are taken
+	/// from the last segment of the URL path (eg "animal" for
+	/// `https://example.com/tables/animal.txt`), and all of the file formats supported by
+	/// [load_file(...)](Interpreter::load_file) (including .zip and .gz files) are supported.
+	/// The download fails if it takes longer than 30 seconds.
+	///
+	/// # Arguments
+	/// * `url`: The URL of the file to download.
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	#[cfg(feature = "url")]
+	pub fn load_url(&mut self, url: &str) -> Result<(), ParsingError> {
+		self.load_url_namespaced(url, "")
+	}
+
+	/// Downloads one (or more) random look-up table(s) from the given HTTP(S) URL (just like
+	/// [load_url(...)](Interpreter::load_url)), but with the given "namespace" prefix added to
+	/// the front of the look-up table ID(s).
+	///
+	/// # Arguments
+	/// * `url`: The URL of the file to download.
+	/// * `id_prefix`: ID prefix
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	#[cfg(feature = "url")]
+	pub fn load_url_namespaced(&mut self, url: &str, id_prefix: &str) -> Result<(), ParsingError> {
+		validate_id(id_prefix)?;
+		// the file name is the last segment of the path, after the scheme and host (eg not
+		// "example.com" for "https://example.com/")
+		let path = url.split(['?', '#']).next().unwrap_or(url);
+		let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+		let path = path.split_once('/').map_or("", |(_, path)| path);
+		let filename = path.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+		if filename.is_empty() || !filename.contains('.') {
+			return Err(ParseError{
+				msg: Some(format!("Cannot determine the file type of {}", url)), line: None, col: None
+			}.into());
+		}
+		let agent = ureq::AgentBuilder::new().timeout(URL_TIMEOUT).build();
+		let response = agent.get(url).call()
+			.map_err(io::Error::other)?;
+		let mut content: Vec<u8> = Vec::new();
+		response.into_reader().read_to_end(&mut content)?;
+		match filename.to_lowercase().ends_with(".zip") {
+			true => self.load_zip_reader_namespaced(Cursor::new(content), id_prefix),
+			false => self.load_named_reader(id_prefix, filename, Cursor::new(content))
+		}
+	}
+
+	/// Without the `url` feature, URLs cannot be loaded
+	#[cfg(not(feature = "url"))]
+	fn load_url_namespaced(&mut self, url: &str, _id_prefix: &str) -> Result<(), ParsingError> {
+		Err(ParseError{
+			msg: Some(format!("Cannot load {}, because twas was compiled without the 'url' feature", url)),
+			line: None, col: None
+		}.into())
+	}
+
+	/// Loads one (or more) random look-up table(s) from the provided stream in the given format,
+	/// such as look-up tables that are received over a network connection or read from a
+	/// database. For a [Format::Zip] archive, the ID is the prefix for the look-up tables of the
+	/// files in the archive.
+	///
+	/// See the [twas module](twas) description for more details on random look-up file formats.
+	/// # Arguments
+	/// * `id`: The look-up table ID (or ID prefix) for the loaded look-up table(s)
+	/// * `reader`: The stream to read
+	/// * `format`: The format of the stream
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// let data: &[u8] = b"- cat\n- dog\n";
+	/// interpreter.load_reader("animal", data, twas::Format::Yaml).unwrap();
+	/// println!("{}", interpreter.eval("I have a pet ${animal}.").expect("Failed to eval"));
+	/// ```
+	pub fn load_reader<I: Read>(&mut self, id: &str, mut reader: I, format: Format) -> Result<(), ParsingError> {
+		validate_id(id)?;
+		// read everything, since some formats need to seek
+		let mut content: Vec<u8> = Vec::new();
+		reader.read_to_end(&mut content)?;
+		self.load_formatted_reader(id, Cursor::new(content), format)
+	}
+
+	/// Parses the provided stream according to the given file type (eg "csv" or "yaml")
+	fn load_typed_reader<I: Read + Seek>(&mut self, id: &str, reader: I, file_type: &str) -> Result<(), ParsingError> {
+		match Format::from_extension(file_type) {
+			Some(format) => self.load_formatted_reader(id, reader, format),
+			None => Err(ParseError{ msg: Some(format!("file type '{}' not supported", file_type)), line: None, col: None }.into())
+		}
+	}
+
+	/// Parses the provided stream according to the given format
+	fn load_formatted_reader<I: Read + Seek>(&mut self, id: &str, mut reader: I, format: Format) -> Result<(), ParsingError> {
+		match format {
+			Format::Xlsx => return self.load_xlsx(id, reader),
+			Format::Zip => return self.load_zip_reader_namespaced(reader, id),
+			_ => {}
+		}
+		// text file, which is not necessarily UTF-8
+		let mut content: Vec<u8> = Vec::new();
+		reader.read_to_end(&mut content)?;
+		let reader = Cursor::new(decode_text(content, self.encoding)?);
+		match format {
+			Format::Txt => {
+				let reader = io::BufReader::new(reader);
+				for line in reader.lines() {
+					let entry = line?;
+					self.get_or_create_lut(id).add_item(entry, 1f64);
+				}
+			},
+			Format::Csv => self.load_csv(id, reader)?,
+			Format::Tsv => self.load_csv_with(id, reader, &CsvOptions{rows: self.csv_options.rows, ..CsvOptions::tsv()})?,
+			Format::Json => self.load_json(id, reader)?,
+			Format::Yaml => self.load_yaml(id, reader)?,
+			Format::Xml => self.load_xml(id, reader)?,
+			Format::Md => self.load_md(id, reader)?,
+			Format::Twee => self.load_twee(id, reader)?,
+			Format::Xlsx | Format::Zip => unreachable!("binary formats are loaded above")
+		}
+		Ok(())
+	}
+
+	/// Parses a YAML map object (recursive). If the map contains key:value pairs where the value
+	/// is a number, then it is parsed as a weighted look-up table. If the map contains
+	/// range:text pairs (eg `"3-5": feeble`), then it is parsed as a tiered look-up table that
+	/// can be selected by value. If the map contains nested maps or lists, then it is recursively
+	/// parsed. In a matrix table (a map with `$matrix: true`, which also applies to its nested
+	/// maps), any other key:text pair is parsed as a single-item look-up table (a cell).
+	fn load_yaml_mapping(&mut self, map: serde_yaml::mapping::Mapping, id_prefix: &str) -> Result<(), ParsingError> {
+		if map.contains_key("$extends") {
+			return self.load_table_extension(map, id_prefix);
+		}
+		let id = String::from(id_prefix);
+		// default substitution options for the tables of this mapping, eg $options: {case: title}
+		let defaults = match map.get("$options") {
+			None => None,
+			Some(options) => Some(parse_table_defaults(serde_yaml::from_value(options.clone())?, id_prefix)?)
+		};
+		let existing: BTreeSet<String> = match defaults {
+			None => BTreeSet::new(),
+			Some(_) => self.registry.keys().cloned().collect()
+		};
+		// matrix table, whose text values are cells, eg $matrix: true
+		let matrix = match map.get("$matrix") {
+			None => false,
+			Some(serde_yaml::Value::Bool(matrix)) => *matrix,
+			Some(v) => return Err(ParseError{
+				msg: Some(format!("$matrix of '{}' must be true or false, but was '{:?}'", id_prefix, v)), line: None, col: None
+			}.into())
+		};
+		for (k, v) in map {
+			let k = match k {
+				serde_yaml::Value::Number(n) => serde_yaml::Value::String(n.to_string()),
+				_ => k
+			};
+			match k {
+				// other file(s) included in this namespace, eg $include: [monsters.yaml, npcs.yaml]
+				serde_yaml::Value::String(text) if text == "$include" => self.load_include(&v, id_prefix)?,
+				serde_yaml::Value::String(text) if text == "$options" || text == "$matrix" => {},
+				serde_yaml::Value::String(text) => match v {
+					// other file included as a sub-table, eg monsters: !include monsters.yaml
+					serde_yaml::Value::Tagged(tagged) if tagged.tag == "include" => {
+						let mut next_id = id.clone();
+						if !id_prefix.is_empty() { next_id.push('/'); }
+						next_id.push_str(text.as_str());
+						validate_id(next_id.as_str())?;
+						self.load_include(&tagged.value, next_id.as_str())?;
+					},
+					serde_yaml::Value::Number(weight) => {
+						let weight: f64 = weight.as_f64().ok_or_else(|| ParseError{
+							msg: Some(format!("Could not convert {:?} to float", weight)), line: None, col: None,
+						})?;
+						let weight = check_weight(weight, text.as_str())?;
+						self.get_or_create_lut(&id).add_item(text, weight);
+					},
+					serde_yaml::Value::Mapping(mut nested_map) => {
+						// sub-table
+						let mut next_id = id.clone();
+						if !id_prefix.is_empty() { next_id.push_str("/"); }
+						next_id.push_str(text.as_str());
+						if matrix {
+							nested_map.insert("$matrix".into(), true.into());
+						}
+						self.load_yaml_mapping(nested_map, next_id.as_str())?;
+					},
+					serde_yaml::Value::Sequence(list) => {
+						let mut next_id = id.clone();
+						if !id_prefix.is_empty() { next_id.push_str("/"); }
+						next_id.push_str(text.as_str());
+						self.load_yaml_sequence(list, next_id.as_str())?;
+					},
+					serde_yaml::Value::String(desc) if parse_range_key(text.as_str()).is_some() => {
+						// tiered table entry, eg "3-5": feeble
+						let (min, max) = parse_range_key(text.as_str()).unwrap();
+						self.get_or_create_lut(&id).add_range_item(desc, min, max);
+					},
+					serde_yaml::Value::String(cell) if matrix => {
+						// single-item table (matrix cell)
+						let mut next_id = id.clone();
+						if !id_prefix.is_empty() { next_id.push('/'); }
+						next_id.push_str(text.as_str());
+						self.get_or_create_lut(&next_id).add_item(cell, 1f64);
+					},
+					serde_yaml::Value::String(_) => return Err(ParseError{ msg: Some(format!(
+						"Weight must be a number, but weight for '{}' was '{:?}' instead (add '$matrix: true' to use text values as the cells of a matrix table)",
+						text, v)), line: None, col: None }.into()),
+					_ => return Err(ParseError{ msg: Some(format!("Weight must be a number, but weight for '{}' was '{:?}' instead", text, v)), line: None, col: None }.into())
+				},
+				_ => return Err(ParseError{ msg: Some(format!("Invalid key format, key must be a string")), line: None, col: None }.into())
+			}
+		}
+		if let Some(defaults) = defaults {
+			// apply to the tables of this mapping (and its nested mappings), where the options of
+			// nested mappings take precedence
+			let in_namespace = |table_id: &str| table_id == id_prefix || id_prefix.is_empty()
+				|| (table_id.starts_with(id_prefix) && table_id[id_prefix.len()..].starts_with('/'));
+			for (table_id, lut) in self.registry.iter_mut() {
+				if in_namespace(table_id) && (table_id == id_prefix || !existing.contains(table_id)) {
+					let mut table_defaults = lut.get_defaults().cloned().unwrap_or_default();
+					table_defaults.apply_defaults(&defaults);
+					Arc::make_mut(lut).set_defaults(Some(table_defaults));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Parses a YAML list object as an unbiased look-up table. Entries can be plain text, or
+	/// objects with a `text` (or else `name`) field. Every field of an object other than `text`,
+	/// `weight`, `key`, `requires`, `max-per-eval`, and `meta` (including `name`) is a record field,
+	/// whose value must be text, a number, or a boolean.
+	fn load_yaml_sequence(&mut self, list: serde_yaml::Sequence, id_prefix: &str) -> Result<(), ParsingError> {
+		let id = String::from(id_prefix);
+		for entry in list{
+			match entry {
+				// list of strings (numbers and booleans are used as text too)
+				serde_yaml::Value::String(_) | serde_yaml::Value::Number(_) | serde_yaml::Value::Bool(_) =>
+					self.get_or_create_lut(&id).add_item(yaml_scalar_text(&entry).unwrap(), 1f64),
+				// item with optional weight and stable key, eg {text: goblin ambush, weight: 2, key: ga-01}
+				// or a record with named fields, eg {name: Longsword, damage: 1d8, cost: 15}
+				serde_yaml::Value::Mapping(entry) => {
+					// number range without text, eg {min: 50, max: 200} or {range: 3-18}
+					let number_range = match entry.contains_key("text") || entry.contains_key("name") {
+						true => None,
+						false => yaml_number_range(&entry)?
+					};
+					let text = match number_range {
+						Some((min, max)) => format!("{}-{}", min, max),
+						None => entry.get("text").or_else(|| entry.get("name")).and_then(yaml_scalar_text).ok_or_else(|| ParseError{
+							msg: Some(format!("List entry {:?} is missing the 'text' (or 'name') field", entry)), line: None, col: None
+						})?
+					};
+					let text = text.as_str();
+					let mut fields: BTreeMap<String, String> = BTreeMap::new();
+					for (name, value) in entry.iter() {
+						let name = name.as_str().ok_or_else(|| ParseError{
+							msg: Some(format!("Field names of '{}' must be strings, found {:?}", text, name)), line: None, col: None
+						})?;
+						if name == "text" || name == "weight" || name == "key" || name == "requires" || name == "max-per-eval" { continue; }
+						if number_range.is_some() && (name == "min" || name == "max" || name == "range") { continue; }
+						if let (true, serde_yaml::Value::Mapping(meta)) = (name == "meta", value) {
+							// metadata mapping, eg {name: Goblin, meta: {cr: 1/4, habitat: forest}}
+							for (meta_name, meta_value) in meta {
+								let (Some(meta_name), Some(meta_text)) = (meta_name.as_str(), yaml_scalar_text(meta_value)) else {
+									return Err(ParseError{
+										msg: Some(format!("Metadata of '{}' must map names to text or numbers, found {:?}: {:?}", text, meta_name, meta_value)),
+										line: None, col: None
+									}.into());
+								};
+								fields.insert(String::from(meta_name), meta_text);
+							}
+							continue;
+						}
+						let value = yaml_scalar_text(value).ok_or_else(|| ParseError{
+							msg: Some(format!("Field '{}' of '{}' must be text or a number, found {:?}", name, text, value)), line: None, col: None
+						})?;
+						fields.insert(String::from(name), value);
+					}
+					let key = match entry.get("key") {
+						None => None,
+						Some(key) => Some(key.as_str().ok_or_else(|| ParseError{
+							msg: Some(format!("Key for '{}' must be a string, found {:?}", text, key)), line: None, col: None
+						})?)
+					};
+					// requirement, eg "$terrain == desert"
+					let requires = match entry.get("requires") {
+						None => None,
+						Some(requires) => {
+							let requires = requires.as_str().ok_or_else(|| ParseError{
+								msg: Some(format!("Requirement for '{}' must be a string, found {:?}", text, requires)), line: None, col: None
+							})?;
+							expr::parse_expr(requires)?;
+							Some(String::from(requires))
+						}
+					};
+					// limit per evaluation, eg 1 for a legendary item
+					let max_per_eval = match entry.get("max-per-eval") {
+						None => None,
+						Some(max) => Some(max.as_u64().ok_or_else(|| ParseError{
+							msg: Some(format!("max-per-eval of '{}' must be a whole number, found {:?}", text, max)), line: None, col: None
+						})? as usize)
+					};
+					match entry.get("weight") {
+						// conditional weight, eg "$region == coast ? 5 : 1"
+						Some(serde_yaml::Value::String(weight_expr)) => {
+							expr::parse_expr(weight_expr.as_str())?;
+							let lut = self.get_or_create_lut(&id);
+							lut.add_conditional_item(text, weight_expr.as_str(), key.map(String::from));
+							if !fields.is_empty() {
+								lut.set_last_item_fields(fields);
+							}
+						},
+						w => {
+							let weight = match w {
+								None => 1f64,
+								Some(w) => check_weight(w.as_f64().ok_or_else(|| ParseError{
+									msg: Some(format!("Weight must be a number, but weight for '{}' was '{:?}' instead", text, w)), line: None, col: None
+								})?, text)?
+							};
+							match key {
+								_ if !fields.is_empty() => self.get_or_create_lut(&id).add_record(text, weight, key.map(String::from), fields),
+								None => self.get_or_create_lut(&id).add_item(text, weight),
+								Some(key) => self.get_or_create_lut(&id).add_keyed_item(text, weight, key)
+							}
+						}
+					}
+					if let Some(requires) = requires {
+						self.get_or_create_lut(&id).set_last_item_requires(requires);
+					}
+					if let Some(max) = max_per_eval {
+						self.get_or_create_lut(&id).set_last_item_max_per_eval(max);
+					}
+					if let Some((min, max)) = number_range {
+						self.get_or_create_lut(&id).set_last_item_number_range(min, max);
+					}
+				},
+				_ => return Err(ParseError{ msg: Some(format!("Only lists of strings or items are supported, found {:?}", entry)), line: None, col: None }.into())
+			}
+		}
+		Ok(())
+	}
+
+	/// Recursively scans the provided directory for random look-up table(s) from all supported
+	/// file formats found within the directory. The base look-up table ID for each table is the
+	/// relative filepath of the look-up table files (eg "bar/animal" for file
+	/// "foo/bar/animal.txt"). In most cases, you should use
+	/// [load_file(...)](Interpreter::load_file) instead of this method.
+	///
+	/// See the [twas module](twas) description for more details on supported random look-up
+	/// file formats.
+	///
+	/// # Arguments
+	/// * `dirpath`: The path to the directory to load.
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_dir<P>(&mut self, dirpath: P) -> Result<(), ParsingError> where P: Into<PathBuf> {
+		self.load_dir_namespaced(dirpath, "")
+	}
+
+	/// Recursively scans the provided directory for random look-up table(s) from all supported
+	/// file formats found within the directory. The base look-up table ID for each table is the
+	/// relative filepath of the look-up table files (eg "bar/animal" for file
+	/// "foo/bar/animal.txt"). In most cases, you should use
+	/// [load_file(...)](Interpreter::load_file) instead of this method.
+	///
+	/// See the [twas module](twas) description for more details on supported random look-up
+	/// file formats.
+	///
+	/// # Arguments
+	/// * `dirpath`: The path to the directory to load.
+	/// * `id_prefix`: ID prefix path, use an empty String ("") if this directory is the root of
+	/// the directory tree
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_dir_namespaced<P>(&mut self, dirpath: P, id_prefix: &str) -> Result<(), ParsingError> where P: Into<PathBuf> {
+		self.load_dir_with(dirpath, id_prefix, &DirLoadOptions::default())
+	}
+
+	/// Recursively scans the provided directory for random look-up table(s), just like
+	/// [load_dir_namespaced(...)](Interpreter::load_dir_namespaced), but only loads the files
+	/// that pass the given filters. This is useful for directories that also contain README
+	/// files, draft files, or other files that should not be loaded.
+	///
+	/// # Arguments
+	/// * `dirpath`: The path to the directory to load.
+	/// * `id_prefix`: ID prefix path, use an empty String ("") if this directory is the root of
+	///   the directory tree
+	/// * `options`: include and exclude patterns, maximum depth, and symbolic link handling
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	///
+	/// # Example
+	/// ```rust,no_run
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// let options = twas::DirLoadOptions::default()
+	///     .with_exclude("README*")
+	///     .with_exclude("drafts")
+	///     .with_max_depth(2);
+	/// interpreter.load_dir_with("tables", "", &options).unwrap();
+	/// ```
+	pub fn load_dir_with<P>(&mut self, dirpath: P, id_prefix: &str, options: &DirLoadOptions) -> Result<(), ParsingError> where P: Into<PathBuf> {
+		validate_id(id_prefix)?;
+		let compile = |patterns: &Vec<String>| patterns.iter().map(|p| glob::Pattern::new(p).map_err(|e| ParseError{
+			msg: Some(format!("Invalid file pattern '{}': {}", p, e)), line: None, col: None
+		})).collect::<Result<Vec<glob::Pattern>, ParseError>>();
+		let filter = DirFilter{include: compile(&options.include)?, exclude: compile(&options.exclude)?,
+			max_depth: options.max_depth, follow_symlinks: options.follow_symlinks};
+		let dedup = self.dedup;
+		self.dedup = options.dedup.unwrap_or(dedup);
+		let result = self.load_dir_filtered(dirpath.into().as_path(), id_prefix, "", 0, &filter);
+		self.dedup_loaded();
+		self.dedup = dedup;
+		result
+	}
+
+	/// Recursively loads the directory (at the given depth and path relative to the loaded
+	/// directory) with the given filter
+	fn load_dir_filtered(&mut self, dir: &Path, id_prefix: &str, rel_dir: &str, depth: usize, filter: &DirFilter) -> Result<(), ParsingError> {
+		for file in fs::read_dir(dir)? {
+			let file = file?;
+			if !filter.follow_symlinks && file.file_type()?.is_symlink() {
+				continue;
+			}
+			let file_path = file.path();
+			let name = file_path.file_name().unwrap_or_default().to_str().ok_or_else(||
+				io::Error::new(ErrorKind::Unsupported, "Invalid characters in file name")
+			)?;
+			let rel_path = match rel_dir.is_empty() {
+				true => String::from(name),
+				false => format!("{}/{}", rel_dir, name)
+			};
+			if filter.exclude.iter().any(|p| pattern_matches(p, rel_path.as_str(), name)) {
+				continue;
+			}
+			match file_path.is_dir() {
+				true => {
+					if filter.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+						continue;
+					}
+					let new_id = namespaced_id(id_prefix, name);
+					self.load_dir_filtered(&file_path, new_id.as_str(), rel_path.as_str(), depth + 1, filter)?;
+				}
+				false => {
+					let included = filter.include.is_empty()
+						|| filter.include.iter().any(|p| pattern_matches(p, rel_path.as_str(), name));
+					if included && is_table_file(name) {
+						let file = LazyFile{path: file_path.clone(), id_prefix: id_prefix.into(),
+							base_id: table_base_id(id_prefix, name), settings: self.get_load_settings()};
+						// a file that adds to look-up tables that are already loaded is parsed right
+						// away, since those tables would hide the file from lazy loading
+						match self.lazy_loading && !self.registry.keys().any(|id| file.may_provide(id)) {
+							// only index the file, it is parsed when one of its tables is needed
+							true => self.lazy.add(file),
+							false => self.load_file_namespaced(file_path.as_path(), id_prefix)?
+						}
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Reads the provided zip archive for random look-up table(s) from all supported
+	/// file formats found within the archive. The base look-up table ID for each table is the
+	/// relative filepath of the look-up table files  within the zip archive (eg "bar/animal" for
+	/// file "bar/animal.txt" in "foo.zip"). In most cases, you should use
+	/// [load_file(...)](Interpreter::load_file) instead of this method.
+	///
+	/// See the [twas module](twas) description for more details on supported random look-up
+	/// file formats.
+	///
+	/// # Arguments
+	/// * `zippath`: The path to the zip file to load.
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_zip<P>(&mut self, zippath: P) -> Result<(), ParsingError> where P: Into<PathBuf> {
+		self.load_zip_namespaced(zippath, "")
+	}
+
+	/// Reads the provided zip archive for random look-up table(s) from all supported
+	/// file formats found within the archive. The base look-up table ID for each table is the
+	/// relative filepath of the look-up table files  within the zip archive (eg "bar/animal" for
+	/// file "bar/animal.txt" in "foo.zip"). In most cases, you should use
+	/// [load_file(...)](Interpreter::load_file) instead of this method.
+	///
+	/// See the [twas module](twas) description for more details on supported random look-up
+	/// file formats.
+	///
+	/// # Arguments
+	/// * `zippath`: The path to the zip file to load.
+	/// * `id_prefix`: ID prefix path, use an empty String ("") if not adding a prefix
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_zip_namespaced<P>(&mut self, zippath: P, id_prefix: &str) -> Result<(), ParsingError> where P: Into<PathBuf> {
+		let input_file = File::open(zippath.into())?;
+		self.load_zip_reader_namespaced(io::BufReader::new(input_file), id_prefix)
+	}
+
+	/// Reads random look-up table(s) from all supported file formats found within the provided
+	/// zip archive data, such as a zip archive that is embedded in the binary with
+	/// `include_bytes!(...)` or that was received over the network. The base look-up table ID for
+	/// each table is the relative filepath of the look-up table file within the zip archive (eg
+	/// "bar/animal" for file "bar/animal.txt" in the archive).
+	///
+	/// # Arguments
+	/// * `reader`: The zip archive data to read (eg a `std::io::Cursor` over the bytes)
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// use std::io::Cursor;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// let pack: &[u8] = include_bytes!("../tests/test-data/testzip.zip");
+	/// interpreter.load_zip_reader(Cursor::new(pack)).expect("Failed to read zip archive");
+	/// println!("{}", interpreter.eval("A ${kind/species}").expect("Failed to eval"));
+	/// ```
+	pub fn load_zip_reader<I: Read + Seek>(&mut self, reader: I) -> Result<(), ParsingError> {
+		self.load_zip_reader_namespaced(reader, "")
+	}
+
+	/// Reads random look-up table(s) from all supported file formats found within the provided
+	/// zip archive data (just like [load_zip_reader(...)](Interpreter::load_zip_reader)), but
+	/// with the given "namespace" prefix added to the front of the look-up table ID(s).
+	///
+	/// # Arguments
+	/// * `reader`: The zip archive data to read (eg a `std::io::Cursor` over the bytes)
+	/// * `id_prefix`: ID prefix path, use an empty String ("") if not adding a prefix
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_zip_reader_namespaced<I: Read + Seek>(&mut self, reader: I, id_prefix: &str) -> Result<(), ParsingError> {
+		validate_id(id_prefix)?;
+		let id_prefix = id_prefix.trim();
+		let mut zip = zip::ZipArchive::new(reader)?;
+		for i in 0..zip.len() {
+			let mut entry = zip.by_index(i)?;
+			if entry.is_dir() { continue; }
+			// skip entries with unsafe paths (eg "../foo.txt")
+			let entry_path = match entry.enclosed_name() {
+				Some(p) => p.to_path_buf(),
+				None => continue
+			};
+			let file_name = entry_path.file_name().unwrap_or_default().to_str().ok_or_else(||
+				io::Error::new(ErrorKind::Unsupported, "Invalid characters in file name")
+			)?;
+			if !is_table_file(file_name) { continue; }
+			// the directories within the archive are added to the ID prefix
+			let mut namespace = String::from(id_prefix);
+			for dir in entry_path.parent().into_iter().flat_map(|p| p.components()) {
+				let dir = dir.as_os_str().to_str().ok_or_else(||
+					io::Error::new(ErrorKind::Unsupported, "Invalid characters in directory name")
+				)?;
+				namespace = namespaced_id(namespace.as_str(), dir);
+			}
+			// the size in the header is not trusted, since the archive may come from anywhere
+			let mut content: Vec<u8> = Vec::new();
+			entry.read_to_end(&mut content)?;
+			self.load_named_reader(namespace.as_str(), file_name, Cursor::new(content))?;
+		}
+		Ok(())
+	}
+
+	/// Loads random look-up table(s) from all supported file formats found within a directory
+	/// that was embedded in the binary with [embed_dir!(...)](embed_dir), so that a program can
+	/// ship with its table packs baked in. The look-up table IDs are the same as if the directory
+	/// was loaded from disk with [load_dir(...)](Interpreter::load_dir) (eg "bar/animal" for file
+	/// "bar/animal.txt" in the embedded directory).
+	///
+	/// # Arguments
+	/// * `dir`: The embedded directory to load.
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	#[cfg(feature = "embed")]
+	pub fn load_embedded(&mut self, dir: &EmbeddedDir) -> Result<(), ParsingError> {
+		self.load_embedded_namespaced(dir, "")
+	}
+
+	/// Loads random look-up table(s) from a directory that was embedded in the binary (just like
+	/// [load_embedded(...)](Interpreter::load_embedded)), but with the given "namespace" prefix
+	/// added to the front of the look-up table ID(s).
+	///
+	/// # Arguments
+	/// * `dir`: The embedded directory to load.
+	/// * `id_prefix`: ID prefix path, use an empty String ("") if not adding a prefix
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	#[cfg(feature = "embed")]
+	pub fn load_embedded_namespaced(&mut self, dir: &EmbeddedDir, id_prefix: &str) -> Result<(), ParsingError> {
+		validate_id(id_prefix)?;
+		let id_prefix = id_prefix.trim();
+		for entry in dir.entries() {
+			let name = entry.path().file_name().unwrap_or_default().to_str().ok_or_else(||
+				io::Error::new(ErrorKind::Unsupported, "Invalid characters in file name")
+			)?;
+			match entry {
+				include_dir::DirEntry::Dir(sub_dir) => {
+					let mut namespace = String::from(id_prefix);
+					if !namespace.is_empty() { namespace.push('/'); }
+					namespace.push_str(name);
+					self.load_embedded_namespaced(sub_dir, namespace.as_str())?;
+				},
+				include_dir::DirEntry::File(file) => {
+					if is_table_file(name) {
+						self.load_named_reader(id_prefix, name, Cursor::new(file.contents()))?;
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Parses the provided string as a .txt file. Each line will be parsed as an entry in a
+	/// look-up table, with all possible values having equal weight.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: look-up table ID to register this look-up table for text substitution
+	/// * `txt`: the text to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_txt_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
+		validate_id(id)?;
+		let id = String::from(id);
+		if ! self.registry.contains_key(&id) {
+			self.registry.insert(id.clone(), Arc::new(LookUpTable::new()));
+		}
+		self.mark_loaded(id.as_str());
+		let lut = Arc::make_mut(self.registry.get_mut(&id).unwrap());
+		let txt: String = txt.into();
+		for line in txt.split("\n") {
+			lut.add_item(line.trim(), 1.);
+		}
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Adds the given items to a look-up table, with all possible values having equal weight,
+	/// just like the lines of a .txt file (but without any parsing). This is handy for data that
+	/// the application already has in memory (eg in a `Vec`).
+	///
+	/// # Arguments
+	/// * `id`: look-up table ID to register this look-up table for text substitution
+	/// * `lines`: the items to add
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).expect("Invalid ID");
+	/// println!("{}", interpreter.eval("I have a pet ${animal}.").expect("Failed to eval"));
+	/// ```
+	pub fn load_lines<T>(&mut self, id: &str, lines: impl IntoIterator<Item=T>) -> Result<(), ParsingError> where T: Into<String> {
+		validate_id(id)?;
+		let lut = self.get_or_create_lut(id);
+		for line in lines {
+			lut.add_item(line, 1.);
+		}
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Adds the given items with their weights to a look-up table, just like a weighted table in
+	/// a YAML file (but without any parsing). This is handy for data that the application already
+	/// has in memory (eg in a `HashMap`).
+	///
+	/// # Arguments
+	/// * `id`: look-up table ID to register this look-up table for text substitution
+	/// * `items`: the items to add, as (text, weight) pairs
+	/// # Returns
+	/// A `Result` indicating success or failure (eg if a weight is negative).
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_items("rarity", vec![("common", 6.), ("rare", 1.)]).expect("Invalid weight");
+	/// println!("{}", interpreter.eval("You found a ${rarity} item.").expect("Failed to eval"));
+	/// ```
+	pub fn load_items<T>(&mut self, id: &str, items: impl IntoIterator<Item=(T, f64)>) -> Result<(), ParsingError> where T: Into<String> {
+		validate_id(id)?;
+		// check all weights first, so that nothing is added if any weight is invalid
+		let mut checked: Vec<(String, f64)> = Vec::new();
+		for (text, weight) in items {
+			let text: String = text.into();
+			let weight = check_weight(weight, text.as_str())?;
+			checked.push((text, weight));
+		}
+		let lut = self.get_or_create_lut(id);
+		for (text, weight) in checked {
+			lut.add_item(text, weight);
+		}
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Parses the provided string as a .csv file. The text will be interpreted as standard
+	/// comma-separate value (CSV) file, where the first row is the header row containing column
+	/// names and all subsequent rows are the possible values for each column. Each column is its
+	/// own random look-up table. All rows have equal probability, unless there is a column
+	/// named `weight`. If a `weight` column is present, then the probability of each row is
+	/// weighted by the decimal value in the corresponding `weight` column.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: each column in the CSV text will be registered as a look-up table with ID `id/column-name`
+	/// * `txt`: the text to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_csv_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
+		let txt: String = txt.into();
+		let reader = BufReader::new(txt.as_bytes());
+		self.load_csv(id, reader)
+	}
+
+	/// Parses the provided string just like [load_csv_str(...)](Interpreter::load_csv_str), but
+	/// with the given delimiter and quote character (eg `CsvOptions::with_delimiter(';')` for
+	/// semicolon-separated values, or `CsvOptions::tsv()` for tab-separated values).
+	///
+	/// # Arguments
+	/// * `id`: each column in the CSV text will be registered as a look-up table with ID `id/column-name`
+	/// * `txt`: the text to parse
+	/// * `options`: delimiter and quote character to use
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_csv_str_with<T>(&mut self, id: &str, txt: T, options: &CsvOptions) -> Result<(), ParsingError> where T: Into<String> {
+		let txt: String = txt.into();
+		let reader = BufReader::new(txt.as_bytes());
+		self.load_csv_with(id, reader, options)
+	}
+
+	/// Parses the provided string as JSON. A JSON object can contain one or multiple random
+	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the JSON
+	/// will be parsed as look-up tables with equal probability for all items, while
+	/// weighted-probabilities are specified using a string-number mapping
+	/// (eg `rarity: {"common": 6, "uncommon": 3, "rare": 0.9, "very rare": 0.1}`). The tables can be
+	/// organized by nesting map objects, with each nesting adding a level to the look-up table
+	/// ID path.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided JSON string
+	/// * `txt`: the text to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_json_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
+		let txt: String = txt.into();
+		let reader = BufReader::new(txt.as_bytes());
+		self.load_json(id, reader)
+	}
+
+	/// Parses the provided string as YAML. A YAML object can contain one or multiple random
+	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the YAML
+	/// content will be parsed as look-up tables with equal probability for all items, while
+	/// weighted-probabilities are specified using a string-number mapping
+	/// (eg `rarity: {common: 6, uncommon: 3, rare: 0.9, "very rare": 0.1}`). The tables can be
+	/// organized by nesting map objects, with each nesting adding a level to the look-up table
+	/// ID path.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided YAML string
+	/// * `txt`: the text to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_yaml_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
+		let txt: String = txt.into();
+		let reader = BufReader::new(txt.as_bytes());
+		self.load_yaml(id, reader)
+	}
+
+	/// Parses the provided stream as a .txt file. Each line will be parsed as an entry in a
+	/// look-up table, with all possible values having equal weight.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: look-up table ID to register this look-up table for text substitution
+	/// * `reader`: the text stream to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_txt<I: Read>(&mut self, id: &str, mut reader: I) -> Result<(), ParsingError> {
+		let mut content = String::new();
+		reader.read_to_string(&mut content)?;
+		self.load_txt_str(id, content)?;
+		Ok(())
+	}
+
+	/// Parses the provided stream as a .csv file. The text will be interpreted as standard
+	/// comma-separate value (CSV) file, where the first row is the header row containing column
+	/// names and all subsequent rows are the possible values for each column. Each column is its
+	/// own random look-up table. All rows have equal probability, unless there is a column
+	/// named `weight`. If a `weight` column is present, then the probability of each row is
+	/// weighted by the decimal value in the corresponding `weight` column. Alternatively, a column
+	/// named `roll` holds the roll range of each row (eg `01-05`, as in a d100 table), which makes
+	/// each column a tiered look-up table where the probability of each row is the width of its
+	/// range.
+	///
+	/// If the first cell of the header row is `$matrix`, then the CSV is instead parsed as a matrix
+	/// table, where the first column holds the row names and each cell is registered as a
+	/// single-item look-up table with ID `id/row-name/column-name` (used with the
+	/// `${id[row][column]}` substitution syntax).
+	///
+	/// The delimiter and quote character are those of
+	/// [set_csv_options(...)](Interpreter::set_csv_options) (a comma and `"` by default).
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: each column in the CSV text will be registered as a look-up table with ID `id/column-name`
+	/// * `reader`: the text stream to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_csv<I: Read>(&mut self, id_prefix: &str, reader: I) -> Result<(), ParsingError> {
+		let options = self.csv_options.clone();
+		self.load_csv_with(id_prefix, reader, &options)
+	}
+
+	/// Parses the provided stream just like [load_csv(...)](Interpreter::load_csv), but with the
+	/// given delimiter and quote character. This is useful for tab-separated values
+	/// (`CsvOptions::tsv()`) and for the semicolon-separated CSV files that are common in
+	/// European locales (`CsvOptions::with_delimiter(';')`).
+	///
+	/// # Arguments
+	/// * `id`: each column in the CSV text will be registered as a look-up table with ID `id/column-name`
+	/// * `reader`: the text stream to parse
+	/// * `options`: delimiter and quote character to use
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_csv_with<I: Read>(&mut self, id_prefix: &str, reader: I, options: &CsvOptions) -> Result<(), ParsingError> {
+		validate_id(id_prefix)?;
+		let mut buffered_reader = BufReader::new(reader);
+		let mut char_iter = buffered_reader.chars();
+		let cols = match read_csv_row(&mut char_iter, options) {
+			Some(row) => row,
+			None => return Err(ParsingError::from(NoValuesError {})),
+		};
+		if cols.len() > 1 && cols[0].trim() == "$matrix" {
+			// matrix table with row names in the first column
+			while let Some(row) = read_csv_row(&mut char_iter, options) {
+				for i in 1..row.len().min(cols.len()) {
+					let cell: &String = &row[i];
+					if !cell.is_empty() {
+						let mut id: String = id_prefix.into();
+						if !id_prefix.is_empty() { id.push('/'); }
+						id.push_str(format!("{}/{}", row[0], cols[i]).as_str());
+						self.get_or_create_lut(&id).add_item(cell.clone(), 1f64);
+					}
+				}
+			}
+			self.dedup_loaded();
+			return Ok(());
+		}
+		let mut weights_col: Option<usize> = None;
+		let mut roll_col: Option<usize> = None;
+		for i in 0..cols.len() {
+			let col = &cols[i];
+			if col.as_str() == "weight" {
+				weights_col = Some(i);
+			} else if col.as_str() == "roll" {
+				roll_col = Some(i);
+			}
+		}
+		let rows_id = rows_table_id(id_prefix);
+		while match read_csv_row(&mut char_iter, options) {
+			None => false,
+			Some(row) => {
+				let w = match weights_col {
+					None => 1f64,
+					Some(c) => check_weight(row[c].trim().parse::<f64>()?, row.join(options.delimiter.to_string().as_str()).as_str())?
+				};
+				let range = match roll_col {
+					None => None,
+					Some(c) => {
+						let cell = row.get(c).map(|c| c.as_str()).unwrap_or("");
+						Some(parse_range_key(cell).ok_or_else(|| ParseError{
+							msg: Some(format!("'{}' is not a valid roll range", cell)), line: None, col: None
+						})?)
+					}
+				};
+				for i in 0..row.len() {
+					let col: &String = &cols[i];
+					let cell: &String = &row[i];
+					if cell.is_empty() || Some(i) == roll_col {
+						// empty cell, assume uneven table and do nothing
+					} else {
+						let mut id: String = id_prefix.into();
+						if !id_prefix.is_empty() { id.push_str("/"); }
+						id.push_str(col.as_str());
+						match range {
+							Some((min, max)) if Some(i) != weights_col => self.get_or_create_lut(&id).add_range_item(cell.clone(), min, max),
+							_ => self.get_or_create_lut(&id).add_item(cell.clone(), w)
+						}
+					}
+				}
+				// keep the whole row too if requested, for drawing all columns of a row together
+				let fields: BTreeMap<String, String> = match options.rows {
+					true => cols.iter().zip(row.iter()).enumerate()
+						.filter(|(i, (_, cell))| !cell.is_empty() && Some(*i) != weights_col && Some(*i) != roll_col)
+						.map(|(_, (col, cell))| (col.clone(), cell.clone())).collect(),
+					false => BTreeMap::new()
+				};
+				if let Some(text) = cols.iter().zip(row.iter()).enumerate()
+						.find(|(i, (col, _))| fields.contains_key(*col) && Some(*i) != weights_col && Some(*i) != roll_col)
+						.map(|(_, (_, cell))| cell.clone()) {
+					let rows = self.get_or_create_lut(&rows_id);
+					match range {
+						Some((min, max)) => {
+							rows.add_range_item(text, min, max);
+							rows.set_last_item_fields(fields);
+						},
+						None => rows.add_record(text, w, None, fields)
+					}
+				}
+				true
+			}
+		}{};
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Parses the provided stream as JSON. A JSON object can contain one or multiple random
+	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the JSON
+	/// will be parsed as look-up tables with equal probability for all items, while
+	/// weighted-probabilities are specified using a string-number mapping
+	/// (eg `rarity: {"common": 6, "uncommon": 3, "rare": 0.9, "very rare": 0.1}`). The tables can be
+	/// organized by nesting map objects, with each nesting adding a level to the look-up table
+	/// ID path. The JSON may also use JSON5 syntax, such as `//` and `/* */` comments, trailing
+	/// commas, and unquoted keys.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided JSON string
+	/// * `reader`: the text stream to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_json<I: Read>(&mut self, id: &str, mut reader: I) -> Result<(), ParsingError> {
+		let mut txt = String::new();
+		reader.read_to_string(&mut txt)?;
+		let parsed: serde_yaml::Value = json5::from_str(txt.as_str())?;
+		self.load_yaml_value(id, parsed)
+	}
+
+	/// Parses the provided stream as YAML. A YAML object can contain one or multiple random
+	/// look-up tables, with arbitrary levels of nested depth. Any lists encountered in the YAML
+	/// stream will be parsed as look-up tables with equal probability for all items, while
+	/// weighted-probabilities are specified using a string-number mapping
+	/// (eg `rarity: {common: 6, uncommon: 3, rare: 0.9, "very rare": 0.1}`). The tables can be
+	/// organized by nesting map objects, with each nesting adding a level to the look-up table
+	/// ID path. Other files can be included with a `$include` key (eg `$include: monsters.yaml`),
+	/// which loads the tables of the other file into the same namespace, or with an `!include` tag
+	/// (eg `monsters: !include monsters.yaml`), which loads them as a sub-table. Includes are only
+	/// allowed in files that are loaded from disk, and only for files within the directory of the
+	/// including file. A map with an
+	/// `$extends` field defines a look-up table as a copy of another look-up table with optional
+	/// `$add`, `$remove`, and `$weights` changes.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided YAML string
+	/// * `reader`: the text stream to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_yaml<I: Read>(&mut self, id: &str, reader: I) -> Result<(), ParsingError> {
+		let parsed: serde_yaml::Value = serde_yaml::from_reader(reader)?;
+		self.load_yaml_value(id, parsed)
+	}
+
+	/// Loads one or multiple random look-up tables from an already parsed YAML value, such as a
+	/// subtree of an application's configuration file, without re-serializing it to a string. The
+	/// value has the same structure as a YAML file (see [load_yaml(...)](Interpreter::load_yaml)):
+	/// lists are look-up tables with equal probability for all items, string-number mappings are
+	/// weighted look-up tables, and nested mappings add a level to the look-up table ID path.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided value
+	/// * `value`: the YAML value to load
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_value(&mut self, id: &str, value: serde_yaml::Value) -> Result<(), ParsingError> {
+		validate_id(id)?;
+		self.load_yaml_value(id, value)
+	}
+
+	/// Loads one or multiple random look-up tables from an already parsed JSON value, without
+	/// re-serializing it to a string (see [load_value(...)](Interpreter::load_value)).
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided value
+	/// * `value`: the JSON value to load
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_json_value(&mut self, id: &str, value: serde_json::Value) -> Result<(), ParsingError> {
+		self.load_value(id, serde_yaml::to_value(value)?)
+	}
+
+	/// Loads a parsed YAML (or JSON) list or mapping as random look-up table(s)
+	fn load_yaml_value(&mut self, id: &str, parsed: serde_yaml::Value) -> Result<(), ParsingError> {
+		match parsed {
+			serde_yaml::Value::Sequence(list) => {
+				self.load_yaml_sequence(list, id)?;
+			},
+			serde_yaml::Value::Mapping(map) => {
+				// map of items and weights or map of maps of items
+				self.load_yaml_mapping(map, id)?
+			},
+			_ => return Err(ParseError{ msg: Some(
+				format!("Failed to parse {}, wrong structure (should be list or mapping object)", id)
+			), line: None, col: None }.into())
+		}
+		if self.loading_files.is_empty() {
+			// when loading a file, this happens after its tables are added to the registry
+			self.resolve_extensions()?;
+		}
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Parses the provided string as XML. Every element that only contains text is a look-up
+	/// table item (the element name does not matter, eg `<item>dog</item>`), with an optional
+	/// `weight` attribute for weighted probability and an optional `key` attribute for a stable
+	/// item key. The items belong to the look-up table of their parent element. Elements nested
+	/// inside other elements add a level to the look-up table ID path, with the root element
+	/// corresponding to the provided ID.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided XML string
+	/// * `txt`: the text to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_xml_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
+		let txt: String = txt.into();
+		let doc = roxmltree::Document::parse(txt.as_str())?;
+		self.load_xml_element(doc.root_element(), id)?;
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Parses the provided stream as XML. Every element that only contains text is a look-up
+	/// table item (the element name does not matter, eg `<item>dog</item>`), with an optional
+	/// `weight` attribute for weighted probability and an optional `key` attribute for a stable
+	/// item key. The items belong to the look-up table of their parent element. Elements nested
+	/// inside other elements add a level to the look-up table ID path, with the root element
+	/// corresponding to the provided ID.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables nested in the provided XML string
+	/// * `reader`: the text stream to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_xml<I: Read>(&mut self, id: &str, mut reader: I) -> Result<(), ParsingError> {
+		let mut txt = String::new();
+		reader.read_to_string(&mut txt)?;
+		self.load_xml_str(id, txt)
+	}
+
+	/// Parses an XML element (recursive). Child elements without child elements of their own are
+	/// items of this element's look-up table, while the other child elements are nested look-up
+	/// tables or namespaces.
+	fn load_xml_element(&mut self, element: roxmltree::Node, id_prefix: &str) -> Result<(), ParsingError> {
+		for child in element.children().filter(|n| n.is_element()) {
+			if child.children().any(|n| n.is_element()) {
+				// nested table or namespace
+				let mut next_id = String::from(id_prefix);
+				if !id_prefix.is_empty() { next_id.push('/'); }
+				next_id.push_str(child.tag_name().name());
+				self.load_xml_element(child, next_id.as_str())?;
+			} else {
+				let text = child.text().unwrap_or("").trim();
+				if text.is_empty() { continue; }
+				let weight = match child.attribute("weight") {
+					None => 1f64,
+					Some(w) => check_weight(w.trim().parse::<f64>()?, text)?
+				};
+				match child.attribute("key") {
+					None => self.get_or_create_lut(id_prefix).add_item(text, weight),
+					Some(key) => self.get_or_create_lut(id_prefix).add_keyed_item(text, weight, key)
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Parses the provided string as Markdown. Each bullet list (or numbered list) item is a
+	/// look-up table item, and each column of a pipe table is a look-up table (like the columns of
+	/// a .csv file, including the optional `weight` column). Headings add a level to the look-up
+	/// table ID path (eg the items of a list under heading `## Animals` belong to look-up table
+	/// `id/Animals`, and the column `Name` of a pipe table under the same heading is look-up
+	/// table `id/Animals/Name`). A pipe table starts with a header row that is directly followed by
+	/// a separator row with the same number of columns (eg `|---|---|`). All other text is
+	/// ignored, including everything in fenced code blocks (between ```` ``` ```` or `~~~` lines).
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables in the provided Markdown string
+	/// * `txt`: the text to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_md_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
+		validate_id(id)?;
+		let txt: String = txt.into();
+		let bullet: Regex = Regex::new(r#"^\s*(?:[-*+]|\d+[.)])\s+(.+)$"#).unwrap();
+		let heading: Regex = Regex::new(r#"^(#{1,6})\s+(.*?)[\s#]*$"#).unwrap();
+		let table_separator: Regex = Regex::new(r#"^\s*\|?\s*:?-+:?\s*(\|\s*:?-+:?\s*)*\|?\s*$"#).unwrap();
+		let fence: Regex = Regex::new(r#"^ {0,3}(`{3,}|~{3,})"#).unwrap();
+		let lines: Vec<&str> = txt.lines().collect();
+		let mut headings: Vec<(usize, String)> = Vec::new();
+		let mut table_id = String::from(id);
+		// opening marker of the fenced code block that the current line is in (if any)
+		let mut open_fence: Option<String> = None;
+		let mut i = 0;
+		while i < lines.len() {
+			let line = lines[i];
+			if let Some(caps) = fence.captures(line) {
+				// start or end of a fenced code block, which is closed by a line with at least as
+				// many of the same fence characters and nothing else
+				let marker = &caps[1];
+				match &open_fence {
+					None => open_fence = Some(String::from(marker)),
+					Some(open) if marker.starts_with(open.as_str()) && line.trim() == marker => open_fence = None,
+					Some(_) => {}
+				}
+				i += 1;
+				continue;
+			}
+			if open_fence.is_some() {
+				// code is not parsed
+				i += 1;
+				continue;
+			}
+			if let Some(caps) = heading.captures(line) {
+				// heading, which sets the look-up table ID
+				let level = caps[1].len();
+				while headings.last().is_some_and(|(l, _)| *l >= level) {
+					headings.pop();
+				}
+				headings.push((level, String::from(caps[2].trim())));
+				table_id = String::from(id);
+				for (_, name) in &headings {
+					if !table_id.is_empty() { table_id.push('/'); }
+					table_id.push_str(name.as_str());
+				}
+				validate_id(table_id.as_str())?;
+			} else if line.contains('|') && i + 1 < lines.len() && lines[i + 1].contains('|')
+					&& table_separator.is_match(lines[i + 1])
+					&& split_md_table_row(lines[i + 1]).len() == split_md_table_row(line).len() {
+				// pipe table, where each column is a look-up table
+				let cols = split_md_table_row(line);
+				let weights_col = cols.iter().position(|c| c.as_str() == "weight");
+				i += 2;
+				while i < lines.len() && lines[i].contains('|') {
+					let row = split_md_table_row(lines[i]);
+					let w = match weights_col {
+						None => 1f64,
+						Some(c) => {
+							let cell = row.get(c).map(|c| c.as_str()).unwrap_or("");
+							check_weight(cell.parse::<f64>()?, lines[i])?
+						}
+					};
+					for (c, (col, cell)) in cols.iter().zip(row.iter()).enumerate() {
+						if cell.is_empty() || Some(c) == weights_col { continue; }
+						let mut col_id = table_id.clone();
+						if !col_id.is_empty() { col_id.push('/'); }
+						col_id.push_str(col.as_str());
+						self.get_or_create_lut(&col_id).add_item(cell.clone(), w);
+					}
+					i += 1;
+				}
+				continue;
+			} else if let Some(caps) = bullet.captures(line) {
+				// list item
+				self.get_or_create_lut(&table_id).add_item(caps[1].trim(), 1f64);
+			}
+			i += 1;
+		}
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Parses the provided stream as Markdown. Each bullet list (or numbered list) item is a
+	/// look-up table item, and each column of a pipe table is a look-up table (like the columns of
+	/// a .csv file, including the optional `weight` column). Headings add a level to the look-up
+	/// table ID path. All other text is ignored.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables in the provided Markdown stream
+	/// * `reader`: the text stream to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_md<I: Read>(&mut self, id: &str, mut reader: I) -> Result<(), ParsingError> {
+		let mut txt = String::new();
+		reader.read_to_string(&mut txt)?;
+		self.load_md_str(id, txt)
+	}
+
+	/// Parses the provided string as a Twee (interactive fiction) story. Each passage, which
+	/// starts with a `:: Name [tags] {metadata}` header line, is a look-up table item in the
+	/// look-up table `id/Name`, and the text of the passage is the item. Passages that share the
+	/// same name are variants of the same look-up table, and the optional `weight` entry in the
+	/// metadata (eg `:: Forest {"weight": 3}`) sets the probability of the variant. The special
+	/// `StoryTitle` and `StoryData` passages and passages tagged `script` or `stylesheet` are
+	/// ignored. Links like `[[Forest]]` are kept as-is; use `${id/Forest}` to draw from another
+	/// passage.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables in the provided Twee string
+	/// * `txt`: the text to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_twee_str<T>(&mut self, id: &str, txt: T) -> Result<(), ParsingError> where T: Into<String> {
+		validate_id(id)?;
+		let txt: String = txt.into();
+		let header: Regex = Regex::new(r#"^::\s*((?:\\.|[^\\\[{])*?)\s*(?:\[((?:\\.|[^\\\]])*)\])?\s*(\{.*\})?\s*$"#).unwrap();
+		let escape: Regex = Regex::new(r#"\\(.)"#).unwrap();
+		// (table ID, weight, ignored, passage text)
+		let mut passages: Vec<(String, f64, bool, String)> = Vec::new();
+		for line in txt.lines() {
+			if let Some(caps) = header.captures(line) {
+				let name = escape.replace_all(&caps[1], "$1").to_string();
+				let tags: Vec<&str> = caps.get(2).map(|t| t.as_str().split_whitespace().collect())
+					.unwrap_or_default();
+				let weight = match caps.get(3)
+					.and_then(|meta| serde_json::from_str::<serde_json::Value>(meta.as_str()).ok())
+					.and_then(|meta| meta.get("weight").and_then(|w| w.as_f64())) {
+					Some(w) => check_weight(w, line)?,
+					None => 1f64
+				};
+				let ignored = name == "StoryTitle" || name == "StoryData"
+					|| tags.iter().any(|t| *t == "script" || *t == "stylesheet");
+				let mut table_id = String::from(id);
+				if !table_id.is_empty() { table_id.push('/'); }
+				table_id.push_str(name.as_str());
+				if !ignored { validate_id(table_id.as_str())?; }
+				passages.push((table_id, weight, ignored, String::new()));
+			} else if let Some((_, _, _, text)) = passages.last_mut() {
+				// any text before the first passage is ignored
+				text.push_str(line);
+				text.push('\n');
+			}
+		}
+		for (table_id, weight, ignored, text) in passages {
+			let text = text.trim();
+			if ignored || text.is_empty() { continue; }
+			self.get_or_create_lut(&table_id).add_item(text, weight);
+		}
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Parses the provided stream as a Twee (interactive fiction) story. Each passage is a look-up
+	/// table item in the look-up table `id/Name` (where `Name` is the passage name), and passages
+	/// that share the same name are variants of the same look-up table.
+	///
+	/// See [load_twee_str(...)](Interpreter::load_twee_str) for more details.
+	///
+	/// # Arguments
+	/// * `id`: this id will be prefixed to the look-up tables in the provided Twee stream
+	/// * `reader`: the text stream to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_twee<I: Read>(&mut self, id: &str, mut reader: I) -> Result<(), ParsingError> {
+		let mut txt = String::new();
+		reader.read_to_string(&mut txt)?;
+		self.load_twee_str(id, txt)
+	}
+
+	/// Parses the provided stream as an Excel spreadsheet (.xlsx file). Each sheet in the workbook
+	/// adds a level to the look-up table ID path, and each sheet is read like a .csv file: the
+	/// first row is the header row containing column names and each column is its own random
+	/// look-up table. All rows have equal probability, unless there is a column named `weight`.
+	/// If a `weight` column is present, then the probability of each row is weighted by the
+	/// decimal value in the corresponding `weight` column.
+	///
+	/// See the [twas module](twas) description for more details on random look-up formats.
+	///
+	/// # Arguments
+	/// * `id`: each column will be registered as a look-up table with ID `id/sheet-name/column-name`
+	/// * `reader`: the .xlsx file data to parse
+	/// # Returns
+	/// A `Result` indicating success or failure.
+	pub fn load_xlsx<I: Read + Seek>(&mut self, id: &str, reader: I) -> Result<(), ParsingError> {
+		validate_id(id)?;
+		let mut workbook: Xlsx<I> = Xlsx::new(reader)?;
+		for sheet in workbook.sheet_names() {
+			let range = workbook.worksheet_range(sheet.as_str())?;
+			let mut rows = range.rows()
+				.map(|row| row.iter().map(|cell| cell.to_string().trim().to_string()).collect::<Vec<String>>());
+			let cols = match rows.next() {
+				Some(row) => row,
+				None => continue // empty sheet
+			};
+			let mut sheet_id = String::from(id);
+			if !sheet_id.is_empty() { sheet_id.push('/'); }
+			sheet_id.push_str(sheet.as_str());
+			validate_id(sheet_id.as_str())?;
+			let weights_col = cols.iter().position(|c| c.as_str() == "weight");
+			for row in rows {
+				if row.iter().all(|cell| cell.is_empty()) { continue; }
+				let w = match weights_col {
+					None => 1f64,
+					Some(c) => {
+						let cell = row.get(c).map(|c| c.as_str()).unwrap_or("");
+						check_weight(cell.parse::<f64>()?, row.join(",").as_str())?
+					}
+				};
+				for (c, (col, cell)) in cols.iter().zip(row.iter()).enumerate() {
+					if cell.is_empty() || col.is_empty() || Some(c) == weights_col { continue; }
+					let mut col_id = sheet_id.clone();
+					col_id.push('/');
+					col_id.push_str(col.as_str());
+					self.get_or_create_lut(&col_id).add_item(cell.clone(), w);
+				}
+			}
+		}
+		self.dedup_loaded();
+		Ok(())
+	}
+
+	/// Gets a random look-up table from the registry by it's registered ID
+	/// (eg `animal` for `animal.txt`). If there is no look-up table for that ID, then a new empty
+	/// look-up table will be created
+	/// #Arguments
+	/// * `id`: look-up table ID to fetch
+	/// # Returns
+	/// A [LookUpTable](twas::data::LookUpTable) object representing a random look-up table
+	pub fn get_or_create_lut(&mut self, id: &str) -> &mut LookUpTable {
+		let id = String::from(id);
+		self.mark_loaded(id.as_str());
+		if !self.registry.contains_key(&id) {
+			self.registry.insert(id.clone(), Arc::new(LookUpTable::new()));
+		}
+		Arc::make_mut(self.registry.get_mut(&id).unwrap())
+	}
+
+	/// Gets a random look-up table from the registry by it's registered ID, if one exists.
+	/// #Arguments
+	/// * `id`: look-up table ID to fetch
+	/// # Returns
+	/// An `Option` containing a [LookUpTable](twas::data::LookUpTable) object representing a
+	/// random look-up table, or None if no look-up table has been registered with the requested ID
+	pub fn get_lut(&mut self, id: &str) -> Option<&mut LookUpTable> {
+		let id = String::from(id);
+		if !self.registry.contains_key(&id) {
+			// parsing errors are reported when the look-up table is used by eval(...)
+			let _ = self.load_lazy(id.as_str());
+		}
+		self.registry.get_mut(&id).map(Arc::make_mut)
+	}
+
+	/// Adds the items of the given look-up table to the registered look-up table with the given
+	/// ID, or registers a copy of it if there is no look-up table with that ID yet (eg to combine a
+	/// base table pack with an expansion pack under one ID).
+	/// # Arguments
+	/// * `id`: ID of the look-up table to merge into
+	/// * `other`: look-up table whose items are added
+	/// * `sum_duplicates`: if `true`, then the weights of items with the same text (or stable key)
+	///   are added together instead of adding the item twice (see
+	///   [LookUpTable::merge(...)](LookUpTable::merge))
+	/// # Returns
+	/// A `Result` indicating success or failure (eg because the ID is not valid)
+	pub fn merge_lut(&mut self, id: &str, other: &LookUpTable, sum_duplicates: bool) -> Result<(), ParsingError> {
+		validate_id(id)?;
+		match self.get_lut(id) {
+			Some(lut) => lut.merge(other, sum_duplicates),
+			None => {self.registry.insert(String::from(id), Arc::new(other.clone()));}
+		}
+		Ok(())
+	}
+
+	/// Adds all look-up tables of another interpreter to this interpreter, optionally in a
+	/// namespace (eg to compose an interpreter from interpreters that were built separately for
+	/// each module of an application). The look-up tables are shared with the other interpreter
+	/// instead of copied, and a look-up table is only copied when either interpreter changes it.
+	/// If there already is a look-up table with the same ID, then the items are added to it (like
+	/// loading another file into the same ID). The depletion modes of the look-up tables are
+	/// imported as well (see [set_depletion(...)](Interpreter::set_depletion)), but not their
+	/// used up items. Like [load_file_namespaced(...)](Interpreter::load_file_namespaced), the
+	/// namespace does not change the substitution tokens in the items. Lazily loaded look-up
+	/// tables that the other interpreter has not parsed yet are not imported.
+	/// # Arguments
+	/// * `other`: the interpreter whose look-up tables to import
+	/// * `id_prefix`: namespace (ID prefix) for the imported look-up tables, or an empty string
+	///   to keep their IDs
+	/// # Returns
+	/// A `Result` indicating success or failure (eg because the namespace is not a valid ID)
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut monsters = twas::Interpreter::new();
+	/// monsters.load_lines("undead", vec!["zombie", "ghost"]).unwrap();
+	/// let mut app = twas::Interpreter::new();
+	/// app.import(&monsters, "monsters").unwrap();
+	/// assert_eq!(app.list_ids(), vec!["monsters/undead"]);
+	/// println!("{}", app.eval("A ${monsters/undead} appears!").unwrap());
+	/// ```
+	pub fn import<G: Rng>(&mut self, other: &Interpreter<G>, id_prefix: &str) -> Result<(), ParsingError> {
+		validate_id(id_prefix)?;
+		let id_prefix = id_prefix.trim().trim_end_matches('/');
+		let new_id = |id: &String| match id_prefix.is_empty() {
+			true => id.clone(),
+			false => format!("{}/{}", id_prefix, id)
+		};
+		for id in other.registry.keys() {
+			let mode = other.draws.get_mode(id);
+			if mode != Depletion::Replace {
+				self.draws.set_mode(new_id(id).as_str(), mode);
+			}
+		}
+		self.add_tables(other.registry.iter().map(|(id, lut)| (new_id(id), Arc::clone(lut))).collect());
+		self.resolve_extensions()?;
+		Ok(())
+	}
+
+	/// Sets the default substitution options of a registered look-up table, which apply whenever
+	/// the table is drawn from, unless the substitution token sets them (eg `{case: title}` for a
+	/// table of names, such that `${names}` works like `${{id: names, case: title}}`). YAML files
+	/// can set the default options with a `$options` key instead.
+	/// # Arguments
+	/// * `id`: look-up table ID
+	/// * `options`: the default options, written like the options of a substitution token (eg
+	///   `{case: title, aan: true}`), or `None` to remove the default options
+	/// # Returns
+	/// A `Result` indicating success or failure (eg because there is no look-up table with that ID)
+	pub fn set_table_defaults(&mut self, id: &str, options: Option<&str>) -> Result<(), ParsingError> {
+		let defaults = match options {
+			None => None,
+			Some(options) => Some(parse_table_defaults(serde_yaml::from_str(options)?, id)?)
+		};
+		self.get_lut(id).ok_or_else(|| KeyNotFoundError{key: id.into()})?.set_defaults(defaults);
+		Ok(())
+	}
+
+	/// Gets a list of all currently registered look-up tables
+	pub fn list_ids(&self) -> Vec<&String> {
+		self.registry.keys().filter(|id| !is_rows_table(id)).collect::<Vec<&String>>()
+	}
+
+	/// Gets a sorted list of the currently registered look-up tables in the given namespace (eg
+	/// namespace "my-story" lists "my-story/animal" and "my-story/pet-names/dog"). An empty
+	/// namespace lists all look-up tables.
+	/// # Arguments
+	/// * `prefix`: namespace (ID prefix) to list
+	/// # Returns
+	/// The IDs of the look-up tables in the namespace
+	pub fn list_ids_with_prefix(&self, prefix: &str) -> Vec<&String> {
+		let prefix = prefix.trim_end_matches('/');
+		let mut ids: Vec<&String> = self.list_ids().into_iter()
+			.filter(|id| prefix.is_empty() || in_namespace(id, prefix)).collect();
+		ids.sort();
+		ids
+	}
+
+	/// Changes the ID of a look-up table (eg to match the IDs that a template expects). The
+	/// look-up table keeps its items, default options, and used up items, but it is no longer
+	/// re-loaded from its file (see [reload_file(...)](Interpreter::reload_file)).
+	/// # Arguments
+	/// * `old_id`: current ID of the look-up table
+	/// * `new_id`: new ID of the look-up table
+	/// # Returns
+	/// A `Result` indicating success or failure (eg because there is no look-up table with the
+	/// old ID, or there already is a look-up table with the new ID)
+	pub fn rename_lut(&mut self, old_id: &str, new_id: &str) -> Result<(), ParsingError> {
+		validate_id(new_id)?;
+		if !self.registry.contains_key(old_id) {
+			self.load_lazy(old_id)?;
+		}
+		if !self.registry.contains_key(old_id) {
+			return Err(KeyNotFoundError{key: old_id.into()}.into());
+		}
+		if old_id == new_id {
+			return Ok(());
+		}
+		if self.registry.contains_key(new_id) || self.lazy.may_provide(new_id) {
+			return Err(InvalidIDError::new(format!("Cannot rename '{}' to '{}', because there already is a look-up table with that ID", old_id, new_id)).into());
+		}
+		let lut = self.registry.remove(old_id).unwrap();
+		self.registry.insert(new_id.into(), lut);
+		for source in self.sources.iter_mut() {
+			source.tables.remove(old_id);
+		}
+		self.draws.rename(old_id, new_id);
+		Ok(())
+	}
+
+	/// Moves all look-up tables in a namespace to another namespace (eg namespace "pack/monsters"
+	/// to "monsters" changes "pack/monsters/undead" to "monsters/undead"), just like
+	/// [rename_lut(...)](Interpreter::rename_lut) for each look-up table in the namespace. An
+	/// empty old namespace moves all look-up tables, and an empty new namespace removes the
+	/// namespace from the IDs. Nothing is moved if any of the new IDs are already taken.
+	/// # Arguments
+	/// * `old_prefix`: namespace (ID prefix) to move
+	/// * `new_prefix`: new namespace (ID prefix) of the look-up tables
+	/// # Returns
+	/// The number of look-up tables that were moved, or an error if any of the new IDs are already
+	/// taken by look-up tables outside the old namespace
+	pub fn move_namespace(&mut self, old_prefix: &str, new_prefix: &str) -> Result<usize, ParsingError> {
+		let old_prefix = old_prefix.trim_end_matches('/');
+		let new_prefix = new_prefix.trim_end_matches('/');
+		validate_id(new_prefix)?;
+		let moving = |id: &str| old_prefix.is_empty() || in_namespace(id, old_prefix);
+		// parse the lazily indexed files that provide look-up tables in the namespace
+		let (files, rest): (Vec<LazyFile>, Vec<LazyFile>) = self.lazy.take_all().into_iter()
+			.partition(|file| moving(file.base_id.as_str()) || file.may_provide(old_prefix));
+		rest.into_iter().for_each(|file| self.lazy.add(file));
+		for file in files {
+			self.load_file_namespaced(file.path.as_path(), file.id_prefix.as_str())?;
+		}
+		let renames: Vec<(String, String)> = self.registry.keys().filter(|id| moving(id))
+			.map(|id| {
+				let rest = match old_prefix.is_empty() {
+					true => id.as_str(),
+					false => id[old_prefix.len()..].trim_start_matches('/')
+				};
+				let new_id = match (new_prefix.is_empty(), rest.is_empty()) {
+					(true, _) => String::from(rest),
+					(false, true) => String::from(new_prefix),
+					(false, false) => format!("{}/{}", new_prefix, rest)
+				};
+				(id.clone(), new_id)
+			}).collect();
+		for (old_id, new_id) in &renames {
+			if new_id.is_empty() || (self.registry.contains_key(new_id) && !moving(new_id)) || self.lazy.may_provide(new_id) {
+				return Err(InvalidIDError::new(format!("Cannot move '{}' to '{}', because there already is a look-up table with that ID", old_id, new_id)).into());
+			}
+		}
+		let tables: Vec<(String, Arc<LookUpTable>)> = renames.iter()
+			.map(|(old_id, new_id)| (new_id.clone(), self.registry.remove(old_id).unwrap())).collect();
+		self.registry.extend(tables);
+		for (old_id, new_id) in &renames {
+			for source in self.sources.iter_mut() {
+				source.tables.remove(old_id);
+			}
+			self.draws.rename(old_id, new_id);
+		}
+		Ok(renames.iter().filter(|(id, _)| !is_rows_table(id)).count())
+	}
+
+	/// Removes a look-up table from the registry, such that it can no longer be used for text
+	/// substitution (until it is loaded again). Its depletion mode and used up items (see
+	/// [set_depletion(...)](Interpreter::set_depletion)) are forgotten too.
+	/// # Arguments
+	/// * `id`: look-up table ID to remove
+	/// # Returns
+	/// The removed look-up table, or `None` if no look-up table was registered with that ID
+	pub fn remove_lut(&mut self, id: &str) -> Option<LookUpTable> {
+		self.lazy.retain(|file| file.base_id != id);
+		for source in self.sources.iter_mut() {
+			source.tables.remove(id);
+		}
+		self.draws.forget(id);
+		self.registry.remove(id).map(Arc::unwrap_or_clone)
+	}
+
+	/// Removes all look-up tables in the given namespace from the registry (eg namespace
+	/// "my-story" removes "my-story/animal" and "my-story/pet-names/dog"), such that a table pack
+	/// can be swapped for another one without creating a new interpreter. Their depletion modes
+	/// and used up items are forgotten too.
+	/// # Arguments
+	/// * `prefix`: namespace (ID prefix) to remove
+	/// # Returns
+	/// The number of look-up tables that were removed
+	pub fn remove_namespace(&mut self, prefix: &str) -> usize {
+		let prefix = prefix.trim_end_matches('/');
+		self.lazy.retain(|file| !in_namespace(file.base_id.as_str(), prefix));
+		for source in self.sources.iter_mut() {
+			source.tables.retain(|id, _| !in_namespace(id, prefix));
+		}
+		let count = self.list_ids().len();
+		let removed: Vec<String> = self.registry.keys().filter(|id| in_namespace(id, prefix)).cloned().collect();
+		for id in &removed {
+			self.draws.forget(id);
+			self.registry.remove(id);
+		}
+		count - self.list_ids().len()
+	}
+
+	/// Removes all look-up tables from the registry. All other settings (such as the evaluation
+	/// options and the random number generator) are kept.
+	pub fn clear(&mut self) {
+		self.lazy.retain(|_| false);
+		self.draws.reset_all();
+		self.extensions.clear();
+		self.sources.clear();
+		self.registry.clear();
+	}
+
+	/// Sets whether the items that are drawn from a look-up table are put back (the default) or
+	/// used up, like cards drawn from a deck. Used up items stay used up across calls to
+	/// [eval(...)](Interpreter::eval), until they are put back with
+	/// [reset_depleted(...)](Interpreter::reset_depleted). A single substitution can also deplete a
+	/// look-up table with the `method: deplete` (or `method: deplete-reset`) option.
+	/// # Arguments
+	/// * `id`: look-up table ID
+	/// * `mode`: the new depletion mode of the look-up table
+	pub fn set_depletion(&mut self, id: &str, mode: Depletion) {
+		self.draws.set_mode(id, mode);
+	}
+
+	/// Gets the depletion mode of a look-up table (see
+	/// [set_depletion(...)](Interpreter::set_depletion))
+	/// # Arguments
+	/// * `id`: look-up table ID
+	/// # Returns
+	/// The depletion mode of the look-up table
+	pub fn get_depletion(&self, id: &str) -> Depletion {
+		self.draws.get_mode(id)
+	}
+
+	/// Puts all used up items of a depleting look-up table back into the table (see
+	/// [set_depletion(...)](Interpreter::set_depletion))
+	/// # Arguments
+	/// * `id`: look-up table ID
+	/// # Returns
+	/// `true` if any items of the look-up table were used up
+	pub fn reset_depleted(&mut self, id: &str) -> bool {
+		self.draws.reset(id)
+	}
+
+	/// Sets whether duplicate items (items with the same text or stable key) of a look-up table
+	/// are merged into one item when look-up tables are loaded (default is `Dedup::Keep`, which
+	/// keeps all duplicates). This applies to all following loads, and items are also merged
+	/// across several loads into the same look-up table. Merged items are recorded in a report
+	/// (see [take_dedup_report()](Interpreter::take_dedup_report)).
+	/// # Arguments
+	/// * `mode`: how duplicate items are merged
+	pub fn set_dedup(&mut self, mode: Dedup) {
+		self.dedup = mode;
+	}
+
+	/// Gets how duplicate items are merged when look-up tables are loaded (see
+	/// [set_dedup(...)](Interpreter::set_dedup))
+	/// # Returns
+	/// The deduplication mode
+	pub fn get_dedup(&self) -> Dedup {
+		self.dedup
+	}
+
+	/// Takes the report of the duplicate items that were merged while loading look-up tables
+	/// (see [set_dedup(...)](Interpreter::set_dedup)), leaving the report empty
+	/// # Returns
+	/// The look-up table ID and merged item of each merge, in the order they happened
+	pub fn take_dedup_report(&mut self) -> Vec<(String, MergedDuplicate)> {
+		std::mem::take(&mut self.dedup_report)
+	}
+
+	/// Records that a look-up table was loaded, such that its duplicate items are merged by
+	/// [dedup_loaded()](Interpreter::dedup_loaded)
+	fn mark_loaded(&mut self, id: &str) {
+		if self.dedup != Dedup::Keep && !self.dedup_pending.contains(id) {
+			self.dedup_pending.insert(id.into());
+		}
+	}
+
+	/// Merges the duplicate items of the look-up tables that were loaded since the last call
+	fn dedup_loaded(&mut self) {
+		for id in std::mem::take(&mut self.dedup_pending) {
+			if let Some(lut) = self.registry.get_mut(&id) {
+				let merged = Arc::make_mut(lut).dedup(self.dedup);
+				self.dedup_report.extend(merged.into_iter().map(|item| (id.clone(), item)));
+			}
+		}
+	}
+
+	/// Puts all used up items of all depleting look-up tables back (see
+	/// [set_depletion(...)](Interpreter::set_depletion)). The depletion modes are kept.
+	pub fn reset_all_depleted(&mut self) {
+		self.draws.reset_all();
+	}
+
+	/// Gets the depletion modes of the look-up tables and the items that are used up, which can
+	/// be saved (eg as JSON with serde) and restored later with
+	/// [set_draw_state(...)](Interpreter::set_draw_state), such that repeated sessions do not
+	/// repeat items (eg plot hooks that were already used in a campaign)
+	/// # Returns
+	/// A copy of the current draw state
+	pub fn get_draw_state(&self) -> DrawState {
+		self.draws.clone()
+	}
+
+	/// Replaces the depletion modes of the look-up tables and the items that are used up with a
+	/// previously saved draw state (see [get_draw_state()](Interpreter::get_draw_state))
+	/// # Arguments
+	/// * `state`: the draw state to restore
+	pub fn set_draw_state(&mut self, state: DrawState) {
+		self.draws = state;
+	}
+
+	/// Sets a persistent reference, which can be used by all following evaluations just like a
+	/// reference that was created in the evaluated text (eg `${@hero}`). Substitution tokens can
+	/// also create persistent references with the `persist` option (eg
+	/// `${{id: name, ref: hero, persist: true}}`).
+	/// # Arguments
+	/// * `ref_id`: reference ID (without the `@`)
+	/// * `text`: text of the reference
+	/// # Returns
+	/// An error if the reference ID is not valid
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.set_persistent_ref("hero", "Alice").unwrap();
+	/// assert_eq!(interpreter.eval("${@hero} wins!").unwrap(), "Alice wins!");
+	/// ```
+	pub fn set_persistent_ref(&mut self, ref_id: &str, text: &str) -> Result<(), ParsingError> {
+		validate_ref(ref_id)?;
+		self.persistent_refs.insert(String::from(ref_id), RefValue{text: String::from(text), key: None, fields: None});
+		Ok(())
+	}
+
+	/// Gets the text of a persistent reference (see
+	/// [set_persistent_ref(...)](Interpreter::set_persistent_ref))
+	/// # Arguments
+	/// * `ref_id`: reference ID (without the `@`)
+	/// # Returns
+	/// The text of the reference, or `None` if there is no persistent reference with that ID
+	pub fn get_persistent_ref(&self, ref_id: &str) -> Option<&str> {
+		self.persistent_refs.get(ref_id).map(|value| value.text.as_str())
+	}
+
+	/// Lists the IDs of all persistent references (see
+	/// [set_persistent_ref(...)](Interpreter::set_persistent_ref)), in sorted order
+	pub fn list_persistent_refs(&self) -> Vec<&String> {
+		self.persistent_refs.keys().collect()
+	}
+
+	/// Removes a persistent reference (see
+	/// [set_persistent_ref(...)](Interpreter::set_persistent_ref))
+	/// # Arguments
+	/// * `ref_id`: reference ID (without the `@`)
+	/// # Returns
+	/// `true` if the persistent reference existed
+	pub fn remove_persistent_ref(&mut self, ref_id: &str) -> bool {
+		self.persistent_refs.remove(ref_id).is_some()
+	}
+
+	/// Removes all persistent references (see
+	/// [set_persistent_ref(...)](Interpreter::set_persistent_ref))
+	pub fn clear_persistent_refs(&mut self) {
+		self.persistent_refs.clear();
+	}
+
+	/// Sets a variable of the host application (eg the name of the current campaign), which all
+	/// following evaluations can substitute with a `var:` token (eg `${var:campaign}`). Unlike
+	/// references, variables cannot be changed by the evaluated text, so they are a good fit for
+	/// constants that would otherwise need a look-up table with a single item. Like a drawn
+	/// item, the text of a variable may contain substitution tokens of its own.
+	/// # Arguments
+	/// * `name`: variable name (without the `var:`)
+	/// * `text`: text of the variable
+	/// # Returns
+	/// An error if the variable name is not valid (the same characters as a reference ID are
+	/// allowed)
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.set_var("campaign", "Ironreach").unwrap();
+	/// assert_eq!(interpreter.eval("Welcome to ${{id: 'var:campaign', case: upper}}!").unwrap(), "Welcome to IRONREACH!");
+	/// ```
+	pub fn set_var(&mut self, name: &str, text: &str) -> Result<(), ParsingError> {
+		validate_ref(name)?;
+		self.vars.insert(String::from(name), String::from(text));
+		Ok(())
+	}
+
+	/// Gets the text of a variable (see [set_var(...)](Interpreter::set_var))
+	/// # Arguments
+	/// * `name`: variable name (without the `var:`)
+	/// # Returns
+	/// The text of the variable, or `None` if there is no variable with that name
+	pub fn get_var(&self, name: &str) -> Option<&str> {
+		self.vars.get(name).map(|text| text.as_str())
+	}
+
+	/// Lists the names of all variables (see [set_var(...)](Interpreter::set_var)), in sorted
+	/// order
+	pub fn list_vars(&self) -> Vec<&String> {
+		self.vars.keys().collect()
+	}
+
+	/// Removes a variable (see [set_var(...)](Interpreter::set_var))
+	/// # Arguments
+	/// * `name`: variable name (without the `var:`)
+	/// # Returns
+	/// `true` if the variable existed
+	pub fn remove_var(&mut self, name: &str) -> bool {
+		self.vars.remove(name).is_some()
+	}
+
+	/// Removes all variables (see [set_var(...)](Interpreter::set_var))
+	pub fn clear_vars(&mut self) {
+		self.vars.clear();
+	}
+
+	/// Checks the weights of all registered look-up tables for common authoring mistakes, such as
+	/// mixing very large and very small weights, items with a weight of zero, or tables where one
+	/// item holds more than 99% of the probability (see
+	/// [LookUpTable::audit_weights(...)](LookUpTable::audit_weights)).
+	/// # Arguments
+	/// * `max_ratio`: Largest acceptable ratio between the largest and smallest non-zero weight in
+	///   a table (eg 1000)
+	/// # Returns
+	/// A list of (look-up table ID, issue) pairs, sorted by ID
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_str("treasure", "{gold: 1, silver: 100000}", "yaml").unwrap();
+	/// for (id, issue) in interpreter.audit_weights(1000.) {
+	///     println!("{}: {}", id, issue);
+	/// }
+	/// ```
+	pub fn audit_weights(&self, max_ratio: f64) -> Vec<(String, WeightIssue)> {
+		let mut ids: Vec<&String> = self.registry.keys().filter(|id| !is_rows_table(id)).collect();
+		ids.sort();
+		let mut issues: Vec<(String, WeightIssue)> = Vec::new();
+		for id in ids {
+			for issue in self.registry[id].audit_weights(max_ratio) {
+				issues.push((id.clone(), issue));
+			}
+		}
+		issues
+	}
+
+	/// Calculates statistics of all registered look-up tables, grouped by namespace, which is the
+	/// first part of the look-up table ID (eg "monsters" for "monsters/undead"). Lazily indexed
+	/// files that were not parsed yet are not included (see
+	/// [set_lazy_loading(...)](Interpreter::set_lazy_loading)).
+	/// # Returns
+	/// The statistics of each namespace (see [NamespaceStats]), by namespace
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_str("monsters", "{undead: [ghost, zombie], beasts: [wolf]}", "yaml").unwrap();
+	/// let stats = interpreter.registry_stats();
+	/// assert_eq!(stats["monsters"].tables, 2);
+	/// assert_eq!(stats["monsters"].items, 3);
+	/// ```
+	pub fn registry_stats(&self) -> BTreeMap<String, NamespaceStats> {
+		let mut stats: BTreeMap<String, NamespaceStats> = BTreeMap::new();
+		for (id, lut) in self.registry.iter().filter(|(id, _)| !is_rows_table(id)) {
+			let namespace = id.split('/').next().unwrap_or(id.as_str());
+			stats.entry(String::from(namespace)).or_default().add(&lut.stats());
+		}
+		stats
+	}
+
+	/// Scans the given text for substitution tokens (without drawing from any look-up tables)
+	/// and lists every look-up table ID and reference ID that the text requires but which is not
+	/// available. Look-up tables used by the text are scanned as well. Missing reference IDs are
+	/// listed with a `@` prefix. Look-up table IDs that depend on references or dice rolls (eg
+	/// `pet-names/$pet`) cannot be checked without evaluating the text and are not listed.
+	/// # Arguments
+	/// * `text`: The target text to check.
+	/// # Returns
+	/// A sorted list of missing reference IDs followed by missing look-up table IDs and missing
+	/// template functions (with a `fn:` prefix), or an error if a substitution token could not be
+	/// parsed
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_file("animal.txt").expect("Failed to load file");
+	/// let missing = interpreter.list_missing("My ${animal} ate my ${vehicle}. ${@pet}!").unwrap();
+	/// assert_eq!(missing, vec!["@pet", "vehicle"]);
+	/// ```
+	pub fn list_missing<T>(&self, text: T) -> Result<Vec<String>, ParsingError> where T: Into<String> {
+		let text: String = text.into();
+		match FrontMatter::split(text.as_str()) {
+			Ok((Some(front), body)) => self.find_missing(body, &front),
+			_ => self.find_missing(text.as_str(), &FrontMatter::default())
+		}
+	}
+
+	/// Checks a template (with optional front matter, see [FrontMatter]) against the currently
+	/// registered look-up tables. Unlike [eval(...)](Interpreter::eval), which treats a malformed
+	/// front matter block as plain text, this method reports it as an error.
+	/// # Arguments
+	/// * `template`: The template to check.
+	/// # Returns
+	/// A list of missing reference IDs (with a `@` prefix) followed by missing look-up table IDs
+	/// (both those listed as `requires` in the front matter and those used by the template) and
+	/// missing template functions (with a `fn:` prefix), or an error if the front matter or a
+	/// substitution token could not be parsed. An empty list means that the template can be
+	/// evaluated.
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_file("animal.txt").expect("Failed to load file");
+	/// let template = "---\nrequires: [animal, pet-names]\nrefs: {owner: Alice}\n---\n${@owner} has a ${animal}.";
+	/// assert_eq!(interpreter.check_template(template).unwrap(), vec!["pet-names"]);
+	/// ```
+	pub fn check_template(&self, template: &str) -> Result<Vec<String>, ParsingError> {
+		let (front, body) = FrontMatter::split(template)?;
+		self.find_missing(body, &front.unwrap_or_default())
+	}
+
+	/// Checks a template (with optional front matter, see [FrontMatter]) without drawing from
+	/// any look-up tables, such as to catch typos before they happen to be rolled. Unlike
+	/// [check_template(...)](Interpreter::check_template), this method also reports which look-up
+	/// tables and references the template uses, and the substitution options that are not known
+	/// (which are otherwise silently ignored, eg `sepp` instead of `sep`).
+	/// # Arguments
+	/// * `template`: The template to check.
+	/// # Returns
+	/// The result of the check (see [Validation]), or an error if the front matter or a
+	/// substitution token could not be parsed
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_file("animal.txt").expect("Failed to load file");
+	/// let validation = interpreter.validate("${{id: animal, count: 2, sepp: \", \"}} and ${vehicle}").unwrap();
+	/// assert!(!validation.is_ok());
+	/// assert_eq!(validation.missing, vec!["vehicle"]);
+	/// assert_eq!(validation.unknown_options[0].1, "sepp");
+	/// ```
+	pub fn validate(&self, template: &str) -> Result<Validation, ParsingError> {
+		let (front, body) = FrontMatter::split(template)?;
+		self.validate_body(body, &front.unwrap_or_default())
+	}
+
+	/// Checks a template (with optional front matter, see [FrontMatter]) for possible problems
+	/// that do not stop the evaluation but are probably mistakes (see [LintKind]), such as
+	/// unknown substitution options, options in single braces, references that are used before
+	/// they are defined, and `last-sep` options that are never used. Unlike
+	/// [validate(...)](Interpreter::validate), this method never fails, so that it can report
+	/// every problem at once (eg to gate changes to a table pack in continuous integration).
+	/// References that are passed in by the host application (see
+	/// [eval_with_refs(...)](Interpreter::eval_with_refs)) are not known to this method, so
+	/// pass them in the front matter to avoid reports about them.
+	/// # Arguments
+	/// * `template`: The template to check.
+	/// # Returns
+	/// The possible problems, in the order of the tokens of the template
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let interpreter = twas::Interpreter::new();
+	/// let lints = interpreter.lint("${@pet} is ${{id: animal, ref: pet, last-sep: ' and '}}");
+	/// assert_eq!(lints[0].kind, twas::LintKind::RefBeforeDefinition);
+	/// assert_eq!(lints[1].kind, twas::LintKind::UnreachableLastSep);
+	/// ```
+	pub fn lint(&self, template: &str) -> Vec<Lint> {
+		let mut refs_defined: BTreeSet<String> = self.persistent_refs.keys().cloned().collect();
+		let mut lints: Vec<Lint> = Vec::new();
+		let body = match FrontMatter::split(template) {
+			Ok((front, body)) => {
+				refs_defined.extend(front.unwrap_or_default().refs.into_keys());
+				body
+			},
+			Err(e) => {
+				lints.push(Lint{kind: LintKind::InvalidFrontMatter, token: String::from("front matter"),
+					message: format!("is not valid and is treated as text: {}", e)});
+				template
+			}
+		};
+		lints.extend(lint::lint_text(body, Some(refs_defined), self.options.default_sep.is_some()));
+		lints
+	}
+
+	/// Checks the items of all loaded look-up tables for possible problems (see
+	/// [lint(...)](Interpreter::lint)), except for references that are used before they are
+	/// defined, since items are evaluated within the references of the template
+	/// # Returns
+	/// The possible problems, with the ID of the look-up table of each, sorted by look-up table ID
+	pub fn lint_tables(&self) -> Vec<(String, Lint)> {
+		let default_sep = self.options.default_sep.is_some();
+		let mut ids: Vec<&String> = self.registry.keys().filter(|id| !is_rows_table(id)).collect();
+		ids.sort();
+		ids.into_iter().flat_map(|id| self.registry[id].items().iter()
+			.flat_map(move |item| lint::lint_text(item.get_text(), None, default_sep))
+			.map(move |lint| (id.clone(), lint)))
+			.collect()
+	}
+
+	/// Builds a graph of which look-up tables (and references) each loaded look-up table uses,
+	/// by scanning the items of every look-up table for substitution tokens and links (without
+	/// drawing from any look-up tables). This makes large table packs easier to maintain, and
+	/// [DependencyGraph::cycles()] finds the look-up tables that use each other in a cycle, which
+	/// otherwise only show up as recursion errors when the evaluation happens to follow them.
+	/// Look-up tables that have not been parsed yet (see
+	/// [set_lazy_loading(...)](Interpreter::set_lazy_loading)) are not included.
+	/// # Returns
+	/// The dependency graph, or an error if a substitution token could not be parsed
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_lines("list", vec!["${item}", "${item} and ${list}"]).unwrap();
+	/// interpreter.load_lines("item", vec!["a sword", "a shield"]).unwrap();
+	/// let graph = interpreter.dependency_graph().unwrap();
+	/// assert!(graph.dependencies("list").unwrap().contains("item"));
+	/// assert_eq!(graph.cycles(), vec![vec!["list"]]);
+	/// ```
+	pub fn dependency_graph(&self) -> Result<DependencyGraph, ParsingError> {
+		self.dependency_graph_with_templates(std::iter::empty::<(&str, &str)>())
+	}
+
+	/// Same as [dependency_graph()](Interpreter::dependency_graph), but also adds the given
+	/// templates (with optional front matter, see [FrontMatter]) to the graph, such as to see
+	/// which look-up tables are used by which templates (and which ones are not used at all)
+	/// # Arguments
+	/// * `templates`: (name, template) pairs, where the name is the ID of the template in the
+	///   graph (which should not be the ID of a look-up table)
+	/// # Returns
+	/// The dependency graph, or an error if the front matter or a substitution token could not be
+	/// parsed
+	pub fn dependency_graph_with_templates<'t>(&self, templates: impl IntoIterator<Item=(&'t str, &'t str)>) -> Result<DependencyGraph, ParsingError> {
+		let mut graph = DependencyGraph::default();
+		for (id, lut) in &self.registry {
+			let mut tables: BTreeSet<String> = BTreeSet::new();
+			let mut refs: BTreeSet<String> = BTreeSet::new();
+			for item in lut.items() {
+				match item.get_table_link() {
+					Some(link) => {tables.insert(String::from(link));},
+					None => {
+						let scan = scan_tokens(item.get_text(), &self.registry, false)?;
+						tables.extend(scan.ids);
+						refs.extend(scan.refs_used.difference(&scan.refs_defined).cloned());
+					}
+				}
+			}
+			graph.add_node(id.clone(), tables, refs);
+		}
+		for (name, template) in templates {
+			let (front, body) = FrontMatter::split(template)?;
+			let front = front.unwrap_or_default();
+			let scan = scan_tokens(body, &self.registry, false)?;
+			let mut tables = scan.ids;
+			tables.extend(front.requires);
+			let refs = scan.refs_used.into_iter()
+				.filter(|ref_id| !scan.refs_defined.contains(ref_id) && !front.refs.contains_key(ref_id)).collect();
+			graph.add_node(String::from(name), tables, refs);
+		}
+		Ok(graph)
+	}
+
+	/// Lists missing references and look-up tables for [list_missing(...)](Interpreter::list_missing)
+	/// and [check_template(...)](Interpreter::check_template)
+	fn find_missing(&self, body: &str, front: &FrontMatter) -> Result<Vec<String>, ParsingError> {
+		self.validate_body(body, front).map(|validation| validation.missing)
+	}
+
+	/// Checks a template (without front matter) for [validate(...)](Interpreter::validate)
+	fn validate_body(&self, body: &str, front: &FrontMatter) -> Result<Validation, ParsingError> {
+		let mut scan = scan_tokens(body, &self.registry, true)?;
+		scan.refs_defined.extend(front.refs.keys().cloned());
+		scan.refs_defined.extend(self.persistent_refs.keys().cloned());
+		scan.ids.extend(front.requires.iter().cloned());
+		let mut missing: Vec<String> = Vec::new();
+		for ref_id in scan.refs_used.difference(&scan.refs_defined) {
+			missing.push(format!("@{}", ref_id));
+		}
+		for id in &scan.ids {
+			if !self.registry.contains_key(id) && !self.lazy.may_provide(id) && !self.lazy.can_resolve(id) {
+				missing.push(id.clone());
+			}
+		}
+		for name in &scan.functions {
+			if self.functions.get(name).is_none() {
+				missing.push(format!("{}{}", FN_PREFIX, name));
+			}
+		}
+		for name in &scan.vars {
+			if !self.vars.contains_key(name) {
+				missing.push(format!("{}{}", VAR_PREFIX, name));
+			}
+		}
+		Ok(Validation{table_ids: scan.ids, refs_defined: scan.refs_defined, refs_used: scan.refs_used,
+			unknown_options: scan.unknown_options, missing})
+	}
+}
+
+impl Interpreter<rand::rngs::StdRng> {
+
+	/// Creates a builder for an interpreter, which collects the configuration (eg the recursion
+	/// limit and the evaluation options) in one place instead of calling the setters one by one
+	/// (see [InterpreterBuilder])
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::builder()
+	///     .with_recursion_limit(50)
+	///     .with_lenient_missing(true)
+	///     .with_loose_ids(true)
+	///     .with_seed(42)
+	///     .build().unwrap();
+	/// assert_eq!(interpreter.eval("I have a ${animal}.").unwrap(), "I have a ⟦missing: animal⟧.");
+	/// ```
+	pub fn builder() -> InterpreterBuilder {
+		InterpreterBuilder::new()
+	}
+
+	/// Creates a new interpreter
+	pub fn new() -> Interpreter<rand::rngs::StdRng> {
+		Interpreter::from_rng(new_simple_rng())
+	}
+
+	/// Creates a new interpreter with a seeded random number generator, such that identical usage
+	/// with the same seed will result in identical results
 	pub fn from_seed(seed: u64) -> Interpreter<rand::rngs::StdRng> {
 		Interpreter::from_rng(simple_rng(seed))
 	}
-
+
+	/// Re-seeds the random number generator (eg with the recommended seed from a template's
+	/// front matter), such that the following results are identical to those of a new
+	/// interpreter created with [from_seed(...)](Interpreter::from_seed)
+	/// # Arguments
+	/// * `seed`: The new seed.
+	pub fn reseed(&mut self, seed: u64) {
+		self.rng = simple_rng(seed);
+	}
+
+	/// Saves the look-up tables, the persistent references (see
+	/// [set_persistent_ref(...)](Interpreter::set_persistent_ref)), the draw state (see
+	/// [get_draw_state()](Interpreter::get_draw_state)), and the state of the random number
+	/// generator as JSON, such that a long-running generation session can be continued later with
+	/// [load_state(...)](Interpreter::load_state), even after the program was restarted. To make
+	/// this possible, the random number generator is re-seeded with a new seed (drawn from the
+	/// current random number generator), which is saved, such that this interpreter and the
+	/// restored interpreter produce identical results from then on. Lazily indexed files that were
+	/// not parsed yet (see [set_lazy_loading(...)](Interpreter::set_lazy_loading)) are parsed
+	/// first, so that all of their look-up tables are saved.
+	/// # Arguments
+	/// * `writer`: where to write the saved state (eg a file)
+	/// # Returns
+	/// An error if a lazily indexed file could not be parsed or the state could not be written
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::from_seed(42);
+	/// interpreter.load_file("animal.txt").expect("Failed to load file");
+	/// interpreter.eval("${{id: animal, ref: pet, persist: true}}").unwrap();
+	/// let mut saved: Vec<u8> = Vec::new();
+	/// interpreter.save_state(&mut saved).unwrap();
+	/// let mut restored = twas::Interpreter::new();
+	/// restored.load_state(saved.as_slice()).unwrap();
+	/// assert_eq!(restored.eval("${@pet} and ${animal}").unwrap(),
+	///     interpreter.eval("${@pet} and ${animal}").unwrap());
+	/// ```
+	pub fn save_state<W: Write>(&mut self, writer: W) -> Result<(), ParsingError> {
+		for file in self.lazy.take_all() {
+			self.load_file_namespaced(file.path.as_path(), file.id_prefix.as_str())?;
+		}
+		let seed: u64 = self.rng.gen();
+		self.reseed(seed);
+		let (rows, tables) = self.registry.iter().map(|(id, lut)| (id.clone(), (**lut).clone()))
+			.partition(|(id, _)| is_rows_table(id));
+		let state = SavedState{
+			version: STATE_VERSION,
+			seed,
+			tables,
+			rows,
+			refs: self.persistent_refs.clone(),
+			draws: self.draws.clone()
+		};
+		serde_json::to_writer(writer, &state)?;
+		Ok(())
+	}
+
+	/// Restores a state that was saved with [save_state(...)](Interpreter::save_state), replacing
+	/// all look-up tables, persistent references, and the draw state, and re-seeding the random
+	/// number generator. All other settings (such as the evaluation options) are kept. Look-up
+	/// table files that were loaded before are no longer re-loaded when they change.
+	/// # Arguments
+	/// * `reader`: where to read the saved state from (eg a file)
+	/// # Returns
+	/// An error if the saved state could not be read or was saved by a newer version of twas
+	pub fn load_state<I: Read>(&mut self, reader: I) -> Result<(), ParsingError> {
+		let state: SavedState = serde_json::from_reader(reader)?;
+		if state.version > STATE_VERSION {
+			return Err(ParseError{
+				msg: Some(format!("Saved state version {} is not supported (the newest supported version is {})", state.version, STATE_VERSION)),
+				line: None, col: None
+			}.into());
+		}
+		self.clear();
+		self.registry = state.tables.into_iter().chain(state.rows)
+			.map(|(id, lut)| (id, Arc::new(lut))).collect();
+		self.persistent_refs = state.refs;
+		self.draws = state.draws;
+		self.reseed(state.seed);
+		Ok(())
+	}
+
+	/// Writes all loaded look-up tables as a single YAML or JSON document, such as to consolidate
+	/// several table packs into one file or to compare the loaded tables of two versions. The
+	/// document maps each full look-up table ID (eg `monsters/undead`) to its items, sorted by
+	/// ID, and can be loaded again with [load_str(...)](Interpreter::load_str) (with an empty
+	/// ID) or as a file. Items keep their weights, stable keys, weight expressions, record
+	/// fields, requirements, `max-per-eval` limits, and number ranges, and tiered look-up tables
+	/// are written as mappings of value ranges (eg `"3-5": feeble`). Default substitution options
+	/// of look-up tables (`$options`) are not written, and extended look-up tables (`$extends`) are
+	/// written with all of their inherited items. Lazily indexed files that were not parsed
+	/// yet (see [set_lazy_loading(...)](Interpreter::set_lazy_loading)) are parsed first.
+	/// # Arguments
+	/// * `writer`: where to write the document (eg a file)
+	/// * `format`: [Format::Yaml] or [Format::Json]
+	/// # Returns
+	/// An error if the format is not YAML or JSON, a lazily indexed file could not be parsed, or
+	/// the document could not be written
+	///
+	/// # Example
+	/// ```rust
+	/// use twas;
+	/// let mut interpreter = twas::Interpreter::new();
+	/// interpreter.load_file("animal.txt").expect("Failed to load file");
+	/// let mut exported: Vec<u8> = Vec::new();
+	/// interpreter.export_registry(&mut exported, twas::Format::Yaml).unwrap();
+	/// let mut copy = twas::Interpreter::new();
+	/// copy.load_str("", String::from_utf8(exported).unwrap(), "yaml").unwrap();
+	/// assert_eq!(copy.get_lut("animal").unwrap().len(), interpreter.get_lut("animal").unwrap().len());
+	/// ```
+	pub fn export_registry<W: Write>(&mut self, writer: W, format: Format) -> Result<(), ParsingError> {
+		if format != Format::Yaml && format != Format::Json {
+			return Err(ParseError{
+				msg: Some(format!("Cannot export look-up tables as {} (only yaml and json are supported)", format)),
+				line: None, col: None
+			}.into());
+		}
+		for file in self.lazy.take_all() {
+			self.load_file_namespaced(file.path.as_path(), file.id_prefix.as_str())?;
+		}
+		let mut ids: Vec<&String> = self.registry.keys().filter(|id| !is_rows_table(id)).collect();
+		ids.sort();
+		let mut document = serde_yaml::Mapping::new();
+		for id in ids {
+			document.insert(serde_yaml::Value::String(id.clone()), export_table(&self.registry[id]));
+		}
+		match format {
+			Format::Json => serde_json::to_writer_pretty(writer, &document)?,
+			_ => serde_yaml::to_writer(writer, &document)?
+		}
+		Ok(())
+	}
+
+}
+
+/// A stored reference: the generated text plus the stable key and record fields of the drawn item
+/// (if exactly one item was drawn)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RefValue {
+	/// Generated text
+	text: String,
+	/// Stable key of the drawn item, used instead of the text when substituting into IDs
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	key: Option<String>,
+	/// Named fields of the drawn record item, accessed like `${@ref.field}`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	fields: Option<BTreeMap<String, String>>
+}
+
+/// Holds everything that is needed while evaluating a string for text substitution
+struct EvalContext<'a, G: Rng> {
+	/// Registry of look-up tables
+	reg: &'a HashMap<String, Arc<LookUpTable>>,
+	/// Random number generator for drawing from look-up tables and rolling dice
+	rng: &'a mut G,
+	/// Maximum recursion depth
+	recursion_limit: usize,
+	/// Evaluation options
+	options: &'a EvalOptions,
+	/// Warnings collected during evaluation (eg missing keys in lenient mode)
+	warnings: &'a mut Vec<String>,
+	/// Look-up table files that are parsed when they are first needed
+	lazy: &'a LazyRegistry,
+	/// Depletion modes of look-up tables and the items that are used up
+	draws: &'a mut DrawState,
+	/// Persistent references, which are kept between evaluations
+	persistent_refs: &'a mut BTreeMap<String, RefValue>,
+	/// Variables set by the host application
+	vars: &'a BTreeMap<String, String>,
+	/// Number of times that each item (by text or stable key) was drawn in this evaluation, by
+	/// look-up table ID, for the `max-per-eval` limits
+	appearances: HashMap<String, HashMap<String, usize>>,
+	/// Items that were drawn in this evaluation and the spans of the text, or `None` if they are
+	/// not recorded
+	recording: Option<Recording>,
+	/// Template functions
+	functions: &'a FunctionRegistry,
+	/// Observer of the evaluation (if any)
+	hook: Option<&'a mut (dyn EvalHook + 'static)>,
+	/// Script of the items to draw instead of drawing at random, or `None` to draw at random
+	/// (see [Interpreter::analyze(...)](Interpreter::analyze))
+	choices: Option<&'a mut ChoiceScript>,
+	/// Recursion depth of the substitution token that is being substituted, or `None` at the top
+	/// level of the evaluation (the tokens of the evaluated text are at depth 0, the tokens in
+	/// the text that they produce at depth 1, and so on)
+	depth: Option<usize>,
+	/// Number of substitutions, draws, and the deepest recursion depth of this evaluation
+	stats: EvalStats,
+	/// Log of the random decisions that are recorded or played back, or `None` (see
+	/// [Interpreter::eval_recorded(...)](Interpreter::eval_recorded))
+	tape: Option<&'a mut DrawTape>,
+	/// Start time and time limit of the evaluation, or `None` if there is no time limit
+	deadline: Option<(Instant, Duration)>,
+	/// Look-up tables with conditional weights that were resolved in this evaluation, by look-up
+	/// table ID, with the weights that they were resolved to (so that a table is only copied
+	/// again when the references change its weights)
+	resolved: HashMap<String, (Vec<f64>, Arc<LookUpTable>)>
+}
+
+/// The settings of one evaluation (see [Interpreter::eval_with(...)](Interpreter::eval_with))
+#[derive(Default)]
+struct EvalCall<'t> {
+	/// References provided by the host application (see
+	/// [eval_with_refs(...)](Interpreter::eval_with_refs))
+	refs: HashMap<String, String>,
+	/// If true, the drawn items and the spans of the text are recorded (see
+	/// [eval_detailed(...)](Interpreter::eval_detailed))
+	record: bool,
+	/// Seed of a temporary random number generator to use instead of the interpreter's (see
+	/// [eval_seeded(...)](Interpreter::eval_seeded)), or `None`
+	seed: Option<u64>,
+	/// Recursion limit to use instead of the interpreter's (see
+	/// [eval_with_limit(...)](Interpreter::eval_with_limit)), or `None`
+	recursion_limit: Option<usize>,
+	/// Log of the random decisions to record or play back (see
+	/// [eval_recorded(...)](Interpreter::eval_recorded)), or `None`
+	tape: Option<&'t mut DrawTape>,
+	/// Time limit of the evaluation (see [eval_with_deadline(...)](Interpreter::eval_with_deadline)),
+	/// or `None`
+	deadline: Option<Duration>
+}
+
+/// The text to evaluate
+enum Source<'t> {
+	/// A text (which may start with front matter)
+	Text(String),
+	/// A compiled template
+	Compiled(&'t Template)
+}
+
+/// Applies the default references from the front matter of a template (if any, and if front
+/// matter is enabled by the evaluation options) that are not already defined
+/// # Returns
+/// The template without its front matter
+fn apply_front_matter_refs<'t>(text: &'t str, refs: &mut HashMap<String, RefValue>, options: &EvalOptions) -> &'t str {
+	if !options.front_matter {
+		return text;
+	}
+	match FrontMatter::split(text) {
+		Ok((Some(front), body)) => {
+			for (ref_id, value) in front.refs {
+				refs.entry(ref_id).or_insert(RefValue{text: value, key: None, fields: None});
+			}
+			body
+		},
+		_ => text
+	}
+}
+
+/// This is where all the action happens when evaluating a string for text substitution
+fn do_eval<G: Rng>(text: String, start_from: usize, ctx: &mut EvalContext<G>, recursion: usize) -> Result<String, ParsingError> {
+	do_eval_scoped(text, start_from, ctx, HashMap::new(), recursion)
+}
+
+/// Same as [do_eval(...)](do_eval), but starting with the provided references (eg a copy of the
+/// parent's references when evaluating in a child reference scope)
+fn do_eval_scoped<G: Rng>(text: String, start_from: usize, ctx: &mut EvalContext<G>, ref_map: HashMap<String, RefValue>, recursion: usize) -> Result<String, ParsingError> {
+	let mut ref_map = ref_map;
+	do_eval_refs(text, start_from, ctx, &mut ref_map, recursion)
+}
+
+/// Same as [do_eval_scoped(...)](do_eval_scoped), but keeps the references in the provided map,
+/// such that the caller can see the references that were saved by the evaluation
+fn do_eval_refs<G: Rng>(text: String, start_from: usize, ctx: &mut EvalContext<G>, ref_map: &mut HashMap<String, RefValue>, recursion: usize) -> Result<String, ParsingError> {
+	if recursion > ctx.recursion_limit {
+		return Err(RecursionLimitReached{limit: ctx.recursion_limit, token: None}.into());
+	}
+	let (text, pos) = do_sub_tokens(text, start_from, ctx, ref_map, recursion)?;
+	do_dice_tokens(text, pos, ctx, recursion)
+}
+
+/// Replaces the substitution tokens of the text (see [do_eval_refs(...)](do_eval_refs)),
+/// including the tokens in the substituted text
+/// # Returns
+/// The text and the position of the last substituted token (or `start_from` if there were none)
+fn do_sub_tokens<G: Rng>(text: String, start_from: usize, ctx: &mut EvalContext<G>, ref_map: &mut HashMap<String, RefValue>, recursion: usize) -> Result<(String, usize), ParsingError> {
+	//println!("'{}'", text);
+	let mut text = text;
+	let mut new_text;
+	let mut pos = start_from;
+	// recursion depth of the text, and the ends of the substituted texts that are being scanned
+	// for nested tokens (innermost last)
+	let base_depth = ctx.depth.map_or(0, |depth| depth + 1);
+	let mut nested: Vec<usize> = Vec::new();
+	loop {
+		match next_token(&text, pos, SUB_START) {
+			None => break,
+			Some((start, end)) => {
+				let (front, tmp) = text.split_at(start);
+				let (token, back) = tmp.split_at(end - start);
+				let full_token = token;
+				let token = token[SUB_START.len()..token.len() - 1].trim();
+				while nested.last().is_some_and(|nested_end| *nested_end <= start) {
+					nested.pop();
+				}
+				let depth = base_depth + nested.len();
+				ctx.stats.max_depth = ctx.stats.max_depth.max(depth);
+				if depth > ctx.recursion_limit {
+					return Err(RecursionLimitReached{limit: ctx.recursion_limit, token: Some(String::from(token))}.into());
+				}
+				if let (Some(recording), 0) = (&mut ctx.recording, recursion) {
+					recording.last_sub = None;
+				}
+				count_substitution(ctx)?;
+				if let Some(hook) = ctx.hook.as_deref_mut() {
+					hook.on_token(token);
+					hook.on_token_depth(token, depth);
+				}
+				let parent_depth = ctx.depth.replace(depth);
+				let substitution = match parse_token(token) {
+					Ok(sub) => recover_error(do_sub_parsed(token, sub, ctx, ref_map, recursion), token, ctx),
+					Err(e) => recover_invalid_token(e, token, ctx)
+				};
+				ctx.depth = parent_depth;
+				let substitution = match substitution? {
+					Some(substitution) => substitution,
+					None => {
+						// leave the token as written and continue after it
+						pos = end;
+						continue;
+					}
+				};
+				if let (Some(recording), 0) = (&mut ctx.recording, recursion) {
+					recording.add_span(start..end, substitution.len(), full_token);
+				}
+				//println!("\tToken substitution: {} -> {}", token, substitution);
+				check_output_len(front.len() + substitution.len() + back.len(), ctx.options)?;
+				new_text = String::from(front);
+				new_text.push_str(substitution.as_str());
+				new_text.push_str(back);
+				pos = start;
+				// the tokens in the substituted text are one level deeper
+				for nested_end in nested.iter_mut() {
+					*nested_end = (*nested_end + substitution.len()).saturating_sub(end - start);
+				}
+				nested.push(start + substitution.len());
+			}
+		}
+		text = new_text;
+	}
+	Ok((text, pos))
 }
 
-/// This is where all the action happens when evaluating a string for text substitution
-fn do_eval<R: Rng>(text: String, start_from: usize, reg: &HashMap<String, LookUpTable>, dice: &mut DiceBag<R>, rng: &mut impl Rng, recursion_limit: usize, recursion: usize) -> Result<String, ParsingError> {
-	if recursion > recursion_limit {
-		return Err(RecursionLimitReached{limit: recursion_limit}.into());
-	}
-	//println!("'{}'", text);
-	let mut ref_map: HashMap<String, String> = HashMap::new();
+/// Rolls the dice tokens of the text (see [do_eval_refs(...)](do_eval_refs)) from the given
+/// position onwards
+fn do_dice_tokens<G: Rng>(text: String, pos: usize, ctx: &mut EvalContext<G>, recursion: usize) -> Result<String, ParsingError> {
 	let mut text = text;
 	let mut new_text;
-	let mut pos = start_from;
+	let mut pos = pos;
 	loop {
-		match next_token(&text, pos, SUB_START) {
+		match next_token(&text, pos, DICE_START) {
 			None => break,
 			Some((start, end)) => {
 				let (front, tmp) = text.split_at(start);
 				let (token, back) = tmp.split_at(end - start);
-				let token = &token[SUB_START.len()..token.len() - 1];
-				let substitution = do_sub(token.trim(), reg, dice, &mut ref_map, rng, recursion_limit, recursion)?;
-				//println!("\tToken substitution: {} -> {}", token, substitution);
+				let dice_exp = &token[DICE_START.len()..token.len()-1];
+				let substitution = taped_text(ctx, |ctx| do_dice(dice_exp.trim(), ctx.rng))?;
+				if let (Some(recording), 0) = (&mut ctx.recording, recursion) {
+					recording.add_span(start..end, substitution.len(), token);
+				}
+				//println!("\tDice substitution: {} -> {}", dice_exp, substitution);
 				new_text = String::from(front);
 				new_text.push_str(substitution.as_str());
 				new_text.push_str(back);
@@ -773,31 +4249,742 @@ fn do_eval<R: Rng>(text: String, start_from: usize, reg: &HashMap<String, LookUp
 		}
 		text = new_text;
 	}
-	loop {
-		match next_token(&text, pos, DICE_START) {
-			None => break,
-			Some((start, end)) => {
-				let (front, tmp) = text.split_at(start);
-				let (token, back) = tmp.split_at(end - start);
-				let dice_exp = &token[DICE_START.len()..token.len()-1];
-				let substitution = do_dice(dice_exp.trim(), dice)?;
-				//println!("\tDice substitution: {} -> {}", dice_exp, substitution);
-				new_text = String::from(front);
-				new_text.push_str(substitution.as_str());
-				new_text.push_str(back);
-				pos = start;
-			}
+	return Ok(text);
+}
+
+/// Generate a substitution from the provided substitution token, such as `${animal}` (note that the
+/// `${` and `}` have already been stripped away), and its parsed options (see
+/// [parse_token(...)](parse_token) and [Template::compile(...)](Template::compile))
+fn do_sub_parsed<G: Rng>(token: &str, sub: SubstitutionOptions, ctx: &mut EvalContext<G>, ref_map: &mut HashMap<String, RefValue>, recursion: usize) -> Result<String, ParsingError> {
+	let mut sub = sub;
+	if let Some(row_id) = &sub.row {
+		let text = do_row_sub(row_id.as_str(), &sub, ctx, ref_map)?;
+		if let (Some(recording), 0) = (&mut ctx.recording, recursion) {
+			recording.last_sub = recording.draws.last().map(|(id, item)| (id.clone(), vec![item.clone()]));
+		}
+		return Ok(text);
+	}
+	// apply references to id
+	if sub.id.contains("$") {
+		sub.id = do_ref_sub_in_id(sub.id.as_str(), ref_map)?;
+	}
+	// roll dice in id (eg `encounters/tier#{1d3}`)
+	if sub.id.contains(DICE_START) {
+		sub.id = do_dice_sub_in_id(sub.id.as_str(), ctx)?;
+	}
+	let is_function = sub.id.starts_with(FN_PREFIX);
+	let is_variable = sub.id.starts_with(VAR_PREFIX);
+	// convert matrix look-ups (eg `reaction[happy][elves]`) to ID paths (eg `reaction/happy/elves`)
+	if sub.id.contains("[") && !is_function && !is_variable {
+		sub.id = matrix_id_to_path(sub.id.as_str());
+	}
+	// match IDs that differ in case or separators (eg `Monster_Names` for `monster-names`)
+	if ctx.options.loose_ids && !sub.id.starts_with("@") && !is_function && !is_variable {
+		if let Some(id) = resolve_loose_id(sub.id.as_str(), ctx.reg) {
+			sub.id = id;
+		}
+	}
+	// generate substitution or recall a reference
+	let mut text;
+	let mut key: Option<String> = None;
+	let mut fields: Option<BTreeMap<String, String>> = None;
+	if sub.id.starts_with("@") {
+		// is a reference, return previously generated item (or a field of it, eg `@w.damage`)
+		let (ref_id, field) = match sub.id[1..].split_once('.') {
+			None => (String::from(&sub.id[1..]), sub.field.as_deref()),
+			Some((ref_id, field)) => (String::from(ref_id), Some(field))
+		};
+		match ref_map.get(&ref_id) {
+			None => return Err(KeyNotFoundError{ key: ref_id }.into()),
+			Some(stored) => match field {
+				None => {
+					text = stored.text.clone();
+					key = stored.key.clone();
+					fields = stored.fields.clone();
+				},
+				Some(field) => {
+					text = stored.fields.as_ref().and_then(|fields| field_value(fields, field)).cloned()
+						.ok_or_else(|| ParseError{
+							msg: Some(format!("Reference '{}' has no field '{}'", ref_id, field)), line: None, col: None
+						})?;
+				}
+			}
+		}
+		// prefix a/an if requested
+		text = match &sub.aan {
+			None => text,
+			Some(aan) => {
+				if *aan {
+					// add a or an as appropriate
+					let mut buffer = String::from(indefinite_article_prefix_for(text.as_str()));
+					buffer.push_str(text.as_str());
+					buffer
+				} else { text }
+			}
+		};
+		// change case if requested
+		text = change_case(text, sub.case.as_ref())?;
+	} else if is_function {
+		// call a template function (eg `fn:weather(season=winter)`)
+		let (name, args) = FunctionArgs::parse_call(&sub.id[FN_PREFIX.len()..])?;
+		text = taped_text(ctx, |ctx| {
+			let function = ctx.functions.get(name)
+				.ok_or_else(|| KeyNotFoundError{ key: format!("{}{}", FN_PREFIX, name) })?;
+			let refs: HashMap<&str, &str> = ref_map.iter()
+				.map(|(ref_id, value)| (ref_id.as_str(), value.text.as_str())).collect();
+			Ok(function(&args, &mut FunctionContext::new(refs, ctx.rng)).map_err(|e| ParseError{
+				msg: Some(format!("Function '{}' failed: {}", name, e)), line: None, col: None
+			})?)
+		})?;
+		if sub.aan == Some(true) {
+			text = format!("{}{}", indefinite_article_prefix_for(text.as_str()), text);
+		}
+		text = change_case(text, sub.case.as_ref())?;
+	} else if is_variable {
+		// substitute a variable of the host application (eg `var:campaign`)
+		text = taped_text(ctx, |ctx| Ok(ctx.vars.get(sub.id[VAR_PREFIX.len()..].trim()).cloned()
+			.ok_or_else(|| KeyNotFoundError{ key: sub.id.clone() })?))?;
+		if sub.aan == Some(true) {
+			text = format!("{}{}", indefinite_article_prefix_for(text.as_str()), text);
+		}
+		text = change_case(text, sub.case.as_ref())?;
+	} else {
+		// draw the items
+		let items: Vec<Item>;
+		let reg = ctx.reg;
+		let (table_id, lookup_value) = split_value_lookup(sub.id.as_str(), reg)?;
+		// copy the table ID, since the table's default options may change the other options
+		let table_id: String = String::from(table_id);
+		let table_id: &str = table_id.as_str();
+		let lazy_lut: Arc<LookUpTable>;
+		let lut = match reg.get(table_id) {
+			Some(lut) => lut,
+			None => {
+				lazy_lut = load_lazy_table(ctx.lazy, reg, table_id)?
+					.ok_or_else(|| KeyNotFoundError { key: table_id.into() })?;
+				&*lazy_lut
+			}
+		};
+		// apply the default options of the table (if any) that the token does not set
+		if let Some(defaults) = lut.get_defaults() {
+			sub.apply_defaults(defaults);
+		}
+		// calculate conditional weights (if any) from the current references
+		let resolved: Arc<LookUpTable>;
+		let lut = if lut.has_weight_exprs() {
+			resolved = resolve_lut(ctx, table_id, lut, ref_map)?;
+			&*resolved
+		} else { lut };
+		// leave out the items that reached their max-per-eval limit (if any)
+		let capped = lookup_value.is_none() && sub.select.is_none() && (sub.max_per_eval.is_some() || lut.has_item_caps());
+		let below_caps: LookUpTable;
+		let lut = if capped {
+			below_caps = items_below_caps(table_id, lut, sub.max_per_eval, ctx.appearances.get(table_id))?;
+			&below_caps
+		} else { lut };
+		let num_to_draw: usize;
+		match sub.count {
+			None => num_to_draw = 1,
+			Some(count_val) => {
+				match count_val {
+					serde_yaml::Value::Number(n) => {
+						num_to_draw = n.as_u64().ok_or_else(|| ParseError { msg: Some(format!("{} as unsigned integer", n)), line: None, col: None })? as usize
+					}
+					serde_yaml::Value::String(dice_ex) => {
+						let mut dice = DiceBag::new(simple_rng(ctx.rng.gen()));
+						let roll = dice.eval_total(dice_ex.as_str()).map_err(|_| ParseError { msg: Some(format!("'{}' is not a valid dice expression", dice_ex)), line: None, col: None })?;
+						if roll < 0 {
+							num_to_draw = 0;
+						} else {
+							num_to_draw = roll as usize;
+						}
+					}
+					serde_yaml::Value::Mapping(range) => {
+						// uniform random count, eg {min: 2, max: 5}
+						let bound = |name: &str, default: Option<u64>| -> Result<u64, ParsingError> {
+							match range.get(name) {
+								None => default.ok_or_else(|| ParseError { msg: Some(format!("count range is missing '{}'", name)), line: None, col: None }.into()),
+								Some(v) => v.as_u64().ok_or_else(|| ParseError { msg: Some(format!("count {} '{:?}' is not an unsigned integer", name, v)), line: None, col: None }.into())
+							}
+						};
+						let min = bound("min", Some(0))?;
+						let max = bound("max", None)?;
+						if min > max {
+							return Err(ParseError { msg: Some(format!("count min ({}) is greater than max ({})", min, max)), line: None, col: None }.into());
+						}
+						num_to_draw = ctx.rng.gen_range(min..=max) as usize;
+					}
+					_ => { return Err(ParsingError::ParseError(ParseError { msg: Some(String::from(token)), line: None, col: None })) }
+				}
+			}
+		}
+		if sub.select.is_some() && lookup_value.is_some() {
+			return Err(InvalidCombinationError::new("Option 'select' cannot be combined with a value look-up").into());
+		}
+		let replay = ctx.tape.as_deref().is_some_and(DrawTape::is_replay);
+		match lookup_value {
+			// play back the drawn items from the draw log instead of drawing them
+			_ if replay => items = replay_draw(ctx)?,
+			None if sub.select.is_some() => {
+				// select a specific item, even if its weight is zero
+				let select = sub.select.as_deref().unwrap_or_default();
+				let item = lut.find_item(select).ok_or_else(|| ParseError {
+					msg: Some(format!("Look-up table '{}' has no item '{}'", table_id, select)), line: None, col: None
+				})?;
+				items = vec![item.clone()];
+			}
+			Some(value) => {
+				// select by value instead of drawing at random
+				let item = lut.lookup_value(value).ok_or_else(|| ParseError {
+					msg: Some(format!("No entry in look-up table '{}' covers value {}", table_id, value)), line: None, col: None
+				})?;
+				items = vec![item.clone()];
+			}
+			None => match &sub.pick {
+				Some(pick) => {
+					if sub.method.as_ref().is_some_and(|m| m.as_str() != "random") {
+						return Err(InvalidCombinationError::new("Option 'pick' can only be used with method 'random'").into());
+					}
+					let mut picked: Vec<Item> = Vec::with_capacity(num_to_draw);
+					for _ in 0..num_to_draw {
+						let candidates = lut.draw_n_random(ctx.rng, pick.of.unwrap_or(2).max(1))?;
+						picked.push(pick_candidate(candidates, pick)?);
+					}
+					items = picked;
+				}
+				None => match sub.method {
+					None if ctx.draws.get_mode(table_id) != Depletion::Replace => {
+						let mode = ctx.draws.get_mode(table_id);
+						items = draw_depleting(table_id, lut, num_to_draw, mode, ctx)?
+					}
+					None if capped => { items = draw_capped(table_id, lut, num_to_draw, sub.max_per_eval, ctx)? }
+					None => { items = draw_n(lut, num_to_draw, ctx)? }
+					Some(method) => {
+						match method.as_str() {
+							"random" if capped => items = draw_capped(table_id, lut, num_to_draw, sub.max_per_eval, ctx)?,
+							"random" => items = draw_n(lut, num_to_draw, ctx)?,
+							"shuffle" => items = lut.shuffle_draw(ctx.rng, num_to_draw)?,
+							"deplete" | "deplete-reset" => {
+								let mode: Depletion = method.parse()?;
+								items = draw_depleting(table_id, lut, num_to_draw, mode, ctx)?
+							}
+							_ => return Err(InvalidOptionError{option: String::from("method"), value: method.clone(),
+								allowed: &["random", "shuffle", "deplete", "deplete-reset"]}.into())
+						}
+					}
+				}
+			}
+		}
+		if capped {
+			let counts = ctx.appearances.entry(String::from(table_id)).or_default();
+			for item in &items {
+				*counts.entry(item.get_id().clone()).or_default() += 1;
+			}
+		}
+		// replace items that link to other look-up tables (eg `@table:monsters/undead`)
+		let items: Vec<(String, Item)> = items.into_iter().map(|item| follow_table_links(table_id, item, ctx, ref_map))
+			.collect::<Result<Vec<(String, Item)>, ParsingError>>()?;
+		// roll the numbers of number range items (eg `{min: 50, max: 200}`)
+		let mut drawn: Vec<DrawnItem> = Vec::with_capacity(items.len());
+		let items: Vec<Item> = items.into_iter().map(|(source_id, item)| {
+			let rolled = item.roll_number(ctx.rng);
+			drawn.push(DrawnItem{table: source_id, item: item.get_id().clone(), number: rolled.as_ref().map(|rolled| rolled.get_text().clone())});
+			rolled.unwrap_or(item)
+		}).collect();
+		if let Some(tape) = ctx.tape.as_deref_mut() {
+			tape.record(Decision::Draw(drawn));
+		}
+		if let Some(recording) = &mut ctx.recording {
+			recording.draws.extend(items.iter().map(|item| (String::from(table_id), item.clone())));
+			if recursion == 0 {
+				recording.last_sub = Some((String::from(table_id), items.clone()));
+			}
+		}
+		ctx.stats.add_draws(table_id, items.len());
+		if let Some(hook) = ctx.hook.as_deref_mut() {
+			items.iter().for_each(|item| hook.on_draw(table_id, item));
+		}
+		// output a field of each drawn record instead of its text, if requested
+		let items: Vec<Item> = match &sub.field {
+			None => items,
+			Some(field) => items.iter().map(|item| item.to_field_item(field).ok_or_else(|| ParseError{
+				msg: Some(format!("Item '{}' of look-up table '{}' has no field '{}'", item.get_text(), table_id, field)),
+				line: None, col: None
+			})).collect::<Result<Vec<Item>, ParseError>>()?
+		};
+		// remember the stable key and record fields of a single drawn item
+		if items.len() == 1 {
+			key = items[0].get_key().cloned();
+			fields = items[0].get_fields().cloned();
+		}
+		// format to text
+		let options = ctx.options;
+		let sep = sub.sep.as_ref().or(options.default_sep.as_ref());
+		text = String::new();
+		// collapse repeated items into one entry with a count if requested (eg "3x old boot")
+		let groups: Vec<(Item, usize)> = if sub.group_duplicates == Some(true) {
+			group_duplicates(items)
+		} else {
+			items.into_iter().map(|item| (item, 1)).collect()
+		};
+		// structured output (eg a JSON array) instead of joined text, if requested
+		let structured: Option<&str> = match sub.format.as_deref() {
+			None | Some("text") => None,
+			Some(format) if format == "json" || format == "yaml" => Some(format),
+			Some(format) => return Err(ParseError { msg: Some(format!("'{}' is not a supported format", format)), line: None, col: None }.into())
+		};
+		if let Some(format) = structured {
+			if sub.sep.is_some() || sub.last_sep.is_some() || sub.prefix.is_some() || sub.suffix.is_some() {
+				return Err(InvalidCombinationError::new(format!(
+					"Options 'sep', 'last-sep', 'prefix', and 'suffix' cannot be used with format '{}'", format
+				)).into());
+			}
+		}
+		let mut entries: Vec<(String, usize)> = Vec::new();
+		let loop_total = groups.len();
+		for (loop_count, (item, repeats)) in groups.into_iter().enumerate() {
+			if structured.is_some() {
+				// evaluate each entry on its own, as it will not be re-scanned (in its own child
+				// reference scope if requested, just like the text output)
+				let drawn_text = match sub.scoped {
+					Some(true) => do_eval_scoped(item.get_text().clone(), 0, ctx, ref_map.clone(), recursion+1)?,
+					_ => do_eval_refs(item.get_text().clone(), 0, ctx, ref_map, recursion+1)?
+				};
+				let drawn_text: String = if sub.collapse_whitespace == Some(true) {
+					collapse_whitespace(drawn_text.as_str())
+				} else if sub.strip.unwrap_or(options.strip_items) {
+					String::from(drawn_text.trim())
+				} else { drawn_text };
+				let drawn_text = if sub.aan == Some(true) && repeats == 1 {
+					format!("{}{}", indefinite_article_prefix_for(drawn_text.as_str()), drawn_text)
+				} else { drawn_text };
+				entries.push((change_case(drawn_text, sub.case.as_ref())?, repeats));
+				continue;
+			}
+			if loop_count > 0 {
+				match sep {
+					None => {}
+					Some(sep) => {
+						if loop_count == loop_total - 1 && (&sub.last_sep).is_some() {
+							text.push_str(&unescape(sub.last_sep.clone().unwrap().as_str())?.as_str())
+						} else {
+							text.push_str(unescape(sep)?.as_str())
+						}
+					}
+				}
+			}
+			match &sub.prefix {
+				None => {}
+				Some(prefix) => text.push_str(prefix.as_str())
+			}
+			// do substitutions in randomly drawn text (if any)
+			text = do_eval(text, 0, ctx, recursion+1)?;
+			// evaluate the drawn item in its own child reference scope if requested
+			let drawn_text: String = match sub.scoped {
+				Some(true) => do_eval_scoped(item.get_text().clone(), 0, ctx, ref_map.clone(), recursion+1)?,
+				_ => item.get_text().clone()
+			};
+			// clean up whitespace if requested
+			let drawn_text: String = if sub.collapse_whitespace == Some(true) {
+				collapse_whitespace(drawn_text.as_str())
+			} else if sub.strip.unwrap_or(options.strip_items) {
+				String::from(drawn_text.trim())
+			} else { drawn_text };
+			// prefix a/an if requested
+			let item_text: String = match &sub.aan {
+				None => drawn_text,
+				Some(aan) => {
+					if *aan && repeats == 1 {
+						// add a or an as appropriate
+						let mut buffer = String::from(indefinite_article_prefix_for(drawn_text.as_str()));
+						buffer.push_str(drawn_text.as_str());
+						buffer
+					} else { drawn_text }
+				}
+			};
+			if repeats > 1 {
+				text.push_str(format!("{}x ", repeats).as_str());
+			}
+			// change case if requested
+			text.push_str(change_case(item_text, sub.case.as_ref())?.as_str());
+			match &sub.suffix {
+				None => {}
+				Some(suffix) => text.push_str(suffix.as_str())
+			}
+		}
+		if let Some(format) = structured {
+			text = format_entries(entries, sub.group_duplicates == Some(true), format)?;
+		}
+	}
+	// store items as ref if requested
+	match &sub.reference {
+		None => {},
+		Some(ref_id) => {
+			validate_ref(ref_id)?;
+			let value = RefValue{text: text.clone(), key, fields};
+			if sub.persist == Some(true) {
+				ctx.persistent_refs.insert(ref_id.clone(), value.clone());
+			}
+			if let Some(hook) = ctx.hook.as_deref_mut() {
+				hook.on_ref(ref_id, value.text.as_str());
+			}
+			let _ = ref_map.insert(ref_id.clone(), value);
+		}
+	}
+	// hide text if requested
+	match sub.hidden {
+		None => {}
+		Some(hide_me) => if hide_me {text = String::from("")}
+	}
+	Ok(text)
+
+}
+
+
+/// Counts a substitution token of the evaluation, or returns an error if there are more than
+/// [EvalOptions::max_substitutions] or the evaluation is past its time limit
+fn count_substitution<G: Rng>(ctx: &mut EvalContext<G>) -> Result<(), ParsingError> {
+	ctx.stats.tokens += 1;
+	if let Some((start, limit)) = ctx.deadline {
+		if start.elapsed() >= limit {
+			return Err(Timeout{limit}.into());
+		}
+	}
+	match ctx.options.max_substitutions {
+		Some(limit) if ctx.stats.tokens > limit => Err(LimitExceeded{what: "substitutions", limit}.into()),
+		_ => Ok(())
+	}
+}
+
+/// Returns an error if a text of the given length (in bytes) is longer than
+/// [EvalOptions::max_output_len]
+fn check_output_len(len: usize, options: &EvalOptions) -> Result<(), ParsingError> {
+	match options.max_output_len {
+		Some(limit) if len > limit => Err(LimitExceeded{what: "bytes of text", limit}.into()),
+		_ => Ok(())
+	}
+}
+
+/// Applies the strictness of the evaluation options (see [Strictness]) to the substitution of a
+/// token that failed: the error is returned, or the substitution is replaced by a placeholder
+/// (eg `⟦missing: dragon⟧`), or the token is left as written
+/// # Arguments
+/// * `result`: the substitution of the token
+/// * `token`: the token, without the enclosing `${` and `}`
+/// * `ctx`: the evaluation context, which records a warning if the error is not returned
+/// # Returns
+/// The substitution, or `None` to leave the token as written
+fn recover_error<G: Rng>(result: Result<String, ParsingError>, token: &str, ctx: &mut EvalContext<G>) -> Result<Option<String>, ParsingError> {
+	let e = match result {
+		Ok(text) => return Ok(Some(text)),
+		Err(e) => e
+	};
+	let strictness = &ctx.options.strictness;
+	let (policy, placeholder) = match &e {
+		ParsingError::KeyNotFoundError(missing) => {
+			let policy = match ctx.options.lenient_missing {
+				true => ErrorPolicy::Placeholder,
+				false => strictness.missing
+			};
+			(policy, format!("⟦missing: {}⟧", missing.key))
+		},
+		ParsingError::NoValuesError(_) => (strictness.empty_tables, format!("⟦empty: {}⟧", token)),
+		ParsingError::InvalidOptionError(_) => (strictness.invalid_options, format!("⟦invalid: {}⟧", token)),
+		_ => (ErrorPolicy::Error, String::new())
+	};
+	recover_with(e, policy, placeholder, token, ctx)
+}
+
+/// Applies the strictness of the evaluation options (see [Strictness]) to a token that cannot be
+/// parsed (see [recover_error(...)](recover_error))
+fn recover_invalid_token<G: Rng>(e: ParsingError, token: &str, ctx: &mut EvalContext<G>) -> Result<Option<String>, ParsingError> {
+	let policy = ctx.options.strictness.invalid_tokens;
+	recover_with(e, policy, format!("⟦invalid: {}⟧", token), token, ctx)
+}
+
+/// Returns the error, or records it as a warning and returns the placeholder (or `None` to leave
+/// the token as written), as per the given policy
+fn recover_with<G: Rng>(e: ParsingError, policy: ErrorPolicy, placeholder: String, token: &str, ctx: &mut EvalContext<G>) -> Result<Option<String>, ParsingError> {
+	let warning = match &e {
+		ParsingError::KeyNotFoundError(missing) => missing.to_string(),
+		e => format!("${{{}}}: {}", token, e)
+	};
+	match policy {
+		ErrorPolicy::Error => Err(e),
+		ErrorPolicy::Placeholder => {
+			ctx.warnings.push(warning);
+			Ok(Some(placeholder))
+		},
+		ErrorPolicy::Literal => {
+			ctx.warnings.push(warning);
+			Ok(None)
+		}
+	}
+}
+
+/// Draws a whole row of a CSV file (eg `${{row: npc-stats, ref-prefix: npc}}`) and saves each
+/// column of the row as a reference (eg `@npc-name` and `@npc-job`). Produces no text.
+fn do_row_sub<G: Rng>(row_id: &str, sub: &SubstitutionOptions, ctx: &mut EvalContext<G>, ref_map: &mut HashMap<String, RefValue>) -> Result<String, ParsingError> {
+	let mut rows_id = rows_table_id(row_id);
+	if ctx.options.loose_ids {
+		if let Some(id) = resolve_loose_id(rows_id.as_str(), ctx.reg) {
+			rows_id = id;
+		}
+	}
+	let lazy_lut: Arc<LookUpTable>;
+	let rows = match ctx.reg.get(&rows_id) {
+		Some(lut) => lut,
+		None => {
+			lazy_lut = match load_lazy_table(ctx.lazy, ctx.reg, rows_id.as_str())? {
+				Some(lut) => lut,
+				// the columns of the CSV file are loaded, but not its rows
+				None if ctx.reg.keys().any(|id| in_namespace(id, row_id)) => return Err(ParseError{
+					msg: Some(format!("The rows of '{}' were not kept (load the file with CsvOptions::with_rows(true) or the --csv-rows flag)", row_id)),
+					line: None, col: None
+				}.into()),
+				None => return Err(KeyNotFoundError { key: row_id.into() }.into())
+			};
+			&*lazy_lut
+		}
+	};
+	let item = match ctx.tape.as_deref().is_some_and(DrawTape::is_replay) {
+		true => replay_draw(ctx)?.into_iter().next().ok_or_else(|| mismatch(String::from("a row draw has no row")))?,
+		false => rows.draw_random(ctx.rng)?
+	};
+	if let Some(tape) = ctx.tape.as_deref_mut() {
+		tape.record(Decision::Draw(vec![DrawnItem{table: rows_id.clone(), item: item.get_id().clone(), number: None}]));
+	}
+	if let Some(recording) = &mut ctx.recording {
+		recording.draws.push((String::from(row_id), item.clone()));
+	}
+	ctx.stats.add_draws(row_id, 1);
+	if let Some(hook) = ctx.hook.as_deref_mut() {
+		hook.on_draw(row_id, &item);
+	}
+	let prefix = sub.ref_prefix.as_deref()
+		.unwrap_or_else(|| row_id.rsplit('/').next().unwrap_or(row_id));
+	for (col, cell) in item.get_fields().into_iter().flatten() {
+		let ref_id = format!("{}-{}", prefix, col);
+		validate_ref(ref_id.as_str())?;
+		let value = RefValue{text: cell.clone(), key: None, fields: None};
+		if sub.persist == Some(true) {
+			ctx.persistent_refs.insert(ref_id.clone(), value.clone());
+		}
+		if let Some(hook) = ctx.hook.as_deref_mut() {
+			hook.on_ref(ref_id.as_str(), cell.as_str());
+		}
+		ref_map.insert(ref_id, value);
+	}
+	if let Some(ref_id) = &sub.reference {
+		validate_ref(ref_id)?;
+		let value = RefValue{text: item.get_text().clone(), key: None, fields: item.get_fields().cloned()};
+		if sub.persist == Some(true) {
+			ctx.persistent_refs.insert(ref_id.clone(), value.clone());
+		}
+		if let Some(hook) = ctx.hook.as_deref_mut() {
+			hook.on_ref(ref_id, value.text.as_str());
+		}
+		ref_map.insert(ref_id.clone(), value);
+	}
+	Ok(String::new())
+}
+
+/// Replaces an item that links to another look-up table (see
+/// [Item::get_table_link()](Item::get_table_link)) with an item drawn from the linked table,
+/// following chains of links up to the recursion limit
+/// # Returns
+/// The ID of the look-up table that the final item belongs to, and the item
+fn follow_table_links<G: Rng>(table_id: &str, item: Item, ctx: &mut EvalContext<G>, ref_map: &HashMap<String, RefValue>) -> Result<(String, Item), ParsingError> {
+	let mut table_id = String::from(table_id);
+	let mut item = item;
+	let mut depth = 0;
+	while let Some(link) = item.get_table_link() {
+		depth += 1;
+		if depth > ctx.recursion_limit {
+			return Err(RecursionLimitReached{limit: ctx.recursion_limit, token: Some(format!("@table:{}", link))}.into());
+		}
+		let reg = ctx.reg;
+		let loose_link = match ctx.options.loose_ids {
+			true => resolve_loose_id(link, reg),
+			false => None
+		};
+		let link = loose_link.as_deref().unwrap_or(link);
+		let lazy_lut: Arc<LookUpTable>;
+		let lut = match reg.get(link) {
+			Some(lut) => lut,
+			None => {
+				lazy_lut = load_lazy_table(ctx.lazy, reg, link)?
+					.ok_or_else(|| KeyNotFoundError { key: link.into() })?;
+				&*lazy_lut
+			}
+		};
+		let resolved: Arc<LookUpTable>;
+		let lut = if lut.has_weight_exprs() {
+			resolved = resolve_lut(ctx, link, lut, ref_map)?;
+			&*resolved
+		} else { lut };
+		table_id = String::from(link);
+		item = match &mut ctx.choices {
+			Some(choices) => choices.choose(lut)?,
+			None => lut.draw_random(ctx.rng)?
+		};
+	}
+	Ok((table_id, item))
+}
+
+/// Plays back the items of the next draw from the draw log (see
+/// [Interpreter::eval_replay(...)](Interpreter::eval_replay)), finding each item by its ID
+fn replay_draw<G: Rng>(ctx: &mut EvalContext<G>) -> Result<Vec<Item>, ParsingError> {
+	let drawn = match ctx.tape.as_deref_mut() {
+		Some(tape) => tape.next_draw()?,
+		None => Vec::new()
+	};
+	let reg = ctx.reg;
+	drawn.into_iter().map(|drawn| {
+		let lazy_lut: Arc<LookUpTable>;
+		let lut = match reg.get(&drawn.table) {
+			Some(lut) => lut,
+			None => {
+				lazy_lut = load_lazy_table(ctx.lazy, reg, drawn.table.as_str())?
+					.ok_or_else(|| mismatch(format!("look-up table '{}' is not loaded", drawn.table)))?;
+				&*lazy_lut
+			}
+		};
+		let item = lut.find_item(drawn.item.as_str())
+			.ok_or_else(|| mismatch(format!("look-up table '{}' has no item '{}'", drawn.table, drawn.item)))?;
+		Ok(match drawn.number {
+			Some(number) => item.to_number_item(number),
+			None => item.clone()
+		})
+	}).collect()
+}
+
+/// Produces a text that the draw log records (eg a dice roll or the result of a template
+/// function), or plays the text back from the draw log instead of producing it
+fn taped_text<G: Rng>(ctx: &mut EvalContext<G>, produce: impl FnOnce(&mut EvalContext<G>) -> Result<String, ParsingError>) -> Result<String, ParsingError> {
+	if let Some(tape) = ctx.tape.as_deref_mut().filter(|tape| tape.is_replay()) {
+		return tape.next_text();
+	}
+	let text = produce(ctx)?;
+	if let Some(tape) = ctx.tape.as_deref_mut() {
+		tape.record(Decision::Text(text.clone()));
+	}
+	Ok(text)
+}
+
+/// Draws the given number of items from a look-up table with replacement, following the script of
+/// choices instead of drawing at random if there is one
+fn draw_n<G: Rng>(lut: &LookUpTable, count: usize, ctx: &mut EvalContext<G>) -> Result<Vec<Item>, ParsingError> {
+	match &mut ctx.choices {
+		Some(choices) => Ok((0..count).map(|_| choices.choose(lut)).collect::<Result<Vec<Item>, NoValuesError>>()?),
+		None => Ok(lut.draw_n_random(ctx.rng, count)?)
+	}
+}
+
+/// Draws the given number of items from a look-up table, using up each drawn item (see
+/// [Depletion])
+fn draw_depleting<G: Rng>(table_id: &str, lut: &LookUpTable, count: usize, mode: Depletion, ctx: &mut EvalContext<G>) -> Result<Vec<Item>, ParsingError> {
+	let mut items: Vec<Item> = Vec::with_capacity(count);
+	for _ in 0..count {
+		items.push(ctx.draws.draw(table_id, lut, ctx.rng, mode)?);
+	}
+	Ok(items)
+}
+
+/// Gets the items of the look-up table that can still be drawn in this evaluation, leaving out
+/// the items that reached their `max-per-eval` limit (an item's own limit takes precedence over
+/// the limit of the draw)
+/// # Arguments
+/// * `table_id`: ID of the look-up table
+/// * `lut`: the look-up table
+/// * `max_per_eval`: limit of the draw (if any)
+/// * `appearances`: number of times that each item of the table was drawn in this evaluation
+/// # Returns
+/// The remaining items, or an error if all items reached their limit
+fn items_below_caps(table_id: &str, lut: &LookUpTable, max_per_eval: Option<usize>, appearances: Option<&HashMap<String, usize>>) -> Result<LookUpTable, ParsingError> {
+	let mut remaining = LookUpTable::new();
+	for item in lut.iter() {
+		let cap = item.get_max_per_eval().or(max_per_eval);
+		let drawn = appearances.and_then(|counts| counts.get(item.get_id())).copied().unwrap_or(0);
+		if cap.is_none_or(|cap| drawn < cap) {
+			remaining.add(item.clone());
+		}
+	}
+	if remaining.iter().all(|item| item.get_weight() <= 0.) {
+		return Err(ParseError{
+			msg: Some(format!("All items of look-up table '{}' reached their max-per-eval limit", table_id)), line: None, col: None
+		}.into());
+	}
+	Ok(remaining)
+}
+
+/// Draws items at random (with replacement) from a look-up table whose items have `max-per-eval`
+/// limits, such that an item that reaches its limit is not drawn again
+fn draw_capped<G: Rng>(table_id: &str, lut: &LookUpTable, count: usize, max_per_eval: Option<usize>, ctx: &mut EvalContext<G>) -> Result<Vec<Item>, ParsingError> {
+	let mut counts: HashMap<String, usize> = ctx.appearances.get(table_id).cloned().unwrap_or_default();
+	let mut items: Vec<Item> = Vec::with_capacity(count);
+	for _ in 0..count {
+		let item = items_below_caps(table_id, lut, max_per_eval, Some(&counts))?.draw_random(ctx.rng)?;
+		*counts.entry(item.get_id().clone()).or_default() += 1;
+		items.push(item);
+	}
+	Ok(items)
+}
+
+/// Changes the capitalization of the text as per the `case` option
+fn change_case(text: String, case: Option<&String>) -> Result<String, ParsingError> {
+	match case {
+		None => Ok(text),
+		Some(ch_case) => match ch_case.as_str() {
+			"original" => Ok(text),
+			"upper" => Ok(text.to_uppercase()),
+			"lower" => Ok(text.to_lowercase()),
+			"title" => Ok(title_case(text)),
+			"first" => match text.chars().next() {
+				None => Ok(text),
+				Some(first) => Ok(format!("{}{}", first.to_uppercase(), &text[first.len_utf8()..]))
+			},
+			_ => Err(InvalidOptionError{option: String::from("case"), value: ch_case.clone(),
+				allowed: &["original", "upper", "lower", "title", "first"]}.into())
+		}
+	}
+}
+
+/// Formats drawn entries as a JSON or YAML array: an array of strings, or an array of
+/// `{text, count}` objects if duplicates were grouped
+fn format_entries(entries: Vec<(String, usize)>, grouped: bool, format: &str) -> Result<String, ParsingError> {
+	let value: serde_json::Value = if grouped {
+		entries.into_iter().map(|(text, count)| serde_json::json!({"text": text, "count": count})).collect()
+	} else {
+		entries.into_iter().map(|(text, _)| serde_json::Value::String(text)).collect()
+	};
+	match format {
+		"yaml" => Ok(String::from(serde_yaml::to_string(&value)?.trim_end())),
+		_ => Ok(serde_json::to_string(&value)?)
+	}
+}
+
+/// Groups identical items (same key or text), keeping the order in which each item was first drawn
+fn group_duplicates(items: Vec<Item>) -> Vec<(Item, usize)> {
+	let mut groups: Vec<(Item, usize)> = Vec::new();
+	for item in items {
+		match groups.iter_mut().find(|(other, _)| other.get_id() == item.get_id() && other.get_text() == item.get_text()) {
+			Some((_, repeats)) => *repeats += 1,
+			None => groups.push((item, 1))
 		}
-		text = new_text;
 	}
-	return Ok(text);
+	groups
 }
 
-/// Generate a substitution from the provided substitution token, such as `${animal}` (note that the
-/// `${` and `}` have already been stripped away).
-fn do_sub<R: Rng>(token: &str, reg: &HashMap<String, LookUpTable>, dice: &mut DiceBag<R>, ref_map: &mut HashMap<String, String>, rng: &mut impl Rng, recursion_limit: usize, recursion: usize) -> Result<String, ParsingError> {
-	// parse the token
-	let mut sub: SubstitutionOptions;
+/// Replaces every run of whitespace with a single space and trims the ends
+fn collapse_whitespace(text: &str) -> String {
+	text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Parses a substitution token, such as `animal@pet` or `{id: animal, aan: true}` (note that the
+/// `${` and `}` have already been stripped away), into its substitution options
+fn parse_token(token: &str) -> Result<SubstitutionOptions, ParsingError> {
+	let sub: SubstitutionOptions;
 	// try YAML parsing in case user forgot to use double braces {{ }}
 	if token.starts_with("{") && token.ends_with("}") {
 		// JSON string with advanced options
@@ -824,163 +5011,121 @@ fn do_sub<R: Rng>(token: &str, reg: &HashMap<String, LookUpTable>, dice: &mut Di
 			}
 		}
 	}
-	// apply references to id
-	if sub.id.contains("$") {
-		sub.id = do_ref_sub_in_id(sub.id.as_str(), ref_map)?;
+	match (&sub.row, sub.id.is_empty()) {
+		(None, true) => return Err(ParseError{
+			msg: Some(format!("Substitution token '{}' is missing the 'id' option", token)), line: None, col: None
+		}.into()),
+		(Some(_), false) => return Err(InvalidCombinationError::new("Options 'id' and 'row' cannot be used together").into()),
+		_ => {}
 	}
-	// generate substitution or recall a reference
-	let mut text;
-	if sub.id.starts_with("@") {
-		// is a reference, return previously generated item
-		let ref_id = String::from(&sub.id[1..]);
-		match ref_map.get(&ref_id) {
-			None => return Err(KeyNotFoundError{ key: ref_id }.into()),
-			Some(stored) => text = stored.clone()
-		}
-		// prefix a/an if requested
-		text = match &sub.aan {
-			None => text,
-			Some(aan) => {
-				if *aan {
-					// add a or an as appropriate
-					let mut buffer = String::from(indefinite_article_prefix_for(text.as_str()));
-					buffer.push_str(text.as_str());
-					buffer
-				} else { text }
-			}
-		};
-		// change case if requested
-		text = match &sub.case {
-			None => text,
-			Some(ch_case) => match ch_case.as_str() {
-				"original" => text,
-				"upper" => text.to_uppercase(),
-				"lower" => text.to_lowercase(),
-				"title" => title_case(text),
-				"first" => {
-					let s = text.as_str();
-					let mut buffer = String::new();
-					buffer.push_str(&s[0..1].to_uppercase().as_str());
-					buffer.push_str(&s[1..]);
-					buffer
-				},
-				_ => return Err(ParsingError::ParseError(ParseError { msg: Some(ch_case.clone()), line: None, col: None }))
-			}
-		}
-	} else {
-		// draw the items
-		let items: Vec<Item>;
-		let lut = reg.get(sub.id.as_str()).ok_or_else(|| KeyNotFoundError { key: sub.id.into() })?;
-		let num_to_draw: usize;
-		match sub.count {
-			None => num_to_draw = 1,
-			Some(count_val) => {
-				match count_val {
-					serde_yaml::Value::Number(n) => {
-						num_to_draw = n.as_u64().ok_or_else(|| ParseError { msg: Some(format!("{} as unsigned integer", n)), line: None, col: None })? as usize
-					}
-					serde_yaml::Value::String(dice_ex) => {
-						let mut dice = DiceBag::new(simple_rng(rng.gen()));
-						let roll = dice.eval_total(dice_ex.as_str()).map_err(|_| ParseError { msg: Some(format!("'{}' is not a valid dice expression", dice_ex)), line: None, col: None })?;
-						if roll < 0 {
-							num_to_draw = 0;
-						} else {
-							num_to_draw = roll as usize;
-						}
-					}
-					_ => { return Err(ParsingError::ParseError(ParseError { msg: Some(String::from(token)), line: None, col: None })) }
-				}
-			}
-		}
-		match sub.method {
-			None => { items = lut.draw_n_random(rng, num_to_draw)? }
-			Some(method) => {
-				match method.as_str() {
-					"random" => items = lut.draw_n_random(rng, num_to_draw)?,
-					"shuffle" => items = lut.shuffle_draw(rng, num_to_draw)?,
-					_ => return Err(ParsingError::ParseError(ParseError { msg: Some(method.clone()), line: None, col: None }))
-				}
+	if sub.persist == Some(true) && sub.reference.is_none() && sub.row.is_none() {
+		return Err(InvalidCombinationError::new("Option 'persist' can only be used with option 'ref' or 'row'").into());
+	}
+	Ok(sub)
+}
+
+/// The look-up table IDs and reference IDs found by [scan_tokens(...)](scan_tokens)
+#[derive(Debug, Default)]
+struct TokenScan {
+	/// Look-up table IDs used (IDs that depend on references, eg `pet-names/$pet`, are not
+	/// included)
+	ids: BTreeSet<String>,
+	/// Reference IDs used
+	refs_used: BTreeSet<String>,
+	/// Reference IDs defined
+	refs_defined: BTreeSet<String>,
+	/// Names of the template functions called
+	functions: BTreeSet<String>,
+	/// Names of the host variables used
+	vars: BTreeSet<String>,
+	/// Unknown substitution options, as (token, option) pairs
+	unknown_options: Vec<(String, String)>
+}
+
+/// Collects the look-up table IDs and reference IDs that are used and defined by the
+/// substitution tokens in the provided text (without drawing from any look-up tables). If
+/// `follow` is true, then look-up tables that are found in the registry are scanned too, since
+/// their items may contain substitution tokens of their own.
+fn scan_tokens(text: &str, reg: &HashMap<String, Arc<LookUpTable>>, follow: bool) -> Result<TokenScan, ParsingError> {
+	let ref_finder: Regex = Regex::new(r#"\$[\d\pL_\-+]+"#).unwrap();
+	let mut scan = TokenScan::default();
+	let mut to_scan: Vec<String> = vec![String::from(text)];
+	while let Some(text) = to_scan.pop() {
+		let mut pos = 0;
+		while let Some((start, end)) = next_token(&text, pos, SUB_START) {
+			pos = end;
+			let token = &text[start + SUB_START.len()..end - 1];
+			let sub = parse_token(token.trim())?;
+			scan.unknown_options.extend(SubstitutionOptions::unknown_keys(token.trim()).into_iter()
+				.map(|key| (String::from(&text[start..end]), key)));
+			if let Some(ref_id) = &sub.reference {
+				scan.refs_defined.insert(ref_id.clone());
 			}
-		}
-		// format to text
-		text = String::new();
-		let mut loop_count = 0;
-		let loop_total = items.len();
-		for item in items {
-			if loop_count > 0 {
-				match &sub.sep {
-					None => {}
-					Some(sep) => {
-						if loop_count == loop_total - 1 && (&sub.last_sep).is_some() {
-							text.push_str(&unescape(sub.last_sep.clone().unwrap().as_str())?.as_str())
-						} else {
-							text.push_str(unescape(sep)?.as_str())
+			if let Some(row_id) = &sub.row {
+				// a row draw saves every column of the row as a reference
+				match reg.get(&rows_table_id(row_id)) {
+					None => {scan.ids.insert(row_id.clone());},
+					Some(rows) => {
+						let prefix = sub.ref_prefix.as_deref()
+							.unwrap_or_else(|| row_id.rsplit('/').next().unwrap_or(row_id));
+						for item in rows.items() {
+							scan.refs_defined.extend(item.get_fields().into_iter().flatten()
+								.map(|(col, _)| format!("{}-{}", prefix, col)));
 						}
 					}
 				}
+				continue;
 			}
-			match &sub.prefix {
-				None => {}
-				Some(prefix) => text.push_str(prefix.as_str())
+			for matched in ref_finder.find_iter(sub.id.as_str()) {
+				scan.refs_used.insert(String::from(&matched.as_str()[1..]));
 			}
-			// do substitutions in randomly drawn text (if any)
-			text = do_eval(text, 0, reg, dice, rng, recursion_limit, recursion+1)?;
-			// prefix a/an if requested
-			let item_text: String = match &sub.aan {
-				None => item.get_text().clone(),
-				Some(aan) => {
-					if *aan {
-						// add a or an as appropriate
-						let mut buffer = String::from(indefinite_article_prefix_for(item.get_text().as_str()));
-						buffer.push_str(item.get_text().as_str());
-						buffer
-					} else { item.get_text().clone() }
+			if let Some(ref_id) = sub.id.strip_prefix("@") {
+				let ref_id = ref_id.split_once('.').map_or(ref_id, |(ref_id, _)| ref_id);
+				scan.refs_used.insert(String::from(ref_id));
+			} else if let Some(call) = sub.id.strip_prefix(FN_PREFIX) {
+				// template functions are not look-up tables
+				if let Ok((name, _)) = FunctionArgs::parse_call(call) {
+					scan.functions.insert(String::from(name));
 				}
-			};
-			// change case if requested
-			match &sub.case {
-				None => text.push_str(item_text.as_str()),
-				Some(ch_case) => match ch_case.as_str() {
-					"original" => text.push_str(item_text.as_str()),
-					"upper" => text.push_str(item_text.to_uppercase().as_str()),
-					"lower" => text.push_str(item_text.to_lowercase().as_str()),
-					"title" => text.push_str(title_case(item_text).as_str()),
-					"first" => {
-						let s = item_text.as_str();
-						text.push_str(&s[0..1].to_uppercase().as_str());
-						text.push_str(&s[1..]);
-					},
-					_ => return Err(ParsingError::ParseError(ParseError { msg: Some(ch_case.clone()), line: None, col: None }))
+			} else if let Some(name) = sub.id.strip_prefix(VAR_PREFIX) {
+				// neither are host variables
+				scan.vars.insert(String::from(name.trim()));
+			} else if !sub.id.contains("$") && !sub.id.contains(DICE_START) {
+				let id = matrix_id_to_path(sub.id.as_str());
+				let table_id = match split_value_lookup(id.as_str(), reg) {
+					Ok((table_id, _)) => String::from(table_id),
+					Err(_) => id.clone()
+				};
+				if scan.ids.insert(table_id.clone()) && follow {
+					if let Some(lut) = reg.get(&table_id) {
+						to_scan.extend(lut.items().iter().map(|item| match item.get_table_link() {
+							// scan a linked look-up table like a substitution of it
+							Some(link) => format!("{}{}}}", SUB_START, link),
+							None => item.get_text().clone()
+						}));
+					}
 				}
 			}
-			match &sub.suffix {
-				None => {}
-				Some(suffix) => text.push_str(suffix.as_str())
-			}
-			loop_count += 1;
-		}
-	}
-	// store items as ref if requested
-	match &sub.reference {
-		None => {},
-		Some(ref_id) => {
-			validate_ref(ref_id)?;
-			let _ = ref_map.insert(ref_id.clone(), text.clone());
 		}
 	}
-	// hide text if requested
-	match sub.hidden {
-		None => {}
-		Some(hide_me) => if hide_me {text = String::from("")}
-	}
-	Ok(text)
-
+	Ok(scan)
 }
 
+/// When using `#{...}` dice notation in an ID string, this function is called to replace each
+/// dice expression with the rolled total
+fn do_dice_sub_in_id<G: Rng>(id: &str, ctx: &mut EvalContext<G>) -> Result<String, ParsingError> {
+	let mut new_id = String::from(id);
+	while let Some((start, end)) = next_token(&new_id, 0, DICE_START) {
+		let dice_exp = &new_id[start + DICE_START.len()..end - 1];
+		let roll = taped_text(ctx, |ctx| do_dice(dice_exp.trim(), ctx.rng))?;
+		new_id = format!("{}{}{}", &new_id[..start], roll, &new_id[end..]);
+	}
+	Ok(new_id)
+}
 
 /// When using `$` reference substitution in an ID string, this function is called to handle it.
 /// Replaces `$ref-id` with the previously generated value that was saved under that ref ID
-fn do_ref_sub_in_id(id: &str, ref_map: &HashMap<String, String>) -> Result<String, ParsingError> {
+fn do_ref_sub_in_id(id: &str, ref_map: &HashMap<String, RefValue>) -> Result<String, ParsingError> {
 	let mut new_id = String::from(id);
 	let mut tmp_id = String::from(id);
 	let finder: Regex = Regex::new(r#"\$[\d\pL_\-+]+"#).unwrap();
@@ -996,7 +5141,7 @@ fn do_ref_sub_in_id(id: &str, ref_map: &HashMap<String, String>) -> Result<Strin
 						let (_, back) = new_id.split_at(matched.end());
 						tmp_id.clear();
 						tmp_id.push_str(front);
-						tmp_id.push_str(ref_value.as_str());
+						tmp_id.push_str(ref_value.key.as_ref().unwrap_or(&ref_value.text).as_str());
 						tmp_id.push_str(back);
 					}
 				}
@@ -1007,6 +5152,315 @@ fn do_ref_sub_in_id(id: &str, ref_map: &HashMap<String, String>) -> Result<Strin
 	Ok(new_id)
 }
 
+/// Returns an error instead of the weight if the weight is negative, NaN, or infinite (eg from a
+/// typo like `nan` or `inf` in a look-up table file)
+fn check_weight(weight: f64, text: &str) -> Result<f64, ParsingError> {
+	if weight >= 0. && weight.is_finite() {
+		Ok(weight)
+	} else {
+		Err(ParseError{
+			msg: Some(format!("Weight must be a non-negative number, but weight for '{}' was {}", text, weight)),
+			line: None, col: None
+		}.into())
+	}
+}
+
+/// Resolves the conditional weights and requirements of a look-up table from the current
+/// references, re-using the copy of the table from an earlier draw of the same evaluation if the
+/// weights are the same
+/// # Returns
+/// The look-up table with the resolved weights
+fn resolve_lut<G: Rng>(ctx: &mut EvalContext<G>, table_id: &str, lut: &LookUpTable, ref_map: &HashMap<String, RefValue>) -> Result<Arc<LookUpTable>, ParsingError> {
+	let weights = lut.resolved_weights(|weight_expr| eval_weight(weight_expr, ref_map),
+		|requires| eval_requirement(requires, ref_map))?;
+	if let Some((cached, resolved)) = ctx.resolved.get(table_id) {
+		if *cached == weights {
+			return Ok(Arc::clone(resolved));
+		}
+	}
+	let resolved = Arc::new(lut.with_resolved_weights(&weights));
+	ctx.resolved.insert(String::from(table_id), (weights, Arc::clone(&resolved)));
+	Ok(resolved)
+}
+
+/// Evaluates a conditional weight expression (eg `$region == coast ? 5 : 1`) using the current
+/// references
+fn eval_weight(weight_expr: &str, ref_map: &HashMap<String, RefValue>) -> Result<f64, ParsingError> {
+	let lookup = |ref_id: &str| -> Result<String, ParsingError> {
+		let ref_value = ref_map.get(ref_id).ok_or_else(|| KeyNotFoundError{ key: ref_id.into() })?;
+		Ok(ref_value.key.as_ref().unwrap_or(&ref_value.text).clone())
+	};
+	let value = expr::parse_expr(weight_expr)?.eval(&lookup)?;
+	match value.as_num() {
+		Some(weight) if weight >= 0. && weight.is_finite() => Ok(weight),
+		_ => Err(ParseError{
+			msg: Some(format!("Weight expression '{}' must produce a non-negative number, but produced {:?}", weight_expr, value)),
+			line: None, col: None
+		}.into())
+	}
+}
+
+/// Evaluates the requirement expression of an item (eg `$terrain == desert`) using the current
+/// references
+fn eval_requirement(requires: &str, ref_map: &HashMap<String, RefValue>) -> Result<bool, ParsingError> {
+	let lookup = |ref_id: &str| -> Result<String, ParsingError> {
+		let ref_value = ref_map.get(ref_id).ok_or_else(|| KeyNotFoundError{ key: ref_id.into() })?;
+		Ok(ref_value.key.as_ref().unwrap_or(&ref_value.text).clone())
+	};
+	Ok(expr::parse_expr(requires)?.eval(&lookup)?.is_true())
+}
+
+/// Chooses one item from a list of candidates according to the provided `pick` options (eg the
+/// rarest of 3 candidates). Ties go to the candidate that was drawn first.
+fn pick_candidate(candidates: Vec<Item>, pick: &PickOptions) -> Result<Item, ParsingError> {
+	let by = pick.by.clone().unwrap_or(String::from("weight"));
+	let take_best = match pick.take.as_deref() {
+		None | Some("best") => true,
+		Some("worst") => false,
+		Some(other) => return Err(InvalidOptionError{option: String::from("pick.take"), value: String::from(other),
+			allowed: &["best", "worst"]}.into())
+	};
+	// returns true if item a ranks better than item b
+	let better = |a: &Item, b: &Item| -> Result<bool, ParsingError> {
+		match by.as_str() {
+			"weight" => Ok(a.get_weight() < b.get_weight()),
+			"length" => Ok(a.get_text().chars().count() > b.get_text().chars().count()),
+			"alpha" => Ok(a.get_text().to_lowercase() < b.get_text().to_lowercase()),
+			_ => Err(InvalidOptionError{option: String::from("pick.by"), value: by.clone(),
+				allowed: &["weight", "length", "alpha"]}.into())
+		}
+	};
+	let mut iter = candidates.into_iter();
+	let mut chosen = iter.next().ok_or(NoValuesError {})?;
+	for candidate in iter {
+		let replace = match take_best {
+			true => better(&candidate, &chosen)?,
+			false => better(&chosen, &candidate)?
+		};
+		if replace {
+			chosen = candidate;
+		}
+	}
+	Ok(chosen)
+}
+
+/// Converts the matrix look-up syntax `id[row][column]` into the equivalent look-up table ID path
+/// `id/row/column`
+fn matrix_id_to_path(id: &str) -> String {
+	let finder: Regex = Regex::new(r#"\[([^\[\]]*)\]"#).unwrap();
+	finder.replace_all(id, |caps: &regex::Captures| format!("/{}", caps[1].trim())).to_string()
+}
+
+/// Normalizes a look-up table ID for loose matching (see [EvalOptions::loose_ids]): Unicode
+/// compatibility normalization (NFKC), lower case, and `_` replaced by `-`
+fn loose_id_key(id: &str) -> String {
+	id.nfkc().flat_map(char::to_lowercase).map(|c| if c == '_' {'-'} else {c}).collect()
+}
+
+/// Finds the registered look-up table ID that loosely matches the given ID (see
+/// [EvalOptions::loose_ids]), also for value look-ups (eg `Strength_Desc:11`). If several
+/// registered IDs match, the first in sort order is used.
+/// # Returns
+/// The matching registered ID, or `None` if the ID is registered as written or nothing matches
+fn resolve_loose_id(id: &str, reg: &HashMap<String, Arc<LookUpTable>>) -> Option<String> {
+	if reg.contains_key(id) {
+		return None;
+	}
+	let find = |id: &str| {
+		let key = loose_id_key(id);
+		reg.keys().filter(|registered| loose_id_key(registered) == key).min().cloned()
+	};
+	find(id).or_else(|| id.rsplit_once(":").and_then(|(table_id, value)|
+		find(table_id.trim()).map(|table_id| format!("{}:{}", table_id, value))))
+}
+
+/// Splits a value look-up ID such as `strength-desc:11` (or `temperature:-5`) into the look-up
+/// table ID and the numeric value to select by. IDs that are registered as-is, that do not
+/// contain a `:`, or that start with a prefix that ends in `:` (`var:`, `fn:`, or `@table:`) are
+/// returned unchanged with no value.
+fn split_value_lookup<'a>(id: &'a str, reg: &HashMap<String, Arc<LookUpTable>>) -> Result<(&'a str, Option<f64>), ParsingError> {
+	if reg.contains_key(id) || [VAR_PREFIX, FN_PREFIX, TABLE_LINK].iter().any(|prefix| id.starts_with(prefix)) {
+		return Ok((id, None));
+	}
+	match id.rsplit_once(":") {
+		None => Ok((id, None)),
+		Some((table_id, value)) => {
+			let value = value.trim().parse::<f64>().map_err(|_| ParseError {
+				msg: Some(format!("'{}' is not a number (in '{}')", value.trim(), id)), line: None, col: None
+			})?;
+			Ok((table_id.trim(), Some(value)))
+		}
+	}
+}
+
+/// Converts the content of a text file to UTF-8. The encoding is taken from the byte-order mark
+/// (if any), or else the given encoding is used. If no encoding is given, then the content is
+/// kept as-is if it is valid UTF-8, or else an error is returned.
+fn decode_text(content: Vec<u8>, encoding: Option<&'static Encoding>) -> Result<Vec<u8>, ParsingError> {
+	let encoding = match (encoding, Encoding::for_bom(&content)) {
+		(_, Some((bom_encoding, _))) => bom_encoding,
+		(Some(encoding), None) => encoding,
+		(None, None) => return match std::str::from_utf8(&content) {
+			Ok(_) => Ok(content),
+			Err(e) => Err(ParseError{msg: Some(format!(
+				"Text is not valid UTF-8 ({}), set the text encoding of the file (eg \"windows-1252\")", e
+			)), line: None, col: None}.into())
+		}
+	};
+	let (text, _, _) = encoding.decode(&content);
+	Ok(text.into_owned().into_bytes())
+}
+
+/// Gets the text of a YAML string, number, or boolean, or `None` for any other kind of value
+fn yaml_scalar_text(value: &serde_yaml::Value) -> Option<String> {
+	match value {
+		serde_yaml::Value::String(text) => Some(text.clone()),
+		serde_yaml::Value::Number(n) => Some(n.to_string()),
+		serde_yaml::Value::Bool(b) => Some(b.to_string()),
+		_ => None
+	}
+}
+
+/// Parses a tiered look-up table key, which is either a single number (eg "7"), an inclusive
+/// range (eg "3-5", "01–05", or "-5--1"), or an open-ended range (eg "13+"). As is common for
+/// d100 tables, an upper bound of all zeros means 100 (eg "96-00"). Returns `None` if the key is
+/// not a range.
+fn parse_range_key(key: &str) -> Option<(f64, f64)> {
+	static MATCHER: OnceLock<Regex> = OnceLock::new();
+	let matcher = MATCHER.get_or_init(|| Regex::new(r#"^\s*(-?\d+(?:\.\d+)?)\s*(?:([-–])\s*(-?\d+(?:\.\d+)?)|(\+))?\s*$"#).unwrap());
+	let caps = matcher.captures(key)?;
+	let min = caps.get(1)?.as_str().parse::<f64>().ok()?;
+	if caps.get(4).is_some() {
+		Some((min, f64::INFINITY))
+	} else if caps.get(2).is_some() {
+		let max_str = caps.get(3)?.as_str();
+		let mut max = max_str.parse::<f64>().ok()?;
+		if max == 0. && min > 0. && max_str.chars().all(|c| c == '0') {
+			max = 10f64.powi(max_str.len() as i32);
+		}
+		Some((min, max))
+	} else {
+		Some((min, min))
+	}
+}
+
+/// Parses the numeric range of a YAML list entry that produces a random number when drawn, given
+/// either as `range` (eg `3-18`) or as `min` and `max`
+/// # Returns
+/// The inclusive range (min, max), `None` if the entry has no range, or an error if the range is
+/// not valid
+fn yaml_number_range(entry: &serde_yaml::Mapping) -> Result<Option<(f64, f64)>, ParsingError> {
+	let invalid = |what: String| -> ParsingError {ParseError{
+		msg: Some(format!("Invalid number range {} (must be eg '3-18' or min and max numbers)", what)), line: None, col: None
+	}.into()};
+	let (min, max) = match (entry.get("range"), entry.get("min"), entry.get("max")) {
+		(None, None, None) => return Ok(None),
+		(Some(range), None, None) => {
+			let range = yaml_scalar_text(range).ok_or_else(|| invalid(format!("{:?}", range)))?;
+			parse_range_key(range.as_str()).ok_or_else(|| invalid(range.clone()))?
+		},
+		(None, Some(min), Some(max)) => (
+			min.as_f64().ok_or_else(|| invalid(format!("{:?} to {:?}", min, max)))?,
+			max.as_f64().ok_or_else(|| invalid(format!("{:?} to {:?}", min, max)))?
+		),
+		_ => return Err(invalid(format!("{:?}", entry)))
+	};
+	if !(min.is_finite() && max.is_finite() && min <= max) {
+		return Err(invalid(format!("{} to {}", min, max)));
+	}
+	Ok(Some((min, max)))
+}
+
+/// Converts a look-up table to a YAML value that loads back as the same look-up table (see
+/// [Interpreter::export_registry(...)](Interpreter::export_registry)): a list of items, or a
+/// mapping of value ranges to texts for a tiered look-up table
+fn export_table(lut: &LookUpTable) -> serde_yaml::Value {
+	if lut.items().iter().any(|item| item.get_range().is_some()) {
+		let mut tiers = serde_yaml::Mapping::new();
+		for item in lut.items() {
+			match item.get_range() {
+				Some((min, max)) => tiers.insert(serde_yaml::Value::String(range_key(min, max)),
+					serde_yaml::Value::String(item.get_text().clone())),
+				None => tiers.insert(serde_yaml::Value::String(item.get_text().clone()), yaml_number(item.get_weight()))
+			};
+		}
+		return serde_yaml::Value::Mapping(tiers);
+	}
+	serde_yaml::Value::Sequence(lut.items().iter().map(export_item).collect())
+}
+
+/// Converts a look-up table item to a YAML list entry: just the text for an item of weight 1
+/// without any other data, or else a mapping (eg `{text: goblin, weight: 2, key: gob}`)
+fn export_item(item: &Item) -> serde_yaml::Value {
+	let plain = item.get_weight() == 1. && item.get_key().is_none() && item.get_weight_expr().is_none()
+		&& item.get_fields().is_none() && item.get_requires().is_none() && item.get_max_per_eval().is_none()
+		&& item.get_number_range().is_none();
+	if plain {
+		return serde_yaml::Value::String(item.get_text().clone());
+	}
+	let mut entry = serde_yaml::Mapping::new();
+	let mut set = |name: &str, value: serde_yaml::Value| entry.insert(serde_yaml::Value::String(String::from(name)), value);
+	match item.get_number_range() {
+		Some((min, max)) => {
+			set("min", yaml_number(min));
+			set("max", yaml_number(max));
+		},
+		None => {
+			set("text", serde_yaml::Value::String(item.get_text().clone()));
+		}
+	}
+	match item.get_weight_expr() {
+		Some(weight_expr) => set("weight", serde_yaml::Value::String(weight_expr.clone())),
+		None if item.get_weight() != 1. => set("weight", yaml_number(item.get_weight())),
+		None => None
+	};
+	if let Some(key) = item.get_key() {
+		set("key", serde_yaml::Value::String(key.clone()));
+	}
+	if let Some(requires) = item.get_requires() {
+		set("requires", serde_yaml::Value::String(requires.clone()));
+	}
+	if let Some(max) = item.get_max_per_eval() {
+		set("max-per-eval", serde_yaml::Value::Number((max as u64).into()));
+	}
+	if let Some(fields) = item.get_fields() {
+		// fields whose names are used by the entry itself go into the metadata mapping
+		let reserved = |name: &str| ["text", "weight", "key", "requires", "max-per-eval", "meta", "min", "max", "range"]
+			.contains(&name);
+		let mut meta = serde_yaml::Mapping::new();
+		for (name, value) in fields {
+			match reserved(name.as_str()) {
+				true => meta.insert(serde_yaml::Value::String(name.clone()), serde_yaml::Value::String(value.clone())),
+				false => set(name.as_str(), serde_yaml::Value::String(value.clone()))
+			};
+		}
+		if !meta.is_empty() {
+			set("meta", serde_yaml::Value::Mapping(meta));
+		}
+	}
+	serde_yaml::Value::Mapping(entry)
+}
+
+/// Formats a value range as a tiered look-up table key (eg "3-5", "7", or "13+"), the reverse
+/// of [parse_range_key(...)](parse_range_key)
+fn range_key(min: f64, max: f64) -> String {
+	if max == f64::INFINITY {
+		format!("{}+", min)
+	} else if min == max {
+		format!("{}", min)
+	} else {
+		format!("{}-{}", min, max)
+	}
+}
+
+/// Converts a number to a YAML number, as a whole number if it has no fractional part
+fn yaml_number(n: f64) -> serde_yaml::Value {
+	match n.fract() == 0. && n.abs() < 1e15 {
+		true => serde_yaml::Value::Number((n as i64).into()),
+		false => serde_yaml::Value::Number(n.into())
+	}
+}
+
 /// Returns an error result if the ID string is not valid, otherwise OK
 fn validate_id<T>(id: T) -> Result<(), ParsingError> where T: Into<String> {
 	let id = id.into();
@@ -1043,10 +5497,11 @@ fn indefinite_article_prefix_for(text: &str) -> &'static str {
 	}
 }
 
-/// Handle `#{...}` number generation (eg "2d6+3")
-fn do_dice<R>(dice_exp: &str, dice: &mut DiceBag<R>) -> Result<String, ParsingError>
+/// Handle `#{...}` number generation (eg "2d6+3"), rolling the dice with a random number generator
+/// that is seeded from the given random number generator
+fn do_dice<R>(dice_exp: &str, rng: &mut R) -> Result<String, ParsingError>
 where R: Rng{
-	let roll = dice.eval_total(dice_exp).map_err(
+	let roll = DiceBag::new(simple_rng(rng.gen())).eval_total(dice_exp).map_err(
 		|e| ParseError{ msg: e.msg, line: None, col: None, }
 	)?;
 	Ok(format!("{}", roll))
@@ -1153,9 +5608,30 @@ fn next_token(text: &String, pos: usize, token_start: &str) -> Option<(usize,usi
 
 }
 
+/// Splits a row of a Markdown pipe table (eg `| dog | 3 |`) into trimmed cells, unescaping any
+/// `\|` in the cells
+fn split_md_table_row(line: &str) -> Vec<String> {
+	let line = line.trim();
+	let line = line.strip_prefix('|').unwrap_or(line);
+	let line = if line.ends_with('|') && !line.ends_with("\\|") { &line[..line.len() - 1] } else { line };
+	let mut cells: Vec<String> = Vec::new();
+	let mut cell = String::new();
+	let mut chars = line.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' if chars.peek() == Some(&'|') => { cell.push('|'); chars.next(); },
+			'|' => cells.push(std::mem::take(&mut cell)),
+			_ => cell.push(c)
+		}
+	}
+	cells.push(cell);
+	cells.into_iter().map(|c| String::from(c.trim())).collect()
+}
+
 /// In-house CSV parser implementation, following the
 /// [RFC-4180 standard](https://www.rfc-editor.org/rfc/rfc4180)
-fn read_csv_row<R: BufRead>(reader: &mut utf8_chars::Chars<R>) -> Option<Vec<String>> {
+fn read_csv_row<R: BufRead>(reader: &mut utf8_chars::Chars<R>, options: &CsvOptions) -> Option<Vec<String>> {
+	let quote = options.quote;
 	let mut last_char = '\0';
 	let mut in_quote = false;
 	let mut cell_buffer = String::new();
@@ -1175,10 +5651,10 @@ fn read_csv_row<R: BufRead>(reader: &mut utf8_chars::Chars<R>) -> Option<Vec<Str
 						match in_quote {
 							true => {
 								// quoted text
-								if c == '"' {
+								if Some(c) == quote {
 									in_quote = !in_quote;
-									if last_char == '"' {
-										cell_buffer.push('"');
+									if Some(last_char) == quote {
+										cell_buffer.push(c);
 										c = '\0';
 									}
 								} else {
@@ -1187,13 +5663,13 @@ fn read_csv_row<R: BufRead>(reader: &mut utf8_chars::Chars<R>) -> Option<Vec<Str
 							}
 							false => {
 								// unquoted text
-								if c == '"' {
+								if Some(c) == quote {
 									in_quote = !in_quote;
-									if last_char == '"' {
-										cell_buffer.push('"');
+									if Some(last_char) == quote {
+										cell_buffer.push(c);
 										c = '\0';
 									}
-								} else if c == ',' {
+								} else if c == options.delimiter {
 									// cell delimiter
 									cells.push(cell_buffer.clone());
 									cell_buffer.clear();
@@ -1232,47 +5708,147 @@ fn read_csv_row<R: BufRead>(reader: &mut utf8_chars::Chars<R>) -> Option<Vec<Str
 	return Some(cells);
 }
 
-/// Unzips the contents of a zip archive located at the specified `zip_path` and extracts them
-/// to the destination directory specified by `dest_dir`.
-/// # Arguments
-/// * `zip_path` - A reference to the path of the zip archive file to be extracted.
-/// * `dest_dir` - A reference to the directory where the contents of the zip archive will be extracted.
-/// # Returns
-/// Returns a `Result` with the unit type `()` if the operation is successful. If an error occurs
-/// during the unzip process, a `ZipError` is returned, encapsulating the specific error information.
-fn unzip_file(zip_path: &Path, dest_dir: &Path) -> Result<(), ZipError> {
-	let file = File::open(zip_path)?;
-	let reader = io::BufReader::new(file);
-	let mut zip = zip::ZipArchive::new(reader)?;
-
-	for i in 0..zip.len() {
-		let mut entry = zip.by_index(i)?;
-		let entry_path = entry.enclosed_name().to_owned();
-		if entry_path.is_none() {
-			continue;
-		}
-		let entry_dest = dest_dir.join(entry_path.unwrap());
-
-		if (&*entry.name()).ends_with('/') {
-			fs::create_dir_all(&entry_dest)?;
-		} else {
-			if let Some(p) = entry_dest.parent() {
-				if !p.exists() {
-					fs::create_dir_all(&p)?;
+/// Parses the lazily indexed file(s) that provide the given look-up table (if any) during an
+/// evaluation, or else asks the table sources for it, and returns that look-up table
+fn load_lazy_table(lazy: &LazyRegistry, reg: &HashMap<String, Arc<LookUpTable>>, id: &str) -> Result<Option<Arc<LookUpTable>>, ParsingError> {
+	if let Some(lut) = lazy.get_loaded(id) {
+		return Ok(Some(lut));
+	}
+	let mut files = lazy.take_matching(id).into_iter();
+	while let Some(file) = files.next() {
+		// parse the file with a separate interpreter, since the registry is in use
+		let mut parser = Interpreter::from_rng(simple_rng(0));
+		parser.set_load_settings(file.settings.clone());
+		if let Err(e) = parser.load_file_namespaced(file.path.as_path(), file.id_prefix.as_str()) {
+			// try again next time
+			lazy.restore(file);
+			files.for_each(|file| lazy.restore(file));
+			return Err(e);
+		}
+		// create the new tables of table extensions whose base table is in the registry or in
+		// another lazily indexed file (tables that replace existing tables are created after the
+		// evaluation, since the existing tables are in use)
+		let mut extensions = Vec::new();
+		for ext in std::mem::take(&mut parser.extensions) {
+			let lut = match reg.contains_key(&ext.id) || lazy.get_loaded(ext.id.as_str()).is_some() {
+				true => None,
+				false => match reg.get(&ext.base) {
+					Some(base) => Some(ext.apply(base)?),
+					None => load_lazy_table(lazy, reg, ext.base.as_str()).ok().flatten()
+						.map(|base| ext.apply(&base)).transpose()?
 				}
+			};
+			match lut {
+				Some(lut) => {parser.registry.insert(ext.id, Arc::new(lut));},
+				None => extensions.push(ext)
 			}
-			let mut outfile = File::create(&entry_dest)?;
-			std::io::copy(&mut entry, &mut outfile)?;
 		}
+		lazy.add_loaded(parser.registry, parser.sources, extensions, parser.dedup_report);
+	}
+	match lazy.get_loaded(id) {
+		Some(lut) => Ok(Some(lut)),
+		None => Ok(lazy.resolve(id))
+	}
+}
+
+/// Checks that the given default substitution options of a look-up table (eg from `$options` in a
+/// YAML file) do not set any options that only make sense in a substitution token
+fn parse_table_defaults(defaults: SubstitutionOptions, id: &str) -> Result<SubstitutionOptions, ParsingError> {
+	if !defaults.id.is_empty() || defaults.reference.is_some() || defaults.row.is_some() || defaults.ref_prefix.is_some()
+			|| defaults.persist.is_some() || defaults.select.is_some() {
+		return Err(InvalidCombinationError::new(format!(
+			"Default options of '{}' cannot set 'id', 'ref', 'row', 'ref-prefix', 'persist', or 'select'", id
+		)).into());
+	}
+	Ok(defaults)
+}
+
+/// Gets the ID of the hidden look-up table that holds the whole rows of the CSV file with the given
+/// ID (eg "npc-stats/$rows" for "npc-stats")
+fn rows_table_id(id: &str) -> String {
+	match id.is_empty() {
+		true => String::from(ROWS_TABLE),
+		false => format!("{}/{}", id, ROWS_TABLE)
+	}
+}
+
+/// Checks whether the given look-up table ID is in the given namespace (eg "my-story/animal" is
+/// in namespace "my-story"), which includes the namespace ID itself
+fn in_namespace(id: &str, prefix: &str) -> bool {
+	id == prefix || (id.starts_with(prefix) && id[prefix.len()..].starts_with('/'))
+}
+
+/// Checks whether the given ID is the ID of a hidden table of CSV rows
+fn is_rows_table(id: &str) -> bool {
+	id == ROWS_TABLE || id.ends_with(format!("/{}", ROWS_TABLE).as_str())
+}
+
+/// Gets the base look-up table ID of a look-up table file (eg "animal" for file "animal.txt" or
+/// "names" for file "names.csv.gz")
+fn table_base_id(id_prefix: &str, file_name: &str) -> String {
+	let mut base_name = file_name;
+	if base_name.to_lowercase().ends_with(".gz") {
+		base_name = &base_name[0..base_name.len() - 3];
+	}
+	namespaced_id(id_prefix, &base_name[0..base_name.rfind('.').unwrap_or(base_name.len())])
+}
+
+/// Gets the ID of a directory or look-up table within the given namespace, separated by `/`
+/// (eg "pack/elf" for directory "elf" in namespace "pack", or just "elf" without a namespace)
+fn namespaced_id(id_prefix: &str, name: &str) -> String {
+	match id_prefix.is_empty() {
+		true => String::from(name),
+		false => format!("{}/{}", id_prefix, name)
 	}
-	Ok(())
+}
+
+/// Compiled filters for loading a directory (see [DirLoadOptions])
+struct DirFilter {
+	include: Vec<glob::Pattern>,
+	exclude: Vec<glob::Pattern>,
+	max_depth: Option<usize>,
+	follow_symlinks: bool
+}
+
+/// Checks whether a file or directory matches the given pattern. Patterns that contain a `/` are
+/// matched against the path relative to the loaded directory, other patterns against the name.
+fn pattern_matches(pattern: &glob::Pattern, rel_path: &str, name: &str) -> bool {
+	let options = glob::MatchOptions{require_literal_separator: true, ..Default::default()};
+	match pattern.as_str().contains('/') {
+		true => pattern.matches_with(rel_path, options),
+		false => pattern.matches_with(name, options)
+	}
+}
+
+/// Checks whether the given path is a glob pattern (eg "tables/**/*.yaml")
+fn is_glob(path: &str) -> bool {
+	path.contains(['*', '?', '['])
+}
+
+/// Checks whether the given file name is a supported look-up table file (eg "animal.txt" or
+/// "names.csv.gz"), which should be loaded when scanning a directory or .zip archive. Files
+/// without a file extension (eg "LICENSE" or "Makefile") are skipped, since they are rarely
+/// look-up tables, but they can still be loaded by name (see [Format::sniff(...)](Format::sniff)).
+fn is_table_file(file_name: &str) -> bool {
+	let file_name = file_name.to_lowercase();
+	let file_name = file_name.strip_suffix(".gz").unwrap_or(file_name.as_str());
+	match file_name.rfind('.') {
+		None => false,
+		Some(i) => TABLE_FILE_TYPES.contains(&&file_name[i + 1..])
+	}
+}
+
+/// Checks whether the given path is an HTTP(S) URL rather than a local filepath
+fn is_url(path: &str) -> bool {
+	let path = path.to_lowercase();
+	path.starts_with("http://") || path.starts_with("https://")
 }
 
 #[cfg(test)]
 mod unit_tests {
 	use std::io::BufReader;
 	use utf8_chars::BufReadCharsExt;
-	use crate::{DICE_START, read_csv_row, SUB_START};
+	use crate::{CsvOptions, DICE_START, read_csv_row, SUB_START};
 
 	#[test]
 	fn test_next_token() {
@@ -1323,91 +5899,153 @@ mod unit_tests {
 		);
 	}
 
+	#[test]
+	fn test_change_case_first() {
+		use crate::change_case;
+		let first = String::from("first");
+		assert_eq!(change_case(String::from("cat nap"), Some(&first)).unwrap(), "Cat nap");
+		assert_eq!(change_case(String::new(), Some(&first)).unwrap(), "");
+		assert_eq!(change_case(String::from("éclair"), Some(&first)).unwrap(), "Éclair");
+		assert_eq!(change_case(String::from("ßeta"), Some(&first)).unwrap(), "SSeta");
+	}
+
+	#[test]
+	fn test_matrix_id_to_path() {
+		use crate::matrix_id_to_path;
+		assert_eq!(matrix_id_to_path("reaction[happy][elves]"), "reaction/happy/elves");
+		assert_eq!(matrix_id_to_path("reaction[ happy ]"), "reaction/happy");
+		assert_eq!(matrix_id_to_path("reaction"), "reaction");
+	}
+
+	#[test]
+	fn test_split_value_lookup() {
+		use std::collections::HashMap;
+		use crate::split_value_lookup;
+		let reg = HashMap::new();
+		assert_eq!(split_value_lookup("strength-desc:11", &reg).unwrap(), ("strength-desc", Some(11.)));
+		assert_eq!(split_value_lookup("cold: -5", &reg).unwrap(), ("cold", Some(-5.)));
+		assert_eq!(split_value_lookup("var:mood", &reg).unwrap(), ("var:mood", None));
+		assert_eq!(split_value_lookup("@table:weather:3", &reg).unwrap(), ("@table:weather:3", None));
+		assert!(split_value_lookup("weather:cold", &reg).is_err());
+	}
+
+	#[test]
+	fn test_parse_range_key() {
+		use crate::parse_range_key;
+		assert_eq!(parse_range_key("3-5"), Some((3., 5.)));
+		assert_eq!(parse_range_key(" 10 - 12 "), Some((10., 12.)));
+		assert_eq!(parse_range_key("7"), Some((7., 7.)));
+		assert_eq!(parse_range_key("13+"), Some((13., f64::INFINITY)));
+		assert_eq!(parse_range_key("01–05"), Some((1., 5.)));
+		assert_eq!(parse_range_key("96-00"), Some((96., 100.)));
+		assert_eq!(parse_range_key("feeble"), None);
+		assert_eq!(parse_range_key("3-"), None);
+		assert_eq!(parse_range_key("-5--1"), Some((-5., -1.)));
+		assert_eq!(parse_range_key("-2-2"), Some((-2., 2.)));
+		assert_eq!(parse_range_key("-3+"), Some((-3., f64::INFINITY)));
+	}
+
 	#[test]
 	fn test_read_csv_row_01() {
 		let mut src = BufReader::new("a,b,c".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b", "c"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_02() {
 		let mut src = BufReader::new("a,b,c\r\n".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b", "c"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_03() {
 		let mut src = BufReader::new("a,b without quotes,c".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b without quotes", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b without quotes", "c"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_04() {
 		let mut src = BufReader::new(r#"a,"b with quotes",c"#.as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b with quotes", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b with quotes", "c"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_05() {
 		let mut src = BufReader::new(r#"a,b with ""quotes"",c"#.as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b with \"quotes\"", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b with \"quotes\"", "c"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_06() {
 		let mut src = BufReader::new(r#"a,"b with more ""quotes""",c"#.as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b with more \"quotes\"", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b with more \"quotes\"", "c"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_07() {
 		let mut src = BufReader::new("a,b,c\r\n1,2,3".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b", "c"]);
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["1", "2", "3"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["1", "2", "3"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_08() {
 		let mut src = BufReader::new("a,b,c\r\n\r\n1,2,3".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b", "c"]);
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["1", "2", "3"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["1", "2", "3"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_09() {
 		let mut src = BufReader::new("a,b,c\n\n1,2,3\n".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b", "c"]);
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["1", "2", "3"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["1", "2", "3"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_10() {
 		let mut src = BufReader::new("a,b,c\n\n\n\n\n1,2,3\n".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b", "c"]);
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["1", "2", "3"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["1", "2", "3"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_11() {
 		let mut src = BufReader::new("a,\"b with\nnew-line\",c".as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b with\nnew-line", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b with\nnew-line", "c"]);
 	}
 
 	#[test]
 	fn test_read_csv_row_12() {
 		let mut src = BufReader::new(r#"a,"b with, comma",c"#.as_bytes());
 		let mut iter = src.chars();
-		assert_eq!(read_csv_row(&mut iter).unwrap(), vec!["a", "b with, comma", "c"]);
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::default()).unwrap(), vec!["a", "b with, comma", "c"]);
+	}
+
+	#[test]
+	fn test_read_csv_row_13() {
+		let mut src = BufReader::new("a;\"b; c\";d,e\n1;2;3".as_bytes());
+		let mut iter = src.chars();
+		let options = CsvOptions::with_delimiter(';');
+		assert_eq!(read_csv_row(&mut iter, &options).unwrap(), vec!["a", "b; c", "d,e"]);
+		assert_eq!(read_csv_row(&mut iter, &options).unwrap(), vec!["1", "2", "3"]);
+	}
+
+	#[test]
+	fn test_read_csv_row_14() {
+		let mut src = BufReader::new("a\t\"b\"\tc, d".as_bytes());
+		let mut iter = src.chars();
+		assert_eq!(read_csv_row(&mut iter, &CsvOptions::tsv()).unwrap(), vec!["a", "\"b\"", "c, d"]);
 	}
 }
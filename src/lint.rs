@@ -0,0 +1,139 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use regex::Regex;
+use crate::subspec::SubstitutionOptions;
+use crate::{next_token, parse_token, SUB_START};
+
+/// The kinds of problems that [Interpreter::lint(...)](crate::Interpreter::lint) reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LintKind {
+	/// The front matter is not valid, so it is treated as part of the text
+	InvalidFrontMatter,
+	/// The substitution token cannot be parsed, so the evaluation would fail
+	InvalidToken,
+	/// The substitution token has an option that is not known (eg the typo `sepp`), which is
+	/// silently ignored
+	UnknownOption,
+	/// The substitution token looks like JSON/YAML options that are only enclosed in single
+	/// braces (eg `${id: animal, count: 2}` instead of `${{id: animal, count: 2}}`)
+	SingleBraceOptions,
+	/// The substitution token uses a reference before any token (or the front matter) defines
+	/// it
+	RefBeforeDefinition,
+	/// The substitution token has a `last-sep` option that is never used, because it draws at
+	/// most one item or has no `sep` option
+	UnreachableLastSep
+}
+
+/// A possible problem in a template or look-up table item that does not stop the evaluation
+/// (see [Interpreter::lint(...)](crate::Interpreter::lint))
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+	/// The kind of problem
+	pub kind: LintKind,
+	/// The substitution token as it was written (eg `${{id: animal, sepp: ", "}}`), or the front
+	/// matter
+	pub token: String,
+	/// Description of the problem
+	pub message: String
+}
+
+impl Display for Lint {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}: {}", self.token, self.message)
+	}
+}
+
+/// Checks the substitution tokens of a text for possible problems, in the order of the tokens
+/// # Arguments
+/// * `text`: The text (without front matter)
+/// * `refs_defined`: The references that are defined before the text (eg by the front matter),
+///   or `None` to not check whether references are defined
+/// * `default_sep`: Whether there is a default separator (see
+///   [EvalOptions::default_sep](crate::EvalOptions::default_sep))
+/// # Returns
+/// The possible problems
+pub(crate) fn lint_text(text: &str, refs_defined: Option<BTreeSet<String>>, default_sep: bool) -> Vec<Lint> {
+	let ref_finder: Regex = Regex::new(r#"\$[\d\pL_\-+]+"#).unwrap();
+	let text = String::from(text);
+	let mut lints: Vec<Lint> = Vec::new();
+	let mut refs_defined = refs_defined;
+	// a row draw defines a reference for each column, whose names are not known here
+	let mut row_prefixes: Vec<String> = Vec::new();
+	let mut pos = 0;
+	while let Some((start, end)) = next_token(&text, pos, SUB_START) {
+		pos = end;
+		let full_token = &text[start..end];
+		let token = text[start + SUB_START.len()..end - 1].trim();
+		let mut lint = |kind: LintKind, message: String| lints.push(Lint{kind, token: String::from(full_token), message});
+		if !token.starts_with('{') {
+			let key = token.split_once(':').map(|(key, _)| key.trim().trim_matches('"'));
+			if key.is_some_and(|key| !key.is_empty() && SubstitutionOptions::is_known_key(key)) {
+				lint(LintKind::SingleBraceOptions, String::from("looks like JSON/YAML options, which must be enclosed in double braces (eg '${{id: animal, count: 2}}')"));
+			}
+		}
+		let sub = match parse_token(token) {
+			Ok(sub) => sub,
+			Err(e) => {
+				lint(LintKind::InvalidToken, format!("cannot be parsed: {}", e));
+				continue;
+			}
+		};
+		for key in SubstitutionOptions::unknown_keys(token) {
+			lint(LintKind::UnknownOption, format!("option '{}' is not known and is ignored", key));
+		}
+		if sub.last_sep.is_some() {
+			let single = match &sub.count {
+				None => true,
+				Some(serde_yaml::Value::Number(n)) => n.as_u64().is_some_and(|n| n <= 1),
+				Some(_) => false
+			};
+			if single {
+				lint(LintKind::UnreachableLastSep, String::from("option 'last-sep' is never used, because only one item is drawn (see option 'count')"));
+			} else if sub.sep.is_none() && !default_sep {
+				lint(LintKind::UnreachableLastSep, String::from("option 'last-sep' is never used without option 'sep'"));
+			}
+		}
+		if let Some(defined) = &mut refs_defined {
+			let mut used: Vec<&str> = ref_finder.find_iter(sub.id.as_str()).map(|m| &m.as_str()[1..]).collect();
+			if let Some(ref_id) = sub.id.strip_prefix('@') {
+				used.push(ref_id.split_once('.').map_or(ref_id, |(ref_id, _)| ref_id));
+			}
+			for ref_id in used {
+				let from_row = row_prefixes.iter().any(|prefix| ref_id.starts_with(prefix.as_str()));
+				if !defined.contains(ref_id) && !from_row {
+					lint(LintKind::RefBeforeDefinition, format!("reference '{}' is used before it is defined", ref_id));
+				}
+			}
+			if let Some(ref_id) = &sub.reference {
+				defined.insert(ref_id.clone());
+			}
+			if let Some(row_id) = &sub.row {
+				let prefix = sub.ref_prefix.as_deref().unwrap_or_else(|| row_id.rsplit('/').next().unwrap_or(row_id));
+				row_prefixes.push(format!("{}-", prefix));
+			}
+		}
+	}
+	lints
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use std::collections::BTreeSet;
+	use crate::lint::{lint_text, LintKind};
+
+	#[test]
+	fn test_lint_text() {
+		let text = "${@owner} ${id: animal, count: 2} ${{id: animal, sepp: ', '}} ${@pet} ${animal@pet} ${@pet} \
+			${{id: animal, last-sep: ' and '}} ${{row: npc}} ${@npc-name}";
+		let kinds: Vec<LintKind> = lint_text(text, Some(BTreeSet::from([String::from("owner")])), false)
+			.into_iter().map(|lint| lint.kind).collect();
+		assert_eq!(kinds, vec![LintKind::SingleBraceOptions, LintKind::UnknownOption, LintKind::RefBeforeDefinition,
+			LintKind::UnreachableLastSep]);
+		assert!(lint_text("${{id: animal, count: 3, last-sep: ' and '}}", None, true).is_empty());
+		assert_eq!(lint_text("${{id: animal, count: 3, last-sep: ' and '}}", None, false)[0].kind, LintKind::UnreachableLastSep);
+		assert_eq!(lint_text("${{case: upper}}", None, false)[0].kind, LintKind::InvalidToken);
+	}
+}
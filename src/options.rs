@@ -0,0 +1,395 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use crate::errors::ParseError;
+
+/// Compatibility level, which selects the default [EvalOptions] for an
+/// [Interpreter](crate::Interpreter). Newer levels opt into improved defaults, while older levels
+/// keep existing look-up tables and templates rendering exactly as before.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum CompatLevel {
+	/// Original behavior (default)
+	#[default]
+	V1,
+	/// Improved defaults: multiple drawn items are separated by `", "` unless `sep` is given,
+	/// leading and trailing whitespace is stripped from drawn items, and evaluated texts may start
+	/// with front matter
+	V2
+}
+
+impl CompatLevel {
+	/// The newest compatibility level
+	pub const LATEST: CompatLevel = CompatLevel::V2;
+}
+
+impl Display for CompatLevel {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			CompatLevel::V1 => write!(f, "v1"),
+			CompatLevel::V2 => write!(f, "v2")
+		}
+	}
+}
+
+impl FromStr for CompatLevel {
+	type Err = ParseError;
+
+	/// Parses a compatibility level, such as "v1" or "2"
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"v1" | "1" => Ok(CompatLevel::V1),
+			"v2" | "2" => Ok(CompatLevel::V2),
+			"latest" => Ok(CompatLevel::LATEST),
+			_ => Err(ParseError{msg: Some(format!("'{}' is not a valid compatibility level", s)), line: None, col: None})
+		}
+	}
+}
+
+/// Whether the items that are drawn from a look-up table are put back, or are used up like
+/// cards drawn from a deck. Use [Interpreter::set_depletion(...)](crate::Interpreter::set_depletion)
+/// to set it for a look-up table, or `method: deplete` to deplete a table for a single draw.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Depletion {
+	/// Drawn items are put back into the look-up table (default)
+	#[default]
+	Replace,
+	/// Drawn items are used up until the look-up table is empty, after which drawing from the
+	/// table is an error (until the table is reset)
+	Deplete,
+	/// Drawn items are used up until the look-up table is empty, after which all items are put
+	/// back into the table
+	#[serde(rename = "deplete-reset")]
+	DepleteAndReset
+}
+
+impl Display for Depletion {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Depletion::Replace => write!(f, "replace"),
+			Depletion::Deplete => write!(f, "deplete"),
+			Depletion::DepleteAndReset => write!(f, "deplete-reset")
+		}
+	}
+}
+
+impl FromStr for Depletion {
+	type Err = ParseError;
+
+	/// Parses a depletion mode: "replace", "deplete", or "deplete-reset"
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"replace" => Ok(Depletion::Replace),
+			"deplete" => Ok(Depletion::Deplete),
+			"deplete-reset" => Ok(Depletion::DepleteAndReset),
+			_ => Err(ParseError{msg: Some(format!("'{}' is not a valid depletion mode", s)), line: None, col: None})
+		}
+	}
+}
+
+/// What to do with duplicate items (items with the same text or stable key) in a look-up table
+/// when loading look-up tables. Use [Interpreter::set_dedup(...)](crate::Interpreter::set_dedup)
+/// to set it for all following loads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Dedup {
+	/// Duplicate items are kept as separate items, such that they are drawn more often (default)
+	#[default]
+	Keep,
+	/// Duplicate items are merged into one item whose weight is the sum of their weights, which
+	/// keeps the probabilities the same
+	Sum,
+	/// Duplicate items are merged into one item whose weight is the largest of their weights,
+	/// which undoes the skew of concatenated lists
+	Max
+}
+
+impl Display for Dedup {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Dedup::Keep => write!(f, "keep"),
+			Dedup::Sum => write!(f, "sum"),
+			Dedup::Max => write!(f, "max")
+		}
+	}
+}
+
+impl FromStr for Dedup {
+	type Err = ParseError;
+
+	/// Parses a deduplication mode: "keep", "sum", or "max"
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"keep" => Ok(Dedup::Keep),
+			"sum" => Ok(Dedup::Sum),
+			"max" => Ok(Dedup::Max),
+			_ => Err(ParseError{msg: Some(format!("'{}' is not a valid deduplication mode", s)), line: None, col: None})
+		}
+	}
+}
+
+/// What happens when a substitution token cannot be evaluated (see [Strictness])
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ErrorPolicy {
+	/// The evaluation fails with an error (default)
+	#[default]
+	Error,
+	/// The token is replaced by a placeholder like `⟦missing: animal⟧`, and a warning is recorded
+	/// (see [Interpreter::get_warnings()](crate::Interpreter::get_warnings))
+	Placeholder,
+	/// The token is left in the text as it was written (eg `${animal}`), and a warning is recorded
+	Literal
+}
+
+impl Display for ErrorPolicy {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ErrorPolicy::Error => write!(f, "error"),
+			ErrorPolicy::Placeholder => write!(f, "placeholder"),
+			ErrorPolicy::Literal => write!(f, "literal")
+		}
+	}
+}
+
+impl FromStr for ErrorPolicy {
+	type Err = ParseError;
+
+	/// Parses an error policy: "error", "placeholder", or "literal"
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"error" => Ok(ErrorPolicy::Error),
+			"placeholder" => Ok(ErrorPolicy::Placeholder),
+			"literal" => Ok(ErrorPolicy::Literal),
+			_ => Err(ParseError{msg: Some(format!("'{}' is not a valid error policy", s)), line: None, col: None})
+		}
+	}
+}
+
+/// How strictly each kind of problem in a substitution token is treated (see
+/// [Interpreter::set_strictness(...)](crate::Interpreter::set_strictness)), such as to fail on
+/// every problem in a release build, but to see the rest of the output while authoring
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Strictness {
+	/// Missing look-up tables and references
+	pub missing: ErrorPolicy,
+	/// Look-up tables that have no items to draw from (eg because every item has a weight of 0)
+	pub empty_tables: ErrorPolicy,
+	/// Substitution options with a value that is not allowed (eg `case: shouting` or
+	/// `method: sorted`)
+	pub invalid_options: ErrorPolicy,
+	/// Substitution tokens that cannot be parsed (eg invalid JSON/YAML options)
+	pub invalid_tokens: ErrorPolicy
+}
+
+impl Strictness {
+	/// Every problem stops the evaluation with an error (default)
+	pub fn strict() -> Self {
+		Strictness::all(ErrorPolicy::Error)
+	}
+
+	/// Every problem is replaced by a placeholder and recorded as a warning
+	pub fn lenient() -> Self {
+		Strictness::all(ErrorPolicy::Placeholder)
+	}
+
+	/// The same policy for every kind of problem
+	/// # Arguments
+	/// * `policy`: what happens when a token cannot be evaluated
+	pub fn all(policy: ErrorPolicy) -> Self {
+		Strictness{missing: policy, empty_tables: policy, invalid_options: policy, invalid_tokens: policy}
+	}
+}
+
+/// Behavior flags that control how text is evaluated. Use [EvalOptions::for_compat(...)](EvalOptions::for_compat)
+/// to get the defaults for a given compatibility level, then change individual flags as needed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalOptions {
+	/// Compatibility level that these options are based on
+	pub compat: CompatLevel,
+	/// Separator placed between multiple drawn items when the `sep` option is not given (`None`
+	/// means that the items are joined without a separator)
+	pub default_sep: Option<String>,
+	/// If true, then leading and trailing whitespace is stripped from drawn items, unless the
+	/// `strip` option is set to false
+	pub strip_items: bool,
+	/// If true, then a missing look-up table or reference does not abort the evaluation. Instead,
+	/// it is replaced by a placeholder like `⟦missing: animal⟧` and a warning is recorded (see
+	/// [Interpreter::get_warnings()](crate::Interpreter::get_warnings)). This is the same as
+	/// `strictness.missing` being [ErrorPolicy::Placeholder], and takes precedence over
+	/// [ErrorPolicy::Error].
+	pub lenient_missing: bool,
+	/// If true, then a look-up table ID that is not registered as written also matches a
+	/// registered ID that differs only in letter case, Unicode normalization, or `-` vs `_`
+	/// separators (eg `Monster_Names` matches `monster-names`)
+	pub loose_ids: bool,
+	/// What happens when a substitution token cannot be evaluated, for each kind of problem
+	pub strictness: Strictness,
+	/// Maximum length (in bytes) of the text while it is evaluated, or `None` for no limit. A
+	/// longer text stops the evaluation with an error.
+	pub max_output_len: Option<usize>,
+	/// Maximum number of substitution tokens that are substituted in one evaluation (including
+	/// the tokens in the drawn items), or `None` for no limit. More substitutions stop the
+	/// evaluation with an error.
+	pub max_substitutions: Option<usize>,
+	/// If true, then a front matter block at the start of an evaluated text (between two `---`
+	/// lines, see [FrontMatter](crate::FrontMatter)) sets the default references and is removed
+	/// from the text. Otherwise, such a block is evaluated as text.
+	pub front_matter: bool
+}
+
+impl EvalOptions {
+	/// Gets the default options for the given compatibility level
+	/// # Arguments
+	/// * `compat`: compatibility level
+	/// # Returns
+	/// The default `EvalOptions` for that compatibility level
+	pub fn for_compat(compat: CompatLevel) -> Self {
+		match compat {
+			CompatLevel::V1 => EvalOptions{compat, default_sep: None, strip_items: false, lenient_missing: false,
+				loose_ids: false, strictness: Strictness::strict(), max_output_len: None, max_substitutions: None,
+				front_matter: false},
+			CompatLevel::V2 => EvalOptions{compat, default_sep: Some(String::from(", ")), strip_items: true,
+				lenient_missing: false, loose_ids: false, strictness: Strictness::strict(), max_output_len: None,
+				max_substitutions: None, front_matter: true}
+		}
+	}
+}
+
+impl Default for EvalOptions {
+	fn default() -> Self {
+		EvalOptions::for_compat(CompatLevel::default())
+	}
+}
+
+/// Options for reading delimiter-separated look-up tables, such as .csv and .tsv files (see
+/// [Interpreter::load_csv_with(...)](crate::Interpreter::load_csv_with))
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvOptions {
+	/// Character that separates the cells of a row (default is `,`)
+	pub delimiter: char,
+	/// Character used to quote cells that contain the delimiter or line breaks (default is `"`),
+	/// or `None` if cells are never quoted
+	pub quote: Option<char>,
+	/// If true, then the whole rows are kept too (in a hidden look-up table), such that the
+	/// `row` substitution option can draw all columns of a row together (default is false, since
+	/// this doubles the memory that the look-up tables of the file use)
+	pub rows: bool
+}
+
+impl CsvOptions {
+	/// Options for tab-separated values (.tsv files), which use tabs as the delimiter and do not
+	/// quote cells
+	pub fn tsv() -> Self {
+		CsvOptions{delimiter: '\t', quote: None, rows: false}
+	}
+
+	/// Options for the given delimiter (eg `;` for semicolon-separated values), with `"` quotes
+	/// # Arguments
+	/// * `delimiter`: character that separates the cells of a row
+	pub fn with_delimiter(delimiter: char) -> Self {
+		CsvOptions{delimiter, ..Default::default()}
+	}
+
+	/// Sets whether the whole rows are kept too, for the `row` substitution option (see
+	/// [rows](CsvOptions::rows))
+	/// # Arguments
+	/// * `rows`: true to keep the rows
+	pub fn with_rows(mut self, rows: bool) -> Self {
+		self.rows = rows;
+		self
+	}
+}
+
+impl Default for CsvOptions {
+	fn default() -> Self {
+		CsvOptions{delimiter: ',', quote: Some('"'), rows: false}
+	}
+}
+
+/// Options for loading a directory of look-up table files (see
+/// [Interpreter::load_dir_with(...)](crate::Interpreter::load_dir_with)). Glob patterns that contain
+/// a `/` are matched against the path relative to the loaded directory (eg `drafts/*.txt`), and
+/// other patterns are matched against the file or directory name (eg `README*`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirLoadOptions {
+	/// Glob patterns of the files to load (default is empty, which loads all supported files)
+	pub include: Vec<String>,
+	/// Glob patterns of the files and directories to skip (default is empty)
+	pub exclude: Vec<String>,
+	/// Maximum depth of subdirectories to load, where 0 only loads the files in the directory
+	/// itself (default is `None`, which loads all subdirectories)
+	pub max_depth: Option<usize>,
+	/// If true, then symbolic links to files and directories are followed, otherwise they are
+	/// skipped (default is true)
+	pub follow_symlinks: bool,
+	/// How duplicate items are merged while loading the directory (default is `None`, which uses
+	/// the mode of the interpreter, see [Interpreter::set_dedup(...)](crate::Interpreter::set_dedup))
+	pub dedup: Option<Dedup>
+}
+
+impl DirLoadOptions {
+	/// Adds a glob pattern of files to load (eg `*.yaml`), such that only matching files are
+	/// loaded
+	/// # Arguments
+	/// * `pattern`: glob pattern
+	pub fn with_include<T>(mut self, pattern: T) -> Self where T: Into<String> {
+		self.include.push(pattern.into());
+		self
+	}
+
+	/// Adds a glob pattern of files and directories to skip (eg `README*` or `drafts`)
+	/// # Arguments
+	/// * `pattern`: glob pattern
+	pub fn with_exclude<T>(mut self, pattern: T) -> Self where T: Into<String> {
+		self.exclude.push(pattern.into());
+		self
+	}
+
+	/// Sets the maximum depth of subdirectories to load (0 only loads the files in the directory
+	/// itself)
+	/// # Arguments
+	/// * `max_depth`: maximum depth
+	pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+		self.max_depth = Some(max_depth);
+		self
+	}
+
+	/// Sets whether symbolic links are followed
+	/// # Arguments
+	/// * `follow_symlinks`: true to follow symbolic links, false to skip them
+	pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+		self.follow_symlinks = follow_symlinks;
+		self
+	}
+
+	/// Sets how duplicate items are merged while loading the directory, instead of the mode of
+	/// the interpreter. This does not apply to files that are lazily loaded later.
+	/// # Arguments
+	/// * `dedup`: how duplicate items are merged
+	pub fn with_dedup(mut self, dedup: Dedup) -> Self {
+		self.dedup = Some(dedup);
+		self
+	}
+}
+
+impl Default for DirLoadOptions {
+	fn default() -> Self {
+		DirLoadOptions{include: Vec::new(), exclude: Vec::new(), max_depth: None, follow_symlinks: true, dedup: None}
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::options::{CompatLevel, EvalOptions};
+
+	#[test]
+	fn test_compat_parse() {
+		assert_eq!("v1".parse::<CompatLevel>().unwrap(), CompatLevel::V1);
+		assert_eq!("2".parse::<CompatLevel>().unwrap(), CompatLevel::V2);
+		assert_eq!("latest".parse::<CompatLevel>().unwrap(), CompatLevel::LATEST);
+		assert!("v0".parse::<CompatLevel>().is_err());
+		assert_eq!(EvalOptions::default(), EvalOptions::for_compat(CompatLevel::V1));
+		assert!(CompatLevel::V1 < CompatLevel::V2);
+	}
+}
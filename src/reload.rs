@@ -0,0 +1,79 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::data::LookUpTable;
+
+/// A look-up table file that was loaded from disk, which is remembered so that its look-up
+/// tables can be swapped out when the file is re-loaded
+#[derive(Clone, Debug)]
+pub(crate) struct SourceFile {
+	/// Canonical path of the file
+	pub path: PathBuf,
+	/// ID prefix that the file was loaded with
+	pub id_prefix: String,
+	/// The look-up tables that were loaded from the file, by ID, which may only be part of the
+	/// registered look-up tables with the same IDs (if other files added items to them)
+	pub tables: BTreeMap<String, Arc<LookUpTable>>,
+	/// Canonical paths of the files that the file includes (with `$include` or `!include`),
+	/// which re-load the file when they change
+	pub includes: BTreeSet<PathBuf>
+}
+
+/// Watches the directories of the loaded look-up table files for changes (requires the
+/// `hot-reload` feature)
+#[cfg(feature = "hot-reload")]
+#[derive(Debug)]
+pub(crate) struct FileWatcher {
+	watcher: notify::RecommendedWatcher,
+	events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+	watched_dirs: BTreeSet<PathBuf>,
+	/// Changed files that have not been re-loaded yet
+	pending: BTreeSet<PathBuf>
+}
+
+#[cfg(feature = "hot-reload")]
+impl FileWatcher {
+	/// Creates a new file watcher that is not watching anything yet
+	pub fn new() -> notify::Result<Self> {
+		let (sender, events) = std::sync::mpsc::channel();
+		let watcher = notify::recommended_watcher(sender)?;
+		Ok(FileWatcher{watcher, events, watched_dirs: BTreeSet::new(), pending: BTreeSet::new()})
+	}
+
+	/// Starts watching the given file. The parent directory is watched instead of the file itself,
+	/// because many text editors save a file by replacing it with a new file.
+	/// # Arguments
+	/// * `file`: canonical path of the file to watch
+	pub fn watch(&mut self, file: &std::path::Path) -> notify::Result<()> {
+		use notify::Watcher;
+		if let Some(dir) = file.parent() {
+			if !self.watched_dirs.contains(dir) {
+				self.watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+				self.watched_dirs.insert(dir.to_path_buf());
+			}
+		}
+		Ok(())
+	}
+
+	/// Takes all of the files that were created, modified, or removed since the last call
+	/// # Returns
+	/// The paths of the changed files, in sorted order
+	pub fn take_changed(&mut self) -> BTreeSet<PathBuf> {
+		while let Ok(event) = self.events.try_recv() {
+			if let Ok(event) = event {
+				if event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove() {
+					self.pending.extend(event.paths);
+				}
+			}
+		}
+		std::mem::take(&mut self.pending)
+	}
+
+	/// Puts back changed files that could not be re-loaded yet, so that they are returned by the
+	/// next call to [take_changed()](FileWatcher::take_changed)
+	pub fn defer(&mut self, paths: impl IntoIterator<Item=PathBuf>) {
+		self.pending.extend(paths);
+	}
+}
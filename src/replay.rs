@@ -0,0 +1,163 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use crate::errors::{ParseError, ParsingError};
+
+/// Version of the draw log format, which is increased whenever the format changes in a way that
+/// older versions of twas cannot read
+pub(crate) const LOG_VERSION: u32 = 1;
+
+/// A compact log of every random decision of an evaluation (see
+/// [Interpreter::eval_recorded(...)](crate::Interpreter::eval_recorded)), which reproduces the
+/// exact same text with [Interpreter::eval_replay(...)](crate::Interpreter::eval_replay), such as
+/// to attach a generated document to a bug report. Unlike a seed, the log does not depend on the
+/// order of the items in the look-up tables or on the random number generator, because it names
+/// the drawn items. The log is written as compact JSON by [to_string()](ToString::to_string) and
+/// read with [parse()](str::parse).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DrawLog {
+	/// Version of the draw log format
+	pub version: u32,
+	/// The evaluated text
+	pub text: String,
+	/// The text of each persistent reference at the start of the evaluation, by reference ID
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub refs: BTreeMap<String, String>,
+	/// The random decisions, in the order that they were made
+	pub decisions: Vec<Decision>
+}
+
+/// A random decision of an evaluation (see [DrawLog])
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+	/// The items that a substitution token (or a `row` draw) drew
+	Draw(Vec<DrawnItem>),
+	/// The text that a dice expression rolled, or that a template function or host variable
+	/// produced
+	Text(String)
+}
+
+/// An item that was drawn (see [Decision::Draw])
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DrawnItem {
+	/// ID of the look-up table that the item belongs to (the linked look-up table if the item
+	/// was drawn through a link)
+	pub table: String,
+	/// ID of the item (its key, or else its text, see [Item::get_id()](crate::Item::get_id))
+	pub item: String,
+	/// The number that was rolled, if the item is a number range
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub number: Option<String>
+}
+
+impl Display for DrawLog {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", serde_json::to_string(self).map_err(|_| std::fmt::Error)?)
+	}
+}
+
+impl FromStr for DrawLog {
+	type Err = ParsingError;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let log: DrawLog = serde_json::from_str(s)?;
+		if log.version > LOG_VERSION {
+			return Err(ParseError{
+				msg: Some(format!("Draw log version {} is not supported (the newest supported version is {})", log.version, LOG_VERSION)),
+				line: None, col: None
+			}.into());
+		}
+		Ok(log)
+	}
+}
+
+/// Records the random decisions of an evaluation, or plays them back instead of deciding at
+/// random
+#[derive(Debug)]
+pub(crate) enum DrawTape {
+	/// Recording the decisions
+	Record(Vec<Decision>),
+	/// Playing back the decisions, with the position of the next decision
+	Replay(Vec<Decision>, usize)
+}
+
+impl DrawTape {
+	/// Whether the decisions are played back
+	pub fn is_replay(&self) -> bool {
+		matches!(self, DrawTape::Replay(..))
+	}
+
+	/// Records a decision (nothing happens when playing back)
+	pub fn record(&mut self, decision: Decision) {
+		if let DrawTape::Record(decisions) = self {
+			decisions.push(decision);
+		}
+	}
+
+	/// Gets the next drawn items when playing back
+	/// # Returns
+	/// The items, or an error if the next decision is not a draw
+	pub fn next_draw(&mut self) -> Result<Vec<DrawnItem>, ParsingError> {
+		match self.next()? {
+			Decision::Draw(items) => Ok(items),
+			Decision::Text(text) => Err(mismatch(format!("expected a draw, found text '{}'", text)))
+		}
+	}
+
+	/// Gets the next text (eg a dice roll) when playing back
+	/// # Returns
+	/// The text, or an error if the next decision is not a text
+	pub fn next_text(&mut self) -> Result<String, ParsingError> {
+		match self.next()? {
+			Decision::Text(text) => Ok(text),
+			Decision::Draw(_) => Err(mismatch(String::from("expected text, found a draw")))
+		}
+	}
+
+	/// Gets the next decision when playing back
+	fn next(&mut self) -> Result<Decision, ParsingError> {
+		match self {
+			DrawTape::Record(_) => Err(mismatch(String::from("nothing is played back"))),
+			DrawTape::Replay(decisions, pos) => {
+				let decision = decisions.get(*pos).cloned()
+					.ok_or_else(|| mismatch(format!("all {} decisions were used up", decisions.len())))?;
+				*pos += 1;
+				Ok(decision)
+			}
+		}
+	}
+
+	/// Takes the recorded decisions
+	pub fn into_decisions(self) -> Vec<Decision> {
+		match self {
+			DrawTape::Record(decisions) | DrawTape::Replay(decisions, _) => decisions
+		}
+	}
+}
+
+/// Creates the error of a draw log that does not match the evaluation
+pub(crate) fn mismatch(what: String) -> ParsingError {
+	ParseError{msg: Some(format!("Draw log does not match the evaluation: {}", what)), line: None, col: None}.into()
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::replay::{Decision, DrawLog, DrawTape, DrawnItem, LOG_VERSION};
+
+	#[test]
+	fn test_draw_log() {
+		let log = DrawLog{version: LOG_VERSION, text: String::from("${animal} #{1d6}"), refs: Default::default(),
+			decisions: vec![Decision::Draw(vec![DrawnItem{table: String::from("animal"), item: String::from("cat"), number: None}]),
+				Decision::Text(String::from("4"))]};
+		let written = log.to_string();
+		assert_eq!(written, r#"{"version":1,"text":"${animal} #{1d6}","decisions":[{"draw":[{"table":"animal","item":"cat"}]},{"text":"4"}]}"#);
+		assert_eq!(written.parse::<DrawLog>().unwrap(), log);
+		let mut tape = DrawTape::Replay(log.decisions, 0);
+		assert!(tape.next_text().is_err());
+		assert_eq!(tape.next_text().unwrap(), "4");
+		assert!(tape.next_draw().is_err());
+	}
+}
@@ -0,0 +1,58 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::fmt::{Debug, Formatter};
+use crate::data::LookUpTable;
+
+/// Provides look-up tables that are not loaded into the interpreter, such as tables backed by a
+/// database, a procedural generator, or a network service (see
+/// [Interpreter::add_table_source(...)](crate::Interpreter::add_table_source)). The interpreter
+/// only asks its table sources for a look-up table when the ID is not in its registry (and not
+/// provided by a lazily loaded file).
+///
+/// Any function or closure that takes a look-up table ID and returns an
+/// `Option<LookUpTable>` is also a table source.
+///
+/// # Example
+/// ```rust
+/// use twas;
+/// struct Dungeon;
+/// impl twas::TableSource for Dungeon {
+///     fn resolve(&self, id: &str) -> Option<twas::LookUpTable> {
+///         let level: u32 = id.strip_prefix("dungeon/level-")?.parse().ok()?;
+///         let mut lut = twas::LookUpTable::new();
+///         lut.add_item(format!("{} goblins", level * 2), 1.);
+///         lut.add_item(format!("a level {} boss", level), 1.);
+///         Some(lut)
+///     }
+/// }
+/// let mut interpreter = twas::Interpreter::new();
+/// interpreter.add_table_source(Dungeon);
+/// println!("{}", interpreter.eval("You meet ${dungeon/level-3}.").unwrap());
+/// ```
+pub trait TableSource: Send + Sync {
+	/// Gets the look-up table with the given ID
+	/// # Arguments
+	/// * `id`: ID of the look-up table
+	/// # Returns
+	/// The look-up table, or `None` if this source does not provide it
+	fn resolve(&self, id: &str) -> Option<LookUpTable>;
+
+	/// Whether the look-up tables from this source are kept in the interpreter's registry after
+	/// the evaluation that needed them (default is true). Return false if the tables change over
+	/// time (eg a procedural generator), such that the source is asked again in every evaluation.
+	fn keep_tables(&self) -> bool {
+		true
+	}
+}
+
+impl<F> TableSource for F where F: Fn(&str) -> Option<LookUpTable> + Send + Sync {
+	fn resolve(&self, id: &str) -> Option<LookUpTable> {
+		self(id)
+	}
+}
+
+impl Debug for dyn TableSource {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "TableSource")
+	}
+}
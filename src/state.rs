@@ -0,0 +1,34 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use crate::data::LookUpTable;
+use crate::deplete::DrawState;
+use crate::RefValue;
+
+/// Version of the saved interpreter state format, which is increased whenever the format changes
+/// in a way that older versions of twas cannot read
+pub(crate) const STATE_VERSION: u32 = 1;
+
+/// Everything that is needed to continue a generation session later (eg after the program was
+/// restarted), as saved by [Interpreter::save_state(...)](crate::Interpreter::save_state)
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SavedState {
+	/// Version of the saved state format
+	pub version: u32,
+	/// Seed of the random number generator, which is re-seeded with this seed when saving, such
+	/// that a restored interpreter continues with exactly the same random draws
+	pub seed: u64,
+	/// Look-up tables by ID
+	pub tables: BTreeMap<String, LookUpTable>,
+	/// Hidden look-up tables of whole CSV rows (for the `row` option) by ID, which are kept apart
+	/// from the look-up tables
+	#[serde(default)]
+	pub rows: BTreeMap<String, LookUpTable>,
+	/// Persistent references by ID
+	#[serde(default)]
+	pub refs: BTreeMap<String, RefValue>,
+	/// Depletion modes of the look-up tables and the items that are used up
+	#[serde(default)]
+	pub draws: DrawState
+}
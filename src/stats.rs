@@ -0,0 +1,99 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+
+/// Summary statistics of a random look-up table (see
+/// [LookUpTable::stats()](crate::LookUpTable::stats)), for sanity-checking large table packs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableStats {
+	/// Number of items
+	pub count: usize,
+	/// Sum of all item weights
+	pub total_weight: f64,
+	/// Smallest item weight (0 if the table is empty)
+	pub min_weight: f64,
+	/// Largest item weight (0 if the table is empty)
+	pub max_weight: f64,
+	/// Shannon entropy of a draw from the table in bits, which is the base-2 logarithm of the
+	/// number of items if all weights are equal, and lower if a few items are drawn most of the
+	/// time (0 if the table has less than two items that can be drawn)
+	pub entropy: f64
+}
+
+impl TableStats {
+	/// Calculates the statistics of the given item weights
+	pub(crate) fn from_weights(weights: impl Iterator<Item=f64> + Clone) -> TableStats {
+		let count = weights.clone().count();
+		let total_weight: f64 = weights.clone().sum();
+		let min_weight = weights.clone().reduce(f64::min).unwrap_or(0.);
+		let max_weight = weights.clone().reduce(f64::max).unwrap_or(0.);
+		let entropy = match total_weight > 0. {
+			false => 0.,
+			true => weights.filter(|w| *w > 0.)
+				.map(|w| w / total_weight)
+				.map(|p| -p * p.log2())
+				.sum::<f64>()
+				.max(0.)
+		};
+		TableStats{count, total_weight, min_weight, max_weight, entropy}
+	}
+}
+
+/// Statistics of all look-up tables in a namespace (see
+/// [Interpreter::registry_stats()](crate::Interpreter::registry_stats))
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct NamespaceStats {
+	/// Number of look-up tables
+	pub tables: usize,
+	/// Number of items in all of the look-up tables
+	pub items: usize,
+	/// Smallest item weight of all of the look-up tables
+	pub min_weight: f64,
+	/// Largest item weight of all of the look-up tables
+	pub max_weight: f64,
+	/// Lowest entropy of any of the look-up tables (see [TableStats::entropy]), where a very low
+	/// entropy suggests that a table almost always produces the same item
+	pub min_entropy: f64,
+	/// Highest entropy of any of the look-up tables (see [TableStats::entropy])
+	pub max_entropy: f64
+}
+
+impl NamespaceStats {
+	/// Adds the statistics of a look-up table to the statistics of its namespace
+	pub(crate) fn add(&mut self, table: &TableStats) {
+		if self.tables == 0 {
+			self.min_weight = table.min_weight;
+			self.max_weight = table.max_weight;
+			self.min_entropy = table.entropy;
+			self.max_entropy = table.entropy;
+		} else {
+			self.min_weight = self.min_weight.min(table.min_weight);
+			self.max_weight = self.max_weight.max(table.max_weight);
+			self.min_entropy = self.min_entropy.min(table.entropy);
+			self.max_entropy = self.max_entropy.max(table.entropy);
+		}
+		self.tables += 1;
+		self.items += table.count;
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::stats::{NamespaceStats, TableStats};
+
+	#[test]
+	fn test_stats() {
+		let stats = TableStats::from_weights([1., 1., 1., 1.].into_iter());
+		assert_eq!(stats, TableStats{count: 4, total_weight: 4., min_weight: 1., max_weight: 1., entropy: 2.});
+		let stats = TableStats::from_weights([3., 1., 0.].into_iter());
+		assert_eq!(stats.min_weight, 0.);
+		assert!((stats.entropy - 0.8112781244591328).abs() < 1e-12);
+		assert_eq!(TableStats::from_weights(std::iter::empty()).entropy, 0.);
+		let mut namespace = NamespaceStats::default();
+		namespace.add(&TableStats::from_weights([1., 1.].into_iter()));
+		namespace.add(&stats);
+		assert_eq!(namespace.tables, 2);
+		assert_eq!(namespace.items, 5);
+		assert_eq!(namespace.max_weight, 3.);
+		assert_eq!(namespace.max_entropy, 1.);
+	}
+}
@@ -4,15 +4,18 @@ use serde::{Serialize, Deserialize};
 use serde_yaml;
 
 /// Struct to hold all the possible substitution options for a substitution token
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct SubstitutionOptions {
-	/// ID of the lookup table to apply
+	/// ID of the lookup table to apply (required unless `row` is used instead)
+	#[serde(default)]
 	pub id: String,
-	/// Option to specify number of items to draw from the lookup table. Can be either a number or
-	/// a dice expression (eg "2d6+3" meaning 'roll two 6-sided dice and then add 3 to the total')
+	/// Option to specify number of items to draw from the lookup table. Can be either a number,
+	/// a dice expression (eg "2d6+3" meaning 'roll two 6-sided dice and then add 3 to the total'),
+	/// or a range (eg `{min: 2, max: 5}` for any whole number from 2 to 5 with equal probability)
 	pub count: Option<serde_yaml::Value>,
-	/// If drawing more than one, what method to use. Either "random" for unbiased random draw or
-	/// "shuffle" to avoid drawing the same item twice (until all items are used)
+	/// If drawing more than one, what method to use. Either "random" for unbiased random draw,
+	/// "shuffle" to avoid drawing the same item twice (until all items are used), or "deplete" (or
+	/// "deplete-reset") to use up drawn items across substitutions and evaluations
 	pub method: Option<String>,
 	/// If drawing more than one item, separate them with this string
 	pub sep: Option<String>,
@@ -32,21 +35,121 @@ pub struct SubstitutionOptions {
 	pub hidden: Option<bool>,
 	/// If set to true, prefix with correct english indefinite article (a/an)
 	pub aan: Option<bool>,
+	/// Draw several candidates for each item and keep only the best (or worst) one, like rolling
+	/// with advantage
+	pub pick: Option<PickOptions>,
+	/// If set to true, each drawn item is evaluated in its own child reference scope, such that
+	/// references created inside the item do not leak into (or overwrite) the other items
+	pub scoped: Option<bool>,
+	/// If set to true, remove leading and trailing whitespace from each drawn item (before adding
+	/// the `prefix` and `suffix`). If not set, the interpreter's evaluation options decide
+	pub strip: Option<bool>,
+	/// If set to true, replace every run of whitespace in each drawn item with a single space and
+	/// remove leading and trailing whitespace (before adding the `prefix` and `suffix`)
+	#[serde(rename="collapse-whitespace")]
+	pub collapse_whitespace: Option<bool>,
+	/// If set to true, identical drawn items are listed once with a count (eg "3x old boot")
+	/// instead of being repeated
+	#[serde(rename="group-duplicates")]
+	pub group_duplicates: Option<bool>,
+	/// Output format of the drawn items. Must be one of: "text" (default), "json" (a JSON array),
+	/// "yaml" (a YAML list)
+	pub format: Option<String>,
+	/// ID of a CSV look-up table file (eg "npc-stats" for file "npc-stats.csv") to draw a whole
+	/// row from instead of drawing from a look-up table, such that the columns of the row stay
+	/// together. Each column of the drawn row is saved as a reference (eg `@npc-stats-name` for
+	/// column `name`), and the row draw itself does not produce any text
+	pub row: Option<String>,
+	/// Prefix of the reference IDs of the columns of a `row` draw (eg "npc" to save column `name`
+	/// as `@npc-name`). Default is the last part of the row ID
+	#[serde(rename="ref-prefix")]
+	pub ref_prefix: Option<String>,
+	/// If set to true, the `ref` reference (and the column references of a `row` draw) is kept
+	/// after the evaluation, such that all later evaluations of the interpreter can use it
+	pub persist: Option<bool>,
+	/// Output the value of this named field of each drawn record item (eg "cr" for the challenge
+	/// rating of a monster) instead of the item's text
+	pub field: Option<String>,
+	/// Maximum number of times that each item of the look-up table can be drawn in a single
+	/// evaluation (eg 1 for "at most one legendary item per hoard"). Items that reached the limit
+	/// are left out of later draws. Items can also set their own limit.
+	#[serde(rename="max-per-eval")]
+	pub max_per_eval: Option<usize>,
+	/// Select the item with this stable key or text instead of drawing at random, which also
+	/// works for items of weight zero (which are never drawn at random). Exactly one item is
+	/// selected, so `count`, `method`, and `pick` do not apply
+	pub select: Option<String>,
+}
+
+/// Options for drawing several candidates and keeping one of them (eg "roll twice, take the
+/// better")
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PickOptions {
+	/// Number of candidates to draw for each item (default is 2)
+	pub of: Option<usize>,
+	/// Criterion used to rank the candidates. Must be one of: "weight" (rarer is better),
+	/// "length" (longer is better), "alpha" (alphabetically first is better). Default is "weight"
+	pub by: Option<String>,
+	/// Which candidate to keep. Must be one of: "best", "worst". Default is "best"
+	pub take: Option<String>,
 }
 
 impl SubstitutionOptions {
 	/// Constructs a new `SubstitutionOptions` with default values
 	pub fn new(id: &str) -> Self {
-		SubstitutionOptions{id: String::from(id), count: None, method: None, sep: None,
-			last_sep: None, prefix: None, suffix: None, case: None, reference: None, hidden: None,
-			aan: None
-		}
+		SubstitutionOptions{id: String::from(id), ..Default::default()}
+	}
+	/// Fills in the options that are not set with the given defaults (eg the default options of a
+	/// look-up table). The ID, reference, `row`, and `persist` options are never taken from the
+	/// defaults.
+	pub fn apply_defaults(&mut self, defaults: &SubstitutionOptions) {
+		if self.count.is_none() { self.count = defaults.count.clone(); }
+		if self.method.is_none() { self.method = defaults.method.clone(); }
+		if self.sep.is_none() { self.sep = defaults.sep.clone(); }
+		if self.last_sep.is_none() { self.last_sep = defaults.last_sep.clone(); }
+		if self.prefix.is_none() { self.prefix = defaults.prefix.clone(); }
+		if self.suffix.is_none() { self.suffix = defaults.suffix.clone(); }
+		if self.case.is_none() { self.case = defaults.case.clone(); }
+		if self.hidden.is_none() { self.hidden = defaults.hidden; }
+		if self.aan.is_none() { self.aan = defaults.aan; }
+		if self.pick.is_none() { self.pick = defaults.pick.clone(); }
+		if self.scoped.is_none() { self.scoped = defaults.scoped; }
+		if self.strip.is_none() { self.strip = defaults.strip; }
+		if self.collapse_whitespace.is_none() { self.collapse_whitespace = defaults.collapse_whitespace; }
+		if self.group_duplicates.is_none() { self.group_duplicates = defaults.group_duplicates; }
+		if self.format.is_none() { self.format = defaults.format.clone(); }
+		if self.field.is_none() { self.field = defaults.field.clone(); }
+		if self.max_per_eval.is_none() { self.max_per_eval = defaults.max_per_eval; }
 	}
 	/// Constructs a new `SubstitutionOptions` with default values plus a reference ID
 	pub fn new_with_ref(id: &str, ref_name: &str) -> Self {
-		SubstitutionOptions{id: String::from(id), count: None, method: None, sep: None,
-			last_sep: None, prefix: None, suffix: None, case: None,
-			reference: Some(ref_name.to_string()), hidden: None, aan: None
+		SubstitutionOptions{id: String::from(id), reference: Some(ref_name.to_string()),
+			..Default::default()}
+	}
+	/// Finds the keys of a JSON/YAML substitution token (eg `{id: animal, sepp: ", "}`) that are
+	/// not substitution options, which are otherwise silently ignored (eg typos)
+	/// # Returns
+	/// The unknown keys, or an empty list if the token is not a JSON/YAML mapping
+	pub fn unknown_keys(token: &str) -> Vec<String> {
+		let known = SubstitutionOptions::known_keys();
+		match serde_yaml::from_str::<serde_yaml::Mapping>(token) {
+			Ok(token) => token.keys().filter(|key| !known.contains_key(*key))
+				.map(|key| key.as_str().map(String::from).unwrap_or_else(|| format!("{:?}", key)))
+				.collect(),
+			Err(_) => Vec::new()
+		}
+	}
+
+	/// Checks whether the given key is a substitution option (eg `last-sep`)
+	pub fn is_known_key(key: &str) -> bool {
+		SubstitutionOptions::known_keys().contains_key(key)
+	}
+
+	/// Gets the keys of all substitution options (with their default values)
+	fn known_keys() -> serde_yaml::Mapping {
+		match serde_yaml::to_value(SubstitutionOptions::default()) {
+			Ok(serde_yaml::Value::Mapping(known)) => known,
+			_ => serde_yaml::Mapping::new()
 		}
 	}
 }
@@ -74,6 +177,24 @@ mod unit_tests {
 		assert!(sub_spec.aan.is_none());
 	}
 	#[test]
+	fn test_unknown_keys() {
+		assert_eq!(SubstitutionOptions::unknown_keys(r#"{id: animal, sepp: ", ", last-sep: " and ", coutn: 2}"#),
+			vec![String::from("sepp"), String::from("coutn")]);
+		assert!(SubstitutionOptions::unknown_keys(r#"{id: animal, max-per-eval: 1, ref: a}"#).is_empty());
+		assert!(SubstitutionOptions::unknown_keys("animal").is_empty());
+	}
+	#[test]
+	fn test_apply_defaults() {
+		let mut sub_spec = SubstitutionOptions::new("city");
+		sub_spec.case = Some(String::from("upper"));
+		let defaults: SubstitutionOptions = serde_yaml::from_str(r#"{case: title, aan: true}"#).expect("Failed to parse");
+		sub_spec.apply_defaults(&defaults);
+		assert_eq!(sub_spec.id.as_str(), "city");
+		assert_eq!(sub_spec.case, Some(String::from("upper")));
+		assert_eq!(sub_spec.aan, Some(true));
+		assert!(sub_spec.sep.is_none());
+	}
+	#[test]
 	fn test_serde_parse_2() {
 		let sub_spec: SubstitutionOptions = serde_yaml::from_str(
 			r#"{"id": "animals.plural", "count": "1d4+1", "method": "random", "sep": ", ", "last-sep": ", and "}"#
@@ -158,4 +279,16 @@ mod unit_tests {
 		assert!(sub_spec.hidden.is_none());
 		assert!(sub_spec.aan.is_none());
 	}
+	#[test]
+	fn test_serde_parse_6() {
+		let sub_spec: SubstitutionOptions = serde_yaml::from_str(
+			r#"{id: loot, pick: {of: 3, by: length, take: worst}}"#
+		).expect("Failed to parse");
+		assert_eq!(sub_spec.id.as_str(), "loot");
+		let pick = sub_spec.pick.expect("Failed to parse pick");
+		assert_eq!(pick.of, Some(3));
+		assert_eq!(pick.by, Some(String::from("length")));
+		assert_eq!(pick.take, Some(String::from("worst")));
+		assert!(sub_spec.count.is_none());
+	}
 }
\ No newline at end of file
@@ -0,0 +1,227 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::{BTreeMap, HashMap};
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use crate::errors::ParsingError;
+use crate::subspec::SubstitutionOptions;
+use crate::{check_output_len, count_substitution, do_dice_tokens, do_sub_parsed, do_sub_tokens, next_token, parse_token, recover_error,
+	EvalContext, RefValue, SUB_START};
+
+/// Marker line that starts and ends a front matter block
+const FRONT_MATTER_MARKER: &str = "---";
+
+/// Optional YAML front matter at the start of a template, between two `---` lines, which
+/// declares what the template needs from the look-up tables. For example:
+/// ```text
+/// ---
+/// requires: [animal, pet-names]
+/// refs: {owner: Alice}
+/// seed: 42
+/// ---
+/// ${owner} has a pet ${animal}.
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatter {
+	/// IDs of the look-up tables that the template requires
+	#[serde(default)]
+	pub requires: Vec<String>,
+	/// Default reference values, available to the template as `${@ref}` and `$ref`
+	#[serde(default)]
+	pub refs: BTreeMap<String, String>,
+	/// Recommended seed for the random number generator
+	pub seed: Option<u64>
+}
+
+impl FrontMatter {
+	/// Splits a template into its front matter (if any) and its body. The front matter must
+	/// start on the first line of the template with a `---` line and end with another `---` line.
+	/// # Arguments
+	/// * `template`: The template text.
+	/// # Returns
+	/// The parsed front matter (or `None` if the template has no front matter) and the remaining
+	/// body of the template, or an error if the front matter is not valid
+	pub fn split(template: &str) -> Result<(Option<FrontMatter>, &str), ParsingError> {
+		let first_line_end = template.find('\n').unwrap_or(template.len());
+		if template[..first_line_end].trim_end() != FRONT_MATTER_MARKER {
+			return Ok((None, template));
+		}
+		let rest = &template[(first_line_end + 1).min(template.len())..];
+		let mut pos = 0;
+		for line in rest.split_inclusive('\n') {
+			if line.trim_end() == FRONT_MATTER_MARKER {
+				let yaml = &rest[..pos];
+				let front: FrontMatter = if yaml.trim().is_empty() {
+					FrontMatter::default()
+				} else {
+					serde_yaml::from_str(yaml)?
+				};
+				return Ok((Some(front), &rest[pos + line.len()..]));
+			}
+			pos += line.len();
+		}
+		// no closing marker, so this is not front matter
+		Ok((None, template))
+	}
+}
+
+/// A template whose substitution tokens have been parsed in advance (see
+/// [Template::compile(...)](Template::compile)), such that
+/// [Interpreter::render(...)](crate::Interpreter::render) can evaluate it again and again
+/// without searching and parsing the tokens of the template every time. Rendering a template
+/// gives the same result as evaluating its text with [Interpreter::eval(...)](crate::Interpreter::eval),
+/// except that the text that a token produces is evaluated on its own (a token cannot be
+/// completed by the text after it).
+///
+/// # Example
+/// ```rust
+/// use twas;
+/// let mut interpreter = twas::Interpreter::new();
+/// interpreter.load_lines("animal", vec!["cat", "dog", "bird"]).unwrap();
+/// let template = twas::Template::compile("I have a pet ${animal}.").unwrap();
+/// for _ in 0..3 {
+///     println!("{}", interpreter.render(&template).unwrap());
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Template {
+	/// The text of the template
+	source: String,
+	/// The front matter of the template (if any)
+	front: Option<FrontMatter>,
+	/// The literal text and the parsed tokens of the body of the template, in order
+	segments: Vec<Segment>
+}
+
+/// A part of the body of a compiled template
+#[derive(Clone, Debug)]
+enum Segment {
+	/// Literal text (which may still contain dice tokens)
+	Text(String),
+	/// A substitution token, without the enclosing `${` and `}`, and its parsed options
+	Token(String, Box<SubstitutionOptions>)
+}
+
+impl Template {
+	/// Parses the front matter (if any) and the substitution tokens of a template
+	/// # Arguments
+	/// * `text`: The text of the template
+	/// # Returns
+	/// The compiled template, or an error if a substitution token is not valid
+	pub fn compile<T>(text: T) -> Result<Template, ParsingError> where T: Into<String> {
+		let source: String = text.into();
+		// like eval(...), treat invalid front matter as part of the body
+		let (front, body) = FrontMatter::split(source.as_str()).unwrap_or((None, source.as_str()));
+		let body = String::from(body);
+		let mut segments: Vec<Segment> = Vec::new();
+		let mut pos = 0;
+		while let Some((start, end)) = next_token(&body, pos, SUB_START) {
+			if start > pos {
+				segments.push(Segment::Text(String::from(&body[pos..start])));
+			}
+			let token = body[start + SUB_START.len()..end - 1].trim();
+			segments.push(Segment::Token(String::from(token), Box::new(parse_token(token)?)));
+			pos = end;
+		}
+		if pos < body.len() {
+			segments.push(Segment::Text(String::from(&body[pos..])));
+		}
+		Ok(Template{source, front, segments})
+	}
+
+	/// Gets the text of the template
+	pub fn as_str(&self) -> &str {
+		self.source.as_str()
+	}
+
+	/// Gets the front matter of the template, or `None` if it has none
+	pub fn get_front_matter(&self) -> Option<&FrontMatter> {
+		self.front.as_ref()
+	}
+
+	/// Gets the substitution tokens of the template, without the enclosing `${` and `}` (eg
+	/// `animal@pet`), in order
+	pub fn tokens(&self) -> impl Iterator<Item=&str> {
+		self.segments.iter().filter_map(|segment| match segment {
+			Segment::Token(token, _) => Some(token.as_str()),
+			Segment::Text(_) => None
+		})
+	}
+
+	/// Evaluates the body of the template (see [Interpreter::render(...)](crate::Interpreter::render))
+	pub(crate) fn render<G: Rng>(&self, ctx: &mut EvalContext<G>, ref_map: &mut HashMap<String, RefValue>) -> Result<String, ParsingError> {
+		// apply the default references from the front matter (if any)
+		for (ref_id, value) in self.front.iter().flat_map(|front| front.refs.iter()) {
+			ref_map.entry(ref_id.clone()).or_insert(RefValue{text: value.clone(), key: None, fields: None});
+		}
+		let mut text = String::new();
+		let mut pos = 0;
+		for segment in &self.segments {
+			match segment {
+				Segment::Text(literal) => text.push_str(literal),
+				Segment::Token(token, sub) => {
+					count_substitution(ctx)?;
+					if let Some(hook) = ctx.hook.as_deref_mut() {
+						hook.on_token(token);
+						hook.on_token_depth(token, 0);
+					}
+					// evaluate the tokens in the substituted text (if any) one level deeper, like
+					// eval(...) does
+					ctx.depth = Some(0);
+					let substitution = recover_error(do_sub_parsed(token, sub.as_ref().clone(), ctx, ref_map, 0), token, ctx)
+						.and_then(|substitution| substitution.map(|substitution| do_sub_tokens(substitution, 0, ctx, ref_map, 0)).transpose());
+					ctx.depth = None;
+					match substitution? {
+						Some((substitution, nested_pos)) => {
+							check_output_len(text.len() + substitution.len(), ctx.options)?;
+							pos = text.len() + nested_pos;
+							text.push_str(substitution.as_str());
+						},
+						// leave the token as written
+						None => {
+							text.push_str(SUB_START);
+							text.push_str(token);
+							text.push('}');
+							pos = text.len();
+						}
+					}
+				}
+			}
+		}
+		do_dice_tokens(text, pos, ctx, 0)
+	}
+}
+
+#[cfg(test)]
+mod unit_tests {
+	use crate::template::{FrontMatter, Template};
+
+	#[test]
+	fn test_split_front_matter() {
+		let (front, body) = FrontMatter::split("---\nrequires: [animal]\nrefs: {owner: Al}\nseed: 7\n---\nHi ${owner}\n").unwrap();
+		let front = front.expect("Missing front matter");
+		assert_eq!(front.requires, vec![String::from("animal")]);
+		assert_eq!(front.refs.get("owner"), Some(&String::from("Al")));
+		assert_eq!(front.seed, Some(7));
+		assert_eq!(body, "Hi ${owner}\n");
+		let (front, body) = FrontMatter::split("---\n---\nbody").unwrap();
+		assert_eq!(front, Some(FrontMatter::default()));
+		assert_eq!(body, "body");
+		let (front, body) = FrontMatter::split("---\nno closing marker").unwrap();
+		assert!(front.is_none());
+		assert_eq!(body, "---\nno closing marker");
+		assert!(FrontMatter::split("No front matter").unwrap().0.is_none());
+		assert!(FrontMatter::split("---\nunknown: 1\n---\n").is_err());
+	}
+
+	#[test]
+	fn test_compile() {
+		let template = Template::compile("---\nrefs: {owner: Al}\n---\n${owner} has ${{id: animal, aan: true}} and #{1d6} ${@pet}").unwrap();
+		assert_eq!(template.tokens().collect::<Vec<_>>(), vec!["owner", "{id: animal, aan: true}", "@pet"]);
+		assert_eq!(template.segments.len(), 5);
+		assert!(template.get_front_matter().is_some());
+		assert!(Template::compile("a ${{case: upper}}").is_err());
+		assert_eq!(Template::compile("no tokens").unwrap().segments.len(), 1);
+	}
+}
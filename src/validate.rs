@@ -0,0 +1,33 @@
+#![deny(unused_must_use)]
+#![deny(missing_docs)]
+use std::collections::BTreeSet;
+
+/// The result of checking a template without drawing from any look-up tables (see
+/// [Interpreter::validate(...)](crate::Interpreter::validate)). Look-up tables that are loaded
+/// are checked too, since their items may contain substitution tokens of their own.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Validation {
+	/// IDs of the look-up tables that are used (IDs that depend on references or dice, eg
+	/// `pet-names/$pet`, are not included)
+	pub table_ids: BTreeSet<String>,
+	/// IDs of the references that are defined (by the `ref` option, row draws, the front matter,
+	/// or persistent references)
+	pub refs_defined: BTreeSet<String>,
+	/// IDs of the references that are used (eg `@pet` or `$pet`)
+	pub refs_used: BTreeSet<String>,
+	/// Unknown substitution options (eg typos like `sepp`) and the tokens that contain them, as
+	/// (token, option) pairs
+	pub unknown_options: Vec<(String, String)>,
+	/// Missing look-up table IDs, references (as `@` followed by the reference ID), template
+	/// functions (as `fn:` followed by the function name), and host variables (as `var:`
+	/// followed by the variable name)
+	pub missing: Vec<String>
+}
+
+impl Validation {
+	/// Checks whether the template has no problems, meaning that nothing is missing and all
+	/// substitution options are known
+	pub fn is_ok(&self) -> bool {
+		self.missing.is_empty() && self.unknown_options.is_empty()
+	}
+}
@@ -181,6 +181,19 @@ ${{"id": "loot/junk", "count": "2d4", "prefix": " * ", "suffix": "\n"}}"#;
 "#, output.as_str(), "Incorrect evaluation");
 }
 
+#[test]
+fn dice_test_3() {
+	// dice are rolled with random number generators that are seeded from the interpreter's own
+	let mut first = twas::Interpreter::from_rng(NotRandom::seed_from_u64(7));
+	let mut second = twas::Interpreter::from_rng(NotRandom::seed_from_u64(7));
+	let rolls = first.eval("#{1d1000} #{1d1000} #{1d1000}").unwrap();
+	assert_eq!(second.eval("#{1d1000} #{1d1000} #{1d1000}").unwrap(), rolls);
+	let mut seeded = twas::Interpreter::from_seed(5);
+	let first = seeded.eval("#{1d1000} #{1d1000}").unwrap();
+	seeded.reseed(5);
+	assert_eq!(seeded.eval("#{1d1000} #{1d1000}").unwrap(), first);
+}
+
 #[test]
 fn ref_test_1() {
 	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
@@ -259,6 +272,1569 @@ fn indefinite_article_test_3() {
 	assert_eq!( "An elephant is a man's best friend. I like the elephant!", output.as_str(), "Incorrect evaluation");
 }
 
+#[test]
+fn value_lookup_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("stats", r#"{strength-desc: {"3-5": feeble, "6-9": average, "10-12": mighty, 13+: legendary}, strength: ["11"]}"#, "yaml").expect("Failure");
+	let input = "${stats/strength@str}: ${stats/strength-desc:$str}, ${stats/strength-desc:4}, ${stats/strength-desc:18}";
+	print!("\ninput = '{}'\n", input);
+	let output = gen.eval(input).unwrap();
+	println!("output = '{}'", output);
+	assert_eq!( "11: mighty, feeble, legendary", output.as_str(), "Incorrect evaluation");
+	assert!(gen.eval("${stats/strength-desc:1}").is_err());
+	gen.load_str("weather", r#"{cold: {"-20--6": freezing, "-5-5": chilly, 6+: mild}}"#, "yaml").expect("Failure");
+	assert_eq!(gen.eval("${weather/cold:-12}, ${weather/cold:0}, ${weather/cold:9}").unwrap(), "freezing, chilly, mild");
+	gen.set_var("mood", "grim").unwrap();
+	assert_eq!(gen.eval("${var:mood}").unwrap(), "grim");
+}
+
+#[test]
+fn pick_test_1() {
+	let mut gen = twas::Interpreter::from_seed(12345);
+	gen.load_str("loot", "{common: 9, rare: 1}", "yaml").expect("Failure");
+	gen.load_str("word", "a\nabcdefghijk", "txt").expect("Failure");
+	let input = r#"${{id: word, pick: {of: 20, by: length}}} ${{id: word, pick: {of: 20, by: length, take: worst}}} ${{id: loot, pick: {of: 50, by: weight, take: best}}}"#;
+	print!("\ninput = '{}'\n", input);
+	let output = gen.eval(input).unwrap();
+	println!("output = '{}'", output);
+	assert_eq!( "abcdefghijk a rare", output.as_str(), "Incorrect evaluation");
+	assert!(gen.eval("${{id: loot, method: shuffle, pick: {of: 2}}}").is_err());
+}
+
+#[test]
+fn matrix_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/reaction.csv").expect("IO Error");
+	gen.load_str("mood", "angry", "txt").expect("Failure");
+	gen.load_str("faction", "dwarves", "txt").expect("Failure");
+	let input = "${{id: mood, ref: m, hidden: true}}The ${faction@f} ${reaction[$m][$f]} and ${reaction[happy][$f]}.";
+	print!("\ninput = '{}'\n", input);
+	let output = gen.eval(input).unwrap();
+	println!("output = '{}'", output);
+	assert_eq!( "The dwarves raises an axe and offers an ale.", output.as_str(), "Incorrect evaluation");
+}
+
+#[test]
+fn matrix_test_2() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("reaction", "{$matrix: true, happy: {elves: cheer, dwarves: [drink, dance]}, angry: {elves: sulk, dwarves: fight}}", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${reaction[happy][elves]}").unwrap().as_str(), "cheer");
+	assert_eq!(gen.eval("${reaction[happy][dwarves]}").unwrap().as_str(), "drink");
+	assert_eq!(gen.eval("${reaction[angry][dwarves]}").unwrap().as_str(), "fight");
+	// text values are only cells in matrix tables
+	assert!(gen.load_str("mood", "{happy: cheer, angry: 2}", "yaml").is_err());
+	assert!(gen.load_str("mood", "{$matrix: yes please, happy: cheer}", "yaml").is_err());
+	assert!(gen.get_lut("mood/happy").is_none());
+	// a CSV file with an empty first header cell is not a matrix table
+	gen.load_str("plain", ",elves\nhappy,cheer", "csv").expect("Failure");
+	assert!(gen.get_lut("plain/happy/elves").is_none());
+}
+
+#[test]
+fn list_missing_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("animal", "dog\n${color} cat", "txt").expect("Failure");
+	gen.load_str("animal_names", include_str!("test-data/animal_names.csv"), "csv").expect("Failure");
+	let input = r#"I have a ${animal@pet} named ${{id: "animal_names/$pet"}} who likes ${@toy} and ${food}."#;
+	let missing = gen.list_missing(input).unwrap();
+	println!("missing = {:?}", missing);
+	assert_eq!(missing, vec!["@toy", "color", "food"]);
+}
+
+#[test]
+fn scoped_test_1() {
+	let mut gen = twas::Interpreter::from_seed(42);
+	gen.load_str("npc", "${name@n} (${@n} the ${job@j}, ${kin})", "txt").expect("Failure");
+	gen.load_str("name", "Ann\nBob\nCyd\nDee\nEve\nFay", "txt").expect("Failure");
+	gen.load_str("job", "baker\nsmith", "txt").expect("Failure");
+	gen.load_str("kin", "child of ${@j}", "txt").expect("Failure");
+	let input = r#"${{id: npc, count: 3, sep: "; ", scoped: true}}"#;
+	print!("\ninput = '{}'\n", input);
+	let output = gen.eval(input).unwrap();
+	println!("output = '{}'", output);
+	let matcher = regex::Regex::new(r"^(\w+) \((\w+) the (\w+), child of (\w+)\)$").unwrap();
+	for npc in output.split("; ") {
+		let caps = matcher.captures(npc).expect("Incorrect evaluation");
+		assert_eq!(&caps[1], &caps[2], "Each item should use its own name reference");
+		assert_eq!(&caps[3], &caps[4], "Each item should use its own job reference");
+	}
+	assert!(gen.eval(r#"${{id: npc, scoped: true}} ${@n}"#).is_err(), "Scoped references should not leak");
+}
+
+#[test]
+fn whitespace_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("animal", "- \"  big \\t  dog  \"", "yaml").expect("Failure");
+	assert_eq!(gen.eval(r#"[${{id: animal, strip: true, prefix: "<", suffix: ">"}}]"#).unwrap(), "[<big \t  dog>]");
+	assert_eq!(gen.eval(r#"[${{id: animal, collapse-whitespace: true, prefix: "<", suffix: ">"}}]"#).unwrap(), "[<big dog>]");
+	assert_eq!(gen.eval(r#"[${{id: animal, strip: true, collapse-whitespace: true, aan: true}}]"#).unwrap(), "[a big dog]");
+	assert_eq!(gen.eval(r#"[${animal}]"#).unwrap(), "[  big \t  dog  ]");
+}
+
+#[test]
+fn item_key_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("encounter", "- {text: a goblin ambush, key: goblin-ambush-01, weight: 2}\n- a quiet night", "yaml").expect("Failure");
+	gen.load_str("detail", "goblin-ambush-01: [three goblins attack]\na goblin ambush: [wrong table]", "yaml").expect("Failure");
+	let output = gen.eval("${encounter@enc}: ${detail/$enc}").unwrap();
+	assert_eq!(output, "a goblin ambush: three goblins attack");
+	let output = gen.eval("${encounter@enc}${{id: \"@enc\", ref: again, hidden: true}}: ${detail/$again}").unwrap();
+	assert_eq!(output, "a goblin ambush: three goblins attack");
+}
+
+#[test]
+fn conditional_weight_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("monster", "- {text: shark, weight: \"$region == coast ? 5 : 0\"}\n- {text: wolf, weight: \"$region == forest ? 5 : 0\"}\n- {text: rat, weight: 1}", "yaml").expect("Failure");
+	gen.load_str("region", "- forest", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${region@region}: ${monster}").unwrap(), "forest: wolf");
+	assert!(gen.eval("${monster}").is_err(), "Missing reference should be an error");
+	assert!(gen.load_str("bad", "- {text: shark, weight: \"$region ==\"}", "yaml").is_err());
+}
+
+#[test]
+fn requires_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("encounter", "- {text: a camel caravan, requires: \"$terrain == desert\"}\n- {text: a pack of wolves, requires: \"$terrain == forest || $terrain == tundra\", weight: 2}\n- a lost traveler", "yaml").expect("Failure");
+	gen.load_str("terrain", "- forest", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${terrain@terrain}: ${encounter}").unwrap(), "forest: a pack of wolves");
+	gen.set_persistent_ref("terrain", "swamp").expect("Failure");
+	assert_eq!(gen.eval("${encounter}").unwrap(), "a lost traveler");
+	let encounters = gen.get_lut("encounter").unwrap();
+	assert_eq!(encounters.get(0).unwrap().get_requires().unwrap(), "$terrain == desert");
+	assert!(encounters.get(0).unwrap().get_field("requires").is_none());
+	gen.clear_persistent_refs();
+	assert!(gen.eval("${encounter}").is_err(), "Missing reference should be an error");
+	assert!(gen.load_str("bad", "- {text: camel, requires: \"$terrain ==\"}", "yaml").is_err());
+}
+
+#[test]
+fn max_per_eval_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("hoard", "- {text: Vorpal Sword, weight: 100, max-per-eval: 1}\n- {text: gold coins, weight: 5}", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${{id: hoard, count: 3, sep: ', '}}").unwrap(), "Vorpal Sword, gold coins, gold coins");
+	assert_eq!(gen.eval("${hoard} and ${hoard}").unwrap(), "Vorpal Sword and gold coins");
+	assert_eq!(gen.get_lut("hoard").unwrap().get(0).unwrap().get_max_per_eval(), Some(1));
+	assert!(gen.get_lut("hoard").unwrap().get(0).unwrap().get_field("max-per-eval").is_none());
+	assert_eq!(gen.eval("${{id: hoard, count: 2, method: shuffle, sep: ', ', max-per-eval: 2}}").unwrap(), "Vorpal Sword, gold coins");
+	gen.load_str("gem", "$options: {max-per-eval: 1}\nkind: [ruby, opal]", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${gem/kind}${gem/kind}").unwrap(), "rubyopal");
+	assert!(gen.eval("${{id: gem/kind, count: 3}}").is_err());
+	assert_eq!(gen.eval("${{id: gem/kind, count: 3, max-per-eval: 3}}").unwrap(), "rubyrubyruby");
+	assert!(gen.load_str("bad", "- {text: sword, max-per-eval: -1}", "yaml").is_err());
+}
+
+#[test]
+fn select_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("sword", "- {text: Excalibur, key: excalibur, weight: 0}\n- {text: rusty sword, weight: 3}\n- {text: bent sword, weight: 0}", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${sword}").unwrap(), "rusty sword");
+	assert_eq!(gen.eval("${{id: sword, count: 3, method: shuffle}}").unwrap(), "rusty swordrusty swordrusty sword");
+	assert_eq!(gen.eval("${{id: sword, select: excalibur, case: upper}}").unwrap(), "EXCALIBUR");
+	assert_eq!(gen.eval("${{id: sword, select: bent sword, ref: s}} (${@s})").unwrap(), "bent sword (bent sword)");
+	assert!(gen.eval("${{id: sword, select: spoon}}").is_err());
+	assert!(gen.load_str("bad", "$options: {select: x}\nthing: [a]", "yaml").is_err());
+	gen.load_str("canon", "{Merlin: 0, Arthur: 0}", "yaml").expect("Failure");
+	assert!(gen.eval("${canon}").is_err());
+	assert_eq!(gen.eval("${{id: canon, select: Arthur}}").unwrap(), "Arthur");
+}
+
+#[test]
+fn number_item_test_1() {
+	let mut gen = twas::Interpreter::from_seed(3);
+	gen.load_str("gold", "- {min: 50, max: 200}\n- {range: 3-18, weight: 2}", "yaml").expect("Failure");
+	gen.load_str("potion", "- {min: 0.5, max: 2.5}", "yaml").expect("Failure");
+	for _ in 0..20 {
+		let value: i64 = gen.eval("${gold}").unwrap().parse().unwrap();
+		assert!((3..=18).contains(&value) || (50..=200).contains(&value), "Out of range: {}", value);
+		let output = gen.eval("${potion}").unwrap();
+		let value: f64 = output.parse().unwrap();
+		assert!((0.5..=2.5).contains(&value) && output.split('.').nth(1).unwrap().len() == 1, "Out of range: {}", output);
+	}
+	gen.load_str("wolves", "- {name: wolves, min: 2, max: 6}", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${wolves} (${{id: wolves, field: max}})").unwrap(), "wolves (6)");
+	assert!(gen.load_str("bad", "- {min: 9, max: 1}", "yaml").is_err());
+	assert!(gen.load_str("bad", "- {min: 9}", "yaml").is_err());
+	assert!(gen.load_str("bad", "- {range: 5+}", "yaml").is_err());
+}
+
+#[test]
+fn eval_detailed_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("npc", "- {name: Alice, job: baker}\n- {name: Bob, job: smith}", "yaml").expect("Failure");
+	gen.load_str("town", "Oakvale ${{id: town-suffix, hidden: true}}", "txt").expect("Failure");
+	gen.load_lines("town-suffix", vec!["upon Hill"]).expect("Failure");
+	gen.set_lenient_missing(true);
+	let result = gen.eval_detailed("${npc@hero} the ${@hero.job} of ${town} meets ${{id: npc, count: 2, sep: \" and \"}}${ghost}").unwrap();
+	assert_eq!(result.text, "Alice the baker of Oakvale  meets Alice and Alice⟦missing: ghost⟧");
+	assert_eq!(result.refs.get("hero").map(String::as_str), Some("Alice"));
+	let ids: Vec<&str> = result.draws.iter().map(|(id, _)| id.as_str()).collect();
+	assert_eq!(ids, vec!["npc", "town", "town-suffix", "npc", "npc"]);
+	assert_eq!(result.draws_from("npc").count(), 3);
+	assert_eq!(result.draws[0].1.get_field("job").unwrap(), "baker");
+	assert_eq!(result.warnings.len(), 1);
+	assert_eq!(gen.eval("${npc}").unwrap(), "Alice");
+}
+
+#[test]
+fn eval_with_refs_test_1() {
+	use std::collections::HashMap;
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.set_front_matter(true);
+	gen.load_str("encounter", "{desert: [a camel], forest: [a wolf]}", "yaml").expect("Failure");
+	let refs = HashMap::from([(String::from("player"), String::from("Robin")), (String::from("region"), String::from("forest"))]);
+	assert_eq!(gen.eval_with_refs("${@player} meets ${encounter/$region}.", refs.clone()).unwrap(), "Robin meets a wolf.");
+	assert_eq!(gen.eval_with_refs("---\nrefs: {region: desert}\n---\n${encounter/$region}", refs.clone()).unwrap(), "a wolf");
+	assert_eq!(gen.eval_with_refs("${{id: encounter/desert, ref: player}}${@player}", refs.clone()).unwrap(), "a camela camel");
+	let result = gen.eval_detailed_with_refs("${encounter/$region}", refs.clone()).unwrap();
+	assert_eq!(result.text, "a wolf");
+	assert_eq!(result.refs["player"], "Robin");
+	assert!(gen.eval("${@player}").is_err());
+	let bad = HashMap::from([(String::from("not valid!"), String::from("x"))]);
+	assert!(gen.eval_with_refs("hello", bad).is_err());
+}
+
+#[test]
+fn iter_test_1() {
+	let mut gen = twas::Interpreter::from_seed(11);
+	gen.load_lines("animal", vec!["cat", "dog", "bird"]).expect("Failure");
+	let pets: Vec<String> = gen.iter("a ${animal}").take(20).collect::<Result<Vec<String>, _>>().unwrap();
+	assert_eq!(pets.len(), 20);
+	assert!(pets.iter().all(|pet| pet == "a cat" || pet == "a dog" || pet == "a bird"));
+	let dog = gen.iter("${animal}").map(Result::unwrap).find(|pet| pet == "dog");
+	assert_eq!(dog.as_deref(), Some("dog"));
+	assert!(gen.iter("${ghost}").take(3).all(|result| result.is_err()));
+}
+
+#[test]
+fn table_source_test_1() {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::sync::Arc;
+	struct Counter {
+		calls: Arc<AtomicUsize>
+	}
+	impl twas::TableSource for Counter {
+		fn resolve(&self, id: &str) -> Option<twas::LookUpTable> {
+			let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+			let mut lut = twas::LookUpTable::new();
+			lut.add_item(format!("{} #{}", id, n), 1.);
+			Some(lut)
+		}
+		fn keep_tables(&self) -> bool { false }
+	}
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("animal", vec!["cat"]).expect("Failure");
+	gen.add_table_source(|id: &str| match id {
+		"color" => {
+			let mut lut = twas::LookUpTable::new();
+			lut.add_item("red", 1.);
+			Some(lut)
+		},
+		_ => None
+	});
+	assert_eq!(gen.eval("a ${color} ${animal}").unwrap(), "a red cat");
+	assert!(gen.list_ids().contains(&&String::from("color")));
+	assert!(gen.eval("${ticket}").is_err());
+	let calls = Arc::new(AtomicUsize::new(0));
+	gen.add_table_source(Counter{calls: calls.clone()});
+	assert!(gen.list_missing("${ticket} ${animal} ${shape}").unwrap().is_empty());
+	let checks = calls.load(Ordering::SeqCst);
+	assert_eq!(gen.eval("${ticket}").unwrap(), format!("ticket #{}", checks + 1));
+	assert_eq!(gen.eval("${ticket}").unwrap(), format!("ticket #{}", checks + 2));
+	assert!(!gen.list_ids().contains(&&String::from("ticket")));
+	assert_eq!(gen.fork().eval("${{id: color, case: upper}}").unwrap(), "RED");
+	gen.clear_table_sources();
+	assert!(gen.eval("${ticket}").is_err());
+	assert_eq!(gen.eval("${color}").unwrap(), "red");
+}
+
+#[test]
+fn function_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("season", vec!["winter"]).expect("Failure");
+	gen.load_lines("animal", vec!["owl"]).expect("Failure");
+	gen.register_function("weather", |args, ctx| {
+		match args.get("season") {
+			Some("winter") => Ok(format!("snowing on {}", ctx.get_ref("hero").unwrap_or("nobody"))),
+			Some(_) => Ok(String::from("sunny")),
+			None => Err(String::from("missing season"))
+		}
+	}).unwrap();
+	gen.register_function("join", |args, _ctx| Ok(args.positional().join("+"))).unwrap();
+	gen.register_function("pet", |_args, _ctx| Ok(String::from("${animal}"))).unwrap();
+	assert_eq!(gen.eval("${season@season}${{id: animal, ref: hero, hidden: true}}: ${fn:weather(season=$season)}").unwrap(),
+		"winter: snowing on owl");
+	assert_eq!(gen.eval("${fn:join(a, b,c)} ${fn:join()} ${fn:join}").unwrap(), "a+b+c  ");
+	gen.register_function("bird", |_args, _ctx| Ok(String::from("owl"))).unwrap();
+	assert_eq!(gen.eval("${fn:pet} ${{id: \"fn:bird\", aan: true, case: upper, ref: p}} ${@p}").unwrap(), "owl AN OWL AN OWL");
+	assert!(gen.eval("${fn:weather}").is_err());
+	assert!(gen.eval("${fn:weather(season=winter}").is_err());
+	assert!(gen.eval("${fn:nothing}").is_err());
+	assert!(gen.list_missing("${season@s} ${fn:weather(season=$s)}").unwrap().is_empty());
+	assert!(gen.register_function("bad name", |_args, _ctx| Ok(String::new())).is_err());
+	assert!(gen.unregister_function("join"));
+	assert!(gen.eval("${fn:join(a)}").is_err());
+}
+
+#[test]
+fn hook_test_1() {
+	use std::sync::{Arc, Mutex};
+	struct Logger {
+		log: Arc<Mutex<Vec<String>>>
+	}
+	impl twas::EvalHook for Logger {
+		fn on_token(&mut self, token: &str) {
+			self.log.lock().unwrap().push(format!("token {}", token));
+		}
+		fn on_draw(&mut self, table_id: &str, item: &twas::Item) {
+			self.log.lock().unwrap().push(format!("draw {} {}", table_id, item.get_text()));
+		}
+		fn on_ref(&mut self, ref_id: &str, text: &str) {
+			self.log.lock().unwrap().push(format!("ref {} {}", ref_id, text));
+		}
+	}
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("animal", vec!["big ${size}"]).expect("Failure");
+	gen.load_lines("size", vec!["cat"]).expect("Failure");
+	let log = Arc::new(Mutex::new(Vec::new()));
+	gen.set_hook(Logger{log: log.clone()});
+	assert_eq!(gen.eval("a ${animal@pet}: ${@pet}").unwrap(), "a big cat: big cat");
+	assert_eq!(*log.lock().unwrap(), vec![
+		"token animal@pet", "draw animal big ${size}", "ref pet big ${size}", "token size", "draw size cat",
+		"token @pet", "token size", "draw size cat"
+	]);
+	assert!(gen.take_hook().is_some());
+	gen.eval("${animal}").unwrap();
+	assert_eq!(log.lock().unwrap().len(), 8);
+}
+
+#[test]
+fn spans_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("monster", vec!["${size} goblin"]).expect("Failure");
+	gen.load_lines("size", vec!["tiny"]).expect("Failure");
+	let result = gen.eval_detailed("A ${monster@m}${{id: size, hidden: true}} and ${@m} #{1d1}!").unwrap();
+	assert_eq!(result.text, "A tiny goblin and tiny goblin 1!");
+	let spans: Vec<(&str, &str, Option<&str>)> = result.spans.iter()
+		.map(|span| (&result.text[span.range.clone()], span.token.as_str(), span.table_id.as_deref())).collect();
+	assert_eq!(spans, vec![
+		("tiny goblin", "${monster@m}", Some("monster")),
+		("tiny", "${size}", Some("size")),
+		("", "${{id: size, hidden: true}}", Some("size")),
+		("tiny goblin", "${@m}", None),
+		("tiny", "${size}", Some("size")),
+		("1", "#{1d1}", None)
+	]);
+	assert_eq!(result.spans[0].items[0].get_text(), "${size} goblin");
+	assert_eq!(result.span_at(3).unwrap().token, "${size}");
+	assert_eq!(result.span_at(9).unwrap().token, "${monster@m}");
+	assert!(result.span_at(0).is_none());
+}
+
+#[test]
+fn validate_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("animal", vec!["${{id: size, cas: upper}} cat"]).expect("Failure");
+	gen.load_lines("size", vec!["big"]).expect("Failure");
+	gen.register_function("weather", |_args, _ctx| Ok(String::from("rain"))).unwrap();
+	let template = "---\nrefs: {owner: Alice}\n---\n${@owner}'s ${animal@pet} likes ${fn:weather} and ${fn:moon($pet)} in ${pet-names/$pet}, ${@friend}";
+	let validation = gen.validate(template).unwrap();
+	assert_eq!(validation.table_ids.iter().collect::<Vec<_>>(), vec!["animal", "size"]);
+	assert_eq!(validation.refs_defined.iter().collect::<Vec<_>>(), vec!["owner", "pet"]);
+	assert_eq!(validation.refs_used.iter().collect::<Vec<_>>(), vec!["friend", "owner", "pet"]);
+	assert_eq!(validation.unknown_options, vec![(String::from("${{id: size, cas: upper}}"), String::from("cas"))]);
+	assert_eq!(validation.missing, vec!["@friend", "fn:moon"]);
+	assert!(!validation.is_ok());
+	assert!(gen.validate("${size} ${{id: animal, sep: \", \"}}").unwrap().unknown_options.len() == 1);
+	assert!(gen.validate("${size}").unwrap().is_ok());
+	assert!(gen.validate("${{id: size, count: [}}").is_err());
+}
+
+#[test]
+fn dependency_graph_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("monster", "- ${size} goblin\n- '@table:undead'", "yaml").expect("Failure");
+	gen.load_lines("size", vec!["tiny", "${size} ${size}"]).expect("Failure");
+	gen.load_lines("undead", vec!["${{id: curse, ref: c}} ghost of ${@c}", "${dragon}", "a ${@hero}"]).expect("Failure");
+	gen.load_lines("curse", vec!["${undead}"]).expect("Failure");
+	let graph = gen.dependency_graph_with_templates([("story", "---\nrequires: [weather]\n---\n${monster@m} ${@m} ${@friend}")]).unwrap();
+	assert_eq!(graph.nodes().collect::<Vec<_>>(), vec!["curse", "monster", "size", "story", "undead"]);
+	assert_eq!(graph.dependencies("monster").unwrap().iter().collect::<Vec<_>>(), vec!["size", "undead"]);
+	assert_eq!(graph.dependencies("story").unwrap().iter().collect::<Vec<_>>(), vec!["monster", "weather"]);
+	assert_eq!(graph.refs_used("undead").unwrap().iter().collect::<Vec<_>>(), vec!["hero"]);
+	assert_eq!(graph.refs_used("story").unwrap().iter().collect::<Vec<_>>(), vec!["friend"]);
+	assert_eq!(graph.dependents("undead").into_iter().collect::<Vec<_>>(), vec!["curse", "monster"]);
+	assert_eq!(graph.missing().into_iter().collect::<Vec<_>>(), vec!["dragon", "weather"]);
+	assert_eq!(graph.cycles(), vec![vec!["curse", "undead"], vec!["size"]]);
+	assert!(gen.dependency_graph().unwrap().dependencies("story").is_none());
+}
+
+#[test]
+fn analyze_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("monster", "{'${size} goblin': 3, '@table:undead': 1}", "yaml").expect("Failure");
+	gen.load_lines("size", vec!["big", "small"]).expect("Failure");
+	gen.load_lines("undead", vec!["ghost", "${size} zombie"]).expect("Failure");
+	let dist = gen.analyze("${monster}", 100).unwrap();
+	assert!(dist.exact);
+	assert_eq!(dist.samples, 5);
+	assert_eq!(dist.probability("big goblin"), 0.375);
+	assert_eq!(dist.probability("ghost"), 0.125);
+	assert_eq!(dist.probability("small zombie"), 0.0625);
+	assert_eq!(dist.most_likely()[0].1, 0.375);
+	assert_eq!(dist.expected_count("size", "big"), 0.4375);
+	assert_eq!(dist.item_frequencies("monster").get(&String::from("${size} goblin")), Some(&0.75));
+	// too many combinations (or dice) are estimated from samples instead
+	let dist = gen.analyze("${size}${size}${size}", 4).unwrap();
+	assert!(!dist.exact);
+	assert_eq!(dist.samples, 4);
+	assert_eq!(dist.outputs.values().sum::<f64>(), 1.);
+	let dist = gen.analyze("${size} #{1d6}", 100).unwrap();
+	assert!(!dist.exact);
+	assert!((dist.expected_count("size", "big") + dist.expected_count("size", "small") - 1.).abs() < 1e-9);
+}
+
+#[test]
+fn enumerate_outputs_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("greeting", vec!["hi", "hello", "hi"]).expect("Failure");
+	gen.load_lines("name", vec!["${title} Ann", "Bob"]).expect("Failure");
+	gen.load_lines("title", vec!["Dr.", "@table:rank"]).expect("Failure");
+	gen.load_lines("rank", vec!["Capt."]).expect("Failure");
+	let outputs = gen.enumerate_outputs("${greeting}, ${name@n}! Bye ${@n}.", 100).unwrap();
+	assert_eq!(outputs, vec![
+		"hello, Bob! Bye Bob.", "hello, Capt. Ann! Bye Capt. Ann.", "hello, Capt. Ann! Bye Dr. Ann.",
+		"hello, Dr. Ann! Bye Capt. Ann.", "hello, Dr. Ann! Bye Dr. Ann.",
+		"hi, Bob! Bye Bob.", "hi, Capt. Ann! Bye Capt. Ann.", "hi, Capt. Ann! Bye Dr. Ann.",
+		"hi, Dr. Ann! Bye Capt. Ann.", "hi, Dr. Ann! Bye Dr. Ann."
+	]);
+	assert!(gen.enumerate_outputs("${greeting}, ${name@n}! Bye ${@n}.", 10).is_err());
+	assert!(gen.enumerate_outputs("${name} rolls #{1d6}", 100).is_err());
+	assert!(gen.enumerate_outputs("${dragon}", 100).is_err());
+	assert_eq!(gen.enumerate_outputs("no tokens", 1).unwrap(), vec!["no tokens"]);
+}
+
+#[test]
+fn eval_seeded_test_1() {
+	let load = |gen: &mut twas::Interpreter<rand::rngs::StdRng>| {
+		gen.load_lines("animal", (0..100).map(|i| format!("animal{}", i))).expect("Failure");
+	};
+	let template = "${animal} ${animal} #{1d1000}";
+	let mut gen = twas::Interpreter::from_seed(7);
+	let mut control = twas::Interpreter::from_seed(7);
+	load(&mut gen);
+	load(&mut control);
+	assert_eq!(gen.eval(template).unwrap(), control.eval(template).unwrap());
+	let seeded = gen.eval_seeded(template, 12345).unwrap();
+	assert_eq!(gen.eval_seeded(template, 12345).unwrap(), seeded);
+	assert_ne!(gen.eval_seeded(template, 54321).unwrap(), seeded);
+	// the interpreter's own random number generator is not used
+	assert_eq!(gen.eval(template).unwrap(), control.eval(template).unwrap());
+	let mut fresh = twas::Interpreter::from_seed(12345);
+	load(&mut fresh);
+	assert_eq!(fresh.eval(template).unwrap(), seeded);
+}
+
+#[test]
+fn freeze_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("animal", vec!["cat", "dog"]).expect("Failure");
+	gen.load_lines("hook", vec!["dragon", "heist"]).expect("Failure");
+	gen.set_depletion("hook", twas::Depletion::Deplete);
+	gen.eval("${{id: animal, ref: pet, persist: true}}").unwrap();
+	let tables = std::sync::Arc::new(gen.freeze());
+	gen.load_lines("plant", vec!["fern"]).expect("Failure");
+	let text = std::thread::scope(|scope| {
+		scope.spawn(|| tables.eval("${animal} ${@pet}", &mut NotRandom::seed_from_u64(0)).unwrap()).join().unwrap()
+	});
+	assert_eq!(text, "cat cat");
+	let mut evaluator = twas::Evaluator::from_rng(tables.clone(), NotRandom::seed_from_u64(0));
+	assert_eq!(evaluator.eval("${hook}").unwrap(), "dragon");
+	assert_eq!(evaluator.eval("${hook} ${{id: animal, ref: pet, persist: true}}").unwrap(), "heist cat");
+	assert!(evaluator.eval("${hook}").is_err());
+	assert_eq!(evaluator.eval_with_refs("${@pet} ${@owner}", std::collections::HashMap::from([(String::from("owner"), String::from("Ann"))])).unwrap(), "cat Ann");
+	// the frozen copy is not changed by evaluators
+	assert_eq!(tables.eval("${hook}", &mut NotRandom::seed_from_u64(0)).unwrap(), "dragon");
+	assert!(tables.eval("${plant}", &mut NotRandom::seed_from_u64(0)).is_err());
+	assert_eq!(gen.eval("${plant}").unwrap(), "fern");
+}
+
+#[test]
+fn render_test_1() {
+	let templates = [
+		"I have ${{id: animal, aan: true, ref: pet}}. My ${@pet} is called ${name/$pet}.",
+		"---\nrefs: {owner: Ann}\n---\n${@owner} meets ${monster} and rolls #{1d20}",
+		"${{id: animal, count: 3, sep: ', ', last-sep: ' and '}} ${dragon}",
+		"no tokens at all"
+	];
+	let load = |gen: &mut twas::Interpreter<rand::rngs::StdRng>| {
+		gen.load_lines("animal", vec!["cat", "dog", "owl", "eel"]).expect("Failure");
+		gen.load_str("name", "{cat: [Tom, Kit], dog: [Rex], owl: [Hoot], eel: [Zap, Volt]}", "yaml").expect("Failure");
+		gen.load_lines("monster", vec!["a ${size} goblin", "an orc", "${animal} swarm"]).expect("Failure");
+		gen.load_lines("size", vec!["big", "small"]).expect("Failure");
+		gen.set_lenient_missing(true);
+		gen.set_front_matter(true);
+	};
+	let mut evaluated = twas::Interpreter::from_seed(99);
+	let mut rendered = twas::Interpreter::from_seed(99);
+	load(&mut evaluated);
+	load(&mut rendered);
+	let compiled: Vec<twas::Template> = templates.iter().map(|t| twas::Template::compile(*t).unwrap()).collect();
+	for _ in 0..20 {
+		for (text, template) in templates.iter().zip(compiled.iter()) {
+			assert_eq!(rendered.render(template).unwrap(), evaluated.eval(*text).unwrap());
+		}
+	}
+	assert_eq!(compiled[0].tokens().count(), 3);
+	assert!(twas::Template::compile("${{case: upper}}").is_err());
+}
+
+#[test]
+fn recursion_depth_test_1() {
+	struct Depths(std::sync::Arc<std::sync::Mutex<Vec<(String, usize)>>>);
+	impl twas::EvalHook for Depths {
+		fn on_token_depth(&mut self, token: &str, depth: usize) {
+			self.0.lock().unwrap().push((String::from(token), depth));
+		}
+	}
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("monster", vec!["${size} goblin"]).expect("Failure");
+	gen.load_lines("size", vec!["${{id: adverb, suffix: ' '}}big"]).expect("Failure");
+	gen.load_lines("adverb", vec!["very"]).expect("Failure");
+	gen.load_lines("echo", vec!["echo ${echo}"]).expect("Failure");
+	let depths = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+	gen.set_hook(Depths(depths.clone()));
+	assert_eq!(gen.eval("${monster} and ${size}").unwrap(), "very big goblin and very big");
+	assert_eq!(*depths.lock().unwrap(), vec![(String::from("monster"), 0), (String::from("size"), 1),
+		(String::from("{id: adverb, suffix: ' '}"), 2), (String::from("size"), 0), (String::from("{id: adverb, suffix: ' '}"), 1)]);
+	// self-expanding items reach the recursion limit
+	gen.set_recursion_limit(20);
+	let e = gen.eval("${echo}").unwrap_err();
+	assert!(e.to_string().contains("Recursion limit 20 exceeded at 'echo'"), "{}", e);
+	// per-call limits
+	assert!(gen.eval_with_limit("${monster}", 1).is_err());
+	assert_eq!(gen.eval_with_limit("${monster}", 2).unwrap(), "very big goblin");
+	assert!(gen.eval_with_limit("${echo}", 5).unwrap_err().to_string().contains("limit 5"));
+	assert_eq!(gen.get_recursion_limit(), 20);
+}
+
+#[test]
+fn lint_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("animal", vec!["cat", "dog"]).expect("Failure");
+	gen.load_lines("pet", vec!["my ${{id: animal, sepp: ', '}}"]).expect("Failure");
+	let template = "---\nrefs: {owner: Ann}\n---\n${@owner} has ${@pet} and ${id: animal, count: 2}. \
+		${{id: animal, ref: pet, last-sep: ' and '}} ${@pet}";
+	let kinds: Vec<twas::LintKind> = gen.lint(template).into_iter().map(|lint| lint.kind).collect();
+	assert_eq!(kinds, vec![twas::LintKind::RefBeforeDefinition, twas::LintKind::SingleBraceOptions,
+		twas::LintKind::UnreachableLastSep]);
+	assert!(gen.lint("${{id: animal, count: 2, sep: ', ', last-sep: ' and '}}").is_empty());
+	assert_eq!(gen.lint("---\nrefs: [oops\n---\n${animal}")[0].kind, twas::LintKind::InvalidFrontMatter);
+	let table_lints = gen.lint_tables();
+	assert_eq!(table_lints.len(), 1);
+	assert_eq!(table_lints[0].0, "pet");
+	assert_eq!(table_lints[0].1.kind, twas::LintKind::UnknownOption);
+}
+
+#[test]
+fn builder_test_1() {
+	let mut built = twas::Interpreter::builder()
+		.with_lenient_missing(true)
+		.with_compat(twas::CompatLevel::V2)
+		.with_recursion_limit(20)
+		.with_loose_ids(true)
+		.with_encoding("latin1")
+		.with_csv_options(twas::CsvOptions::with_delimiter(';'))
+		.with_seed(7)
+		.build().expect("Failure");
+	let mut set = twas::Interpreter::from_seed(7);
+	set.set_compat(twas::CompatLevel::V2);
+	set.set_lenient_missing(true);
+	set.set_loose_ids(true);
+	set.set_recursion_limit(20);
+	assert_eq!(built.get_options(), set.get_options());
+	assert_eq!(built.get_recursion_limit(), 20);
+	assert_eq!(built.get_encoding(), Some("windows-1252"));
+	assert_eq!(built.get_csv_options().delimiter, ';');
+	built.load_str("pet", "animal;name\ncat;Tom\ndog;Rex\nowl;Hoot", "csv").expect("Failure");
+	set.load_csv_str_with("pet", "animal;name\ncat;Tom\ndog;Rex\nowl;Hoot", &twas::CsvOptions::with_delimiter(';')).expect("Failure");
+	for _ in 0..10 {
+		let template = "${{id: Pet/Animal, count: 2}} ${monster}";
+		assert_eq!(built.eval(template).unwrap(), set.eval(template).unwrap());
+	}
+	assert!(twas::Interpreter::builder().with_encoding("klingon").build().is_err());
+	let rng_built = twas::Interpreter::builder().build_with_rng(NotRandom::seed_from_u64(0)).expect("Failure");
+	assert_eq!(rng_built.get_options(), &twas::EvalOptions::default());
+}
+
+#[test]
+fn strictness_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("animal", vec!["cat"]).expect("Failure");
+	gen.load_str("nothing", "{never: 0}", "yaml").expect("Failure");
+	let template = "${animal} ${dragon} ${nothing} ${{id: animal, case: shouting}} ${{id: animal, method: sorted}} ${{id: [}}";
+	assert_eq!(gen.get_strictness(), twas::Strictness::strict());
+	assert!(gen.eval(template).is_err());
+	gen.set_strictness(twas::Strictness::lenient());
+	assert_eq!(gen.eval(template).unwrap(), "cat ⟦missing: dragon⟧ ⟦empty: nothing⟧ ⟦invalid: {id: animal, case: shouting}⟧ \
+		⟦invalid: {id: animal, method: sorted}⟧ ⟦invalid: {id: [}⟧");
+	assert_eq!(gen.get_warnings().len(), 5);
+	gen.set_strictness(twas::Strictness::all(twas::ErrorPolicy::Literal));
+	assert_eq!(gen.eval(template).unwrap(), template.replacen("${animal}", "cat", 1));
+	let mut strictness = twas::Strictness::lenient();
+	strictness.invalid_options = twas::ErrorPolicy::Error;
+	gen.set_strictness(strictness);
+	assert!(gen.eval("${{id: animal, case: shouting}}").is_err());
+	assert_eq!(gen.eval("${{id: animal, case: upper}} ${nothing}").unwrap(), "CAT ⟦empty: nothing⟧");
+	// lenient mode takes precedence for missing look-up tables
+	gen.set_strictness(twas::Strictness::strict());
+	gen.set_lenient_missing(true);
+	assert_eq!(gen.eval("${dragon}").unwrap(), "⟦missing: dragon⟧");
+	assert!(gen.render(&twas::Template::compile("${animal} ${nothing}").unwrap()).is_err());
+	gen.set_strictness(twas::Strictness::all(twas::ErrorPolicy::Literal));
+	assert_eq!(gen.render(&twas::Template::compile("${animal} ${nothing} #{1d1}").unwrap()).unwrap(), "cat ${nothing} 1");
+}
+
+#[test]
+fn limits_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	for level in 0..5 {
+		let next = format!("${{level{}}}", level + 1);
+		gen.load_lines(format!("level{}", level).as_str(), vec![next.repeat(4)]).expect("Failure");
+	}
+	gen.load_lines("level5", vec!["0123456789"]).expect("Failure");
+	// 1 + 4 + 16 + ... + 1024 = 1365 substitutions for 10240 bytes of text
+	assert_eq!(gen.eval("${level0}").unwrap().len(), 10240);
+	gen.set_substitution_limit(Some(1365));
+	assert_eq!(gen.eval("${level0}").unwrap().len(), 10240);
+	gen.set_substitution_limit(Some(1000));
+	let e = gen.eval("${level0}").unwrap_err();
+	assert!(e.to_string().contains("Limit of 1000 substitutions exceeded"), "{}", e);
+	let mut strict = twas::Strictness::lenient();
+	strict.invalid_tokens = twas::ErrorPolicy::Literal;
+	gen.set_strictness(strict);
+	assert!(gen.eval("${level0}").is_err());
+	gen.set_substitution_limit(None);
+	gen.set_output_limit(Some(5000));
+	let e = gen.eval("${level0}").unwrap_err();
+	assert!(e.to_string().contains("Limit of 5000 bytes of text exceeded"), "{}", e);
+	assert!(gen.render(&twas::Template::compile("${level0}").unwrap()).is_err());
+	assert!(gen.freeze().eval("${level0}", &mut NotRandom::seed_from_u64(0)).is_err());
+	assert_eq!(gen.eval("${level4}").unwrap().len(), 40);
+	let mut built = twas::Interpreter::builder().with_output_limit(100).with_substitution_limit(3).build().expect("Failure");
+	built.load_lines("animal", vec!["cat"]).expect("Failure");
+	assert_eq!(built.eval("${animal}${animal}${animal}").unwrap(), "catcatcat");
+	assert!(built.eval("${animal}${animal}${animal}${animal}").is_err());
+}
+
+#[test]
+fn eval_stats_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("monster", vec!["${size} ${{id: color, count: 2}} goblin"]).expect("Failure");
+	gen.load_lines("size", vec!["${{id: adverb, suffix: ' '}}big"]).expect("Failure");
+	gen.load_lines("adverb", vec!["very"]).expect("Failure");
+	gen.load_lines("color", vec!["red", "green"]).expect("Failure");
+	let result = gen.eval_detailed("${monster} and ${monster}").unwrap();
+	assert_eq!(result.stats.tokens, 2 + 2 * 3);
+	assert_eq!(result.stats.draws.get("monster"), Some(&2));
+	assert_eq!(result.stats.draws.get("color"), Some(&4));
+	assert_eq!(result.stats.total_draws(), 2 + 2 + 2 + 4);
+	assert_eq!(result.stats.max_depth, 2);
+	assert_eq!(&result.stats, gen.last_eval_stats());
+	gen.eval("no tokens").unwrap();
+	assert_eq!(gen.last_eval_stats().tokens, 0);
+	assert!(gen.last_eval_stats().draws.is_empty());
+	assert!(gen.eval("${size} ${dragon}").is_err());
+	assert_eq!(gen.last_eval_stats().tokens, 3);
+	assert_eq!(gen.last_eval_stats().draws.get("adverb"), Some(&1));
+}
+
+#[test]
+fn import_test_1() {
+	let mut monsters = twas::Interpreter::from_seed(1);
+	monsters.load_lines("undead", vec!["zombie", "ghost"]).expect("Failure");
+	monsters.set_csv_options(twas::CsvOptions::default().with_rows(true));
+	monsters.load_str("npc", "name,job\nAnn,smith\nBo,cook", "csv").expect("Failure");
+	monsters.set_depletion("undead", twas::Depletion::Deplete);
+	let mut names = twas::Interpreter::from_seed(2);
+	names.load_lines("undead", vec!["mummy"]).expect("Failure");
+	let mut app = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	app.import(&monsters, "pack/").expect("Failure");
+	app.import(&names, "").expect("Failure");
+	let mut ids: Vec<&String> = app.list_ids();
+	ids.sort();
+	assert_eq!(ids, vec!["pack/npc/job", "pack/npc/name", "pack/undead", "undead"]);
+	assert_eq!(app.get_depletion("pack/undead"), twas::Depletion::Deplete);
+	assert_eq!(app.eval("${{row: pack/npc}}${@npc-name}").unwrap(), "Ann");
+	app.import(&names, "pack").expect("Failure");
+	assert_eq!(app.get_lut("pack/undead").unwrap().len(), 3);
+	// the other interpreter's look-up table does not change
+	assert_eq!(monsters.get_lut("undead").unwrap().len(), 2);
+	assert!(app.import(&names, "bad$prefix").is_err());
+}
+
+#[test]
+fn export_registry_test_1() {
+	let mut gen = twas::Interpreter::from_seed(1);
+	for file in ["weapons.yaml", "places.yaml", "npc-stats.csv", "rarity.json", "hoard.json", "quality.csv"] {
+		gen.load_file(format!("tests/test-data/{}", file)).expect("Failure");
+	}
+	gen.load_str("tiers", "{'1-3': weak, '4': fair, '5+': strong}", "yaml").expect("Failure");
+	gen.load_str("loot", "- {text: sword, weight: 2.5, key: sw, requires: '$level > 2', max-per-eval: 1}\n\
+		- {text: shield, weight: '$level > 3 ? 4 : 1', meta: {key: k1, text: t1}}\n- {min: 10, max: 20}", "yaml").expect("Failure");
+	for format in [twas::Format::Yaml, twas::Format::Json] {
+		let mut exported: Vec<u8> = Vec::new();
+		gen.export_registry(&mut exported, format).expect("Failure");
+		// hidden tables of CSV rows are not exported
+		assert!(!String::from_utf8_lossy(&exported).contains("$rows"));
+		let mut copy = twas::Interpreter::from_seed(1);
+		copy.load_str("", String::from_utf8(exported).unwrap(), format.extension()).expect("Failure");
+		let mut ids: Vec<&String> = gen.list_ids();
+		ids.sort();
+		let mut copy_ids: Vec<&String> = copy.list_ids();
+		copy_ids.sort();
+		assert_eq!(ids, copy_ids);
+		let ids: Vec<String> = ids.into_iter().cloned().collect();
+		for id in ids {
+			let items = format!("{:?}", gen.get_lut(id.as_str()).unwrap().iter().collect::<Vec<_>>());
+			assert_eq!(format!("{:?}", copy.get_lut(id.as_str()).unwrap().iter().collect::<Vec<_>>()), items, "{}", id);
+		}
+	}
+	assert!(gen.export_registry(Vec::new(), twas::Format::Csv).is_err());
+	assert!(gen.lint_tables().iter().all(|(id, _)| !id.contains("$rows")));
+	assert!(gen.freeze().get_ids().all(|id| !id.contains("$rows")));
+}
+
+#[test]
+fn var_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("town", "Oakmoor\nRiverbend", "txt").expect("Failure");
+	gen.set_var("campaign", "Ironreach").expect("Failure");
+	gen.set_var("region", "the ${town} marches").expect("Failure");
+	assert_eq!(gen.eval("${var:campaign}: ${var:region}").unwrap(), "Ironreach: the Oakmoor marches");
+	assert_eq!(gen.eval("${{id: 'var:campaign', case: upper}}").unwrap(), "IRONREACH");
+	// the evaluated text cannot change a variable
+	assert_eq!(gen.eval("${town@campaign} ${var:campaign}").unwrap(), "Oakmoor Ironreach");
+	assert_eq!(gen.list_missing("${var:campaign} ${var:era}").unwrap(), vec!["var:era"]);
+	assert!(gen.eval("${var:era}").is_err());
+	assert!(gen.set_var("bad name", "x").is_err());
+	assert_eq!(gen.fork().eval("${var:campaign}").unwrap(), "Ironreach");
+	assert_eq!(gen.freeze().eval("${var:campaign}", &mut rand::thread_rng()).unwrap(), "Ironreach");
+	assert_eq!(gen.list_vars(), vec!["campaign", "region"]);
+	assert!(gen.remove_var("region"));
+	assert_eq!(gen.get_var("region"), None);
+	gen.clear_vars();
+	assert!(gen.get_var("campaign").is_none());
+}
+
+#[test]
+fn replay_test_1() {
+	let mut gen = twas::Interpreter::from_seed(11);
+	gen.load_str("monster", "- ${size} goblin\n- '@table:undead'\n- {text: ogre, key: og}", "yaml").expect("Failure");
+	gen.load_lines("size", vec!["small", "big", "huge"]).expect("Failure");
+	gen.load_lines("undead", vec!["zombie", "ghost", "mummy"]).expect("Failure");
+	gen.load_str("gold", "- {min: 50, max: 200}", "yaml").expect("Failure");
+	gen.set_csv_options(twas::CsvOptions::default().with_rows(true));
+	gen.load_str("npc", "name,job\nAnn,smith\nBo,cook\nCy,baker", "csv").expect("Failure");
+	gen.register_function("luck", |_args, ctx| Ok((ctx.rng().next_u32() % 100).to_string())).unwrap();
+	gen.set_var("campaign", "Ironreach").expect("Failure");
+	gen.set_persistent_ref("hero", "Alice").expect("Failure");
+	let template = "${@hero} of ${var:campaign} meets ${{id: monster, count: 1d4, sep: ', '}} \
+		with ${gold} gold and #{2d6} arrows. ${{row: npc}}${@npc-name} the ${@npc-job} rolls ${fn:luck()}.";
+	for _ in 0..20 {
+		let (text, log) = gen.eval_recorded(template).expect("Failure");
+		// replay with the items in a different order, a different seed, and no references
+		let mut other = twas::Interpreter::from_seed(99);
+		other.set_csv_options(twas::CsvOptions::default().with_rows(true));
+		other.load_str("monster", "- {text: ogre, key: og}\n- '@table:undead'\n- ${size} goblin", "yaml").expect("Failure");
+		other.load_lines("size", vec!["huge", "big", "small"]).expect("Failure");
+		other.load_lines("undead", vec!["mummy", "ghost", "zombie"]).expect("Failure");
+		other.load_str("gold", "- {min: 50, max: 200}", "yaml").expect("Failure");
+		other.load_str("npc", "name,job\nCy,baker\nBo,cook\nAnn,smith", "csv").expect("Failure");
+		let log: twas::DrawLog = log.to_string().parse().expect("Failure");
+		assert_eq!(other.eval_replay(&log).expect("Failure"), text);
+		assert!(other.list_persistent_refs().is_empty());
+	}
+	// a drawn item that no longer exists
+	let (_, log) = gen.eval_recorded("${undead}").expect("Failure");
+	let mut other = twas::Interpreter::new();
+	other.load_lines("undead", vec!["vampire"]).expect("Failure");
+	assert!(other.eval_replay(&log).is_err());
+	assert!("{\"version\": 999, \"text\": \"\", \"decisions\": []}".parse::<twas::DrawLog>().is_err());
+}
+
+#[test]
+fn deadline_test_1() {
+	use std::time::{Duration, Instant};
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	for level in 0..6 {
+		let next = format!("${{level{}}}", level + 1);
+		gen.load_lines(format!("level{}", level).as_str(), vec![next.repeat(10)]).expect("Failure");
+	}
+	gen.load_lines("level6", vec!["x"]).expect("Failure");
+	// a million substitutions, which take much longer than the time limit
+	let start = Instant::now();
+	let e = gen.eval_with_deadline("${level0}", Duration::from_millis(20)).unwrap_err();
+	assert!(e.to_string().contains("Time limit of 20ms exceeded"), "{}", e);
+	assert!(start.elapsed() < Duration::from_secs(5));
+	// the time limit is not recoverable in lenient mode
+	gen.set_strictness(twas::Strictness::lenient());
+	assert!(gen.eval_with_deadline("${level0}", Duration::from_millis(20)).is_err());
+	assert_eq!(gen.eval_with_deadline("${level5}", Duration::from_secs(10)).unwrap(), "xxxxxxxxxx");
+}
+
+#[test]
+fn weight_audit_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("treasure", "{gold: 1, silver: 100000, lint: 0}", "yaml").expect("Failure");
+	gen.load_str("animal", "dog\ncat", "txt").expect("Failure");
+	let issues = gen.audit_weights(1000.);
+	assert_eq!(issues.len(), 3);
+	assert!(issues.iter().all(|(id, _)| id == "treasure"));
+	assert!(gen.load_str("bad", "thing,weight\nrock,NaN", "csv").is_err());
+	assert!(gen.load_str("bad", "{rock: .inf}", "yaml").is_err());
+}
+
+#[test]
+fn dice_in_id_test_1() {
+	use regex::Regex;
+	let mut gen = twas::Interpreter::from_seed(7);
+	gen.load_str("encounters", "tier1: [rats]\ntier2: [wolves]\ntier3: [a dragon]", "yaml").expect("Failure");
+	let matcher = Regex::new("^You meet (rats|wolves|a dragon).$").unwrap();
+	for _ in 0..10 {
+		let output = gen.eval("You meet ${encounters/tier#{1d3}}.").unwrap();
+		assert!(matcher.is_match(output.as_str()), "Incorrect evaluation: {}", output);
+	}
+	let output = gen.eval(r#"${{id: "encounters/tier#{1d1+1}", case: upper}}"#).unwrap();
+	assert_eq!(output, "WOLVES");
+}
+
+#[test]
+fn compat_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("animal", "- \" cat \"", "yaml").expect("Failure");
+	let input = "[${{id: animal, count: 2}}]";
+	assert_eq!(gen.get_compat(), twas::CompatLevel::V1);
+	assert_eq!(gen.eval(input).unwrap(), "[ cat  cat ]");
+	gen.set_compat(twas::CompatLevel::V2);
+	assert_eq!(gen.eval(input).unwrap(), "[cat, cat]");
+	assert_eq!(gen.eval("[${{id: animal, count: 2, sep: \" & \", strip: false}}]").unwrap(), "[ cat  &  cat ]");
+	let mut options = gen.get_options().clone();
+	options.default_sep = Some(String::from("/"));
+	gen.set_options(options);
+	assert_eq!(gen.eval(input).unwrap(), "[cat/cat]");
+}
+
+#[test]
+fn count_range_test_1() {
+	let mut gen = twas::Interpreter::from_seed(99);
+	gen.load_str("animal", "cat", "txt").expect("Failure");
+	for _ in 0..20 {
+		let output = gen.eval("${{id: animal, count: {min: 2, max: 4}, sep: \",\"}}").unwrap();
+		let n = output.split(',').count();
+		assert!((2..=4).contains(&n), "Incorrect count: {}", output);
+	}
+	assert_eq!(gen.eval("${{id: animal, count: {max: 0}}}").unwrap(), "");
+	assert!(gen.eval("${{id: animal, count: {min: 5, max: 2}}}").is_err());
+	assert!(gen.eval("${{id: animal, count: {min: 2}}}").is_err());
+}
+
+#[test]
+fn group_duplicates_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/loot.json").expect("IO Error");
+	let output = gen.eval(r#"${{id: loot/junk, count: 3, sep: ", ", group-duplicates: true, case: title}}"#).unwrap();
+	assert_eq!(output, "3x Old Boot");
+	let output = gen.eval(r#"${{id: loot/junk, count: 1, group-duplicates: true, aan: true}}"#).unwrap();
+	assert_eq!(output, "an old boot");
+	let output = gen.eval(r#"${{id: loot/junk, count: 4, sep: ", ", method: shuffle, group-duplicates: true}}"#).unwrap();
+	assert_eq!(output.matches(", ").count() + 1, 3, "Incorrect grouping: {}", output);
+	assert!(output.contains("2x "), "Incorrect grouping: {}", output);
+}
+
+#[test]
+fn format_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("animal", "${color} \"dog\"", "txt").expect("Failure");
+	gen.load_str("color", "brown", "txt").expect("Failure");
+	let output = gen.eval("${{id: animal, count: 2, format: json, case: upper}}").unwrap();
+	assert_eq!(output, r#"["BROWN \"DOG\"","BROWN \"DOG\""]"#);
+	assert!(gen.eval("${{id: animal, count: 2, format: json, prefix: \"* \"}}").is_err());
+	assert!(gen.eval("${{id: animal, count: 2, format: yaml, sep: \", \"}}").is_err());
+	let output = gen.eval("${{id: animal, count: 2, format: json, group-duplicates: true}}").unwrap();
+	let parsed: serde_json::Value = serde_json::from_str(output.as_str()).expect("Invalid JSON");
+	assert_eq!(parsed, serde_json::json!([{"text": "brown \"dog\"", "count": 2}]));
+	let output = gen.eval("${{id: color, count: 2, format: yaml}}").unwrap();
+	assert_eq!(output, "- brown\n- brown");
+	assert!(gen.eval("${{id: color, format: xml}}").is_err());
+	// references created by the items are kept after the token, unless it is scoped
+	gen.load_str("hero", "${color@hue} knight", "txt").expect("Failure");
+	assert_eq!(gen.eval("${{id: hero, format: json}} (${@hue})").unwrap(), r#"["brown knight"] (brown)"#);
+	assert!(gen.eval("${{id: hero, format: json, scoped: true}} (${@hue})").is_err());
+}
+
+#[test]
+fn front_matter_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/animal.txt").expect("IO Error");
+	let template = "---\nrequires: [animal, pet-names]\nrefs: {owner: Alice}\nseed: 42\n---\n${@owner} has a ${animal}.";
+	assert_eq!(gen.check_template(template).unwrap(), vec!["pet-names"]);
+	// front matter is only read by eval(...) when enabled (or with CompatLevel::V2)
+	assert!(gen.eval(template).is_err());
+	gen.set_front_matter(true);
+	assert_eq!(gen.eval(template).unwrap(), "Alice has a dog.");
+	assert_eq!(gen.check_template("${@owner} has a ${animal}.").unwrap(), vec!["@owner"]);
+	assert!(gen.check_template("---\nrequire: [animal]\n---\n${animal}").is_err());
+	assert_eq!(gen.eval("---\nnot front matter\n---\n").unwrap(), "---\nnot front matter\n---\n");
+}
+
+#[test]
+fn lenient_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("animal", "dog\n${color} cat", "txt").expect("Failure");
+	let input = "My ${animal} ate my ${vehicle}, said ${@owner}.";
+	assert!(gen.eval(input).is_err());
+	gen.set_lenient_missing(true);
+	let output = gen.eval(input).unwrap();
+	assert_eq!(output, "My dog ate my ⟦missing: vehicle⟧, said ⟦missing: owner⟧.");
+	assert_eq!(gen.get_warnings().len(), 2);
+	let output = gen.eval("${{id: animal, count: 2, method: shuffle, sep: \" and \"}}").unwrap();
+	assert_eq!(output, "dog and ⟦missing: color⟧ cat");
+	assert_eq!(gen.get_warnings().len(), 1, "Warnings should be reset for each evaluation");
+}
+
+#[test]
+fn xml_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/treasure.xml").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["treasure/junk", "treasure/money"]);
+	assert_eq!(gen.eval("${treasure/money}, ${treasure/junk}").unwrap(), "100 copper pennies, old boot");
+	gen.load_str("animal", "<animals><a>dog</a><a weight=\"2\">cat</a></animals>", "xml").expect("Failure");
+	assert_eq!(gen.eval("${animal}").unwrap(), "dog");
+	assert!(gen.load_str("bad", "<animals><a>dog</animals>", "xml").is_err());
+	assert!(gen.load_str("bad", "<animals><a weight=\"heavy\">dog</a></animals>", "xml").is_err());
+}
+
+#[test]
+fn markdown_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/creatures.md").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["creatures/Creatures/Coast/Deep Sea", "creatures/Creatures/Coast/Mood",
+		"creatures/Creatures/Coast/Name", "creatures/Creatures/Forest"]);
+	assert_eq!(gen.eval("${creatures/Creatures/Forest}, ${creatures/Creatures/Coast/Deep Sea}").unwrap(), "wolf, kraken");
+	let sorted_draw = |gen: &mut twas::Interpreter<NotRandom>, id: &str| {
+		let output = gen.eval(format!("${{{{id: \"{}\", count: 3, method: shuffle, sep: \",\"}}}}", id)).unwrap();
+		let mut items: Vec<String> = output.split(',').map(String::from).collect();
+		items.sort();
+		items
+	};
+	assert_eq!(sorted_draw(&mut gen, "creatures/Creatures/Coast/Name"), vec!["crab", "gull", "sea|snake"]);
+	assert_eq!(sorted_draw(&mut gen, "creatures/Creatures/Forest"), vec!["bear", "owl", "wolf"]);
+	gen.load_str("pets", "- dog\n- cat", "md").expect("Failure");
+	assert_eq!(gen.eval("${pets}").unwrap(), "dog");
+	// fenced code blocks are skipped, and a pipe table needs a separator row directly under its
+	// header row
+	gen.load_str("notes", "- tip\n```yaml\n- not an item\n# not a heading\n| a | b |\n|---|---|\n| 1 | 2 |\n```\n\
+		~~~~\n```\n- still code\n~~~~\n| x | y |\nsome text\n---\n| z |\n|---|---|\n| 3 |", "md").expect("Failure");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.retain(|id| id.starts_with("notes"));
+	assert_eq!(loaded_ids, vec!["notes"]);
+	assert_eq!(gen.get_lut("notes").unwrap().len(), 1);
+}
+
+#[test]
+fn delimiter_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/colors.tsv").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["colors/color", "colors/weight"]);
+	assert_eq!(gen.eval("${colors/color}").unwrap(), "dark red");
+	gen.load_csv_str_with("prices", "fruit;price\napple;\"1,20\"\n", &twas::CsvOptions::with_delimiter(';'))
+		.expect("Failure");
+	assert_eq!(gen.eval("${prices/fruit} costs ${prices/price}").unwrap(), "apple costs 1,20");
+	gen.load_str("pets", "dog\tcat\nspot\tpaws", "tsv").expect("Failure");
+	assert_eq!(gen.eval("${pets/cat}").unwrap(), "paws");
+}
+
+#[test]
+fn xlsx_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/armory.xlsx").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["armory/armor/armor", "armory/weapons/material", "armory/weapons/weapon"]);
+	assert_eq!(gen.eval("${armory/weapons/material} ${armory/weapons/weapon} and ${armory/armor/armor}").unwrap(),
+		"iron sword and chain mail");
+}
+
+#[test]
+fn twee_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/adventure.twee").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["adventure/Forest", "adventure/Start"]);
+	assert_eq!(gen.eval("${adventure/Start}").unwrap(),
+		"You wake up at the edge of a forest. Tall pines sway in the wind.\n[[Enter the forest->Forest]]");
+	gen.load_str("story", ":: Cave\\[dark\\] [tag]\nIt is dark.\n", "twee").expect("Parse Error");
+	assert!(gen.get_lut("story/Cave[dark]").is_some());
+	assert!(gen.load_str("story", ":: Bad {\"weight\": -1}\nNope\n", "twee").is_err());
+}
+
+#[test]
+fn d100_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/encounters.csv").expect("IO Error");
+	gen.load_file("tests/test-data/hoard.json").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["encounters/encounter", "hoard/loot"]);
+	let encounters = gen.get_lut("encounters/encounter").unwrap();
+	assert_eq!(encounters.lookup_value(1.).unwrap().get_weight(), 5.);
+	assert_eq!(encounters.lookup_value(100.).unwrap().get_text(), "a dragon");
+	assert_eq!(encounters.lookup_value(100.).unwrap().get_weight(), 5.);
+	let loot = gen.get_lut("hoard/loot").unwrap();
+	assert_eq!(loot.lookup_value(75.).unwrap().get_text(), "a silver ring");
+	assert_eq!(loot.lookup_value(75.).unwrap().get_weight(), 30.);
+	assert_eq!(gen.eval("${encounters/encounter:42}").unwrap(), "nothing happens");
+	assert!(gen.load_str("bad", "roll,encounter\nsoon,bandits\n", "csv").is_err());
+}
+
+#[test]
+fn mixed_list_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("prize", r#"[{"text": "a gold coin", "weight": 3, "note": "common"}, "a gem", 7, {"text": 42}]"#, "json")
+		.expect("Failure");
+	let prize = gen.get_lut("prize").unwrap();
+	assert_eq!(prize.draw_n_random(&mut NotRandom::seed_from_u64(0), 1).unwrap()[0].get_text(), "a gold coin");
+	assert!(prize.remove_item("a gem"));
+	assert!(prize.remove_item("7"));
+	assert!(prize.remove_item("42"));
+	assert!(gen.load_str("bad", r#"[{"weight": 2}]"#, "json").is_err());
+	assert!(gen.load_str("bad", r#"[["nested"]]"#, "json").is_err());
+}
+
+#[test]
+fn include_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/include/bestiary.yaml").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["bestiary/beasts", "bestiary/undead", "bestiary/villagers"]);
+	assert_eq!(gen.eval("A ${bestiary/undead} attacks the ${bestiary/villagers}").unwrap(), "A skeleton attacks the miller");
+	assert!(gen.load_file("tests/test-data/include/loop.yaml").is_err());
+	// includes cannot leave the directory of the including file, or come from a text
+	assert!(gen.load_file("tests/test-data/include/escape.yaml").is_err());
+	assert!(gen.load_str("secret", "$include: tests/test-data/animal.txt", "yaml").is_err());
+	assert!(gen.list_ids().iter().all(|id| !id.starts_with("secret") && !id.starts_with("escape")));
+}
+
+#[test]
+fn include_test_2() {
+	use std::fs;
+	let dir = std::env::temp_dir().join(format!("twas-include-test-{}", std::process::id()));
+	fs::create_dir_all(dir.join("parts")).unwrap();
+	fs::write(dir.join("pack.yaml"), "villagers: !include parts/villagers.txt").unwrap();
+	fs::write(dir.join("parts/villagers.txt"), "miller").unwrap();
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file(dir.join("pack.yaml")).expect("IO Error");
+	assert_eq!(gen.eval("${pack/villagers}").unwrap(), "miller");
+	// re-loading an included file re-loads the file that includes it
+	fs::write(dir.join("parts/villagers.txt"), "baker").unwrap();
+	assert!(gen.reload_file(dir.join("parts/villagers.txt")).unwrap());
+	assert_eq!(gen.eval("${pack/villagers}").unwrap(), "baker");
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn json5_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/weather.json5").expect("IO Error");
+	gen.load_str("mood", "[\"cheerful\", // happy\n \"gloomy\",]", "json").expect("Parse Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["mood", "weather/summer", "weather/winter"]);
+	assert_eq!(gen.eval("${weather/winter} and ${mood}").unwrap(), "snowy and cheerful");
+	assert!(gen.load_str("bad", "{\"a\": [}", "json").is_err());
+}
+
+#[test]
+fn extends_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	// the extension is created once its base table is loaded
+	gen.load_file("tests/test-data/extends/campaign.yaml").expect("IO Error");
+	assert!(gen.get_lut("campaign/monsters").is_none());
+	assert_eq!(gen.unresolved_extensions(), vec![("campaign/monsters", "core/monsters")]);
+	gen.load_file("tests/test-data/extends/core.yaml").expect("IO Error");
+	assert!(gen.unresolved_extensions().is_empty());
+	assert_eq!(gen.eval("${campaign/monsters}").unwrap(), "goblin");
+	let monsters = gen.get_lut("campaign/monsters").unwrap();
+	assert!(!monsters.remove_item("rat"));
+	assert!(monsters.remove_item("wolf"));
+	assert!(monsters.remove_item("dragon"));
+	// overriding a table with an extension of itself
+	gen.load_str("core", "monsters: {$extends: core/monsters, $add: [troll], $remove: goblin}", "yaml").expect("Failure");
+	assert_eq!(gen.eval("${core/monsters}").unwrap(), "rat");
+	assert!(gen.get_lut("core/monsters").unwrap().remove_item("troll"));
+	assert!(gen.load_str("bad", "{$extends: core/monsters, $replace: [troll]}", "yaml").is_err());
+	assert!(gen.load_str("bad", "{$extends: core/monsters, $weights: {rat: -1}}", "yaml").is_err());
+	// lazily loaded extensions
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.set_lazy_loading(true);
+	gen.load_file("tests/test-data/extends").expect("IO Error");
+	assert_eq!(gen.eval("${campaign/monsters}").unwrap(), "goblin");
+	assert!(gen.get_lut("campaign/monsters").unwrap().remove_item("dragon"));
+}
+
+#[test]
+fn encoding_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	// files that are not valid UTF-8 need an explicit encoding
+	assert!(gen.load_file("tests/test-data/cafes.csv").is_err());
+	assert!(gen.eval("${cafes/name}").is_err());
+	// Windows-1252 and UTF-16 with byte-order mark
+	gen.set_encoding(Some("windows-1252")).expect("Unknown encoding");
+	gen.load_file("tests/test-data/cafes.csv").expect("IO Error");
+	gen.load_file("tests/test-data/greetings.txt").expect("IO Error");
+	assert_eq!(gen.eval("${greetings} from ${cafes/name}: ${cafes/dish}").unwrap(),
+		"grüß dich from Café Noël: crème brûlée");
+	// explicit encoding
+	gen.set_encoding(Some("iso-8859-15")).expect("Unknown encoding");
+	assert_eq!(gen.get_encoding(), Some("ISO-8859-15"));
+	gen.load_file_namespaced("tests/test-data/cafes.csv", "latin9").expect("IO Error");
+	assert_eq!(gen.eval("${latin9/cafes/name}").unwrap(), "Café Noël");
+}
+
+#[test]
+fn glob_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/glob/**/*.yaml").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["monsters/beasts", "monsters/undead"]);
+	// matching directories are loaded like load_file(...) with the directory, but namespaced
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file_namespaced("tests/test-data/glob/[mp]*", "pack").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["pack/monsters/beasts", "pack/monsters/undead", "pack/places"]);
+	assert_eq!(gen.eval("A ${pack/monsters/undead} at the ${pack/places}").unwrap(), "A skeleton at the inn");
+	// no duplicates when both a directory and its files match
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/glob/**").expect("IO Error");
+	assert!(gen.get_lut("monsters/beasts").unwrap().remove_item("dire wolf"));
+	assert!(gen.get_lut("monsters/beasts").unwrap().draw_random(&mut NotRandom::seed_from_u64(0)).is_err());
+	assert!(gen.load_file("tests/test-data/glob/*.csv").is_err());
+}
+
+#[test]
+fn dir_options_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_dir("tests/test-data/filtered").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["README/Table pack", "drafts/ideas", "monsters/old/undead", "monsters/undead", "notes-draft", "places"]);
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	let options = twas::DirLoadOptions::default()
+		.with_exclude("README*")
+		.with_exclude("drafts")
+		.with_exclude("*-draft.txt")
+		.with_max_depth(1);
+	gen.load_dir_with("tests/test-data/filtered", "pack", &options).expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["pack/monsters/undead", "pack/places"]);
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	let options = twas::DirLoadOptions::default().with_include("monsters/*.yaml");
+	gen.load_dir_with("tests/test-data/filtered", "", &options).expect("IO Error");
+	assert_eq!(gen.list_ids(), vec!["monsters/undead"]);
+	assert!(gen.load_dir_with("tests/test-data/filtered", "", &twas::DirLoadOptions::default().with_include("[")).is_err());
+}
+
+#[test]
+fn load_reader_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	let csv: &[u8] = b"weight,color\n3,red\n1,blue\n";
+	gen.load_reader("paint", csv, twas::Format::Csv).expect("Parse Error");
+	let zip = std::fs::File::open("tests/test-data/testzip.zip").expect("IO Error");
+	gen.load_reader("pack", zip, "zip".parse().unwrap()).expect("Parse Error");
+	assert!(gen.list_ids().iter().any(|id| id.starts_with("pack/")));
+	assert_eq!(gen.eval("${paint/color}").unwrap(), "red");
+	assert!(gen.load_reader("bad", csv, twas::Format::Xlsx).is_err());
+}
+
+#[test]
+fn sniff_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/sniff/names").expect("Parse Error");
+	for name in ["colors", "menu", "names"] {
+		gen.load_file_namespaced(format!("tests/test-data/sniff/{}", name), "sniffed").expect("Parse Error");
+	}
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["names", "sniffed/colors/color", "sniffed/colors/weight", "sniffed/menu/drinks",
+		"sniffed/menu/snacks", "sniffed/names"]);
+	assert_eq!(gen.eval("${names} ordered ${sniffed/menu/snacks} and ${sniffed/colors/color} ${sniffed/menu/drinks}").unwrap(),
+		"Alice ordered chips and red soda");
+	// files without an extension are not picked up when scanning a directory
+	let mut scanned = twas::Interpreter::new();
+	scanned.load_dir("tests/test-data/sniff").expect("IO Error");
+	assert!(scanned.list_ids().is_empty());
+}
+
+#[test]
+fn record_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/weapons.yaml").expect("Parse Error");
+	assert_eq!(gen.eval("${weapons/weapons@w} (${@w.damage}, ${@w.cost} gp)").unwrap(), "Longsword (1d8, 15 gp)");
+	assert_eq!(gen.eval("${{id: weapons/weapons, ref: w, hidden: true}}${@w.name} costs ${@w.cost} gp").unwrap(),
+		"Longsword costs 15 gp");
+	let weapons = gen.get_lut("weapons/weapons").unwrap();
+	assert!(weapons.remove_item("Longsword"));
+	let dagger = weapons.draw_random(&mut NotRandom::seed_from_u64(0)).unwrap();
+	assert_eq!(dagger.get_weight(), 3.);
+	assert!(dagger.get_field("weight").is_none());
+	assert!(weapons.remove_item("Dagger"));
+	let hammer = weapons.draw_random(&mut NotRandom::seed_from_u64(0)).unwrap();
+	assert_eq!(hammer.get_field("properties").unwrap(), "versatile");
+	assert!(gen.eval("${weapons/weapons@w}${@w.color}").is_err());
+	gen.load_str("shields", "- {name: Buckler}\n- text: Tower shield", "yaml").expect("Parse Error");
+	assert_eq!(gen.eval("${shields@s}${@s.name}").unwrap(), "BucklerBuckler");
+	assert!(gen.load_str("armor", "- {name: Mail, cost: [50, 60]}", "yaml").is_err());
+}
+
+#[test]
+fn row_test_1() {
+	// the rows are only kept when requested
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/npc-stats.csv").expect("Parse Error");
+	assert!(gen.eval("${{row: npc-stats}}").is_err());
+	assert!(gen.get_lut("npc-stats/$rows").is_none());
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.set_csv_options(twas::CsvOptions::default().with_rows(true));
+	gen.load_file("tests/test-data/npc-stats.csv").expect("Parse Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["npc-stats/job", "npc-stats/name", "npc-stats/quirk", "npc-stats/weight"]);
+	let template = "${{row: npc-stats, ref-prefix: npc}}${@npc-name} the ${@npc-job} ${@npc-quirk}";
+	assert_eq!(gen.eval(template).unwrap(), "Brunhilde the blacksmith hums constantly");
+	assert!(gen.check_template(template).unwrap().is_empty());
+	assert_eq!(gen.eval("${{row: npc-stats, ref: npc}}${@npc-stats-job}, ${@npc} (${@npc.quirk})").unwrap(),
+		"blacksmith, Brunhilde (hums constantly)");
+	assert!(gen.eval("${{row: npc-stats}}${@npc-stats-weight}").is_err());
+	assert!(gen.eval("${{row: npc-stats, id: npc-stats/name}}").is_err());
+	assert!(gen.eval("${{row: animal}}").is_err());
+	assert_eq!(gen.check_template("${{row: animal}}").unwrap(), vec!["animal"]);
+}
+
+#[test]
+fn merge_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("monsters", "goblin\nrat", "txt").expect("Failure");
+	let mut expansion = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	expansion.load_str("monsters", "{goblin: 2, dragon: 1}", "yaml").expect("Failure");
+	let extra = expansion.get_lut("monsters").unwrap().clone();
+	gen.merge_lut("monsters", &extra, true).expect("Failure");
+	gen.merge_lut("new/monsters", &extra, false).expect("Failure");
+	let monsters = gen.get_lut("monsters").unwrap();
+	let items: Vec<(String, f64)> = monsters.iter().map(|item| (item.get_text().clone(), item.get_weight())).collect();
+	assert_eq!(items, vec![(String::from("goblin"), 3.), (String::from("rat"), 1.), (String::from("dragon"), 1.)]);
+	assert_eq!(gen.get_lut("new/monsters").unwrap().len(), 2);
+	assert!(gen.merge_lut("bad@id", &extra, false).is_err());
+}
+
+#[test]
+fn deplete_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("cards", "ace\nking\nqueen", "txt").expect("Failure");
+	assert_eq!(gen.eval("${{id: cards, method: deplete, count: 2, sep: \", \"}}").unwrap(), "ace, king");
+	assert_eq!(gen.eval("${{id: cards, method: deplete}}").unwrap(), "queen");
+	assert!(gen.eval("${{id: cards, method: deplete}}").is_err());
+	assert_eq!(gen.eval("${cards}").unwrap(), "ace");
+	assert_eq!(gen.eval("${{id: cards, method: deplete-reset}}").unwrap(), "ace");
+	assert!(gen.reset_depleted("cards"));
+	gen.set_depletion("cards", twas::Depletion::DepleteAndReset);
+	assert_eq!(gen.get_depletion("cards"), twas::Depletion::DepleteAndReset);
+	assert_eq!(gen.eval("${cards} ${cards} ${cards} ${cards}").unwrap(), "ace king queen ace");
+	assert!(gen.eval("${{id: cards, method: deplete-forever}}").is_err());
+}
+
+#[test]
+fn draw_state_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("hooks", "a stolen relic\na missing heir\na cursed well", "txt").expect("Failure");
+	gen.set_depletion("hooks", twas::Depletion::Deplete);
+	assert_eq!(gen.eval("${hooks}").unwrap(), "a stolen relic");
+	let saved = serde_json::to_string(&gen.get_draw_state()).unwrap();
+	// next session
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("hooks", "a stolen relic\na missing heir\na cursed well", "txt").expect("Failure");
+	gen.set_draw_state(serde_json::from_str(saved.as_str()).unwrap());
+	assert_eq!(gen.get_depletion("hooks"), twas::Depletion::Deplete);
+	assert_eq!(gen.eval("${hooks}, ${hooks}").unwrap(), "a missing heir, a cursed well");
+	assert!(gen.eval("${hooks}").is_err());
+	gen.reset_all_depleted();
+	assert_eq!(gen.eval("${hooks}").unwrap(), "a stolen relic");
+	gen.set_draw_state(twas::DrawState::default());
+	assert_eq!(gen.get_depletion("hooks"), twas::Depletion::Replace);
+}
+
+#[test]
+fn table_defaults_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/places.yaml").expect("Parse Error");
+	assert_eq!(gen.eval("${places/cities}").unwrap(), "New York");
+	assert_eq!(gen.eval("${{id: places/cities, case: upper}}").unwrap(), "NEW YORK");
+	assert_eq!(gen.eval("${places/taverns/names}").unwrap(), "A Prancing Pony");
+	gen.set_table_defaults("places/cities", Some("{case: upper}")).expect("Failure");
+	assert_eq!(gen.eval("${places/cities}").unwrap(), "NEW YORK");
+	gen.set_table_defaults("places/cities", None).expect("Failure");
+	assert_eq!(gen.eval("${places/cities}").unwrap(), "new york");
+	assert!(gen.set_table_defaults("places/cities", Some("{ref: city}")).is_err());
+	assert!(gen.set_table_defaults("places/villages", Some("{case: title}")).is_err());
+	assert!(gen.load_str("bad", "{$options: {id: animal}, pets: [dog]}", "yaml").is_err());
+}
+
+#[test]
+fn persistent_ref_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.set_front_matter(true);
+	gen.load_str("hero", "Alice\nBob", "txt").expect("Failure");
+	assert_eq!(gen.eval("${{id: hero, ref: h, persist: true}} sets out.").unwrap(), "Alice sets out.");
+	assert_eq!(gen.get_persistent_ref("h"), Some("Alice"));
+	assert_eq!(gen.eval("${@h} returns.").unwrap(), "Alice returns.");
+	// front matter refs are only defaults
+	assert_eq!(gen.eval("---\nrefs: {h: Carol}\n---\n${@h}").unwrap(), "Alice");
+	assert!(gen.list_missing("${@h}").unwrap().is_empty());
+	assert!(gen.eval("${{id: hero, persist: true}}").is_err());
+	gen.set_persistent_ref("villain", "Mallory").expect("Failure");
+	assert_eq!(gen.list_persistent_refs(), vec!["h", "villain"]);
+	assert!(gen.remove_persistent_ref("h"));
+	assert!(gen.eval("${@h}").is_err());
+	gen.clear_persistent_refs();
+	assert!(gen.get_persistent_ref("villain").is_none());
+}
+
+#[test]
+fn save_state_test_1() {
+	let mut gen = twas::Interpreter::from_seed(7);
+	gen.set_csv_options(twas::CsvOptions::default().with_rows(true));
+	gen.load_file("tests/test-data/npc-stats.csv").expect("Parse Error");
+	gen.load_str("animal", "aardvark\nbird\ncat\ndog\nelephant", "txt").expect("Failure");
+	gen.set_depletion("animal", twas::Depletion::Deplete);
+	gen.eval("${{row: npc-stats, ref-prefix: npc, persist: true}}${{id: animal, ref: pet, persist: true}}").unwrap();
+	let mut saved: Vec<u8> = Vec::new();
+	gen.save_state(&mut saved).expect("Failed to save");
+	let saved_json: serde_json::Value = serde_json::from_slice(&saved).unwrap();
+	assert!(saved_json["tables"].as_object().unwrap().keys().all(|id| !id.contains("$rows")));
+	// next session
+	let mut restored = twas::Interpreter::new();
+	restored.load_state(saved.as_slice()).expect("Failed to load");
+	let mut ids = restored.list_ids();
+	ids.sort();
+	let mut expected_ids = gen.list_ids();
+	expected_ids.sort();
+	assert_eq!(ids, expected_ids);
+	assert_eq!(restored.get_persistent_ref("pet"), gen.get_persistent_ref("pet"));
+	assert_eq!(restored.get_depletion("animal"), twas::Depletion::Deplete);
+	let template = "${@npc-name} has a ${@pet}. ${{id: animal, count: 4, sep: \", \"}}";
+	let expected = gen.eval(template).unwrap();
+	assert_eq!(restored.eval(template).unwrap(), expected);
+	assert!(restored.eval("${animal}").is_err());
+	let template = "${{row: npc-stats, ref-prefix: npc}}${@npc-name}";
+	assert_eq!(restored.eval(template).unwrap(), gen.eval(template).unwrap());
+	assert!(restored.load_state("{\"version\": 999, \"seed\": 0, \"tables\": {}}".as_bytes()).is_err());
+}
+
+#[test]
+fn metadata_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/monsters.json").expect("Parse Error");
+	assert_eq!(gen.eval("${monsters@m} (CR ${@m.meta.cr}, ${@m.habitat})").unwrap(), "Goblin (CR 1/4, forest)");
+	assert_eq!(gen.eval("${{id: monsters, field: cr}}").unwrap(), "1/4");
+	assert_eq!(gen.eval("${{id: monsters, field: habitat, ref: m, case: upper}} ${{id: \"@m\", field: cr}}").unwrap(),
+		"FOREST 1/4");
+	assert!(gen.eval("${{id: monsters, field: hp}}").is_err());
+	let monsters = gen.get_lut("monsters").unwrap();
+	assert!(monsters.remove_item("Goblin"));
+	assert!(monsters.remove_item("Troll"));
+	let owlbear = monsters.draw_random(&mut NotRandom::seed_from_u64(0)).unwrap();
+	assert_eq!(owlbear.get_text(), "a hungry owlbear");
+	assert_eq!(owlbear.get_field("name").unwrap(), "Owlbear");
+	assert_eq!(owlbear.get_field("meta.cr").unwrap(), "3");
+	assert!(gen.load_str("bad", "[{name: Imp, meta: {tags: [small]}}]", "yaml").is_err());
+}
+
+#[test]
+fn table_link_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("monster", "@table:monster/undead\n@table:beast\ngoblin", "txt").expect("Failure");
+	gen.load_str("monster/undead", "@table:monster/undead/ghost\nzombie", "txt").expect("Failure");
+	gen.load_str("monster/undead/ghost", "- {text: \"a wailing ${ghost-kind}\", key: banshee}", "yaml").expect("Failure");
+	gen.load_str("ghost-kind", "spirit", "txt").expect("Failure");
+	gen.load_str("weakness/banshee", "silver", "txt").expect("Failure");
+	assert_eq!(gen.eval("${monster@m}, weak to ${weakness/$m}").unwrap(), "a wailing spirit, weak to silver");
+	assert_eq!(gen.get_lut("monster").unwrap().linked_ids(), vec!["monster/undead", "beast"]);
+	assert_eq!(gen.list_missing("${monster}").unwrap(), vec!["beast"]);
+	gen.load_str("beast", "@table:beast", "txt").expect("Failure");
+	gen.get_lut("monster").unwrap().remove_item("@table:monster/undead");
+	assert!(gen.eval("${monster}").is_err(), "Circular links should reach the recursion limit");
+}
+
+#[test]
+fn dedup_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	assert_eq!(gen.get_dedup(), twas::Dedup::Keep);
+	gen.load_txt_str("animal", "cat\ndog\ncat").unwrap();
+	assert_eq!(gen.get_lut("animal").unwrap().len(), 3);
+	assert!(gen.take_dedup_report().is_empty());
+	gen.set_dedup(twas::Dedup::Sum);
+	gen.load_lines("animal", vec!["dog", "bird"]).unwrap();
+	let lut = gen.get_lut("animal").unwrap();
+	assert_eq!(lut.len(), 3);
+	assert_eq!(lut.get(0).unwrap().get_weight(), 2.);
+	assert_eq!(lut.get(1).unwrap().get_weight(), 2.);
+	let report = gen.take_dedup_report();
+	assert_eq!(report.len(), 2);
+	assert_eq!(report[0].0, "animal");
+	assert_eq!(report[0].1.text, "cat");
+	assert_eq!(report[1].1.count, 2);
+	assert!(gen.take_dedup_report().is_empty());
+	gen.set_dedup(twas::Dedup::Max);
+	gen.load_items("gem", vec![("ruby", 1.), ("opal", 3.), ("ruby", 4.)]).unwrap();
+	assert_eq!(gen.get_lut("gem").unwrap().get(0).unwrap().get_weight(), 4.);
+	assert_eq!(gen.get_lut("gem").unwrap().len(), 2);
+	assert_eq!("max".parse::<twas::Dedup>().unwrap(), twas::Dedup::Max);
+	assert!("most".parse::<twas::Dedup>().is_err());
+}
+
+#[test]
+fn loose_ids_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_lines("monster-names", vec!["Grog"]).unwrap();
+	gen.load_lines("caf\u{e9}/menu", vec!["tea"]).unwrap();
+	gen.load_yaml_str("strength", "strength-desc:\n  1-10: weak\n  11-20: strong").unwrap();
+	assert!(gen.eval("${Monster_Names}").is_err());
+	gen.set_loose_ids(true);
+	assert_eq!(gen.eval("${Monster_Names}").unwrap(), "Grog");
+	assert_eq!(gen.eval("${MONSTER-NAMES@m} ${@m}").unwrap(), "Grog Grog");
+	// decomposed e + combining acute accent
+	assert_eq!(gen.eval("${Cafe\u{301}/Menu}").unwrap(), "tea");
+	assert_eq!(gen.eval("${Strength/Strength_Desc:12}").unwrap(), "strong");
+	assert!(gen.eval("${monster names}").is_err());
+}
+
+#[test]
+fn namespace_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.set_csv_options(twas::CsvOptions::default().with_rows(true));
+	gen.load_file_namespaced("tests/test-data/npc-stats.csv", "pack").expect("Parse Error");
+	gen.load_lines("pack-animal", vec!["mule"]).unwrap();
+	gen.load_lines("animal", vec!["cat"]).unwrap();
+	assert_eq!(gen.list_ids_with_prefix("pack/"),
+		vec!["pack/npc-stats/job", "pack/npc-stats/name", "pack/npc-stats/quirk", "pack/npc-stats/weight"]);
+	assert_eq!(gen.list_ids_with_prefix("").len(), 6);
+	gen.rename_lut("animal", "pet").unwrap();
+	assert_eq!(gen.eval("${pet}").unwrap(), "cat");
+	assert!(gen.eval("${animal}").is_err());
+	assert!(gen.rename_lut("animal", "pet").is_err());
+	assert!(gen.rename_lut("pet", "pack-animal").is_err());
+	assert!(gen.rename_lut("pet", "$pet").is_err());
+	assert_eq!(gen.move_namespace("pack/npc-stats", "npc").unwrap(), 4);
+	assert!(gen.list_ids_with_prefix("pack").is_empty());
+	assert_eq!(gen.eval("${{row: npc, ref-prefix: npc}}${@npc-name} the ${npc/job}").unwrap(), "Brunhilde the blacksmith");
+	assert!(gen.move_namespace("npc", "pack-animal").is_ok());
+	gen.load_lines("npc/name", vec!["Bob"]).unwrap();
+	assert!(gen.move_namespace("pack-animal", "npc").is_err());
+	assert_eq!(gen.eval("${pack-animal/name}").unwrap(), "Brunhilde");
+	assert_eq!(gen.move_namespace("missing", "other").unwrap(), 0);
+}
+
+#[test]
+fn fork_test_1() {
+	let mut gen = twas::Interpreter::from_seed(42);
+	gen.load_lines("animal", vec!["cat", "dog", "bird", "fish"]).unwrap();
+	gen.set_persistent_ref("hero", "Alice").unwrap();
+	let mut fork = gen.fork();
+	assert_eq!(fork.eval("${@hero} has a ${animal}").unwrap().split(' ').next(), Some("Alice"));
+	fork.load_lines("animal", vec!["dragon"]).unwrap();
+	fork.set_persistent_ref("hero", "Bob").unwrap();
+	fork.load_lines("color", vec!["red"]).unwrap();
+	assert_eq!(fork.get_lut("animal").unwrap().len(), 5);
+	assert_eq!(gen.get_lut("animal").unwrap().len(), 4);
+	assert!(gen.eval("${color}").is_err());
+	assert_eq!(gen.eval("${@hero}").unwrap(), "Alice");
+	gen.remove_lut("animal");
+	assert_eq!(fork.get_lut("animal").unwrap().len(), 5);
+	// forks of identically seeded interpreters are identical
+	let mut a = twas::Interpreter::from_seed(7);
+	let mut b = twas::Interpreter::from_seed(7);
+	a.load_lines("n", (0..100).map(|i| i.to_string())).unwrap();
+	b.load_lines("n", (0..100).map(|i| i.to_string())).unwrap();
+	let template = "${{id: n, count: 10}}";
+	assert_eq!(a.fork().eval(template).unwrap(), b.fork().eval(template).unwrap());
+}
+
+#[test]
+fn gzip_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file("tests/test-data/surnames.txt.gz").expect("IO Error");
+	gen.load_file_namespaced("tests/test-data/compressed", "gz").expect("IO Error");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["gz/places/town", "surnames"]);
+	assert_eq!(gen.eval("${surnames} of ${gz/places/town}").unwrap(), "Smith of Riverford");
+}
+
+#[test]
+fn url_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	// no file name in the URL, so the file type is unknown before anything is downloaded (or
+	// the url feature is disabled)
+	assert!(gen.load_file("https://example.com/").is_err());
+	assert!(gen.load_file("https://example.com").is_err());
+	assert!(gen.load_file("https://example.com/tables/?file=animal.txt").is_err());
+	assert!(gen.list_ids().is_empty());
+}
+
+#[test]
+#[cfg(feature = "url")]
+fn url_test_2() {
+	use std::io::{BufRead, BufReader, Write};
+	use std::net::TcpListener;
+	// serve a look-up table file (and a 404 for anything else) from a local HTTP listener
+	let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+	let port = listener.local_addr().unwrap().port();
+	let server = std::thread::spawn(move || {
+		for stream in listener.incoming().take(2) {
+			let mut stream = stream.unwrap();
+			let mut request_line = String::new();
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+			reader.read_line(&mut request_line).unwrap();
+			let mut header = String::new();
+			while reader.read_line(&mut header).unwrap() > 2 {
+				header.clear();
+			}
+			let (status, body) = match request_line.starts_with("GET /packs/monster.txt ") {
+				true => ("200 OK", "goblin\norc\n"),
+				false => ("404 Not Found", "")
+			};
+			write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+		}
+	});
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file(format!("http://127.0.0.1:{}/packs/monster.txt", port)).expect("Download Error");
+	assert_eq!(gen.list_ids(), vec!["monster"]);
+	assert_eq!(gen.eval("${monster}").unwrap(), "goblin");
+	assert!(gen.load_file(format!("http://127.0.0.1:{}/packs/missing.txt", port)).is_err());
+	server.join().unwrap();
+}
+
 #[test]
 fn dir_test_1() {
 	use regex;
@@ -299,6 +1875,19 @@ fn dir_test_2() {
 	assert!( matcher.is_match(output.as_str()), "Incorrect evaluation");
 }
 
+#[test]
+fn dir_test_3() {
+	// nested directories are separated from the namespace by '/' (eg not "packelf/names/male")
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_dir_namespaced("tests/test-data/testdir", "pack").expect("Failed to load dir");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(&loaded_ids[..], &["pack/elf/names/female", "pack/elf/names/male", "pack/elf/names/nonbinary",
+		"pack/elf/names/surname", "pack/gender", "pack/human/names/female", "pack/human/names/male",
+		"pack/human/names/nonbinary", "pack/human/names/surname", "pack/kind/species", "pack/kind/weight"]);
+	assert!(gen.get_lut("packelf/names/male").is_none());
+}
+
 #[test]
 fn zip_test_1() {
 	use regex;
@@ -319,6 +1908,211 @@ fn zip_test_1() {
 	assert!( matcher.is_match(output.as_str()), "Incorrect evaluation");
 }
 
+#[test]
+fn zip_reader_test_1() {
+	use std::io::Cursor;
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	let pack: &[u8] = include_bytes!("test-data/testzip.zip");
+	gen.load_zip_reader_namespaced(Cursor::new(pack), "pack").expect("Failed to read zip archive");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(&loaded_ids[..], &["pack/elf/names/female", "pack/elf/names/male", "pack/elf/names/nonbinary",
+		"pack/elf/names/surname", "pack/gender", "pack/human/names/female", "pack/human/names/male",
+		"pack/human/names/nonbinary", "pack/human/names/surname", "pack/kind/species", "pack/kind/weight"]);
+	assert!(gen.load_zip_reader(Cursor::new(b"not a zip archive")).is_err());
+}
+
+#[cfg(feature = "embed")]
+#[test]
+fn embed_test_1() {
+	static TABLES: twas::EmbeddedDir = twas::embed_dir!("$CARGO_MANIFEST_DIR/tests/test-data/testdir");
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_embedded(&TABLES).expect("Failed to load embedded tables");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(&loaded_ids[..], &["elf/names/female", "elf/names/male", "elf/names/nonbinary",
+		"elf/names/surname", "gender", "human/names/female", "human/names/male",
+		"human/names/nonbinary", "human/names/surname", "kind/species", "kind/weight"]);
+	gen.load_embedded_namespaced(&TABLES, "pack").expect("Failed to load embedded tables");
+	assert!(gen.list_ids().contains(&&String::from("pack/elf/names/female")));
+}
+
+#[test]
+fn load_items_test_1() {
+	use std::collections::BTreeMap;
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	let names: Vec<String> = vec![String::from("Rex"), String::from("Spot")];
+	gen.load_lines("dog-names", names).unwrap();
+	let mut rarity: BTreeMap<String, f64> = BTreeMap::new();
+	rarity.insert(String::from("common"), 6.);
+	rarity.insert(String::from("rare"), 1.);
+	gen.load_items("rarity", rarity).unwrap();
+	assert_eq!(gen.eval("${dog-names} found a ${rarity} bone").unwrap(), "Rex found a common bone");
+	assert!(gen.load_items("bad", vec![("x", -1.)]).is_err());
+	assert!(gen.get_lut("bad").is_none());
+	assert!(gen.load_lines("$bad", ["x"]).is_err());
+}
+
+#[test]
+fn load_value_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	let config: serde_yaml::Value = serde_yaml::from_str("tables: {animal: [cat, dog], mood: {happy: 3, sad: 1}}").unwrap();
+	gen.load_value("story", config["tables"].clone()).unwrap();
+	let json: serde_json::Value = serde_json::json!({"color": ["red", "blue"]});
+	gen.load_json_value("paint", json).unwrap();
+	assert_eq!(gen.eval("${story/mood} ${paint/color} ${story/animal}").unwrap(), "happy red cat");
+	assert!(gen.load_value("number", serde_yaml::Value::from(7)).is_err());
+}
+
+#[test]
+fn remove_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file_namespaced("tests/test-data/testdir", "pack").expect("Failed to load dir");
+	gen.load_str("animal", "cat", "txt").unwrap();
+	assert_eq!(gen.list_ids().len(), 12);
+	assert!(gen.remove_lut("pack/gender").is_some());
+	assert!(gen.remove_lut("pack/gender").is_none());
+	assert!(gen.eval("${pack/gender}").is_err());
+	assert_eq!(gen.remove_namespace("pack/elf"), 4);
+	assert_eq!(gen.remove_namespace("pack/"), 6);
+	assert_eq!(gen.list_ids(), vec!["animal"]);
+	gen.set_recursion_limit(7);
+	gen.clear();
+	assert!(gen.list_ids().is_empty());
+	assert_eq!(gen.get_recursion_limit(), 7);
+	gen.load_file("tests/test-data/animal.txt").unwrap();
+	assert_eq!(gen.eval("${animal}").unwrap(), "dog");
+	// a removed and re-loaded table starts afresh, without any used up items
+	gen.load_str("deck", "ace", "txt").unwrap();
+	gen.load_str("cards/deck", "king", "txt").unwrap();
+	gen.set_depletion("deck", twas::Depletion::Deplete);
+	gen.set_depletion("cards/deck", twas::Depletion::Deplete);
+	assert_eq!(gen.eval("${deck} ${cards/deck}").unwrap(), "ace king");
+	assert!(gen.eval("${deck}").is_err());
+	gen.remove_lut("deck");
+	assert_eq!(gen.remove_namespace("cards"), 1);
+	gen.load_str("deck", "ace", "txt").unwrap();
+	gen.load_str("cards/deck", "king", "txt").unwrap();
+	assert_eq!(gen.get_depletion("deck"), twas::Depletion::Replace);
+	assert_eq!(gen.eval("${deck} ${deck} ${cards/deck} ${cards/deck}").unwrap(), "ace ace king king");
+}
+
+#[test]
+fn lazy_test_1() {
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.set_lazy_loading(true);
+	gen.load_dir("tests/test-data/testdir").expect("Failed to index dir");
+	assert!(gen.list_ids().is_empty());
+	assert!(gen.check_template("${gender} ${elf/names/male}").unwrap().is_empty());
+	assert_eq!(gen.check_template("${dwarf/names/male}").unwrap(), vec!["dwarf/names/male"]);
+	assert_eq!(gen.eval("${kind/species}").unwrap(), "human");
+	let mut loaded_ids = gen.list_ids();
+	loaded_ids.sort();
+	assert_eq!(loaded_ids, vec!["kind/species", "kind/weight"]);
+	assert!(gen.get_lut("elf/names/surname").is_some());
+	assert_eq!(gen.list_ids().len(), 6);
+	assert!(gen.eval("${dwarf/names/male}").is_err());
+}
+
+#[test]
+fn lazy_test_2() {
+	use std::fs;
+	let dir = std::env::temp_dir().join(format!("twas-lazy-test-2-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("price.txt"), b"5 \xa4").unwrap();
+	fs::write(dir.join("animal.txt"), "cat\ndog\ncat").unwrap();
+	fs::write(dir.join("color.txt"), "blue").unwrap();
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_str("color", "red", "txt").unwrap();
+	// lazily indexed files are parsed with the settings from when they were indexed
+	gen.set_lazy_loading(true);
+	gen.set_encoding(Some("iso-8859-15")).expect("Unknown encoding");
+	gen.set_dedup(twas::Dedup::Sum);
+	gen.load_dir(&dir).expect("Failed to index dir");
+	gen.set_encoding(None).unwrap();
+	gen.set_dedup(twas::Dedup::Keep);
+	assert_eq!(gen.eval("${price}").unwrap(), "5 €");
+	assert_eq!(gen.eval("${animal}").unwrap(), "cat");
+	assert_eq!(gen.get_lut("animal").unwrap().len(), 2);
+	assert_eq!(gen.take_dedup_report().len(), 1);
+	// files that add to a look-up table that is already loaded are parsed right away
+	assert_eq!(gen.get_lut("color").unwrap().len(), 2);
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reload_test_1() {
+	use std::fs;
+	let dir = std::env::temp_dir().join(format!("twas-reload-test-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("animal.txt"), "cat").unwrap();
+	fs::write(dir.join("color.yaml"), "[red]").unwrap();
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_dir(&dir).expect("IO Error");
+	gen.load_str("name", "Tom", "txt").unwrap();
+	fs::write(dir.join("animal.txt"), "cow").unwrap();
+	assert!(gen.reload_file(dir.join("animal.txt")).unwrap());
+	assert_eq!(gen.eval("${name} the ${color} ${animal}").unwrap(), "Tom the red cow");
+	// parsing errors keep the previous tables
+	fs::write(dir.join("color.yaml"), "[unclosed").unwrap();
+	assert!(gen.reload_file(dir.join("color.yaml")).is_err());
+	assert_eq!(gen.eval("${color}").unwrap(), "red");
+	// deleted files are unloaded
+	fs::remove_file(dir.join("animal.txt")).unwrap();
+	assert!(gen.reload_file(dir.join("animal.txt")).unwrap());
+	assert!(gen.eval("${animal}").is_err());
+	assert!(!gen.reload_file(dir.join("animal.txt")).unwrap());
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reload_test_2() {
+	use std::fs;
+	let dir = std::env::temp_dir().join(format!("twas-reload-test-2-{}", std::process::id()));
+	fs::create_dir_all(dir.join("expansion")).unwrap();
+	fs::write(dir.join("animal.txt"), "cat").unwrap();
+	fs::write(dir.join("expansion/animal.txt"), "dog").unwrap();
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_file(dir.join("animal.txt")).expect("IO Error");
+	gen.load_file(dir.join("expansion/animal.txt")).expect("IO Error");
+	gen.load_str("animal", "bird", "txt").unwrap();
+	assert_eq!(gen.get_lut("animal").unwrap().len(), 3);
+	// only the items of the re-loaded file are replaced
+	fs::write(dir.join("animal.txt"), "cow\nfox").unwrap();
+	assert!(gen.reload_file(dir.join("animal.txt")).unwrap());
+	let mut items: Vec<String> = gen.get_lut("animal").unwrap().iter().map(|item| item.get_text().clone()).collect();
+	items.sort();
+	assert_eq!(items, vec!["bird", "cow", "dog", "fox"]);
+	fs::remove_file(dir.join("expansion/animal.txt")).unwrap();
+	assert!(gen.reload_file(dir.join("expansion/animal.txt")).unwrap());
+	assert_eq!(gen.get_lut("animal").unwrap().len(), 3);
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "hot-reload")]
+#[test]
+fn hot_reload_test_1() {
+	use std::fs;
+	let dir = std::env::temp_dir().join(format!("twas-hot-reload-test-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("animal.txt"), "cat").unwrap();
+	fs::write(dir.join("color.txt"), "red").unwrap();
+	let mut gen = twas::Interpreter::from_rng(NotRandom::seed_from_u64(0));
+	gen.load_dir(&dir).expect("IO Error");
+	gen.enable_hot_reload().expect("Failed to watch files");
+	fs::write(dir.join("animal.txt"), "cow").unwrap();
+	let mut reloaded = Vec::new();
+	for _ in 0..50 {
+		reloaded.extend(gen.reload_changed().expect("Failed to reload"));
+		if !reloaded.is_empty() { break; }
+		std::thread::sleep(std::time::Duration::from_millis(100));
+	}
+	assert_eq!(reloaded, vec![fs::canonicalize(dir.join("animal.txt")).unwrap()]);
+	assert_eq!(gen.eval("${color} ${animal}").unwrap(), "red cow");
+	gen.disable_hot_reload();
+	fs::remove_dir_all(&dir).unwrap();
+}
+
 #[test]
 #[allow(unused_imports)]
 fn example01(){